@@ -0,0 +1,40 @@
+//! 通过内置函数 `sum` 对大数组求和的基准
+//!
+//! 运行：`cargo bench --bench native_sum`。数组存储是共享的：读取全局变量 `xs`
+//! 和把它传给内置函数都只增加引用计数，不复制元素
+
+use std::time::{Duration, Instant};
+use Zero_compiler::compiler::Compiler;
+use Zero_compiler::lexer::Lexer;
+use Zero_compiler::parser::Parser;
+use Zero_compiler::vm::VM;
+
+const LEN: usize = 1_000_000;
+const RUNS: usize = 5;
+
+/// 编译并执行 `RUNS` 次，返回最快一次的耗时（只计执行，不计编译）
+fn time(source: &str) -> Duration {
+    let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+    let chunk = Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap();
+
+    (0..RUNS)
+        .map(|_| {
+            let mut vm = VM::new();
+            let start = Instant::now();
+            vm.execute(chunk.clone()).unwrap();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let setup = format!("let xs = to_array(0..{});", LEN);
+    let baseline = time(&setup);
+    let native = time(&format!("{} let total = sum(xs);", setup));
+    let looped = time(&format!("{} var total = 0; for x in xs {{ total = total + x; }}", setup));
+
+    println!("build {} element array: {:?}", LEN, baseline);
+    println!("sum(xs):                 {:?}", native.saturating_sub(baseline));
+    println!("for loop:                {:?}", looped.saturating_sub(baseline));
+}
@@ -1,4 +1,5 @@
 use crate::lexer::token::Token;
+use std::fmt;
 
 // 类型系统定义
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -11,17 +12,39 @@ pub enum Type {
     Void,
     Null,
     Array(Box<Type>),  // 数组类型
+    FixedArray(Box<Type>, usize),  // 定长数组类型 [T; N]
+    Tuple(Vec<Type>),  // 元组类型 (T1, T2)，单元素写作 (T,)
     Function(FunctionType),
     Struct(StructType),  // 结构体类型
     Named(String),  // 类型别名引用
+    Range,  // 整数范围类型
     Unknown,  // 用于类型推导
 }
 
+/// `int` 类型的位宽（默认64位，受限目标可选32位）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntWidth {
+    I32,
+    #[default]
+    I64,
+}
+
+impl IntWidth {
+    /// 检查整数值是否在当前位宽的范围内
+    pub fn contains(&self, value: i64) -> bool {
+        match self {
+            IntWidth::I32 => i32::try_from(value).is_ok(),
+            IntWidth::I64 => true,
+        }
+    }
+}
+
 // 结构体字段定义
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct StructField {
     pub name: String,
     pub field_type: Type,
+    pub is_mutable: bool,  // let 字段构造后只读，var 或无标记字段可赋值
 }
 
 // 结构体类型定义
@@ -59,6 +82,21 @@ impl Type {
             (Type::Unknown, _) | (_, Type::Unknown) => true,
             // 数组类型需要元素类型兼容
             (Type::Array(a), Type::Array(b)) => a.is_compatible_with(b),
+            // 定长数组需要长度相同；与变长数组之间按元素类型兼容（长度在运行时不检查）
+            (Type::FixedArray(a, n), Type::FixedArray(b, m)) => n == m && a.is_compatible_with(b),
+            (Type::FixedArray(a, _), Type::Array(b)) | (Type::Array(a), Type::FixedArray(b, _)) => {
+                a.is_compatible_with(b)
+            }
+            // 元组需要元素个数相同且逐一兼容
+            (Type::Tuple(a), Type::Tuple(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.is_compatible_with(y))
+            }
+            // 函数类型需要参数数量相同，参数和返回类型逐一兼容
+            (Type::Function(a), Type::Function(b)) => {
+                a.params.len() == b.params.len()
+                    && a.params.iter().zip(&b.params).all(|(x, y)| x.is_compatible_with(y))
+                    && a.return_type.is_compatible_with(&b.return_type)
+            }
             // 结构体类型需要名称和字段匹配
             (Type::Struct(a), Type::Struct(b)) => a == b,
             _ => false,
@@ -67,12 +105,65 @@ impl Type {
     
     pub fn get_element_type(&self) -> Option<&Type> {
         match self {
-            Type::Array(element_type) => Some(element_type),
+            Type::Array(element_type) | Type::FixedArray(element_type, _) => Some(element_type),
             _ => None,
         }
     }
 }
 
+/// 以源码中的类型注解语法显示类型
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::String => write!(f, "string"),
+            Type::Bool => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+            Type::Void => write!(f, "void"),
+            Type::Null => write!(f, "null"),
+            Type::Array(element_type) => write!(f, "[{}]", element_type),
+            Type::FixedArray(element_type, len) => write!(f, "[{}; {}]", element_type, len),
+            Type::Tuple(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                match elements.as_slice() {
+                    [single] => write!(f, "({},)", single),
+                    _ => write!(f, "({})", elements.join(", ")),
+                }
+            }
+            Type::Function(func_type) => {
+                let params: Vec<String> = func_type.params.iter().map(|p| p.to_string()).collect();
+                write!(f, "fn({}) -> {}", params.join(", "), func_type.return_type)
+            }
+            Type::Struct(struct_type) => write!(f, "{}", struct_type.name),
+            Type::Named(name) => write!(f, "{}", name),
+            Type::Range => write!(f, "range"),
+            Type::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// 类型检查器为字段访问和字段赋值标注的结构体名（解析器产生的节点为空），编译器据此确定字段下标。
+/// 标注不属于语法，比较 AST 时忽略
+#[derive(Debug, Clone, Default)]
+pub struct FieldOwner(std::cell::RefCell<Option<String>>);
+
+impl FieldOwner {
+    pub fn get(&self) -> Option<String> {
+        self.0.borrow().clone()
+    }
+
+    pub fn set(&self, struct_name: String) {
+        *self.0.borrow_mut() = Some(struct_name);
+    }
+}
+
+impl PartialEq for FieldOwner {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     // 字面量
@@ -88,6 +179,9 @@ pub enum Expr {
         elements: Vec<Expr>,
     },
     
+    // 元组字面量 (a, b)，单元素写作 (a,)
+    Tuple(Vec<Expr>),
+
     // 结构体字面量
     StructLiteral {
         struct_name: String,
@@ -131,11 +225,18 @@ pub enum Expr {
         name: String,
         value: Box<Expr>,
     },
+
+    // 后置自增/自减 (i++ / i--)：等价于 i = i + 1 / i = i - 1，表达式的值是旧值
+    PostfixUpdate {
+        name: String,
+        operator: BinaryOp,  // Add 或 Subtract
+    },
     
     // 字段访问 (object.field)
     FieldAccess {
         object: Box<Expr>,
         field: String,
+        owner: FieldOwner,
     },
 
     // 字段赋值
@@ -143,6 +244,7 @@ pub enum Expr {
         object: Box<Expr>,
         field: String,
         value: Box<Expr>,
+        owner: FieldOwner,
     },
 
     // 方法调用 (object.method(args))
@@ -151,6 +253,21 @@ pub enum Expr {
         method: String,
         arguments: Vec<Expr>,
     },
+
+    // if 表达式 (if c { a } else { b })，两个分支各产生一个值
+    If {
+        condition: Box<Expr>,
+        then_expr: Box<Expr>,
+        else_expr: Box<Expr>,
+    },
+
+    // 范围表达式 (start..end 或 start..=end，可带步长 `start..end step n`)
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+        step: Option<Box<Expr>>,
+        inclusive: bool,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -175,10 +292,44 @@ pub enum BinaryOp {
     Or,
 }
 
+impl BinaryOp {
+    /// 返回运算符的源码写法（用于错误信息）
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum UnaryOp {
     Not,
     Negate,
+    /// 一元加号：数值的恒等运算，与一元负号对称
+    Plus,
+}
+
+impl UnaryOp {
+    /// 返回运算符的源码写法（用于错误信息）
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOp::Not => "!",
+            UnaryOp::Negate => "-",
+            UnaryOp::Plus => "+",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -192,6 +343,7 @@ pub enum Stmt {
         mutable: bool,
         type_annotation: Option<Type>,
         initializer: Option<Expr>,
+        is_const: bool,  // `const` 声明：不可变，初始值是编译期常量
     },
     
     // 函数声明
@@ -200,6 +352,7 @@ pub enum Stmt {
         parameters: Vec<Parameter>,
         return_type: Option<Type>,
         body: Vec<Stmt>,
+        body_lines: Vec<usize>,  // body 中各语句所在的源码行号（缺失或 0 表示未知）
     },
     
     // 结构体声明
@@ -224,12 +377,15 @@ pub enum Stmt {
         condition: Expr,
         then_branch: Vec<Stmt>,
         else_branch: Option<Vec<Stmt>>,
+        then_lines: Vec<usize>,  // 各分支中语句所在的源码行号（缺失或 0 表示未知，下同）
+        else_lines: Vec<usize>,
     },
     
     // while 循环
     While {
         condition: Expr,
         body: Vec<Stmt>,
+        body_lines: Vec<usize>,
     },
     
     // for 循环
@@ -237,7 +393,26 @@ pub enum Stmt {
         variable: String,
         start: Expr,
         end: Expr,
+        inclusive: bool,  // `start..=end` 包含结束值
+        body: Vec<Stmt>,
+        body_lines: Vec<usize>,
+    },
+
+    // 遍历数组或范围值的 for 循环
+    ForIn {
+        variable: String,
+        iterable: Expr,
         body: Vec<Stmt>,
+        body_lines: Vec<usize>,
+    },
+    
+    // match 语句：按顺序与常量模式比较，`_` 分支作为默认分支
+    Match {
+        scrutinee: Expr,
+        arms: Vec<(Expr, Vec<Stmt>)>,
+        default: Option<Vec<Stmt>>,
+        arm_lines: Vec<Vec<usize>>,  // 与 arms 一一对应
+        default_lines: Vec<usize>,
     },
     
     // 打印语句
@@ -248,6 +423,7 @@ pub enum Stmt {
     // 代码块
     Block {
         statements: Vec<Stmt>,
+        lines: Vec<usize>,
     },
 
     // Break 语句（仅在循环中有效）
@@ -270,22 +446,32 @@ pub struct MethodDeclaration {
     pub parameters: Vec<Parameter>,  // 不包含 self
     pub return_type: Option<Type>,
     pub body: Vec<Stmt>,
+    pub body_lines: Vec<usize>,  // body 中各语句所在的源码行号
+    pub is_static: bool,  // 没有 self 参数的关联函数，通过类型名调用（`Point.origin()`）
 }
 
 #[derive(Debug, Clone)]
 pub struct Program {
     pub statements: Vec<Stmt>,
+    pub lines: Vec<usize>,  // 各语句所在的源码行号，与 statements 一一对应（缺失或 0 表示未知）
 }
 
 impl Program {
     pub fn new() -> Self {
         Program {
             statements: Vec::new(),
+            lines: Vec::new(),
         }
     }
     
     pub fn add_statement(&mut self, stmt: Stmt) {
+        self.add_statement_at(stmt, 0);
+    }
+
+    /// 添加语句并记录其源码行号
+    pub fn add_statement_at(&mut self, stmt: Stmt, line: usize) {
         self.statements.push(stmt);
+        self.lines.push(line);
     }
 }
 
@@ -320,6 +506,14 @@ impl Expr {
     pub fn array(elements: Vec<Expr>) -> Self {
         Expr::Array { elements }
     }
+
+    pub fn tuple(elements: Vec<Expr>) -> Self {
+        Expr::Tuple(elements)
+    }
+
+    pub fn postfix_update(name: String, operator: BinaryOp) -> Self {
+        Expr::PostfixUpdate { name, operator }
+    }
     
     pub fn binary(left: Expr, operator: BinaryOp, right: Expr) -> Self {
         Expr::Binary {
@@ -343,6 +537,23 @@ impl Expr {
         }
     }
     
+    pub fn range(start: Expr, end: Expr, step: Option<Expr>, inclusive: bool) -> Self {
+        Expr::Range {
+            start: Box::new(start),
+            end: Box::new(end),
+            step: step.map(Box::new),
+            inclusive,
+        }
+    }
+    
+    pub fn if_expr(condition: Expr, then_expr: Expr, else_expr: Expr) -> Self {
+        Expr::If {
+            condition: Box::new(condition),
+            then_expr: Box::new(then_expr),
+            else_expr: Box::new(else_expr),
+        }
+    }
+    
     pub fn index(object: Expr, index: Expr) -> Self {
         Expr::Index {
             object: Box::new(object),
@@ -376,6 +587,7 @@ impl Expr {
         Expr::FieldAccess {
             object: Box::new(object),
             field,
+            owner: FieldOwner::default(),
         }
     }
     
@@ -384,6 +596,7 @@ impl Expr {
             object: Box::new(object),
             field,
             value: Box::new(value),
+            owner: FieldOwner::default(),
         }
     }
 
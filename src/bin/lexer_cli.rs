@@ -1,7 +1,7 @@
 //! Zero语言词法分析器CLI工具
 //! 支持批量处理文件和格式化token输出
 
-use Zero_compiler::lexer::{Lexer, TokenPreprocessor};
+use Zero_compiler::lexer::Lexer;
 use std::env;
 use std::fs;
 use std::io::Write;
@@ -86,9 +86,6 @@ fn tokenize_file(path: &Path) -> Result<(), String> {
     let tokens = lexer.tokenize()
         .map_err(|e| format!("Lexer error: {}", e))?;
 
-    // 应用预处理器
-    let tokens = TokenPreprocessor::preprocess(tokens);
-
     println!("\nTokens ({} total):", tokens.len());
     println!("{:-<60}", "");
     
@@ -158,9 +155,6 @@ fn process_and_save(input_path: &Path, output_path: &Path) -> Result<(), String>
     let tokens = lexer.tokenize()
         .map_err(|e| format!("Lexer error: {}", e))?;
 
-    // 应用预处理器
-    let tokens = TokenPreprocessor::preprocess(tokens);
-
     // 写入格式化的token文件
     let mut output = fs::File::create(output_path)
         .map_err(|e| format!("Failed to create output file: {}", e))?;
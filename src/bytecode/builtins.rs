@@ -0,0 +1,119 @@
+use super::{NativeError, NativeFunction, NativeHost, Value};
+
+/// 所有内置函数（作为普通全局变量注册到VM中，可被别名或遮蔽）
+pub fn all() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction { name: "print", arity: 1, function: native_print },
+        NativeFunction { name: "len", arity: 1, function: native_len },
+        NativeFunction { name: "to_array", arity: 1, function: native_to_array },
+        NativeFunction { name: "contains", arity: 2, function: native_contains },
+        NativeFunction { name: "exit", arity: 1, function: native_exit },
+        NativeFunction { name: "assert", arity: 1, function: native_assert },
+        NativeFunction { name: "debug_assert", arity: 1, function: native_debug_assert },
+        NativeFunction { name: "typeof", arity: 1, function: native_typeof },
+        NativeFunction { name: "sum", arity: 1, function: native_sum },
+    ]
+}
+
+/// 按名称查找内置函数
+pub fn lookup(name: &str) -> Option<NativeFunction> {
+    all().into_iter().find(|native| native.name == name)
+}
+
+fn native_print(host: &mut dyn NativeHost, args: &[Value]) -> Result<Value, NativeError> {
+    host.print_line(&args[0]);
+    Ok(Value::Null)
+}
+
+fn native_len(_host: &mut dyn NativeHost, args: &[Value]) -> Result<Value, NativeError> {
+    match &args[0] {
+        Value::Array(arr) => Ok(Value::Integer(arr.borrow().len() as i64)),
+        Value::String(s) => Ok(Value::Integer(s.chars().count() as i64)),
+        range @ Value::Range { .. } => Ok(Value::Integer(range.range_len().unwrap_or(0) as i64)),
+        other => Err(format!("len() expects an array, string or range, got {}", other.to_string()).into()),
+    }
+}
+
+fn native_to_array(_host: &mut dyn NativeHost, args: &[Value]) -> Result<Value, NativeError> {
+    match &args[0] {
+        range @ Value::Range { .. } => {
+            let len = range.range_len().unwrap_or(0);
+            let elements = (0..len)
+                .filter_map(|i| range.range_get(i))
+                .map(Value::Integer)
+                .collect();
+            Ok(Value::array(elements))
+        }
+        // 返回的数组与参数共享存储，不复制元素
+        Value::Array(arr) => Ok(Value::Array(arr.clone())),
+        other => Err(format!("to_array() expects a range or array, got {}", other.to_string()).into()),
+    }
+}
+
+fn native_contains(_host: &mut dyn NativeHost, args: &[Value]) -> Result<Value, NativeError> {
+    match (&args[0], &args[1]) {
+        (range @ Value::Range { .. }, Value::Integer(value)) => {
+            Ok(Value::Boolean(range.range_contains(*value)))
+        }
+        (Value::Range { .. }, _) => Ok(Value::Boolean(false)),
+        (Value::Array(arr), value) => Ok(Value::Boolean(arr.borrow().contains(value))),
+        (other, _) => Err(format!("contains() expects a range or array, got {}", other.to_string()).into()),
+    }
+}
+
+fn native_assert(_host: &mut dyn NativeHost, args: &[Value]) -> Result<Value, NativeError> {
+    check_assertion("assert", &args[0])
+}
+
+/// 编译器在 release 模式下会移除对 `debug_assert` 的直接调用；通过别名调用时仍会检查断言
+fn native_debug_assert(_host: &mut dyn NativeHost, args: &[Value]) -> Result<Value, NativeError> {
+    check_assertion("debug_assert", &args[0])
+}
+
+fn check_assertion(name: &str, condition: &Value) -> Result<Value, NativeError> {
+    match condition {
+        Value::Boolean(true) => Ok(Value::Null),
+        Value::Boolean(false) => Err(format!("{}() failed", name).into()),
+        other => Err(format!("{}() expects a bool, got {}", name, other.to_string()).into()),
+    }
+}
+
+fn native_typeof(_host: &mut dyn NativeHost, args: &[Value]) -> Result<Value, NativeError> {
+    Ok(Value::String(args[0].type_name().to_string()))
+}
+
+/// 数组与调用方共享存储，求和只借用元素而不复制；全是整数时结果为整数
+/// （超出宿主的整数位宽时报错，与 `+` 一致），含浮点数时为浮点数
+fn native_sum(host: &mut dyn NativeHost, args: &[Value]) -> Result<Value, NativeError> {
+    let Value::Array(arr) = &args[0] else {
+        return Err(format!("sum() expects an array, got {}", args[0].to_string()).into());
+    };
+
+    let int_width = host.int_width();
+    let mut int_total: i64 = 0;
+    let mut float_total: Option<f64> = None;
+    for value in arr.borrow().iter() {
+        match (value, float_total.as_mut()) {
+            (Value::Integer(n), None) => {
+                int_total = int_total
+                    .checked_add(*n)
+                    .filter(|total| int_width.contains(*total))
+                    .ok_or_else(|| "sum() overflowed".to_string())?;
+            }
+            (Value::Integer(n), Some(total)) => *total += *n as f64,
+            (Value::Float(x), _) => *float_total.get_or_insert(int_total as f64) += x,
+            (other, _) => return Err(format!("sum() expects numbers, got {}", other.to_string()).into()),
+        }
+    }
+    Ok(float_total.map_or(Value::Integer(int_total), Value::Float))
+}
+
+fn native_exit(_host: &mut dyn NativeHost, args: &[Value]) -> Result<Value, NativeError> {
+    match &args[0] {
+        Value::Integer(code) => match i32::try_from(*code) {
+            Ok(code) => Err(NativeError::Exit(code)),
+            Err(_) => Err(format!("exit() code {} is out of range", code).into()),
+        },
+        other => Err(format!("exit() expects an int, got {}", other.to_string()).into()),
+    }
+}
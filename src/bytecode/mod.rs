@@ -1,5 +1,10 @@
+pub mod builtins;
 pub mod serializer;
 
+use crate::ast::IntWidth;
+use std::cell::{RefCell, RefMut};
+use std::rc::Rc;
+
 /// Zero语言的字节码指令集
 #[derive(Debug, Clone, PartialEq)]
 pub enum OpCode {
@@ -46,6 +51,8 @@ pub enum OpCode {
     
     // 数组操作
     NewArray(usize),       // 创建新数组（参数：元素数量）
+    NewRange(bool),        // 创建范围 (start, end -> range)，参数表示是否包含end
+    RangeStep,             // 设置范围步长 (range, step -> range)
     ArrayGet,              // 获取数组元素 (array, index -> value)
     ArraySet,              // 设置数组元素 (array, index, value -> value)
     ArrayLen,              // 获取数组长度 (array -> length)
@@ -62,8 +69,51 @@ pub enum OpCode {
     // 其他
     Print,                 // 打印
     Halt,                  // 停止执行
+
+    // 嵌入方扩展
+    Extension(u8, u32),    // 调用 VM 中注册的扩展处理器（扩展 id, 操作数）
+}
+
+impl OpCode {
+    /// 指令执行后操作数栈深度的变化量
+    pub fn stack_effect(&self) -> isize {
+        match self {
+            OpCode::LoadConst(_) | OpCode::LoadNull | OpCode::LoadLocal(_)
+            | OpCode::LoadGlobal(_) | OpCode::Dup => 1,
+            OpCode::StoreLocal(_) | OpCode::StoreGlobal(_) => 0,
+            OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide
+            | OpCode::Modulo | OpCode::Equal | OpCode::NotEqual | OpCode::Greater
+            | OpCode::GreaterEqual | OpCode::Less | OpCode::LessEqual
+            | OpCode::And | OpCode::Or => -1,
+            OpCode::Negate | OpCode::Not => 0,
+            OpCode::Jump(_) | OpCode::JumpIfFalse(_) | OpCode::JumpIfTrue(_)
+            | OpCode::Loop(_) => 0,
+            // 弹出被调用者和参数，压入返回值
+            OpCode::Call(arg_count) => -(*arg_count as isize),
+            OpCode::Return | OpCode::Halt => 0,
+            OpCode::NewArray(size) => 1 - *size as isize,
+            OpCode::NewRange(_) => -1,
+            OpCode::RangeStep => -1,
+            OpCode::ArrayGet => -1,
+            OpCode::ArraySet => -2,
+            OpCode::ArrayLen => 0,
+            // 弹出结构体名和字段值，压入结构体
+            OpCode::NewStruct(field_count) => -(*field_count as isize),
+            OpCode::FieldGet(_) => 0,
+            OpCode::FieldSet(_) => -1,
+            OpCode::Pop | OpCode::Print => -1,
+            // 处理器的栈效果无法静态得知，按净效果为零估算
+            OpCode::Extension(_, _) => 0,
+        }
+    }
 }
 
+/// 单个字节码块常量池的默认上限（编译和反序列化时检查，防止资源耗尽）
+pub const DEFAULT_MAX_CONSTANTS: usize = 65536;
+
+/// 单个函数局部变量（含参数）数量的上限，编译和反序列化时检查
+pub const MAX_LOCALS: usize = 256;
+
 /// 常量值类型
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
@@ -72,12 +122,30 @@ pub enum Value {
     String(String),
     Boolean(bool),
     Char(char),            // 字符值
-    Array(Vec<Value>),     // 数组值
+    Array(ArrayRef),       // 数组值（共享存储，写入时复制）
     Struct(StructValue),   // 结构体值
     Function(Function),
+    NativeFunction(NativeFunction), // 内置函数
+    Range { start: i64, end: i64, step: i64, inclusive: bool }, // 整数范围值
     Null,
 }
 
+/// 数组的共享存储：复制数组值只增加引用计数，原生函数借用同一份元素。
+/// 数组仍是值语义，`Value::as_array_mut` 在存储被共享时先复制再写入
+pub type ArrayRef = Rc<RefCell<Vec<Value>>>;
+
+/// 把元素包装为共享数组存储
+pub fn array_ref(elements: Vec<Value>) -> ArrayRef {
+    Rc::new(RefCell::new(elements))
+}
+
+/// 取出共享数组存储中的元素：唯一持有时直接移出，否则复制一份
+pub fn array_into_vec(array: ArrayRef) -> Vec<Value> {
+    Rc::try_unwrap(array)
+        .map(RefCell::into_inner)
+        .unwrap_or_else(|shared| shared.borrow().clone())
+}
+
 /// 结构体值
 #[derive(Debug, Clone, PartialEq)]
 pub struct StructValue {
@@ -85,38 +153,168 @@ pub struct StructValue {
     pub fields: Vec<Value>,  // 按字段定义顺序存储
 }
 
+/// 可作为映射键的值（可哈希、相等关系满足自反性）
+/// 结构体键按名称和字段逐一比较，与 `==` 的结构相等一致
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ValueKey {
+    Integer(i64),
+    String(String),
+    Boolean(bool),
+    Char(char),
+    Array(Vec<ValueKey>),
+    Struct { struct_name: String, fields: Vec<ValueKey> },
+    Null,
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(elements: Vec<Value>) -> Self {
+        Value::Array(array_ref(elements))
+    }
+}
+
 impl Value {
+    /// 由元素构造数组值
+    pub fn array(elements: Vec<Value>) -> Self {
+        Value::from(elements)
+    }
+
     pub fn to_string(&self) -> String {
+        self.to_string_limited(usize::MAX, usize::MAX)
+    }
+
+    /// 带输出上限的字符串表示：每个数组最多显示 `max_elements` 个元素，
+    /// 其余以 `... (N more)` 概括；嵌套超过 `max_depth` 层的数组显示为 `[...]`
+    pub fn to_string_limited(&self, max_elements: usize, max_depth: usize) -> String {
         match self {
             Value::Integer(i) => i.to_string(),
             Value::Float(f) => f.to_string(),
             Value::String(s) => s.clone(),
             Value::Boolean(b) => b.to_string(),
             Value::Char(c) => c.to_string(),
+            Value::Array(arr) if max_depth == 0 && !arr.borrow().is_empty() => "[...]".to_string(),
             Value::Array(arr) => {
-                let elements: Vec<String> = arr.iter().map(|v| v.to_string()).collect();
+                let arr = arr.borrow();
+                let mut elements: Vec<String> = arr
+                    .iter()
+                    .take(max_elements)
+                    .map(|v| v.to_string_limited(max_elements, max_depth - 1))
+                    .collect();
+                if arr.len() > max_elements {
+                    elements.push(format!("... ({} more)", arr.len() - max_elements));
+                }
                 format!("[{}]", elements.join(", "))
             }
             Value::Struct(s) => {
                 format!("{} {{ {} fields }}", s.struct_name, s.fields.len())
             }
             Value::Function(_) => "<function>".to_string(),
+            Value::NativeFunction(native) => format!("<native fn {}>", native.name),
+            Value::Range { start, end, step, inclusive } => {
+                let op = if *inclusive { "..=" } else { ".." };
+                if *step == 1 {
+                    format!("{}{}{}", start, op, end)
+                } else {
+                    format!("{}{}{} step {}", start, op, end, step)
+                }
+            }
             Value::Null => "null".to_string(),
         }
     }
 
+    /// 运行时类型名：基本类型与类型注解中的写法一致，结构体为结构体名
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::Integer(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "bool",
+            Value::Char(_) => "char",
+            Value::Array(_) => "array",
+            Value::Struct(s) => &s.struct_name,
+            Value::Function(_) | Value::NativeFunction(_) => "function",
+            Value::Range { .. } => "range",
+            Value::Null => "null",
+        }
+    }
+
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Boolean(b) => *b,
             Value::Null => false,
             Value::Integer(0) => false,
             Value::Float(f) if *f == 0.0 => false,
-            Value::Array(arr) => !arr.is_empty(),
+            Value::Array(arr) => !arr.borrow().is_empty(),
             Value::Struct(_) => true,
             _ => true,
         }
     }
 
+    /// 转换为映射键；浮点数（NaN 不等于自身）、函数和范围不能作为键，
+    /// 包含它们的数组或结构体同样返回None
+    pub fn to_key(&self) -> Option<ValueKey> {
+        match self {
+            Value::Integer(i) => Some(ValueKey::Integer(*i)),
+            Value::String(s) => Some(ValueKey::String(s.clone())),
+            Value::Boolean(b) => Some(ValueKey::Boolean(*b)),
+            Value::Char(c) => Some(ValueKey::Char(*c)),
+            Value::Array(arr) => arr.borrow().iter().map(Value::to_key).collect::<Option<_>>().map(ValueKey::Array),
+            Value::Struct(s) => {
+                let fields = s.fields.iter().map(Value::to_key).collect::<Option<_>>()?;
+                Some(ValueKey::Struct { struct_name: s.struct_name.clone(), fields })
+            }
+            Value::Null => Some(ValueKey::Null),
+            Value::Float(_) | Value::Function(_) | Value::NativeFunction(_) | Value::Range { .. } => None,
+        }
+    }
+
+    /// 范围中的元素数量（非范围值返回None）
+    pub fn range_len(&self) -> Option<usize> {
+        match self {
+            Value::Range { start, end, step, inclusive } => {
+                if *step == 0 {
+                    return Some(0);
+                }
+                // 统一成半开区间后按步长向上取整，在 i128 中计算避免溢出；
+                // 长度超过 i64::MAX 时饱和（元素个数仍能表示为整数值）
+                let (start, end, step) = (*start as i128, *end as i128, *step as i128);
+                let span = if step > 0 { end - start } else { start - end } + if *inclusive { 1 } else { 0 };
+                let step = step.abs();
+                let len = if span <= 0 { 0 } else { (span + step - 1) / step };
+                Some(usize::try_from(len.min(i64::MAX as i128)).unwrap_or(usize::MAX))
+            }
+            _ => None,
+        }
+    }
+
+    /// 范围中的第index个元素
+    pub fn range_get(&self, index: usize) -> Option<i64> {
+        match self {
+            Value::Range { start, step, .. } if index < self.range_len()? => {
+                // 下标在长度以内时结果落在 start..end 之间，一定能表示为 i64
+                i64::try_from(*start as i128 + *step as i128 * index as i128).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// 范围是否包含某个整数
+    pub fn range_contains(&self, value: i64) -> bool {
+        match self {
+            Value::Range { start, end, step, inclusive } if *step != 0 => {
+                // 直接比较边界（长度可能已饱和），在 i128 中计算避免溢出
+                let (offset, step) = (value as i128 - *start as i128, *step as i128);
+                let in_bounds = match (step > 0, *inclusive) {
+                    (true, true) => value <= *end,
+                    (true, false) => value < *end,
+                    (false, true) => value >= *end,
+                    (false, false) => value > *end,
+                };
+                in_bounds && offset % step == 0 && offset / step >= 0
+            }
+            _ => false,
+        }
+    }
+
     pub fn as_integer(&self) -> Option<i64> {
         match self {
             Value::Integer(i) => Some(*i),
@@ -132,16 +330,23 @@ impl Value {
         }
     }
     
-    pub fn as_array(&self) -> Option<&Vec<Value>> {
+    pub fn as_array(&self) -> Option<&ArrayRef> {
         match self {
             Value::Array(arr) => Some(arr),
             _ => None,
         }
     }
     
-    pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
+    /// 可写地借用数组元素；存储被其他值共享时先复制，保证写入不影响其他副本
+    pub fn as_array_mut(&mut self) -> Option<RefMut<'_, Vec<Value>>> {
         match self {
-            Value::Array(arr) => Some(arr),
+            Value::Array(arr) => {
+                if Rc::strong_count(arr) > 1 {
+                    let elements = arr.borrow().clone();
+                    *arr = array_ref(elements);
+                }
+                Some(arr.borrow_mut())
+            }
             _ => None,
         }
     }
@@ -168,6 +373,74 @@ pub struct Function {
     pub arity: usize,          // 参数数量
     pub chunk: Chunk,           // 函数字节码
     pub locals_count: usize,    // 局部变量数量
+    pub max_stack: usize,       // 操作数栈最大深度（含参数和局部变量）
+}
+
+impl Function {
+    /// 检查参数个数和局部变量数是否自洽：局部变量包含参数且不超过上限，
+    /// 代码只访问帧内已有的局部变量槽位（防止手写或损坏的字节码在调用时越界访问栈）
+    pub fn verify(&self) -> Result<(), String> {
+        if self.locals_count > MAX_LOCALS {
+            return Err(format!(
+                "function '{}' declares {} locals, exceeding the limit of {}",
+                self.name, self.locals_count, MAX_LOCALS
+            ));
+        }
+        if self.arity > self.locals_count {
+            return Err(format!(
+                "function '{}' takes {} parameters but declares only {} locals",
+                self.name, self.arity, self.locals_count
+            ));
+        }
+        self.chunk
+            .verify_local_access(self.arity)
+            .map_err(|message| format!("function '{}': {}", self.name, message))
+    }
+}
+
+/// 内置函数对象
+#[derive(Clone, Copy)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&mut dyn NativeHost, &[Value]) -> Result<Value, NativeError>,
+}
+
+/// 内置函数可以使用的宿主（VM）功能
+pub trait NativeHost {
+    /// 按宿主的 print 上限和输出目标输出一行
+    fn print_line(&mut self, value: &Value);
+
+    /// 宿主整数运算的位宽，内置函数的整数结果同样受其限制
+    fn int_width(&self) -> IntWidth;
+}
+
+/// 内置函数的非正常结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum NativeError {
+    /// 运行时错误
+    Message(String),
+    /// `exit(code)` 请求以该退出码立即终止程序（不是错误）
+    Exit(i32),
+}
+
+impl From<String> for NativeError {
+    fn from(message: String) -> Self {
+        NativeError::Message(message)
+    }
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+// 内置函数按名称唯一，比较名称即可
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
 }
 
 /// 字节码块
@@ -199,47 +472,140 @@ impl Chunk {
         self.constants.len() - 1
     }
 
+    /// 查找与 `value` 相同的标量常量（整数、浮点数、字符串、布尔值、字符），供编译器复用槽位
+    /// 浮点数按位比较：`0.0` 与 `-0.0` 是不同的常量（`1.0 / -0.0` 为 -inf），位模式相同的 NaN 可以复用
+    pub fn find_constant(&self, value: &Value) -> Option<usize> {
+        self.constants.iter().position(|constant| match (constant, value) {
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            (Value::Integer(_) | Value::String(_) | Value::Boolean(_) | Value::Char(_), _) => constant == value,
+            _ => false,
+        })
+    }
+
     /// 获取指令数量
     pub fn len(&self) -> usize {
         self.code.len()
     }
 
+    /// 计算执行该块所需的最大栈深度，`initial` 为入口处已在栈上的值（如参数）。
+    /// 沿所有跳转路径模拟栈深度，每条指令只按首次到达的深度计算一次。
+    pub fn max_stack_depth(&self, initial: usize) -> usize {
+        let max_depth = self.stack_depths(initial)
+            .iter()
+            .zip(&self.code)
+            .filter_map(|(depth, op)| depth.map(|depth| depth + op.stack_effect()))
+            .fold(initial as isize, isize::max);
+        max_depth.max(0) as usize
+    }
+
+    /// 检查局部变量访问：`LoadLocal`/`StoreLocal` 的槽位必须落在执行到该指令时帧内已有的值上，
+    /// `initial` 为入口处帧内的值数（函数的参数个数）
+    pub fn verify_local_access(&self, initial: usize) -> Result<(), String> {
+        for (ip, (depth, op)) in self.stack_depths(initial).iter().zip(&self.code).enumerate() {
+            let (Some(depth), OpCode::LoadLocal(slot) | OpCode::StoreLocal(slot)) = (depth, op) else {
+                continue;
+            };
+            if *slot as isize >= *depth {
+                return Err(format!(
+                    "instruction {} accesses local slot {} but the frame holds only {} values",
+                    ip, slot, depth.max(&0)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// 每条指令执行前的栈深度（不可达的指令为 None）
+    fn stack_depths(&self, initial: usize) -> Vec<Option<isize>> {
+        let mut depths = vec![None; self.code.len()];
+        let mut worklist = vec![(0, initial as isize)];
+
+        while let Some((ip, depth)) = worklist.pop() {
+            if ip >= self.code.len() || depths[ip].is_some() {
+                continue;
+            }
+            depths[ip] = Some(depth);
+
+            let op = &self.code[ip];
+            let next_depth = depth + op.stack_effect();
+
+            match op {
+                OpCode::Return | OpCode::Halt => {}
+                OpCode::Jump(target) | OpCode::Loop(target) => {
+                    worklist.push((*target, next_depth));
+                }
+                OpCode::JumpIfFalse(target) | OpCode::JumpIfTrue(target) => {
+                    worklist.push((*target, next_depth));
+                    worklist.push((ip + 1, next_depth));
+                }
+                _ => worklist.push((ip + 1, next_depth)),
+            }
+        }
+
+        depths
+    }
+
     /// 反汇编（用于调试）
     pub fn disassemble(&self, name: &str) {
-        println!("== {} ==", name);
+        print!("{}", self.disassemble_to_string(name));
+    }
+
+    /// 反汇编为字符串（包含常量池中的函数），用于测试和工具输出
+    pub fn disassemble_to_string(&self, name: &str) -> String {
+        let mut output = format!("== {} ==\n", name);
         for (offset, op) in self.code.iter().enumerate() {
-            self.disassemble_instruction(offset, op);
+            output.push_str(&self.format_instruction(offset, op));
+            output.push('\n');
         }
+
+        // 递归反汇编函数常量
+        for constant in &self.constants {
+            if let Value::Function(func) = constant {
+                output.push('\n');
+                output.push_str(&func.chunk.disassemble_to_string(&func.name));
+            }
+        }
+
+        output
     }
 
     pub fn disassemble_instruction(&self, offset: usize, op: &OpCode) {
-        print!("{:04} ", offset);
-        
-        if offset > 0 && self.lines[offset] == self.lines[offset - 1] {
-            print!("   | ");
+        println!("{}", self.format_instruction(offset, op));
+    }
+
+    /// 格式化单条指令
+    pub fn format_instruction(&self, offset: usize, op: &OpCode) -> String {
+        let line = self.lines.get(offset).copied().unwrap_or(0);
+        let line_column = if offset > 0 && self.lines.get(offset - 1) == Some(&line) {
+            "   | ".to_string()
         } else {
-            print!("{:4} ", self.lines[offset]);
-        }
+            format!("{:4} ", line)
+        };
 
-        match op {
-            OpCode::LoadConst(idx) => {
-                println!("LoadConst {} '{:?}'", idx, self.constants.get(*idx));
-            }
-            OpCode::LoadLocal(idx) => println!("LoadLocal {}", idx),
-            OpCode::StoreLocal(idx) => println!("StoreLocal {}", idx),
-            OpCode::LoadGlobal(idx) => println!("LoadGlobal {}", idx),
-            OpCode::StoreGlobal(idx) => println!("StoreGlobal {}", idx),
-            OpCode::Jump(offset) => println!("Jump -> {}", offset),
-            OpCode::JumpIfFalse(offset) => println!("JumpIfFalse -> {}", offset),
-            OpCode::JumpIfTrue(offset) => println!("JumpIfTrue -> {}", offset),
-            OpCode::Loop(offset) => println!("Loop -> {}", offset),
-            OpCode::Call(arity) => println!("Call({})", arity),
-            OpCode::NewArray(size) => println!("NewArray({})", size),
-            OpCode::NewStruct(field_count) => println!("NewStruct({})", field_count),
-            OpCode::FieldGet(idx) => println!("FieldGet({})", idx),
-            OpCode::FieldSet(idx) => println!("FieldSet({})", idx),
-            _ => println!("{:?}", op),
-        }
+        let text = match op {
+            OpCode::LoadConst(idx) => match self.constants.get(*idx) {
+                Some(Value::Function(func)) => format!("LoadConst {} '<fn {}>'", idx, func.name),
+                constant => format!("LoadConst {} '{:?}'", idx, constant),
+            },
+            OpCode::LoadLocal(idx) => format!("LoadLocal {}", idx),
+            OpCode::StoreLocal(idx) => format!("StoreLocal {}", idx),
+            OpCode::LoadGlobal(idx) => format!("LoadGlobal {}", idx),
+            OpCode::StoreGlobal(idx) => format!("StoreGlobal {}", idx),
+            OpCode::Jump(offset) => format!("Jump -> {}", offset),
+            OpCode::JumpIfFalse(offset) => format!("JumpIfFalse -> {}", offset),
+            OpCode::JumpIfTrue(offset) => format!("JumpIfTrue -> {}", offset),
+            OpCode::Loop(offset) => format!("Loop -> {}", offset),
+            OpCode::Call(arity) => format!("Call({})", arity),
+            OpCode::NewArray(size) => format!("NewArray({})", size),
+            OpCode::NewRange(inclusive) => format!("NewRange({})", if *inclusive { "..=" } else { ".." }),
+            OpCode::NewStruct(field_count) => format!("NewStruct({})", field_count),
+            OpCode::FieldGet(idx) => format!("FieldGet({})", idx),
+            OpCode::FieldSet(idx) => format!("FieldSet({})", idx),
+            OpCode::Extension(id, operand) => format!("Extension #{} {}", id, operand),
+            _ => format!("{:?}", op),
+        };
+
+        format!("{:04} {}{}", offset, line_column, text)
     }
 }
 
@@ -1,10 +1,53 @@
-use super::{Chunk, OpCode, Value, Function};
+use super::{Chunk, OpCode, Value, Function, DEFAULT_MAX_CONSTANTS};
+use crate::ast::IntWidth;
 use std::io::{Write, Read, Result as IoResult, Error, ErrorKind};
 
 /// Zero字节码文件魔数 "ZERO"
 const MAGIC: [u8; 4] = [0x5A, 0x45, 0x52, 0x4F];
-const VERSION_MAJOR: u16 = 0;
+const VERSION_MAJOR: u16 = 1;
 const VERSION_MINOR: u16 = 1;
+/// 按文件中的数量预分配的最大元素个数
+const MAX_PREALLOCATION: usize = 1024;
+
+/// 常量池字符串扫描结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringConstants {
+    pub strings: Vec<String>,
+    pub complete: bool,     // 为false时表示遇到未知数据，后续常量未扫描
+}
+
+/// 指令操作数的编码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    None,
+    Byte,    // 1字节
+    Varint,  // LEB128 变长 u32
+    ByteVarint,  // 1字节 + 变长 u32（扩展指令）
+}
+
+/// 操作码表：返回指令操作数的编码方式，未知操作码返回None
+pub fn operand_kind(opcode: u8) -> Option<OperandKind> {
+    match opcode {
+        // 带u32操作数的指令
+        0x00 | 0x02..=0x05 | 0x40..=0x43 | 0x50 | 0x60 | 0x64..=0x66 => Some(OperandKind::Varint),
+        // 带1字节操作数的指令
+        0x67 => Some(OperandKind::Byte),
+        // 扩展指令：扩展 id + 操作数
+        0xE0 => Some(OperandKind::ByteVarint),
+        // 无操作数的指令
+        0x01 | 0x10..=0x15 | 0x20..=0x25 | 0x30..=0x32 | 0x51
+        | 0x61..=0x63 | 0x68 | 0x70 | 0x71 | 0xF0 | 0xFF => Some(OperandKind::None),
+        _ => None,
+    }
+}
+
+/// 文件头中记录的整数位宽
+fn int_width_bits(int_width: IntWidth) -> u8 {
+    match int_width {
+        IntWidth::I32 => 32,
+        IntWidth::I64 => 64,
+    }
+}
 
 /// 字节码序列化器
 pub struct BytecodeSerializer;
@@ -12,16 +55,26 @@ pub struct BytecodeSerializer;
 impl BytecodeSerializer {
     /// 将Chunk序列化为字节码文件
     pub fn serialize<W: Write>(chunk: &Chunk, writer: &mut W) -> IoResult<()> {
+        Self::serialize_with_int_width(chunk, writer, IntWidth::default())
+    }
+
+    /// 按指定整数位宽序列化（i32模式下整数常量标记为Int32并检查范围）
+    pub fn serialize_with_int_width<W: Write>(
+        chunk: &Chunk,
+        writer: &mut W,
+        int_width: IntWidth,
+    ) -> IoResult<()> {
         // 写入文件头
         writer.write_all(&MAGIC)?;
         writer.write_all(&VERSION_MAJOR.to_le_bytes())?;
         writer.write_all(&VERSION_MINOR.to_le_bytes())?;
-        writer.write_all(&(chunk.constants.len() as u32).to_le_bytes())?;
-        writer.write_all(&(chunk.code.len() as u32).to_le_bytes())?;
+        writer.write_all(&[int_width_bits(int_width)])?;
+        Self::write_u32(writer, chunk.constants.len() as u32)?;
+        Self::write_u32(writer, chunk.code.len() as u32)?;
 
         // 写入常量池
         for constant in &chunk.constants {
-            Self::write_value(constant, writer)?;
+            Self::write_value(constant, writer, int_width)?;
         }
 
         // 写入指令序列
@@ -31,19 +84,31 @@ impl BytecodeSerializer {
 
         // 写入行号信息
         for line in &chunk.lines {
-            writer.write_all(&(*line as u32).to_le_bytes())?;
+            Self::write_u32(writer, *line as u32)?;
         }
 
         Ok(())
     }
 
     /// 写入Value
-    fn write_value<W: Write>(value: &Value, writer: &mut W) -> IoResult<()> {
+    pub(crate) fn write_value<W: Write>(value: &Value, writer: &mut W, int_width: IntWidth) -> IoResult<()> {
         match value {
-            Value::Integer(i) => {
-                writer.write_all(&[0x01])?; // Type ID
-                writer.write_all(&i.to_le_bytes())?;
-            }
+            Value::Integer(i) => match int_width {
+                IntWidth::I64 => {
+                    writer.write_all(&[0x01])?; // Type ID
+                    Self::write_i64(writer, *i)?;
+                }
+                IntWidth::I32 => {
+                    if i32::try_from(*i).is_err() {
+                        return Err(Error::new(
+                            ErrorKind::InvalidData,
+                            format!("Integer {} out of i32 range", i),
+                        ));
+                    }
+                    writer.write_all(&[0x0A])?;
+                    Self::write_i64(writer, *i)?;
+                }
+            },
             Value::Float(f) => {
                 writer.write_all(&[0x02])?;
                 writer.write_all(&f.to_le_bytes())?;
@@ -51,7 +116,7 @@ impl BytecodeSerializer {
             Value::String(s) => {
                 writer.write_all(&[0x03])?;
                 let bytes = s.as_bytes();
-                writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+                Self::write_u32(writer, bytes.len() as u32)?;
                 writer.write_all(bytes)?;
             }
             Value::Boolean(b) => {
@@ -66,26 +131,40 @@ impl BytecodeSerializer {
                 writer.write_all(encoded.as_bytes())?;
             }
             Value::Array(arr) => {
+                let arr = arr.borrow();
                 writer.write_all(&[0x05])?;
-                writer.write_all(&(arr.len() as u32).to_le_bytes())?;
-                for elem in arr {
-                    Self::write_value(elem, writer)?;
+                Self::write_u32(writer, arr.len() as u32)?;
+                for elem in arr.iter() {
+                    Self::write_value(elem, writer, int_width)?;
                 }
             }
             Value::Function(func) => {
                 writer.write_all(&[0x06])?;
-                Self::write_function(func, writer)?;
+                Self::write_function(func, writer, int_width)?;
             }
             Value::Struct(s) => {
                 writer.write_all(&[0x08])?;
                 let name_bytes = s.struct_name.as_bytes();
-                writer.write_all(&(name_bytes.len() as u32).to_le_bytes())?;
+                Self::write_u32(writer, name_bytes.len() as u32)?;
                 writer.write_all(name_bytes)?;
-                writer.write_all(&(s.fields.len() as u32).to_le_bytes())?;
+                Self::write_u32(writer, s.fields.len() as u32)?;
                 for field in &s.fields {
-                    Self::write_value(field, writer)?;
+                    Self::write_value(field, writer, int_width)?;
                 }
             }
+            Value::Range { start, end, step, inclusive } => {
+                writer.write_all(&[0x0C])?;
+                Self::write_i64(writer, *start)?;
+                Self::write_i64(writer, *end)?;
+                Self::write_i64(writer, *step)?;
+                writer.write_all(&[*inclusive as u8])?;
+            }
+            Value::NativeFunction(native) => {
+                writer.write_all(&[0x0B])?;
+                let name_bytes = native.name.as_bytes();
+                Self::write_u32(writer, name_bytes.len() as u32)?;
+                writer.write_all(name_bytes)?;
+            }
             Value::Null => {
                 writer.write_all(&[0x07])?;
             }
@@ -94,22 +173,23 @@ impl BytecodeSerializer {
     }
 
     /// 写入Function
-    fn write_function<W: Write>(func: &Function, writer: &mut W) -> IoResult<()> {
+    pub(crate) fn write_function<W: Write>(func: &Function, writer: &mut W, int_width: IntWidth) -> IoResult<()> {
         // 写入函数名
         let name_bytes = func.name.as_bytes();
-        writer.write_all(&(name_bytes.len() as u32).to_le_bytes())?;
+        Self::write_u32(writer, name_bytes.len() as u32)?;
         writer.write_all(name_bytes)?;
 
-        // 写入参数数量和局部变量数量
-        writer.write_all(&(func.arity as u32).to_le_bytes())?;
-        writer.write_all(&(func.locals_count as u32).to_le_bytes())?;
+        // 写入参数数量、局部变量数量和最大栈深度
+        Self::write_u32(writer, func.arity as u32)?;
+        Self::write_u32(writer, func.locals_count as u32)?;
+        Self::write_u32(writer, func.max_stack as u32)?;
 
         // 递归写入函数的Chunk
-        writer.write_all(&(func.chunk.constants.len() as u32).to_le_bytes())?;
-        writer.write_all(&(func.chunk.code.len() as u32).to_le_bytes())?;
+        Self::write_u32(writer, func.chunk.constants.len() as u32)?;
+        Self::write_u32(writer, func.chunk.code.len() as u32)?;
 
         for constant in &func.chunk.constants {
-            Self::write_value(constant, writer)?;
+            Self::write_value(constant, writer, int_width)?;
         }
 
         for opcode in &func.chunk.code {
@@ -117,35 +197,57 @@ impl BytecodeSerializer {
         }
 
         for line in &func.chunk.lines {
-            writer.write_all(&(*line as u32).to_le_bytes())?;
+            Self::write_u32(writer, *line as u32)?;
         }
 
         Ok(())
     }
 
+    /// 写入 LEB128 无符号变长整数（每字节7位，最高位表示后续还有字节）
+    fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> IoResult<()> {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                return writer.write_all(&[byte]);
+            }
+            writer.write_all(&[byte | 0x80])?;
+        }
+    }
+
+    /// 写入长度、索引等u32字段（变长编码，小值只占1字节）
+    pub(crate) fn write_u32<W: Write>(writer: &mut W, value: u32) -> IoResult<()> {
+        Self::write_varint(writer, value as u64)
+    }
+
+    /// 写入有符号整数（ZigZag 映射后按变长编码，绝对值小的负数同样紧凑）
+    fn write_i64<W: Write>(writer: &mut W, value: i64) -> IoResult<()> {
+        Self::write_varint(writer, ((value << 1) ^ (value >> 63)) as u64)
+    }
+
     /// 写入OpCode
     fn write_opcode<W: Write>(opcode: &OpCode, writer: &mut W) -> IoResult<()> {
         match opcode {
             OpCode::LoadConst(idx) => {
                 writer.write_all(&[0x00])?;
-                writer.write_all(&(*idx as u32).to_le_bytes())?;
+                Self::write_u32(writer, *idx as u32)?;
             }
             OpCode::LoadNull => writer.write_all(&[0x01])?,
             OpCode::LoadLocal(slot) => {
                 writer.write_all(&[0x02])?;
-                writer.write_all(&(*slot as u32).to_le_bytes())?;
+                Self::write_u32(writer, *slot as u32)?;
             }
             OpCode::StoreLocal(slot) => {
                 writer.write_all(&[0x03])?;
-                writer.write_all(&(*slot as u32).to_le_bytes())?;
+                Self::write_u32(writer, *slot as u32)?;
             }
             OpCode::LoadGlobal(idx) => {
                 writer.write_all(&[0x04])?;
-                writer.write_all(&(*idx as u32).to_le_bytes())?;
+                Self::write_u32(writer, *idx as u32)?;
             }
             OpCode::StoreGlobal(idx) => {
                 writer.write_all(&[0x05])?;
-                writer.write_all(&(*idx as u32).to_le_bytes())?;
+                Self::write_u32(writer, *idx as u32)?;
             }
             OpCode::Add => writer.write_all(&[0x10])?,
             OpCode::Subtract => writer.write_all(&[0x11])?,
@@ -164,48 +266,57 @@ impl BytecodeSerializer {
             OpCode::Or => writer.write_all(&[0x32])?,
             OpCode::Jump(offset) => {
                 writer.write_all(&[0x40])?;
-                writer.write_all(&(*offset as u32).to_le_bytes())?;
+                Self::write_u32(writer, *offset as u32)?;
             }
             OpCode::JumpIfFalse(offset) => {
                 writer.write_all(&[0x41])?;
-                writer.write_all(&(*offset as u32).to_le_bytes())?;
+                Self::write_u32(writer, *offset as u32)?;
             }
             OpCode::JumpIfTrue(offset) => {
                 writer.write_all(&[0x42])?;
-                writer.write_all(&(*offset as u32).to_le_bytes())?;
+                Self::write_u32(writer, *offset as u32)?;
             }
             OpCode::Loop(offset) => {
                 writer.write_all(&[0x43])?;
-                writer.write_all(&(*offset as u32).to_le_bytes())?;
+                Self::write_u32(writer, *offset as u32)?;
             }
             OpCode::Call(argc) => {
                 writer.write_all(&[0x50])?;
-                writer.write_all(&(*argc as u32).to_le_bytes())?;
+                Self::write_u32(writer, *argc as u32)?;
             }
             OpCode::Return => writer.write_all(&[0x51])?,
             OpCode::NewArray(size) => {
                 writer.write_all(&[0x60])?;
-                writer.write_all(&(*size as u32).to_le_bytes())?;
+                Self::write_u32(writer, *size as u32)?;
             }
+            OpCode::NewRange(inclusive) => {
+                writer.write_all(&[0x67])?;
+                writer.write_all(&[*inclusive as u8])?;
+            }
+            OpCode::RangeStep => writer.write_all(&[0x68])?,
             OpCode::ArrayGet => writer.write_all(&[0x61])?,
             OpCode::ArraySet => writer.write_all(&[0x62])?,
             OpCode::ArrayLen => writer.write_all(&[0x63])?,
             OpCode::NewStruct(field_count) => {
                 writer.write_all(&[0x64])?;
-                writer.write_all(&(*field_count as u32).to_le_bytes())?;
+                Self::write_u32(writer, *field_count as u32)?;
             }
             OpCode::FieldGet(idx) => {
                 writer.write_all(&[0x65])?;
-                writer.write_all(&(*idx as u32).to_le_bytes())?;
+                Self::write_u32(writer, *idx as u32)?;
             }
             OpCode::FieldSet(idx) => {
                 writer.write_all(&[0x66])?;
-                writer.write_all(&(*idx as u32).to_le_bytes())?;
+                Self::write_u32(writer, *idx as u32)?;
             }
             OpCode::Pop => writer.write_all(&[0x70])?,
             OpCode::Dup => writer.write_all(&[0x71])?,
             OpCode::Print => writer.write_all(&[0xF0])?,
             OpCode::Halt => writer.write_all(&[0xFF])?,
+            OpCode::Extension(id, operand) => {
+                writer.write_all(&[0xE0, *id])?;
+                Self::write_u32(writer, *operand)?;
+            }
         }
         Ok(())
     }
@@ -217,7 +328,71 @@ pub struct BytecodeDeserializer;
 impl BytecodeDeserializer {
     /// 从字节码文件反序列化为Chunk
     pub fn deserialize<R: Read>(reader: &mut R) -> IoResult<Chunk> {
-        // 读取并验证文件头
+        Self::deserialize_with_int_width(reader).map(|(chunk, _)| chunk)
+    }
+
+    /// 反序列化并返回文件头记录的整数位宽（1.0 文件没有记录，按 64 位处理）
+    pub fn deserialize_with_int_width<R: Read>(reader: &mut R) -> IoResult<(Chunk, IntWidth)> {
+        let int_width = Self::read_header(reader)?;
+
+        // 读取常量和指令数量
+        let constants_count = Self::read_constants_count(reader)?;
+        let code_count = Self::read_u32(reader)?;
+
+        // 读取常量池
+        let mut constants = Vec::with_capacity(preallocation(constants_count as usize));
+        for _ in 0..constants_count {
+            constants.push(Self::read_value(reader)?);
+        }
+
+        // 读取指令序列
+        let mut code = Vec::with_capacity(preallocation(code_count as usize));
+        for _ in 0..code_count {
+            code.push(Self::read_opcode(reader)?);
+        }
+
+        // 读取行号信息
+        let mut lines = Vec::with_capacity(preallocation(code_count as usize));
+        for _ in 0..code_count {
+            lines.push(Self::read_u32(reader)? as usize);
+        }
+
+        let chunk = Chunk {
+            code,
+            constants,
+            lines,
+        };
+        chunk
+            .verify_local_access(0)
+            .map_err(|message| Error::new(ErrorKind::InvalidData, message))?;
+        Ok((chunk, int_width))
+    }
+
+    /// 只读取文件头和常量池，提取所有字符串常量（包括函数常量内的）。
+    /// 顶层指令序列位于常量池之后，无需解码；函数常量内的指令按长度表跳过，
+    /// 遇到无法确定长度的未知操作码时停止扫描并标记为不完整。
+    pub fn read_string_constants<R: Read>(reader: &mut R) -> IoResult<StringConstants> {
+        Self::read_header(reader)?;
+
+        let constants_count = Self::read_constants_count(reader)?;
+        let _code_count = Self::read_u32(reader)?;
+
+        let mut result = StringConstants {
+            strings: Vec::new(),
+            complete: true,
+        };
+        for _ in 0..constants_count {
+            if !Self::scan_value(reader, &mut result.strings)? {
+                result.complete = false;
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// 读取并验证文件头，返回记录的整数位宽
+    fn read_header<R: Read>(reader: &mut R) -> IoResult<IntWidth> {
         let mut magic = [0u8; 4];
         reader.read_exact(&mut magic)?;
         if magic != MAGIC {
@@ -239,45 +414,145 @@ impl BytecodeDeserializer {
             ));
         }
 
-        // 读取常量和指令数量
-        let constants_count = Self::read_u32(reader)?;
+        if ver_minor == 0 {
+            return Ok(IntWidth::I64);
+        }
+        let mut bits = [0u8; 1];
+        reader.read_exact(&mut bits)?;
+        match bits[0] {
+            32 => Ok(IntWidth::I32),
+            64 => Ok(IntWidth::I64),
+            other => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported integer width {}", other),
+            )),
+        }
+    }
+
+    /// 扫描一个常量并收集其中的字符串，返回false表示遇到无法跳过的数据
+    fn scan_value<R: Read>(reader: &mut R, strings: &mut Vec<String>) -> IoResult<bool> {
+        let mut type_id = [0u8; 1];
+        reader.read_exact(&mut type_id)?;
+
+        match type_id[0] {
+            0x01 | 0x0A => {
+                Self::read_varint(reader)?;
+            }
+            0x02 => Self::skip_bytes(reader, 8)?,
+            0x0C => {
+                for _ in 0..3 {
+                    Self::read_varint(reader)?;
+                }
+                Self::skip_bytes(reader, 1)?;
+            }
+            0x04 => Self::skip_bytes(reader, 1)?,
+            0x07 => {}
+            0x03 => {
+                let s = Self::read_string(reader)?;
+                strings.push(s);
+            }
+            0x09 => {
+                let mut len_byte = [0u8; 1];
+                reader.read_exact(&mut len_byte)?;
+                Self::skip_bytes(reader, len_byte[0] as usize)?;
+            }
+            0x0B => {
+                let name_len = Self::read_u32(reader)? as usize;
+                Self::skip_bytes(reader, name_len)?;
+            }
+            0x05 => {
+                let len = Self::read_u32(reader)?;
+                for _ in 0..len {
+                    if !Self::scan_value(reader, strings)? {
+                        return Ok(false);
+                    }
+                }
+            }
+            0x08 => {
+                let name_len = Self::read_u32(reader)? as usize;
+                Self::skip_bytes(reader, name_len)?;
+                let field_count = Self::read_u32(reader)?;
+                for _ in 0..field_count {
+                    if !Self::scan_value(reader, strings)? {
+                        return Ok(false);
+                    }
+                }
+            }
+            0x06 => return Self::scan_function(reader, strings),
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// 扫描函数常量：收集其常量池中的字符串并跳过指令和行号
+    fn scan_function<R: Read>(reader: &mut R, strings: &mut Vec<String>) -> IoResult<bool> {
+        let name_len = Self::read_u32(reader)? as usize;
+        Self::skip_bytes(reader, name_len)?;
+
+        // 参数数量、局部变量数量、最大栈深度
+        for _ in 0..3 {
+            Self::read_u32(reader)?;
+        }
+
+        let constants_count = Self::read_constants_count(reader)?;
         let code_count = Self::read_u32(reader)?;
 
-        // 读取常量池
-        let mut constants = Vec::with_capacity(constants_count as usize);
         for _ in 0..constants_count {
-            constants.push(Self::read_value(reader)?);
+            if !Self::scan_value(reader, strings)? {
+                return Ok(false);
+            }
         }
 
-        // 读取指令序列
-        let mut code = Vec::with_capacity(code_count as usize);
         for _ in 0..code_count {
-            code.push(Self::read_opcode(reader)?);
+            let mut opcode = [0u8; 1];
+            reader.read_exact(&mut opcode)?;
+            match operand_kind(opcode[0]) {
+                Some(OperandKind::None) => {}
+                Some(OperandKind::Byte) => Self::skip_bytes(reader, 1)?,
+                Some(OperandKind::Varint) => {
+                    Self::read_u32(reader)?;
+                }
+                Some(OperandKind::ByteVarint) => {
+                    Self::skip_bytes(reader, 1)?;
+                    Self::read_u32(reader)?;
+                }
+                None => return Ok(false),
+            }
         }
 
-        // 读取行号信息
-        let mut lines = Vec::with_capacity(code_count as usize);
+        // 行号信息
         for _ in 0..code_count {
-            lines.push(Self::read_u32(reader)? as usize);
+            Self::read_u32(reader)?;
         }
+        Ok(true)
+    }
 
-        Ok(Chunk {
-            code,
-            constants,
-            lines,
-        })
+    /// 辅助方法：跳过指定字节数
+    fn skip_bytes<R: Read>(reader: &mut R, count: usize) -> IoResult<()> {
+        let skipped = std::io::copy(&mut reader.take(count as u64), &mut std::io::sink())?;
+        if skipped < count as u64 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Unexpected end of bytecode"));
+        }
+        Ok(())
     }
 
     /// 读取Value
-    fn read_value<R: Read>(reader: &mut R) -> IoResult<Value> {
+    pub(crate) fn read_value<R: Read>(reader: &mut R) -> IoResult<Value> {
         let mut type_id = [0u8; 1];
         reader.read_exact(&mut type_id)?;
 
         match type_id[0] {
-            0x01 => {
-                let mut bytes = [0u8; 8];
-                reader.read_exact(&mut bytes)?;
-                Ok(Value::Integer(i64::from_le_bytes(bytes)))
+            0x01 => Ok(Value::Integer(Self::read_i64(reader)?)),
+            0x0A => {
+                let value = Self::read_i64(reader)?;
+                if i32::try_from(value).is_err() {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Integer {} out of i32 range", value),
+                    ));
+                }
+                Ok(Value::Integer(value))
             }
             0x02 => {
                 let mut bytes = [0u8; 8];
@@ -285,12 +560,7 @@ impl BytecodeDeserializer {
                 Ok(Value::Float(f64::from_le_bytes(bytes)))
             }
             0x03 => {
-                let len = Self::read_u32(reader)? as usize;
-                let mut bytes = vec![0u8; len];
-                reader.read_exact(&mut bytes)?;
-                String::from_utf8(bytes)
-                    .map(Value::String)
-                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+                Self::read_string(reader).map(Value::String)
             }
             0x04 => {
                 let mut byte = [0u8; 1];
@@ -309,23 +579,36 @@ impl BytecodeDeserializer {
             }
             0x05 => {
                 let len = Self::read_u32(reader)? as usize;
-                let mut arr = Vec::with_capacity(len);
+                let mut arr = Vec::with_capacity(preallocation(len));
                 for _ in 0..len {
                     arr.push(Self::read_value(reader)?);
                 }
-                Ok(Value::Array(arr))
+                Ok(Value::array(arr))
             }
             0x06 => Ok(Value::Function(Self::read_function(reader)?)),
             0x07 => Ok(Value::Null),
+            0x0C => {
+                let start = Self::read_i64(reader)?;
+                let end = Self::read_i64(reader)?;
+                let step = Self::read_i64(reader)?;
+                let mut inclusive = [0u8; 1];
+                reader.read_exact(&mut inclusive)?;
+                Ok(Value::Range { start, end, step, inclusive: inclusive[0] != 0 })
+            }
+            0x0B => {
+                let name = Self::read_string(reader)?;
+                super::builtins::lookup(&name)
+                    .map(Value::NativeFunction)
+                    .ok_or_else(|| Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Unknown native function: {}", name),
+                    ))
+            }
             0x08 => {
-                let name_len = Self::read_u32(reader)? as usize;
-                let mut name_bytes = vec![0u8; name_len];
-                reader.read_exact(&mut name_bytes)?;
-                let struct_name = String::from_utf8(name_bytes)
-                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                let struct_name = Self::read_string(reader)?;
                 
                 let field_count = Self::read_u32(reader)? as usize;
-                let mut fields = Vec::with_capacity(field_count);
+                let mut fields = Vec::with_capacity(preallocation(field_count));
                 for _ in 0..field_count {
                     fields.push(Self::read_value(reader)?);
                 }
@@ -342,38 +625,35 @@ impl BytecodeDeserializer {
     }
 
     /// 读取Function
-    fn read_function<R: Read>(reader: &mut R) -> IoResult<Function> {
+    pub(crate) fn read_function<R: Read>(reader: &mut R) -> IoResult<Function> {
         // 读取函数名
-        let name_len = Self::read_u32(reader)? as usize;
-        let mut name_bytes = vec![0u8; name_len];
-        reader.read_exact(&mut name_bytes)?;
-        let name = String::from_utf8(name_bytes)
-            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let name = Self::read_string(reader)?;
 
-        // 读取参数和局部变量数量
+        // 读取参数数量、局部变量数量和最大栈深度
         let arity = Self::read_u32(reader)? as usize;
         let locals_count = Self::read_u32(reader)? as usize;
+        let max_stack = Self::read_u32(reader)? as usize;
 
         // 读取函数的Chunk
-        let constants_count = Self::read_u32(reader)?;
+        let constants_count = Self::read_constants_count(reader)?;
         let code_count = Self::read_u32(reader)?;
 
-        let mut constants = Vec::with_capacity(constants_count as usize);
+        let mut constants = Vec::with_capacity(preallocation(constants_count as usize));
         for _ in 0..constants_count {
             constants.push(Self::read_value(reader)?);
         }
 
-        let mut code = Vec::with_capacity(code_count as usize);
+        let mut code = Vec::with_capacity(preallocation(code_count as usize));
         for _ in 0..code_count {
             code.push(Self::read_opcode(reader)?);
         }
 
-        let mut lines = Vec::with_capacity(code_count as usize);
+        let mut lines = Vec::with_capacity(preallocation(code_count as usize));
         for _ in 0..code_count {
             lines.push(Self::read_u32(reader)? as usize);
         }
 
-        Ok(Function {
+        let function = Function {
             name,
             arity,
             chunk: Chunk {
@@ -382,7 +662,12 @@ impl BytecodeDeserializer {
                 lines,
             },
             locals_count,
-        })
+            max_stack,
+        };
+        function
+            .verify()
+            .map_err(|message| Error::new(ErrorKind::InvalidData, message))?;
+        Ok(function)
     }
 
     /// 读取OpCode
@@ -425,10 +710,21 @@ impl BytecodeDeserializer {
             0x64 => Ok(OpCode::NewStruct(Self::read_u32(reader)? as usize)),
             0x65 => Ok(OpCode::FieldGet(Self::read_u32(reader)? as usize)),
             0x66 => Ok(OpCode::FieldSet(Self::read_u32(reader)? as usize)),
+            0x67 => {
+                let mut inclusive = [0u8; 1];
+                reader.read_exact(&mut inclusive)?;
+                Ok(OpCode::NewRange(inclusive[0] != 0))
+            }
+            0x68 => Ok(OpCode::RangeStep),
             0x70 => Ok(OpCode::Pop),
             0x71 => Ok(OpCode::Dup),
             0xF0 => Ok(OpCode::Print),
             0xFF => Ok(OpCode::Halt),
+            0xE0 => {
+                let mut id = [0u8; 1];
+                reader.read_exact(&mut id)?;
+                Ok(OpCode::Extension(id[0], Self::read_u32(reader)?))
+            }
             _ => Err(Error::new(
                 ErrorKind::InvalidData,
                 format!("Unknown opcode: 0x{:02X}", opcode[0]),
@@ -436,10 +732,286 @@ impl BytecodeDeserializer {
         }
     }
 
+    /// 辅助方法：读取 LEB128 无符号变长整数
+    fn read_varint<R: Read>(reader: &mut R) -> IoResult<u64> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let mut byte = [0u8; 1];
+            reader.read_exact(&mut byte)?;
+            let payload = (byte[0] & 0x7F) as u64;
+            if (shift == 63 && payload > 1) || shift > 63 {
+                return Err(Error::new(ErrorKind::InvalidData, "Varint too long"));
+            }
+            value |= payload << shift;
+            if byte[0] & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    /// 辅助方法：读取ZigZag编码的i64
+    fn read_i64<R: Read>(reader: &mut R) -> IoResult<i64> {
+        let raw = Self::read_varint(reader)?;
+        Ok((raw >> 1) as i64 ^ -((raw & 1) as i64))
+    }
+
     /// 辅助方法：读取u32
-    fn read_u32<R: Read>(reader: &mut R) -> IoResult<u32> {
-        let mut bytes = [0u8; 4];
-        reader.read_exact(&mut bytes)?;
-        Ok(u32::from_le_bytes(bytes))
+    pub(crate) fn read_u32<R: Read>(reader: &mut R) -> IoResult<u32> {
+        let value = Self::read_varint(reader)?;
+        u32::try_from(value)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, format!("Value {} out of u32 range", value)))
+    }
+
+    /// 读取长度前缀的 UTF-8 字符串。按实际读到的字节增长缓冲区，长度字段再大也不会预先分配
+    pub(crate) fn read_string<R: Read>(reader: &mut R) -> IoResult<String> {
+        let len = Self::read_u32(reader)? as u64;
+        let mut bytes = Vec::new();
+        reader.take(len).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < len {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Unexpected end of bytecode"));
+        }
+        String::from_utf8(bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// 读取常量池条目数量，超过 `DEFAULT_MAX_CONSTANTS` 时拒绝（在按数量预分配之前检查）
+    fn read_constants_count<R: Read>(reader: &mut R) -> IoResult<u32> {
+        let count = Self::read_u32(reader)?;
+        if count as usize > DEFAULT_MAX_CONSTANTS {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Constant pool has {} entries, exceeding the limit of {}", count, DEFAULT_MAX_CONSTANTS),
+            ));
+        }
+        Ok(count)
+    }
+}
+
+/// 按文件中的数量预分配时的容量：数量来自未验证的输入，超出 `MAX_PREALLOCATION` 的部分随读取增长
+pub(crate) fn preallocation(count: usize) -> usize {
+    count.min(MAX_PREALLOCATION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::MAX_LOCALS;
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_string_constants_from_compiled_file() {
+        let source = r#"
+            fn greet() {
+                print("hello");
+            }
+            let name = "zero";
+            print(name);
+        "#;
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let chunk = Compiler::new().compile(program).unwrap();
+
+        let mut bytes = Vec::new();
+        BytecodeSerializer::serialize(&chunk, &mut bytes).unwrap();
+
+        let result = BytecodeDeserializer::read_string_constants(&mut Cursor::new(bytes)).unwrap();
+        assert!(result.complete);
+        assert!(result.strings.contains(&"hello".to_string()));
+        assert!(result.strings.contains(&"zero".to_string()));
+    }
+
+    #[test]
+    fn test_escaped_string_constant_round_trip() {
+        let tokens = Lexer::new(r#"let s = "tab\there";"#.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let chunk = Compiler::new().compile(program).unwrap();
+        assert!(chunk.constants.contains(&Value::String("tab\there".to_string())));
+
+        let mut bytes = Vec::new();
+        BytecodeSerializer::serialize(&chunk, &mut bytes).unwrap();
+        let result = BytecodeDeserializer::read_string_constants(&mut Cursor::new(bytes)).unwrap();
+        assert!(result.strings.contains(&"tab\there".to_string()));
+    }
+
+    #[test]
+    fn test_operand_kind_covers_all_opcodes() {
+        let opcodes = [
+            OpCode::LoadConst(1), OpCode::LoadNull, OpCode::Jump(3), OpCode::Call(2),
+            OpCode::Return, OpCode::NewStruct(1), OpCode::FieldSet(0), OpCode::NewRange(true), OpCode::RangeStep,
+            OpCode::Print, OpCode::Halt, OpCode::Extension(7, 300),
+        ];
+        for opcode in &opcodes {
+            let mut bytes = Vec::new();
+            BytecodeSerializer::write_opcode(opcode, &mut bytes).unwrap();
+            let expected = match bytes.len() - 1 {
+                0 => OperandKind::None,
+                _ if matches!(opcode, OpCode::NewRange(_)) => OperandKind::Byte,
+                _ if matches!(opcode, OpCode::Extension(_, _)) => OperandKind::ByteVarint,
+                _ => OperandKind::Varint,
+            };
+            assert_eq!(operand_kind(bytes[0]), Some(expected), "{:?}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_varint_round_trip_edge_values() {
+        let integers = [
+            0, 1, -1, 63, -64, 64, -65, 127, 128, 300, -300,
+            i32::MIN as i64, i32::MAX as i64, i64::MIN, i64::MIN + 1, i64::MAX, i64::MAX - 1,
+        ];
+        for &value in &integers {
+            let mut bytes = Vec::new();
+            BytecodeSerializer::write_i64(&mut bytes, value).unwrap();
+            assert!(bytes.len() <= 10);
+            let decoded = BytecodeDeserializer::read_i64(&mut Cursor::new(bytes)).unwrap();
+            assert_eq!(decoded, value);
+        }
+
+        for &value in &[0u32, 1, 127, 128, 16_383, 16_384, 1 << 21, 1 << 28, u32::MAX] {
+            let mut bytes = Vec::new();
+            BytecodeSerializer::write_u32(&mut bytes, value).unwrap();
+            assert!(bytes.len() <= 5);
+            let decoded = BytecodeDeserializer::read_u32(&mut Cursor::new(bytes)).unwrap();
+            assert_eq!(decoded, value);
+        }
+
+        // 小整数只占1字节
+        let mut bytes = Vec::new();
+        BytecodeSerializer::write_i64(&mut bytes, -5).unwrap();
+        assert_eq!(bytes.len(), 1);
+
+        // 超出u32范围或过长的变长整数被拒绝
+        let mut bytes = Vec::new();
+        BytecodeSerializer::write_varint(&mut bytes, u32::MAX as u64 + 1).unwrap();
+        assert!(BytecodeDeserializer::read_u32(&mut Cursor::new(bytes)).is_err());
+        let overlong = vec![0xFF; 11];
+        assert!(BytecodeDeserializer::read_varint(&mut Cursor::new(overlong)).is_err());
+    }
+
+    #[test]
+    fn test_chunk_round_trip_with_large_values() {
+        let mut chunk = Chunk::new();
+        for value in [i64::MIN, i64::MAX, -1, 0] {
+            chunk.add_constant(Value::Integer(value));
+        }
+        chunk.add_constant(Value::Range { start: i64::MIN, end: i64::MAX, step: -3, inclusive: true });
+        chunk.write(OpCode::LoadConst(3), 1);
+        chunk.write(OpCode::Jump(u32::MAX as usize), 70_000);
+        chunk.write(OpCode::LoadGlobal(200), 2);
+        chunk.write(OpCode::Extension(255, u32::MAX), 2);
+        chunk.write(OpCode::Halt, 3);
+
+        let mut bytes = Vec::new();
+        BytecodeSerializer::serialize(&chunk, &mut bytes).unwrap();
+        let restored = BytecodeDeserializer::deserialize(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(restored, chunk);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_header_records_int_width() {
+        let mut chunk = Chunk::new();
+        chunk.add_constant(Value::Integer(i32::MIN as i64));
+        chunk.write(OpCode::Halt, 1);
+
+        for width in [IntWidth::I32, IntWidth::I64] {
+            let mut bytes = Vec::new();
+            BytecodeSerializer::serialize_with_int_width(&chunk, &mut bytes, width).unwrap();
+            let (restored, restored_width) =
+                BytecodeDeserializer::deserialize_with_int_width(&mut Cursor::new(bytes)).unwrap();
+            assert_eq!((restored, restored_width), (chunk.clone(), width));
+        }
+
+        // 1.0 文件没有位宽字节，按 64 位读取
+        let mut bytes = Vec::new();
+        BytecodeSerializer::serialize(&chunk, &mut bytes).unwrap();
+        bytes[6..8].copy_from_slice(&0u16.to_le_bytes());
+        bytes.remove(8);
+        let (restored, width) = BytecodeDeserializer::deserialize_with_int_width(&mut Cursor::new(bytes.clone())).unwrap();
+        assert_eq!((restored, width), (chunk.clone(), IntWidth::I64));
+
+        // 未知位宽被拒绝
+        bytes[6..8].copy_from_slice(&VERSION_MINOR.to_le_bytes());
+        bytes.insert(8, 16);
+        let err = BytecodeDeserializer::deserialize(&mut Cursor::new(bytes)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_signed_zero_round_trip() {
+        let mut chunk = Chunk::new();
+        chunk.add_constant(Value::Float(0.0));
+        chunk.add_constant(Value::Float(-0.0));
+        chunk.add_constant(Value::Float(f64::NAN));
+        chunk.write(OpCode::Halt, 1);
+
+        let mut bytes = Vec::new();
+        BytecodeSerializer::serialize(&chunk, &mut bytes).unwrap();
+        let restored = BytecodeDeserializer::deserialize(&mut Cursor::new(bytes)).unwrap();
+        // `==` 认为 0.0 与 -0.0 相等，因此比较位模式
+        let bits: Vec<u64> = restored.constants.iter().map(|c| match c {
+            Value::Float(f) => f.to_bits(),
+            other => panic!("expected float, got {:?}", other),
+        }).collect();
+        assert_eq!(bits, vec![0.0f64.to_bits(), (-0.0f64).to_bits(), f64::NAN.to_bits()]);
+    }
+
+    #[test]
+    fn test_inconsistent_function_arity_rejected() {
+        let source = "fn add(a: int, b: int) -> int { let sum = a + b; return sum; } let x = add(1, 2);";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let chunk = Compiler::new().compile(program).unwrap();
+        let index = chunk.constants.iter().position(|c| matches!(c, Value::Function(_))).unwrap();
+
+        let load = |chunk: &Chunk| {
+            let mut bytes = Vec::new();
+            BytecodeSerializer::serialize(chunk, &mut bytes).unwrap();
+            BytecodeDeserializer::deserialize(&mut Cursor::new(bytes))
+        };
+        let with_function = |edit: &dyn Fn(&mut Function)| {
+            let mut chunk = chunk.clone();
+            let Value::Function(function) = &mut chunk.constants[index] else { unreachable!() };
+            edit(function);
+            chunk
+        };
+        assert_eq!(load(&chunk).unwrap(), chunk);
+
+        // 声明的参数比代码使用的少：局部变量 sum 的槽位越过了帧内的值
+        let err = load(&with_function(&|f| f.arity = 1)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("function 'add': instruction"), "{}", err);
+        let err = load(&with_function(&|f| f.arity = 0)).unwrap_err();
+        assert!(err.to_string().contains("instruction 0 accesses local slot 0 but the frame holds only 0 values"), "{}", err);
+
+        let err = load(&with_function(&|f| f.arity = 4)).unwrap_err();
+        assert!(err.to_string().contains("takes 4 parameters but declares only 3 locals"), "{}", err);
+        let err = load(&with_function(&|f| f.locals_count = MAX_LOCALS + 1)).unwrap_err();
+        assert!(err.to_string().contains("exceeding the limit"), "{}", err);
+
+        // 顶层代码同样检查
+        let mut script = Chunk::new();
+        script.write(OpCode::LoadLocal(0), 1);
+        script.write(OpCode::Halt, 1);
+        assert_eq!(load(&script).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_oversized_constant_pool_rejected() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&VERSION_MAJOR.to_le_bytes());
+        bytes.extend_from_slice(&VERSION_MINOR.to_le_bytes());
+        bytes.push(64);
+        BytecodeSerializer::write_u32(&mut bytes, DEFAULT_MAX_CONSTANTS as u32 + 1).unwrap();
+        BytecodeSerializer::write_u32(&mut bytes, 0).unwrap();
+
+        let err = BytecodeDeserializer::deserialize(&mut Cursor::new(bytes.clone())).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let err = BytecodeDeserializer::read_string_constants(&mut Cursor::new(bytes)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,333 @@
+//! 命令行参数解析：把参数解析为运行模式和全局选项
+//! 新增模式时只需在 `Command` 中加一个变体，并在 `MODES` 中登记
+
+use Zero_compiler::ast::IntWidth;
+use Zero_compiler::error::ErrorMode;
+use std::fmt;
+
+/// 运行模式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// 编译源文件并在虚拟机上运行（未指定模式时的默认行为）
+    Run { source: String },
+    /// 使用旧的树遍历解释器运行
+    Old { source: String },
+    /// 编译到字节码文件（多个源文件按顺序链接为一个程序）
+    Compile { sources: Vec<String>, output: String },
+    /// 运行字节码文件
+    RunBytecode { bytecode: String },
+    /// 打印推断出的全局符号
+    Symbols { source: String },
+    /// 报告类型覆盖率（推导出具体类型的表达式比例）
+    Coverage { source: String },
+    /// 列出字节码文件中的字符串常量
+    Strings { bytecode: String },
+    /// 求值并打印单个表达式
+    Eval { expression: String },
+    /// 逐条语句展示源码、生成的字节码和执行后的虚拟机状态（教学用）
+    Explain { source: String },
+}
+
+/// 对所有模式生效的全局选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    pub error_mode: ErrorMode,
+    pub int_width: IntWidth,
+    pub lint: bool,
+    pub newline_terminators: bool,
+    pub release: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            error_mode: ErrorMode::Simple,
+            int_width: IntWidth::default(),
+            lint: false,
+            newline_terminators: false,
+            release: false,
+        }
+    }
+}
+
+/// 解析后的一次调用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub command: Command,
+    pub options: Options,
+}
+
+/// 参数解析错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliError {
+    /// 没有提供任何模式或输入文件
+    NoInput,
+    /// 未知的 `--` 选项
+    UnknownOption(String),
+    /// 指定了多个模式
+    ConflictingModes(String, String),
+    /// 模式缺少必需的参数
+    MissingArgument { mode: String, usage: String },
+    /// 多余的位置参数
+    UnexpectedArgument(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::NoInput => write!(f, "no input file or mode given"),
+            CliError::UnknownOption(option) => write!(f, "unknown option '{}'", option),
+            CliError::ConflictingModes(first, second) => {
+                write!(f, "conflicting modes '{}' and '{}'", first, second)
+            }
+            CliError::MissingArgument { mode, usage } => {
+                write!(f, "missing argument for '{}' (expected: {} {})", mode, mode, usage)
+            }
+            CliError::UnexpectedArgument(arg) => write!(f, "unexpected argument '{}'", arg),
+        }
+    }
+}
+
+/// 模式描述：标志、位置参数名、说明，以及由位置参数（数量与 `args` 一致）构造命令的函数
+/// `variadic` 为 true 时第一个位置参数可以重复出现，位置参数数量只设下限
+struct Mode {
+    flag: &'static str,
+    args: &'static [&'static str],
+    variadic: bool,
+    help: &'static str,
+    build: fn(Vec<String>) -> Command,
+}
+
+const MODES: &[Mode] = &[
+    Mode {
+        flag: "--old",
+        args: &["<source_file.zero>"],
+        variadic: false,
+        help: "use old interpreter",
+        build: |mut a| Command::Old { source: a.remove(0) },
+    },
+    Mode {
+        flag: "--compile",
+        args: &["<source_file.zero>...", "<output.zbc>"],
+        variadic: true,
+        help: "compile one or more source files, linked in order, to bytecode",
+        build: |mut a| {
+            let output = a.pop().unwrap_or_default();
+            Command::Compile { sources: a, output }
+        },
+    },
+    Mode {
+        flag: "--run",
+        args: &["<bytecode_file.zbc>"],
+        variadic: false,
+        help: "run bytecode file",
+        build: |mut a| Command::RunBytecode { bytecode: a.remove(0) },
+    },
+    Mode {
+        flag: "--symbols",
+        args: &["<source_file.zero>"],
+        variadic: false,
+        help: "print inferred global symbols",
+        build: |mut a| Command::Symbols { source: a.remove(0) },
+    },
+    Mode {
+        flag: "--coverage",
+        args: &["<source_file.zero>"],
+        variadic: false,
+        help: "report how many expressions have a known type",
+        build: |mut a| Command::Coverage { source: a.remove(0) },
+    },
+    Mode {
+        flag: "--strings",
+        args: &["<bytecode_file.zbc>"],
+        variadic: false,
+        help: "list string constants",
+        build: |mut a| Command::Strings { bytecode: a.remove(0) },
+    },
+    Mode {
+        flag: "--eval",
+        args: &["<expression>"],
+        variadic: false,
+        help: "evaluate and print a single expression",
+        build: |mut a| Command::Eval { expression: a.remove(0) },
+    },
+    Mode {
+        flag: "--explain",
+        args: &["<source_file.zero>"],
+        variadic: false,
+        help: "walk through each statement's bytecode and the VM state after it",
+        build: |mut a| Command::Explain { source: a.remove(0) },
+    },
+];
+
+/// 全局选项：标志和说明
+const OPTIONS: &[(&str, &str)] = &[
+    ("--dtl", "显示详细的错误信息（包含源码片段和修复建议）"),
+    ("--int32", "将 int 视为32位整数（字面量范围检查、溢出检测、序列化为 Int32 常量并在文件头记录位宽）"),
+    ("--lint", "启用额外的代码检查警告（自赋值、未使用的函数参数、调用实参类型不一致、遮蔽内置函数、未使用的变量、遮蔽外层变量）"),
+    ("--asi", "允许用换行代替语句末尾的分号"),
+    ("--release", "release 模式编译：移除 debug_assert 调用"),
+];
+
+/// 解析命令行参数（不含程序名）
+/// 全局选项可以出现在任意位置；模式标志之后的位置参数按顺序作为该模式的参数
+pub fn parse_args(args: &[String]) -> Result<Invocation, CliError> {
+    let mut options = Options::default();
+    let mut mode: Option<&Mode> = None;
+    let mut positional = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "--dtl" => options.error_mode = ErrorMode::Detailed,
+            "--int32" => options.int_width = IntWidth::I32,
+            "--lint" => options.lint = true,
+            "--asi" => options.newline_terminators = true,
+            "--release" => options.release = true,
+            flag if flag.starts_with("--") => {
+                let found = MODES
+                    .iter()
+                    .find(|m| m.flag == flag)
+                    .ok_or_else(|| CliError::UnknownOption(flag.to_string()))?;
+                if let Some(previous) = mode {
+                    return Err(CliError::ConflictingModes(
+                        previous.flag.to_string(),
+                        found.flag.to_string(),
+                    ));
+                }
+                mode = Some(found);
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let expected = mode.map_or(1, |m| m.args.len());
+    if positional.len() > expected && !mode.is_some_and(|m| m.variadic) {
+        return Err(CliError::UnexpectedArgument(positional[expected].clone()));
+    }
+    if positional.len() < expected {
+        return Err(match mode {
+            Some(m) => CliError::MissingArgument {
+                mode: m.flag.to_string(),
+                usage: m.args.join(" "),
+            },
+            None => CliError::NoInput,
+        });
+    }
+
+    let command = match mode {
+        Some(m) => (m.build)(positional),
+        None => Command::Run { source: positional.remove(0) },
+    };
+
+    Ok(Invocation { command, options })
+}
+
+/// 统一的用法说明
+pub fn usage(program: &str) -> String {
+    let mut text = format!("Usage: {} <source_file.zero> [options]\n", program);
+    for mode in MODES {
+        text.push_str(&format!(
+            "       {} {} {} [options]  ({})\n",
+            program,
+            mode.flag,
+            mode.args.join(" "),
+            mode.help
+        ));
+    }
+    text.push_str("\nOptions:\n");
+    for (flag, help) in OPTIONS {
+        text.push_str(&format!("  {:<8} {}\n", flag, help));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Invocation, CliError> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        parse_args(&args)
+    }
+
+    fn command(args: &[&str]) -> Command {
+        parse(args).unwrap().command
+    }
+
+    #[test]
+    fn test_parse_each_mode() {
+        assert_eq!(command(&["a.zero"]), Command::Run { source: "a.zero".to_string() });
+        assert_eq!(command(&["--old", "a.zero"]), Command::Old { source: "a.zero".to_string() });
+        assert_eq!(
+            command(&["--compile", "a.zero", "a.zbc"]),
+            Command::Compile { sources: vec!["a.zero".to_string()], output: "a.zbc".to_string() }
+        );
+        assert_eq!(
+            command(&["--compile", "a.zero", "b.zero", "out.zbc"]),
+            Command::Compile {
+                sources: vec!["a.zero".to_string(), "b.zero".to_string()],
+                output: "out.zbc".to_string(),
+            }
+        );
+        assert_eq!(command(&["--run", "a.zbc"]), Command::RunBytecode { bytecode: "a.zbc".to_string() });
+        assert_eq!(command(&["--symbols", "a.zero"]), Command::Symbols { source: "a.zero".to_string() });
+        assert_eq!(command(&["--coverage", "a.zero"]), Command::Coverage { source: "a.zero".to_string() });
+        assert_eq!(command(&["--strings", "a.zbc"]), Command::Strings { bytecode: "a.zbc".to_string() });
+        assert_eq!(command(&["--eval", "-1 + 2"]), Command::Eval { expression: "-1 + 2".to_string() });
+        assert_eq!(command(&["--explain", "a.zero"]), Command::Explain { source: "a.zero".to_string() });
+    }
+
+    #[test]
+    fn test_parse_options_anywhere() {
+        let invocation = parse(&["--dtl", "--compile", "a.zero", "--int32", "a.zbc", "--lint", "--asi", "--release"]).unwrap();
+        assert_eq!(
+            invocation.command,
+            Command::Compile { sources: vec!["a.zero".to_string()], output: "a.zbc".to_string() }
+        );
+        assert_eq!(
+            invocation.options,
+            Options {
+                error_mode: ErrorMode::Detailed,
+                int_width: IntWidth::I32,
+                lint: true,
+                newline_terminators: true,
+                release: true,
+            }
+        );
+        assert_eq!(parse(&["a.zero"]).unwrap().options, Options::default());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse(&[]), Err(CliError::NoInput));
+        assert_eq!(parse(&["--dtl"]), Err(CliError::NoInput));
+        assert_eq!(parse(&["--bogus", "a.zero"]), Err(CliError::UnknownOption("--bogus".to_string())));
+        assert_eq!(
+            parse(&["--compile", "a.zero"]),
+            Err(CliError::MissingArgument {
+                mode: "--compile".to_string(),
+                usage: "<source_file.zero>... <output.zbc>".to_string(),
+            })
+        );
+        assert_eq!(
+            parse(&["--run", "a.zbc", "b.zbc"]),
+            Err(CliError::UnexpectedArgument("b.zbc".to_string()))
+        );
+        assert_eq!(parse(&["a.zero", "b.zero"]), Err(CliError::UnexpectedArgument("b.zero".to_string())));
+        assert_eq!(
+            parse(&["--old", "--run", "a.zero"]),
+            Err(CliError::ConflictingModes("--old".to_string(), "--run".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_usage_lists_every_mode() {
+        let text = usage("zero");
+        for mode in MODES {
+            assert!(text.contains(mode.flag), "usage is missing {}", mode.flag);
+        }
+        for (flag, _) in OPTIONS {
+            assert!(text.contains(flag), "usage is missing {}", flag);
+        }
+    }
+}
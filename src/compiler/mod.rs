@@ -1,6 +1,7 @@
-use crate::ast::{Expr, Program, Stmt, BinaryOp, UnaryOp, Parameter, Type, StructType, MethodDeclaration};
-use crate::bytecode::{Chunk, OpCode, Value, Function};
+use crate::ast::{Expr, FieldOwner, Program, Stmt, BinaryOp, UnaryOp, Parameter, Type, StructType, MethodDeclaration, IntWidth};
+use crate::bytecode::{Chunk, OpCode, Value, Function, DEFAULT_MAX_CONSTANTS, MAX_LOCALS};
 use std::collections::HashMap;
+use std::fmt;
 
 /// 编译错误
 #[derive(Debug)]
@@ -11,6 +12,36 @@ pub enum CompileError {
     InvalidBreakContinue,
     UndefinedStruct(String),
     UndefinedField(String, String), // (struct_name, field_name)
+    ReadonlyField(String, String),  // (struct_name, field_name)
+    NotAFunctionDeclaration,
+    SideEffectingIndexInExpression,  // 作为值使用的多维赋值，外层索引有副作用
+    UnknownFieldOwner(String),  // 无法确定字段所属的结构体（字段名）
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::UndefinedVariable(name) => write!(f, "undefined variable '{}'", name),
+            CompileError::TooManyConstants => write!(f, "too many constants in one function"),
+            CompileError::TooManyLocals => write!(f, "too many local variables (at most {})", MAX_LOCALS),
+            CompileError::InvalidBreakContinue => write!(f, "'break' or 'continue' outside of a loop"),
+            CompileError::UndefinedStruct(name) => write!(f, "undefined struct '{}'", name),
+            CompileError::UndefinedField(struct_name, field) => {
+                write!(f, "struct '{}' has no field '{}'", struct_name, field)
+            }
+            CompileError::ReadonlyField(struct_name, field) => {
+                write!(f, "cannot assign to readonly field '{}' of '{}'", field, struct_name)
+            }
+            CompileError::NotAFunctionDeclaration => write!(f, "expected a function declaration"),
+            CompileError::SideEffectingIndexInExpression => write!(
+                f,
+                "a nested index assignment used as a value cannot have side effects in its outer index"
+            ),
+            CompileError::UnknownFieldOwner(field) => {
+                write!(f, "cannot determine which struct field '{}' belongs to", field)
+            }
+        }
+    }
 }
 
 type CompileResult<T> = Result<T, CompileError>;
@@ -33,6 +64,8 @@ struct Scope {
 #[derive(Debug, Clone)]
 struct StructDef {
     fields: Vec<String>,  // 字段名列表（按顺序）
+    field_types: Vec<Type>,  // 与 fields 对应的声明类型
+    readonly_fields: Vec<String>,  // 用 let 声明、构造后不可赋值的字段
 }
 
 /// 局部变量的类型信息
@@ -46,13 +79,24 @@ struct LocalTypeInfo {
 pub struct Compiler {
     chunk: Chunk,
     locals: Vec<Local>,
+    max_locals: usize,  // 同时存活的局部变量数的峰值（含参数），即函数的 locals_count
     scope_depth: usize,
     loop_starts: Vec<usize>,      // 循环开始位置栈
     loop_breaks: Vec<Vec<usize>>,  // 循环break跳转位置栈
+    loop_locals: Vec<usize>,       // 进入各层循环体前的局部变量数（break/continue 先弹出循环体内的局部变量）
     structs: HashMap<String, StructDef>, // 结构体定义
     local_types: Vec<LocalTypeInfo>, // 局部变量类型信息
     global_types: HashMap<String, Type>, // 全局变量类型信息
+    const_values: HashMap<String, Value>, // 字面量值的全局常量，使用处直接内联
     methods: HashMap<String, HashMap<String, Function>>,  // type_name -> (method_name -> function)
+    method_return_types: HashMap<String, HashMap<String, Type>>,  // type_name -> (method_name -> 声明的返回类型)
+    function_return_types: HashMap<String, Type>,  // 函数名 -> 声明的返回类型
+    type_aliases: HashMap<String, Type>,  // 类型别名 -> 目标类型
+    max_constants: usize,  // 每个函数常量池的上限
+    debug_assertions: bool,  // 为 false（release 模式）时移除 debug_assert 调用
+    debug_assert_redeclared: bool,  // 顶层声明了同名的 fn/let，debug_assert 不再是内置函数
+    statement_offsets: Vec<usize>,  // 每条顶层语句的第一条指令位置
+    line: usize,  // 当前语句（含嵌套代码块中的语句）的源码行号，0 表示未知
 }
 
 impl Compiler {
@@ -60,76 +104,141 @@ impl Compiler {
         Compiler {
             chunk: Chunk::new(),
             locals: Vec::new(),
+            max_locals: 0,
             scope_depth: 0,
             loop_starts: Vec::new(),
             loop_breaks: Vec::new(),
+            loop_locals: Vec::new(),
             structs: HashMap::new(),
             local_types: Vec::new(),
             global_types: HashMap::new(),
+            const_values: HashMap::new(),
             methods: HashMap::new(),
+            method_return_types: HashMap::new(),
+            function_return_types: HashMap::new(),
+            type_aliases: HashMap::new(),
+            max_constants: DEFAULT_MAX_CONSTANTS,
+            debug_assertions: true,
+            debug_assert_redeclared: false,
+            statement_offsets: Vec::new(),
+            line: 0,
         }
     }
 
+    /// 设置每个字节码块常量池的上限（默认 `DEFAULT_MAX_CONSTANTS`）
+    pub fn with_max_constants(mut self, max_constants: usize) -> Self {
+        self.max_constants = max_constants;
+        self
+    }
+
+    /// 是否保留 `debug_assert(...)` 调用（默认保留；release 模式下关闭，调用连同参数一起被移除）
+    pub fn with_debug_assertions(mut self, enabled: bool) -> Self {
+        self.debug_assertions = enabled;
+        self
+    }
+
     /// 编译程序
     pub fn compile(&mut self, program: Program) -> CompileResult<Chunk> {
-        for stmt in program.statements {
+        self.debug_assert_redeclared |= program.statements.iter().any(|stmt| matches!(stmt,
+            Stmt::FnDeclaration { name, .. } | Stmt::VarDeclaration { name, .. } if name == "debug_assert"));
+        for (index, stmt) in program.statements.into_iter().enumerate() {
+            self.statement_offsets.push(self.chunk.len());
+            self.line = program.lines.get(index).copied().unwrap_or(0);
             self.compile_statement(stmt)?;
         }
         
         // 添加Halt指令
-        self.emit(OpCode::Halt, 0);
+        self.emit(OpCode::Halt);
         
         Ok(self.chunk.clone())
     }
 
+    /// 上次 `compile` 中各顶层语句的起始指令位置，与程序的语句一一对应
+    /// 第 i 条语句的指令到第 i+1 条语句的起始位置为止，最后一条语句之后是 Halt
+    pub fn statement_offsets(&self) -> &[usize] {
+        &self.statement_offsets
+    }
+
+    /// 单独编译一个函数声明（用于热重载）
+    /// 使用此前 `compile` 注册的结构体和方法作为上下文，不修改当前字节码
+    pub fn compile_function_declaration(&mut self, stmt: Stmt) -> CompileResult<Function> {
+        match stmt {
+            Stmt::FnDeclaration { name, parameters, return_type: _, body, body_lines } => {
+                self.compile_function(name, &parameters, body, &body_lines)
+            }
+            _ => Err(CompileError::NotAFunctionDeclaration),
+        }
+    }
+
     /// 编译语句
     fn compile_statement(&mut self, stmt: Stmt) -> CompileResult<()> {
         match stmt {
+            // release 模式下 `debug_assert(...);` 不生成任何指令
+            Stmt::Expression(expr) if self.is_stripped_debug_assert(&expr) => {}
+
+            Stmt::Expression(Expr::IndexAssign { object, index, value }) => {
+                self.compile_index_assign(*object, *index, *value, true)?;
+            }
+
             Stmt::Expression(expr) => {
                 self.compile_expression(expr)?;
-                self.emit(OpCode::Pop, 0);
+                self.emit(OpCode::Pop);
             }
 
             Stmt::StructDeclaration { name, fields } => {
                 // 注册结构体定义
                 let field_names: Vec<String> = fields.iter().map(|f| f.name.clone()).collect();
-                self.structs.insert(name, StructDef { fields: field_names });
+                let readonly_fields: Vec<String> = fields.iter()
+                    .filter(|f| !f.is_mutable)
+                    .map(|f| f.name.clone())
+                    .collect();
+                let field_types: Vec<Type> = fields.iter().map(|f| f.field_type.clone()).collect();
+                self.structs.insert(name, StructDef { fields: field_names, field_types, readonly_fields });
                 // 结构体声明在运行时不需要操作
             }
 
-            Stmt::TypeAlias { name: _, target_type: _ } => {
-                // 类型别名在编译时处理，运行时不需要操作
+            Stmt::TypeAlias { name, target_type } => {
+                // 类型别名在编译时处理（记录下来用于推断字段索引），运行时不需要操作
+                self.type_aliases.insert(name, target_type);
             }
 
             Stmt::ImplBlock { type_name, methods } => {
                 // 编译每个方法并存储到方法表中
                 let mut method_map = HashMap::new();
+                let return_types: HashMap<String, Type> = methods.iter()
+                    .filter_map(|method| Some((method.name.clone(), method.return_type.clone()?)))
+                    .collect();
+                self.method_return_types.entry(type_name.clone()).or_default().extend(return_types);
 
                 for method in methods {
-                    // 创建包含 self 参数的参数列表
-                    let mut params_with_self = vec![Parameter {
-                        name: "self".to_string(),
-                        type_annotation: Some(Type::Named(type_name.clone())),
-                    }];
+                    // 创建包含 self 参数的参数列表（静态方法就是普通函数）
+                    let mut params_with_self = Vec::new();
+                    if !method.is_static {
+                        params_with_self.push(Parameter {
+                            name: "self".to_string(),
+                            type_annotation: Some(Type::Named(type_name.clone())),
+                        });
+                    }
                     params_with_self.extend(method.parameters.clone());
 
                     // 编译方法体（作为函数）
                     let function = self.compile_function(
                         format!("{}.{}", type_name, method.name),
                         &params_with_self,
-                        method.body.clone()
+                        method.body.clone(),
+                        &method.body_lines,
                     )?;
 
                     method_map.insert(method.name.clone(), function);
                 }
 
-                // 存储方法到方法表
-                self.methods.insert(type_name.clone(), method_map);
+                // 存储方法到方法表（同一类型可以有多个 impl 块）
+                self.methods.entry(type_name.clone()).or_default().extend(method_map);
 
                 // Impl块在运行时不需要额外操作
             }
 
-            Stmt::VarDeclaration { name, mutable, type_annotation, initializer } => {
+            Stmt::VarDeclaration { name, mutable, type_annotation, initializer, is_const } => {
                 // 推断变量类型
                 let var_type = if let Some(annotated) = type_annotation {
                     annotated.clone()
@@ -139,17 +248,24 @@ impl Compiler {
                     Type::Null
                 };
 
+                // 常量仍然存入全局变量（供声明之前编译的函数和宿主读取），之后的使用处直接加载值
+                let const_value = initializer.as_ref().filter(|_| is_const).and_then(Self::constant_value);
+
                 if let Some(init) = initializer {
                     self.compile_expression(init)?;
                 } else {
-                    self.emit(OpCode::LoadNull, 0);
+                    self.emit(OpCode::LoadNull);
                 }
 
                 if self.scope_depth == 0 {
                     // 全局变量
+                    match const_value {
+                        Some(value) => self.const_values.insert(name.clone(), value),
+                        None => self.const_values.remove(&name),
+                    };
                     let idx = self.identifier_constant(&name)?;
-                    self.emit(OpCode::StoreGlobal(idx), 0);
-                    self.emit(OpCode::Pop, 0);
+                    self.emit(OpCode::StoreGlobal(idx));
+                    self.emit(OpCode::Pop);
                     // 记录全局变量类型
                     self.global_types.insert(name.clone(), var_type);
                 } else {
@@ -163,15 +279,21 @@ impl Compiler {
                 }
             }
 
-            Stmt::FnDeclaration { name, parameters, return_type: _, body } => {
-                let function = self.compile_function(name.clone(), &parameters, body)?;
-                let idx = self.chunk.add_constant(Value::Function(function));
-                self.emit(OpCode::LoadConst(idx), 0);
+            Stmt::FnDeclaration { name, parameters, return_type, body, body_lines } => {
+                // 先记录返回类型，递归调用的结果也能确定字段索引
+                match return_type {
+                    Some(t) => self.function_return_types.insert(name.clone(), t),
+                    None => self.function_return_types.remove(&name),
+                };
+                let function = self.compile_function(name.clone(), &parameters, body, &body_lines)?;
+                let idx = self.make_constant(Value::Function(function))?;
+                self.emit(OpCode::LoadConst(idx));
                 
                 if self.scope_depth == 0 {
+                    self.const_values.remove(&name);
                     let name_idx = self.identifier_constant(&name)?;
-                    self.emit(OpCode::StoreGlobal(name_idx), 0);
-                    self.emit(OpCode::Pop, 0);
+                    self.emit(OpCode::StoreGlobal(name_idx));
+                    self.emit(OpCode::Pop);
                 } else {
                     self.add_local(name, false)?;
                 }
@@ -181,56 +303,87 @@ impl Compiler {
                 if let Some(expr) = value {
                     self.compile_expression(expr)?;
                 } else {
-                    self.emit(OpCode::LoadNull, 0);
+                    self.emit(OpCode::LoadNull);
                 }
-                self.emit(OpCode::Return, 0);
+                self.emit(OpCode::Return);
             }
 
-            Stmt::If { condition, then_branch, else_branch } => {
+            Stmt::If { condition, then_branch, else_branch, then_lines, else_lines } => {
                 self.compile_expression(condition)?;
                 
                 let then_jump = self.emit_jump(OpCode::JumpIfFalse(0));
-                self.emit(OpCode::Pop, 0);
+                self.emit(OpCode::Pop);
                 
                 self.begin_scope();
-                for stmt in then_branch {
-                    self.compile_statement(stmt)?;
-                }
+                self.compile_block(then_branch, &then_lines)?;
                 self.end_scope();
                 
                 let else_jump = self.emit_jump(OpCode::Jump(0));
                 self.patch_jump(then_jump);
-                self.emit(OpCode::Pop, 0);
+                self.emit(OpCode::Pop);
                 
                 if let Some(else_stmts) = else_branch {
                     self.begin_scope();
-                    for stmt in else_stmts {
-                        self.compile_statement(stmt)?;
-                    }
+                    self.compile_block(else_stmts, &else_lines)?;
                     self.end_scope();
                 }
                 
                 self.patch_jump(else_jump);
             }
 
-            Stmt::While { condition, body } => {
+            Stmt::Match { scrutinee, arms, default, arm_lines, default_lines } => {
+                // 被匹配的值只求值一次，存入隐藏的局部变量后与各模式依次比较
+                self.begin_scope();
+                self.compile_expression(scrutinee)?;
+                let slot = self.locals.len();
+                self.add_local("__match__".to_string(), false)?;
+
+                let mut end_jumps = Vec::new();
+                for (index, (pattern, body)) in arms.into_iter().enumerate() {
+                    self.emit(OpCode::LoadLocal(slot));
+                    self.compile_expression(pattern)?;
+                    self.emit(OpCode::Equal);
+                    let next_arm = self.emit_jump(OpCode::JumpIfFalse(0));
+                    self.emit(OpCode::Pop);
+
+                    self.begin_scope();
+                    self.compile_block(body, arm_lines.get(index).map_or(&[], Vec::as_slice))?;
+                    self.end_scope();
+                    end_jumps.push(self.emit_jump(OpCode::Jump(0)));
+
+                    self.patch_jump(next_arm);
+                    self.emit(OpCode::Pop);
+                }
+
+                if let Some(default) = default {
+                    self.begin_scope();
+                    self.compile_block(default, &default_lines)?;
+                    self.end_scope();
+                }
+
+                for jump in end_jumps {
+                    self.patch_jump(jump);
+                }
+                self.end_scope();
+            }
+
+            Stmt::While { condition, body, body_lines } => {
                 let loop_start = self.chunk.len();
                 self.loop_starts.push(loop_start);
                 self.loop_breaks.push(Vec::new());
+                self.loop_locals.push(self.locals.len());
                 
                 self.compile_expression(condition)?;
                 let exit_jump = self.emit_jump(OpCode::JumpIfFalse(0));
-                self.emit(OpCode::Pop, 0);
+                self.emit(OpCode::Pop);
                 
                 self.begin_scope();
-                for stmt in body {
-                    self.compile_statement(stmt)?;
-                }
+                self.compile_block(body, &body_lines)?;
                 self.end_scope();
                 
-                self.emit(OpCode::Loop(loop_start), 0);
+                self.emit(OpCode::Loop(loop_start));
                 self.patch_jump(exit_jump);
-                self.emit(OpCode::Pop, 0);
+                self.emit(OpCode::Pop);
                 
                 // 修补所有break跳转
                 if let Some(breaks) = self.loop_breaks.pop() {
@@ -239,9 +392,10 @@ impl Compiler {
                     }
                 }
                 self.loop_starts.pop();
+                self.loop_locals.pop();
             }
 
-            Stmt::For { variable, start, end, body } => {
+            Stmt::For { variable, start, end, inclusive, body, body_lines } => {
                 self.begin_scope();
                 
                 // 初始化循环变量
@@ -252,37 +406,128 @@ impl Compiler {
                 self.compile_expression(end)?;
                 let end_local = self.locals.len();
                 self.add_local("__end__".to_string(), false)?;
-                
+                let var_slot = self.resolve_local(&variable)?;
+
+                // 首次进入时跳过递增；continue 跳回递增处
+                let first_iteration = self.emit_jump(OpCode::Jump(0));
                 let loop_start = self.chunk.len();
                 self.loop_starts.push(loop_start);
                 self.loop_breaks.push(Vec::new());
-                
-                // 条件检查: i < end
-                let var_slot = self.resolve_local(&variable)?;
-                self.emit(OpCode::LoadLocal(var_slot), 0);
-                self.emit(OpCode::LoadLocal(end_local), 0);
-                self.emit(OpCode::Less, 0);
+                self.loop_locals.push(self.locals.len());
+
+                // 闭区间在递增前检查 i < end，到达结束值即退出，避免 end 为最大整数时递增溢出
+                let last_iteration = if inclusive {
+                    self.emit(OpCode::LoadLocal(var_slot));
+                    self.emit(OpCode::LoadLocal(end_local));
+                    self.emit(OpCode::Less);
+                    let jump = self.emit_jump(OpCode::JumpIfFalse(0));
+                    self.emit(OpCode::Pop);
+                    Some(jump)
+                } else {
+                    None
+                };
+
+                // 递增: i = i + 1
+                self.emit(OpCode::LoadLocal(var_slot));
+                let one_idx = self.make_constant(Value::Integer(1))?;
+                self.emit(OpCode::LoadConst(one_idx));
+                self.emit(OpCode::Add);
+                self.emit(OpCode::StoreLocal(var_slot));
+                self.emit(OpCode::Pop);
+                self.patch_jump(first_iteration);
+
+                // 条件检查: i < end（闭区间为 i <= end）
+                self.emit(OpCode::LoadLocal(var_slot));
+                self.emit(OpCode::LoadLocal(end_local));
+                self.emit(if inclusive { OpCode::LessEqual } else { OpCode::Less });
                 
                 let exit_jump = self.emit_jump(OpCode::JumpIfFalse(0));
-                self.emit(OpCode::Pop, 0);
+                self.emit(OpCode::Pop);
                 
                 // 循环体
-                for stmt in body {
-                    self.compile_statement(stmt)?;
+                self.begin_scope();
+                self.compile_block(body, &body_lines)?;
+                self.end_scope();
+
+                self.emit(OpCode::Loop(loop_start));
+                self.patch_jump(exit_jump);
+                if let Some(jump) = last_iteration {
+                    self.patch_jump(jump);
                 }
+                self.emit(OpCode::Pop);
                 
-                // 递增: i = i + 1
-                self.emit(OpCode::LoadLocal(var_slot), 0);
-                let one_idx = self.chunk.add_constant(Value::Integer(1));
-                self.emit(OpCode::LoadConst(one_idx), 0);
-                self.emit(OpCode::Add, 0);
-                self.emit(OpCode::StoreLocal(var_slot), 0);
-                self.emit(OpCode::Pop, 0);
+                // 修补break跳转
+                if let Some(breaks) = self.loop_breaks.pop() {
+                    for break_jump in breaks {
+                        self.patch_jump(break_jump);
+                    }
+                }
+                self.loop_starts.pop();
+                self.loop_locals.pop();
                 
-                self.emit(OpCode::Loop(loop_start), 0);
+                self.end_scope();
+            }
+
+            Stmt::ForIn { variable, iterable, body, body_lines } => {
+                self.begin_scope();
+
+                // 元素类型（用于循环体内的字段访问解析）
+                let element_type = match self.infer_expression_type(&iterable) {
+                    Type::Array(element_type) | Type::FixedArray(element_type, _) => *element_type,
+                    Type::Range => Type::Int,
+                    _ => Type::Unknown,
+                };
+
+                // 隐藏局部变量：被遍历的值和当前索引（从-1开始，进入循环时先递增）
+                self.compile_expression(iterable)?;
+                let iter_slot = self.locals.len();
+                self.add_local("__iter__".to_string(), false)?;
+                let minus_one = self.make_constant(Value::Integer(-1))?;
+                self.emit(OpCode::LoadConst(minus_one));
+                let index_slot = self.locals.len();
+                self.add_local("__index__".to_string(), true)?;
+
+                // continue 跳回这里，因此递增放在条件检查之前
+                let loop_start = self.chunk.len();
+                self.loop_starts.push(loop_start);
+                self.loop_breaks.push(Vec::new());
+                self.loop_locals.push(self.locals.len());
+
+                // 递增: index = index + 1
+                self.emit(OpCode::LoadLocal(index_slot));
+                let one_idx = self.make_constant(Value::Integer(1))?;
+                self.emit(OpCode::LoadConst(one_idx));
+                self.emit(OpCode::Add);
+                self.emit(OpCode::StoreLocal(index_slot));
+                self.emit(OpCode::Pop);
+
+                // 条件检查: index < len(iter)
+                self.emit(OpCode::LoadLocal(index_slot));
+                self.emit(OpCode::LoadLocal(iter_slot));
+                self.emit(OpCode::ArrayLen);
+                self.emit(OpCode::Less);
+
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse(0));
+                self.emit(OpCode::Pop);
+
+                // 循环变量: variable = iter[index]
+                self.begin_scope();
+                self.emit(OpCode::LoadLocal(iter_slot));
+                self.emit(OpCode::LoadLocal(index_slot));
+                self.emit(OpCode::ArrayGet);
+                self.add_local(variable.clone(), false)?;
+                self.local_types.push(LocalTypeInfo {
+                    name: variable,
+                    var_type: element_type,
+                });
+
+                self.compile_block(body, &body_lines)?;
+                self.end_scope();
+
+                self.emit(OpCode::Loop(loop_start));
                 self.patch_jump(exit_jump);
-                self.emit(OpCode::Pop, 0);
-                
+                self.emit(OpCode::Pop);
+
                 // 修补break跳转
                 if let Some(breaks) = self.loop_breaks.pop() {
                     for break_jump in breaks {
@@ -290,20 +535,19 @@ impl Compiler {
                     }
                 }
                 self.loop_starts.pop();
-                
+                self.loop_locals.pop();
+
                 self.end_scope();
             }
 
             Stmt::Print { value } => {
                 self.compile_expression(value)?;
-                self.emit(OpCode::Print, 0);
+                self.emit(OpCode::Print);
             }
 
-            Stmt::Block { statements } => {
+            Stmt::Block { statements, lines } => {
                 self.begin_scope();
-                for stmt in statements {
-                    self.compile_statement(stmt)?;
-                }
+                self.compile_block(statements, &lines)?;
                 self.end_scope();
             }
 
@@ -311,6 +555,7 @@ impl Compiler {
                 if self.loop_breaks.is_empty() {
                     return Err(CompileError::InvalidBreakContinue);
                 }
+                self.pop_loop_locals();
                 let break_jump = self.emit_jump(OpCode::Jump(0));
                 if let Some(breaks) = self.loop_breaks.last_mut() {
                     breaks.push(break_jump);
@@ -322,7 +567,8 @@ impl Compiler {
                     return Err(CompileError::InvalidBreakContinue);
                 }
                 let loop_start = *self.loop_starts.last().unwrap();
-                self.emit(OpCode::Loop(loop_start), 0);
+                self.pop_loop_locals();
+                self.emit(OpCode::Loop(loop_start));
             }
         }
 
@@ -337,49 +583,61 @@ impl Compiler {
                 let struct_def = self.structs.get(&struct_name).cloned()
                     .ok_or_else(|| CompileError::UndefinedStruct(struct_name.clone()))?;
 
-                // 按照结构体定义的字段顺序编译字段值
+                // 每个声明的字段都要给出
                 for defined_field in &struct_def.fields {
-                    // 查找用户提供的对应字段
-                    let field_value = fields.iter()
-                        .find(|(name, _)| name == defined_field)
-                        .map(|(_, value)| value)
-                        .ok_or_else(|| CompileError::UndefinedField(
-                            struct_name.clone(),
-                            defined_field.clone()
-                        ))?;
-
-                    self.compile_expression(field_value.clone())?;
+                    if !fields.iter().any(|(name, _)| name == defined_field) {
+                        return Err(CompileError::UndefinedField(struct_name.clone(), defined_field.clone()));
+                    }
                 }
 
-                // 推送结构体名称到栈
-                let name_idx = self.chunk.add_constant(Value::String(struct_name));
-                self.emit(OpCode::LoadConst(name_idx), 0);
+                let in_declared_order = fields.len() == struct_def.fields.len()
+                    && fields.iter().zip(&struct_def.fields).all(|((name, _), defined)| name == defined);
 
-                // 创建结构体（字段数量作为参数）
-                self.emit(OpCode::NewStruct(struct_def.fields.len()), 0);
+                if in_declared_order {
+                    // 字段值按顺序入栈，直接创建结构体
+                    for (_, value) in fields {
+                        self.compile_expression(value)?;
+                    }
+                    let name_idx = self.make_constant(Value::String(struct_name))?;
+                    self.emit(OpCode::LoadConst(name_idx));
+                    self.emit(OpCode::NewStruct(struct_def.fields.len()));
+                } else {
+                    // 字段顺序与声明不同：先创建字段为 null 的结构体，再按书写顺序求值并写入
+                    // 各自的字段，字段值的副作用仍按源码顺序发生
+                    for _ in &struct_def.fields {
+                        self.emit(OpCode::LoadNull);
+                    }
+                    let name_idx = self.make_constant(Value::String(struct_name.clone()))?;
+                    self.emit(OpCode::LoadConst(name_idx));
+                    self.emit(OpCode::NewStruct(struct_def.fields.len()));
+
+                    for (name, value) in fields {
+                        let index = struct_def.fields.iter().position(|defined| *defined == name)
+                            .ok_or_else(|| CompileError::UndefinedField(struct_name.clone(), name.clone()))?;
+                        self.compile_expression(value)?;
+                        self.emit(OpCode::FieldSet(index));
+                    }
+                }
             }
 
-            Expr::FieldAccess { object, field } => {
+            Expr::FieldAccess { object, field, owner } => {
                 // 编译对象表达式
                 self.compile_expression(*object.clone())?;
 
-                // 推断对象类型并获取字段索引
-                let obj_type = self.infer_expression_type(&object);
-
-                let field_index = match obj_type {
-                    Type::Struct(struct_type) => {
-                        // 从结构体类型中查找字段索引
-                        self.get_field_index(&struct_type, &field)
-                            .unwrap_or(0) // 如果找不到，使用 0 作为回退
-                    }
-                    _ => 0, // 非结构体类型，使用 0
-                };
+                // 元组在运行时是数组，`t.0` 按下标取元素（结构体字段名不会是数字）
+                if let Ok(index) = field.parse::<i64>() {
+                    let idx = self.make_constant(Value::Integer(index))?;
+                    self.emit(OpCode::LoadConst(idx));
+                    self.emit(OpCode::ArrayGet);
+                    return Ok(());
+                }
 
-                // 使用实际的字段索引
-                self.emit(OpCode::FieldGet(field_index), 0);
+                let struct_type = self.field_owner_type(&object, &field, &owner)?;
+                let field_index = self.get_field_index(&struct_type, &field)?;
+                self.emit(OpCode::FieldGet(field_index));
             }
 
-            Expr::FieldAssign { object, field, value } => {
+            Expr::FieldAssign { object, field, value, owner } => {
                 // 编译字段赋值
                 let var_name = if let Expr::Identifier(name) = object.as_ref() {
                     Some(name.clone())
@@ -387,67 +645,65 @@ impl Compiler {
                     None
                 };
 
-                // 推断对象类型并获取字段索引
-                let obj_type = self.infer_expression_type(&object);
-
-                let field_index = match obj_type {
-                    Type::Struct(struct_type) => {
-                        // 从结构体类型中查找字段索引
-                        self.get_field_index(&struct_type, &field)
-                            .unwrap_or(0) // 如果找不到，使用 0 作为回退
-                    }
-                    _ => 0, // 非结构体类型，使用 0
-                };
+                let struct_type = self.field_owner_type(&object, &field, &owner)?;
+                // let 字段只能在结构体字面量中初始化
+                if struct_type.fields.iter().any(|f| f.name == field && !f.is_mutable) {
+                    return Err(CompileError::ReadonlyField(struct_type.name, field));
+                }
+                let field_index = self.get_field_index(&struct_type, &field)?;
 
                 // 编译对象和值
                 self.compile_expression(*object)?;
                 self.compile_expression(*value)?;
 
                 // 使用实际的字段索引
-                self.emit(OpCode::FieldSet(field_index), 0);
+                self.emit(OpCode::FieldSet(field_index));
 
                 // 如果object是标识符，将修改后的结构体存回
                 if let Some(name) = var_name {
                     if let Ok(slot) = self.resolve_local(&name) {
-                        self.emit(OpCode::StoreLocal(slot), 0);
+                        self.emit(OpCode::StoreLocal(slot));
                     } else {
                         let idx = self.identifier_constant(&name)?;
-                        self.emit(OpCode::StoreGlobal(idx), 0);
+                        self.emit(OpCode::StoreGlobal(idx));
                     }
                 }
             }
 
             Expr::Integer(n) => {
-                let idx = self.chunk.add_constant(Value::Integer(n));
-                self.emit(OpCode::LoadConst(idx), 0);
+                let idx = self.make_constant(Value::Integer(n))?;
+                self.emit(OpCode::LoadConst(idx));
             }
 
             Expr::Float(f) => {
-                let idx = self.chunk.add_constant(Value::Float(f));
-                self.emit(OpCode::LoadConst(idx), 0);
+                let idx = self.make_constant(Value::Float(f))?;
+                self.emit(OpCode::LoadConst(idx));
             }
 
             Expr::String(s) => {
-                let idx = self.chunk.add_constant(Value::String(s));
-                self.emit(OpCode::LoadConst(idx), 0);
+                let idx = self.make_constant(Value::String(s))?;
+                self.emit(OpCode::LoadConst(idx));
             }
 
             Expr::Boolean(b) => {
-                let idx = self.chunk.add_constant(Value::Boolean(b));
-                self.emit(OpCode::LoadConst(idx), 0);
+                let idx = self.make_constant(Value::Boolean(b))?;
+                self.emit(OpCode::LoadConst(idx));
             }
 
             Expr::Char(c) => {
-                let idx = self.chunk.add_constant(Value::Char(c));
-                self.emit(OpCode::LoadConst(idx), 0);
+                let idx = self.make_constant(Value::Char(c))?;
+                self.emit(OpCode::LoadConst(idx));
             }
 
             Expr::Identifier(name) => {
                 if let Ok(slot) = self.resolve_local(&name) {
-                    self.emit(OpCode::LoadLocal(slot), 0);
+                    self.emit(OpCode::LoadLocal(slot));
+                } else if let Some(value) = self.const_values.get(&name).cloned() {
+                    let idx = self.make_constant(value)?;
+                    self.emit(OpCode::LoadConst(idx));
                 } else {
                     let idx = self.identifier_constant(&name)?;
-                    self.emit(OpCode::LoadGlobal(idx), 0);
+                    self.emit(OpCode::LoadGlobal(idx));
                 }
             }
 
@@ -457,7 +713,7 @@ impl Compiler {
                     BinaryOp::And => {
                         self.compile_expression(*left)?;
                         let jump = self.emit_jump(OpCode::JumpIfFalse(0));
-                        self.emit(OpCode::Pop, 0);
+                        self.emit(OpCode::Pop);
                         self.compile_expression(*right)?;
                         self.patch_jump(jump);
                         return Ok(());
@@ -465,7 +721,7 @@ impl Compiler {
                     BinaryOp::Or => {
                         self.compile_expression(*left)?;
                         let jump = self.emit_jump(OpCode::JumpIfTrue(0));
-                        self.emit(OpCode::Pop, 0);
+                        self.emit(OpCode::Pop);
                         self.compile_expression(*right)?;
                         self.patch_jump(jump);
                         return Ok(());
@@ -473,21 +729,28 @@ impl Compiler {
                     _ => {}
                 }
 
+                // 两侧都是字面量时在编译期求值
+                if let Some(value) = Self::fold_binary(&operator, &left, &right) {
+                    let idx = self.make_constant(value)?;
+                    self.emit(OpCode::LoadConst(idx));
+                    return Ok(());
+                }
+
                 self.compile_expression(*left)?;
                 self.compile_expression(*right)?;
 
                 match operator {
-                    BinaryOp::Add => self.emit(OpCode::Add, 0),
-                    BinaryOp::Subtract => self.emit(OpCode::Subtract, 0),
-                    BinaryOp::Multiply => self.emit(OpCode::Multiply, 0),
-                    BinaryOp::Divide => self.emit(OpCode::Divide, 0),
-                    BinaryOp::Modulo => self.emit(OpCode::Modulo, 0),
-                    BinaryOp::Equal => self.emit(OpCode::Equal, 0),
-                    BinaryOp::NotEqual => self.emit(OpCode::NotEqual, 0),
-                    BinaryOp::Greater => self.emit(OpCode::Greater, 0),
-                    BinaryOp::GreaterEqual => self.emit(OpCode::GreaterEqual, 0),
-                    BinaryOp::Less => self.emit(OpCode::Less, 0),
-                    BinaryOp::LessEqual => self.emit(OpCode::LessEqual, 0),
+                    BinaryOp::Add => self.emit(OpCode::Add),
+                    BinaryOp::Subtract => self.emit(OpCode::Subtract),
+                    BinaryOp::Multiply => self.emit(OpCode::Multiply),
+                    BinaryOp::Divide => self.emit(OpCode::Divide),
+                    BinaryOp::Modulo => self.emit(OpCode::Modulo),
+                    BinaryOp::Equal => self.emit(OpCode::Equal),
+                    BinaryOp::NotEqual => self.emit(OpCode::NotEqual),
+                    BinaryOp::Greater => self.emit(OpCode::Greater),
+                    BinaryOp::GreaterEqual => self.emit(OpCode::GreaterEqual),
+                    BinaryOp::Less => self.emit(OpCode::Less),
+                    BinaryOp::LessEqual => self.emit(OpCode::LessEqual),
                     BinaryOp::And | BinaryOp::Or => unreachable!(), // 已处理
                 };
             }
@@ -495,8 +758,10 @@ impl Compiler {
             Expr::Unary { operator, operand } => {
                 self.compile_expression(*operand)?;
                 match operator {
-                    UnaryOp::Negate => self.emit(OpCode::Negate, 0),
-                    UnaryOp::Not => self.emit(OpCode::Not, 0),
+                    UnaryOp::Negate => self.emit(OpCode::Negate),
+                    UnaryOp::Not => self.emit(OpCode::Not),
+                    // 类型检查已保证操作数是数值，恒等运算不生成指令
+                    UnaryOp::Plus => {}
                 };
             }
 
@@ -504,13 +769,35 @@ impl Compiler {
                 self.compile_expression(*value)?;
                 
                 if let Ok(slot) = self.resolve_local(&name) {
-                    self.emit(OpCode::StoreLocal(slot), 0);
+                    self.emit(OpCode::StoreLocal(slot));
                 } else {
                     let idx = self.identifier_constant(&name)?;
-                    self.emit(OpCode::StoreGlobal(idx), 0);
+                    self.emit(OpCode::StoreGlobal(idx));
                 }
             }
 
+            // 旧值复制一份留在栈上作为表达式的值，再计算并写回新值
+            Expr::PostfixUpdate { name, operator } => {
+                self.compile_expression(Expr::Identifier(name.clone()))?;
+                self.emit(OpCode::Dup);
+                let one = self.make_constant(Value::Integer(1))?;
+                self.emit(OpCode::LoadConst(one));
+                self.emit(if operator == BinaryOp::Add { OpCode::Add } else { OpCode::Subtract });
+
+                if let Ok(slot) = self.resolve_local(&name) {
+                    self.emit(OpCode::StoreLocal(slot));
+                } else {
+                    let idx = self.identifier_constant(&name)?;
+                    self.emit(OpCode::StoreGlobal(idx));
+                }
+                self.emit(OpCode::Pop);
+            }
+
+            // 出现在表达式中的 debug_assert 被移除后以 null 作为值
+            call @ Expr::Call { .. } if self.is_stripped_debug_assert(&call) => {
+                self.emit(OpCode::LoadNull);
+            }
+
             Expr::Call { callee, arguments } => {
                 self.compile_expression(*callee)?;
 
@@ -518,12 +805,21 @@ impl Compiler {
                     self.compile_expression(arg.clone())?;
                 }
 
-                self.emit(OpCode::Call(arguments.len()), 0);
+                self.emit(OpCode::Call(arguments.len()));
             }
 
             Expr::MethodCall { object, method, arguments } => {
+                // 接收者是结构体类型名本身时为静态方法调用，不传 self
+                let static_type = match object.as_ref() {
+                    Expr::Identifier(name) if self.structs.contains_key(name) => Some(name.clone()),
+                    _ => None,
+                };
+
                 // 推断对象类型以确定方法所属的类型
-                let obj_type = self.infer_expression_type(&object);
+                let obj_type = match &static_type {
+                    Some(name) => Type::Named(name.clone()),
+                    None => self.infer_expression_type(&object),
+                };
 
                 let type_name = match obj_type {
                     Type::Struct(struct_type) => struct_type.name.clone(),
@@ -545,11 +841,16 @@ impl Compiler {
                     .clone();
 
                 // 将函数加载到栈
-                let func_idx = self.chunk.add_constant(Value::Function(function));
-                self.emit(OpCode::LoadConst(func_idx), 0);
+                let func_idx = self.make_constant(Value::Function(function))?;
+                self.emit(OpCode::LoadConst(func_idx));
 
                 // 编译 self 参数（对象）
-                self.compile_expression(*object)?;
+                let self_count = if static_type.is_none() {
+                    self.compile_expression(*object)?;
+                    1
+                } else {
+                    0
+                };
 
                 // 编译其他参数
                 for arg in arguments.iter() {
@@ -557,17 +858,53 @@ impl Compiler {
                 }
 
                 // 调用方法（参数数量 = arguments.len() + 1 for self）
-                self.emit(OpCode::Call(arguments.len() + 1), 0);
+                self.emit(OpCode::Call(arguments.len() + self_count));
             }
 
+            // 元组在运行时表示为数组
+            Expr::Tuple(elements) => self.compile_expression(Expr::array(elements))?,
+
             Expr::Array { elements } => {
+                // 全部元素都是常量时折叠为常量池中的单个数组
+                if let Some(values) = elements.iter().map(Self::constant_value).collect::<Option<Vec<_>>>() {
+                    let idx = self.make_constant(Value::array(values))?;
+                    self.emit(OpCode::LoadConst(idx));
+                    return Ok(());
+                }
+
                 // 编译每个数组元素
                 let len = elements.len();
                 for element in elements {
                     self.compile_expression(element)?;
                 }
                 // 创建数组（栈上的元素会被收集到数组中）
-                self.emit(OpCode::NewArray(len), 0);
+                self.emit(OpCode::NewArray(len));
+            }
+
+            Expr::If { condition, then_expr, else_expr } => {
+                // 与 if 语句相同的跳转结构，但每个分支在栈上留下一个值
+                self.compile_expression(*condition)?;
+
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse(0));
+                self.emit(OpCode::Pop);
+                self.compile_expression(*then_expr)?;
+
+                let else_jump = self.emit_jump(OpCode::Jump(0));
+                self.patch_jump(then_jump);
+                self.emit(OpCode::Pop);
+                self.compile_expression(*else_expr)?;
+
+                self.patch_jump(else_jump);
+            }
+
+            Expr::Range { start, end, step, inclusive } => {
+                self.compile_expression(*start)?;
+                self.compile_expression(*end)?;
+                self.emit(OpCode::NewRange(inclusive));
+                if let Some(step) = step {
+                    self.compile_expression(*step)?;
+                    self.emit(OpCode::RangeStep);
+                }
             }
 
             Expr::Index { object, index } => {
@@ -575,92 +912,255 @@ impl Compiler {
                 self.compile_expression(*object)?;
                 self.compile_expression(*index)?;
                 // 执行数组索引访问
-                self.emit(OpCode::ArrayGet, 0);
+                self.emit(OpCode::ArrayGet);
             }
             
             Expr::IndexAssign { object, index, value } => {
-                // 对于数组元素赋值，我们需要特殊处理来确保原数组被更新
-                // 如果object是标识符，我们需要：
-                // 1. 加载数组
-                // 2. 加载索引
-                // 3. 加载值
-                // 4. 执行ArraySet（修改数组并将新数组留在栈上）
-                // 5. 将新数组存回变量
-
-                // 先检查是否是标识符，保存名称
-                let var_name = if let Expr::Identifier(name) = object.as_ref() {
-                    Some(name.clone())
-                } else {
-                    None
-                };
+                self.compile_index_assign(*object, *index, *value, false)?;
+            }
+        }
 
-                // 编译表达式
-                self.compile_expression(*object)?;
-                self.compile_expression(*index)?;
-                self.compile_expression(*value)?;
-                // ArraySet返回修改后的数组
-                self.emit(OpCode::ArraySet, 0);
+        Ok(())
+    }
 
-                // 如果object是标识符，将修改后的数组存回
-                if let Some(name) = var_name {
-                    if let Ok(slot) = self.resolve_local(&name) {
-                        self.emit(OpCode::StoreLocal(slot), 0);
-                    } else {
-                        let idx = self.identifier_constant(&name)?;
-                        self.emit(OpCode::StoreGlobal(idx), 0);
-                    }
-                }
-                // 否则留在栈上作为表达式结果
+    /// 编译数组元素赋值。数组是值类型：ArraySet 修改数组副本并把修改后的数组留在栈上，需要存回变量。
+    /// 多维赋值 `m[i][j] = v` 沿索引链找到根变量，依次压入每一层的数组和索引：
+    ///   m, i, m[i], j, v  --ArraySet-->  m, i, m[i]'  --ArraySet-->  m'
+    /// 再把 m' 存回变量。外层索引先求值一次存入隐藏的局部变量，之后每一层重新加载。
+    /// 作为语句时（`as_statement`）结果被丢弃；作为值时栈上可能还有临时值，
+    /// 隐藏局部变量的槽位不可靠，外层索引只允许没有副作用的表达式。
+    fn compile_index_assign(&mut self, object: Expr, index: Expr, value: Expr, as_statement: bool) -> CompileResult<()> {
+        let mut outer = Vec::new();
+        let mut root = object.clone();
+        while let Expr::Index { object, index } = root {
+            outer.push(*index);
+            root = *object;
+        }
+        outer.reverse();
+
+        let Expr::Identifier(name) = root else {
+            // 根不是变量（如函数调用的结果）时没有可以存回的位置，修改后的数组留在栈上作为表达式结果
+            self.compile_expression(object)?;
+            self.compile_expression(index)?;
+            self.compile_expression(value)?;
+            self.emit(OpCode::ArraySet);
+            if as_statement {
+                self.emit(OpCode::Pop);
+            }
+            return Ok(());
+        };
+
+        let hoisted = as_statement && !outer.is_empty();
+        if !as_statement && !outer.iter().all(Self::is_pure_index) {
+            return Err(CompileError::SideEffectingIndexInExpression);
+        }
+
+        let mut slots = Vec::new();
+        if hoisted {
+            self.begin_scope();
+            for index in &outer {
+                self.compile_expression(index.clone())?;
+                slots.push(self.locals.len());
+                self.add_local("__subscript__".to_string(), false)?;
             }
         }
 
+        for depth in 0..=outer.len() {
+            self.compile_expression(Expr::Identifier(name.clone()))?;
+            for level in 0..depth {
+                self.compile_outer_index(&outer, &slots, level)?;
+                self.emit(OpCode::ArrayGet);
+            }
+            if depth < outer.len() {
+                self.compile_outer_index(&outer, &slots, depth)?;
+            }
+        }
+        self.compile_expression(index)?;
+        self.compile_expression(value)?;
+        for _ in 0..=outer.len() {
+            self.emit(OpCode::ArraySet);
+        }
+
+        if let Ok(slot) = self.resolve_local(&name) {
+            self.emit(OpCode::StoreLocal(slot));
+        } else {
+            let idx = self.identifier_constant(&name)?;
+            self.emit(OpCode::StoreGlobal(idx));
+        }
+
+        if as_statement {
+            self.emit(OpCode::Pop);
+        }
+        if hoisted {
+            self.end_scope();
+        }
         Ok(())
     }
 
+    /// 加载第 `level` 层外层索引：已存入隐藏局部变量时直接读取，否则重新求值（只用于无副作用的索引）
+    fn compile_outer_index(&mut self, outer: &[Expr], slots: &[usize], level: usize) -> CompileResult<()> {
+        match slots.get(level) {
+            Some(&slot) => {
+                self.emit(OpCode::LoadLocal(slot));
+                Ok(())
+            }
+            None => self.compile_expression(outer[level].clone()),
+        }
+    }
+
+    /// 重复求值不会产生副作用的索引表达式
+    fn is_pure_index(expr: &Expr) -> bool {
+        match expr {
+            Expr::Integer(_) | Expr::Identifier(_) => true,
+            Expr::Unary { operator: UnaryOp::Negate, operand } => Self::is_pure_index(operand),
+            _ => false,
+        }
+    }
+
+    /// release 模式下需要移除的 `debug_assert(...)` 调用（在调用处识别，类似宏）
+    /// 只移除直接调用内置函数的情况：同名的局部变量或顶层 fn/let 声明是普通函数，照常调用；
+    /// 通过别名调用（`let da = debug_assert; da(x)`）也是普通调用，会检查断言
+    fn is_stripped_debug_assert(&self, expr: &Expr) -> bool {
+        !self.debug_assertions
+            && !self.debug_assert_redeclared
+            && matches!(expr, Expr::Call { callee, .. }
+                if matches!(callee.as_ref(), Expr::Identifier(name)
+                    if name == "debug_assert" && self.resolve_local(name).is_err()))
+    }
+
     /// 编译函数
     fn compile_function(
         &mut self,
         name: String,
         parameters: &[Parameter],
         body: Vec<Stmt>,
+        body_lines: &[usize],
     ) -> CompileResult<Function> {
-        let mut function_compiler = Compiler::new();
+        let mut function_compiler = Compiler::new()
+            .with_max_constants(self.max_constants)
+            .with_debug_assertions(self.debug_assertions);
+        function_compiler.debug_assert_redeclared = self.debug_assert_redeclared;
 
         // 复制结构体定义和方法定义到新编译器
         function_compiler.structs = self.structs.clone();
         function_compiler.methods = self.methods.clone();
+        function_compiler.method_return_types = self.method_return_types.clone();
+        function_compiler.function_return_types = self.function_return_types.clone();
+        function_compiler.type_aliases = self.type_aliases.clone();
+        function_compiler.const_values = self.const_values.clone();
 
         function_compiler.begin_scope();
 
-        // 添加参数为局部变量
+        // 添加参数为局部变量，并记录标注的类型（字段访问按类型确定字段索引）
         for param in parameters {
             function_compiler.add_local(param.name.clone(), false)?;
+            if let Some(param_type) = &param.type_annotation {
+                function_compiler.local_types.push(LocalTypeInfo {
+                    name: param.name.clone(),
+                    var_type: param_type.clone(),
+                });
+            }
         }
 
-        // 编译函数体
-        for stmt in body {
+        // 编译函数体，每条语句的指令记录该语句所在的行
+        for (index, stmt) in body.into_iter().enumerate() {
+            function_compiler.line = body_lines.get(index).copied().unwrap_or(0);
             function_compiler.compile_statement(stmt)?;
         }
 
         // 如果没有显式return，添加返回null
-        function_compiler.emit(OpCode::LoadNull, 0);
-        function_compiler.emit(OpCode::Return, 0);
+        function_compiler.emit(OpCode::LoadNull);
+        function_compiler.emit(OpCode::Return);
+
+        let max_stack = function_compiler.chunk.max_stack_depth(parameters.len());
 
         Ok(Function {
             name,
             arity: parameters.len(),
             chunk: function_compiler.chunk,
-            locals_count: function_compiler.locals.len(),
+            locals_count: function_compiler.max_locals,
+            max_stack,
         })
     }
 
+    /// 编译嵌套代码块中的语句，每条语句的指令记录该语句所在的行（未知时沿用外层语句的行），
+    /// 之后恢复外层语句的行号（循环的回跳等指令仍属于外层语句）
+    fn compile_block(&mut self, statements: Vec<Stmt>, lines: &[usize]) -> CompileResult<()> {
+        let outer_line = self.line;
+        for (index, stmt) in statements.into_iter().enumerate() {
+            self.line = lines.get(index).copied().filter(|line| *line > 0).unwrap_or(outer_line);
+            self.compile_statement(stmt)?;
+        }
+        self.line = outer_line;
+        Ok(())
+    }
+
+    /// 编译期可确定的常量值（字面量及全部由字面量组成的数组）
+    fn constant_value(expr: &Expr) -> Option<Value> {
+        match expr {
+            Expr::Integer(n) => Some(Value::Integer(*n)),
+            Expr::Float(f) => Some(Value::Float(*f)),
+            Expr::String(s) => Some(Value::String(s.clone())),
+            Expr::Boolean(b) => Some(Value::Boolean(*b)),
+            Expr::Char(c) => Some(Value::Char(*c)),
+            Expr::Array { elements } => elements
+                .iter()
+                .map(Self::constant_value)
+                .collect::<Option<Vec<_>>>()
+                .map(Value::array),
+            Expr::Binary { left, operator, right } => Self::fold_binary(operator, left, right),
+            _ => None,
+        }
+    }
+
+    /// 常量折叠：按 VM 的运算规则计算字面量之间的算术运算和字符串拼接
+    /// 除数为零、溢出以及超出 32 位范围的整数结果不折叠，留给运行时按当前位宽报错
+    fn fold_binary(operator: &BinaryOp, left: &Expr, right: &Expr) -> Option<Value> {
+        let (left, right) = (Self::constant_value(left)?, Self::constant_value(right)?);
+        let as_float = |value: &Value| match value {
+            Value::Integer(n) => Some(*n as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        };
+        if matches!(operator, BinaryOp::Divide | BinaryOp::Modulo) && as_float(&right) == Some(0.0) {
+            return None;
+        }
+
+        let value = match (&left, &right) {
+            (Value::Integer(x), Value::Integer(y)) => Value::Integer(match operator {
+                BinaryOp::Add => x.checked_add(*y)?,
+                BinaryOp::Subtract => x.checked_sub(*y)?,
+                BinaryOp::Multiply => x.checked_mul(*y)?,
+                BinaryOp::Divide => x.checked_div(*y)?,
+                BinaryOp::Modulo => x.checked_rem(*y)?,
+                _ => return None,
+            }),
+            (Value::String(x), Value::String(y)) if *operator == BinaryOp::Add => Value::String(format!("{}{}", x, y)),
+            _ => {
+                let (x, y) = (as_float(&left)?, as_float(&right)?);
+                Value::Float(match operator {
+                    BinaryOp::Add => x + y,
+                    BinaryOp::Subtract => x - y,
+                    BinaryOp::Multiply => x * y,
+                    BinaryOp::Divide => x / y,
+                    _ => return None,
+                })
+            }
+        };
+        match value {
+            Value::Integer(n) if !IntWidth::I32.contains(n) => None,
+            value => Some(value),
+        }
+    }
+
     // 辅助方法
-    fn emit(&mut self, op: OpCode, line: usize) {
-        self.chunk.write(op, line);
+    /// 发出指令，记录当前语句的源码行号
+    fn emit(&mut self, op: OpCode) {
+        self.chunk.write(op, self.line);
     }
 
     fn emit_jump(&mut self, op: OpCode) -> usize {
-        self.emit(op, 0);
+        self.emit(op);
         self.chunk.len() - 1
     }
 
@@ -675,12 +1175,22 @@ impl Compiler {
     }
 
     fn identifier_constant(&mut self, name: &str) -> CompileResult<usize> {
-        let value = Value::String(name.to_string());
+        self.make_constant(Value::String(name.to_string()))
+    }
+
+    /// 向常量池添加常量，超过上限时返回 `TooManyConstants`
+    fn make_constant(&mut self, value: Value) -> CompileResult<usize> {
+        if let Some(index) = self.chunk.find_constant(&value) {
+            return Ok(index);
+        }
+        if self.chunk.constants.len() >= self.max_constants {
+            return Err(CompileError::TooManyConstants);
+        }
         Ok(self.chunk.add_constant(value))
     }
 
     fn add_local(&mut self, name: String, is_mutable: bool) -> CompileResult<()> {
-        if self.locals.len() >= 256 {
+        if self.locals.len() >= MAX_LOCALS {
             return Err(CompileError::TooManyLocals);
         }
         
@@ -689,6 +1199,7 @@ impl Compiler {
             depth: self.scope_depth,
             is_mutable,
         });
+        self.max_locals = self.max_locals.max(self.locals.len());
         
         Ok(())
     }
@@ -702,6 +1213,14 @@ impl Compiler {
         Err(CompileError::UndefinedVariable(name.to_string()))
     }
 
+    /// 跳出或跳回循环前弹出循环体内声明的局部变量（编译期的局部变量表不变，后续代码仍在原作用域中）
+    fn pop_loop_locals(&mut self) {
+        let base = self.loop_locals.last().copied().unwrap_or(self.locals.len());
+        for _ in base..self.locals.len() {
+            self.emit(OpCode::Pop);
+        }
+    }
+
     fn begin_scope(&mut self) {
         self.scope_depth += 1;
     }
@@ -713,7 +1232,7 @@ impl Compiler {
         while !self.locals.is_empty()
             && self.locals.last().unwrap().depth > self.scope_depth
         {
-            self.emit(OpCode::Pop, 0);
+            self.emit(OpCode::Pop);
             self.locals.pop();
         }
 
@@ -760,36 +1279,32 @@ impl Compiler {
                 }
             }
 
+            Expr::Tuple(elements) => {
+                Type::Tuple(elements.iter().map(|e| self.infer_expression_type(e)).collect())
+            }
+
             Expr::StructLiteral { struct_name, .. } => {
                 // 从结构体定义查找类型
-                if let Some(struct_def) = self.structs.get(struct_name) {
-                    // 构建完整的 StructType
-                    let fields = struct_def.fields.iter().map(|name| {
-                        crate::ast::StructField {
-                            name: name.clone(),
-                            field_type: Type::Unknown, // 简化处理
-                        }
-                    }).collect();
-                    Type::Struct(StructType {
-                        name: struct_name.clone(),
-                        fields,
-                    })
-                } else {
-                    Type::Unknown
+                match self.resolve_named_type(&Type::Named(struct_name.clone())) {
+                    Type::Named(_) => Type::Unknown,
+                    t => t,
                 }
             }
 
-            Expr::FieldAccess { object, field } => {
+            Expr::FieldAccess { object, field, .. } => {
                 let obj_type = self.infer_expression_type(object);
                 match obj_type {
                     Type::Struct(struct_type) => {
                         for f in &struct_type.fields {
                             if &f.name == field {
-                                return f.field_type.clone();
+                                return self.resolve_named_type(&f.field_type);
                             }
                         }
                         Type::Unknown
                     }
+                    Type::Tuple(elements) => field.parse::<usize>().ok()
+                        .and_then(|i| elements.get(i).cloned())
+                        .unwrap_or(Type::Unknown),
                     _ => Type::Unknown,
                 }
             }
@@ -797,16 +1312,45 @@ impl Compiler {
             Expr::Index { object, .. } => {
                 let obj_type = self.infer_expression_type(object);
                 match obj_type {
-                    Type::Array(element_type) => *element_type,
+                    Type::Array(element_type) | Type::FixedArray(element_type, _) => *element_type,
+                    Type::Range => Type::Int,
+                    Type::String => Type::Char,
                     _ => Type::Unknown,
                 }
             }
 
+            Expr::Range { .. } => Type::Range,
+
+            Expr::If { then_expr, else_expr, .. } => {
+                let then_type = self.infer_expression_type(then_expr);
+                if then_type == self.infer_expression_type(else_expr) {
+                    then_type
+                } else {
+                    Type::Unknown
+                }
+            }
+
             Expr::Binary { .. } => Type::Unknown, // 简化处理
             Expr::Unary { .. } => Type::Unknown,
             Expr::Assign { .. } => Type::Unknown,
-            Expr::Call { .. } => Type::Unknown,
-            Expr::MethodCall { .. } => Type::Unknown,
+            Expr::PostfixUpdate { name, .. } => self.infer_expression_type(&Expr::Identifier(name.clone())),
+            Expr::Call { callee, .. } => match callee.as_ref() {
+                Expr::Identifier(name) => self.function_return_types.get(name)
+                    .map_or(Type::Unknown, |t| self.resolve_named_type(t)),
+                _ => Type::Unknown,
+            },
+            Expr::MethodCall { object, method, .. } => {
+                let type_name = match object.as_ref() {
+                    Expr::Identifier(name) if self.structs.contains_key(name) => name.clone(),
+                    _ => match self.infer_expression_type(object) {
+                        Type::Struct(struct_type) => struct_type.name,
+                        _ => return Type::Unknown,
+                    },
+                };
+                self.method_return_types.get(&type_name)
+                    .and_then(|types| types.get(method))
+                    .map_or(Type::Unknown, |t| self.resolve_named_type(t))
+            }
             Expr::IndexAssign { .. } => Type::Unknown,
             Expr::FieldAssign { .. } => Type::Unknown,
         }
@@ -818,16 +1362,21 @@ impl Compiler {
             Type::Named(name) => {
                 // 查找结构体定义
                 if let Some(struct_def) = self.structs.get(name) {
-                    let fields = struct_def.fields.iter().map(|field_name| {
+                    // 字段类型保持声明时的形式，访问字段时再解析（避免递归结构体无限展开）
+                    let fields = struct_def.fields.iter().zip(&struct_def.field_types).map(|(field_name, field_type)| {
                         crate::ast::StructField {
                             name: field_name.clone(),
-                            field_type: Type::Unknown, // 简化处理
+                            field_type: field_type.clone(),
+                            is_mutable: !struct_def.readonly_fields.contains(field_name),
                         }
                     }).collect();
                     Type::Struct(StructType {
                         name: name.clone(),
                         fields,
                     })
+                } else if let Some(target) = self.type_aliases.get(name) {
+                    // 类型别名解析为目标类型（类型检查器已拒绝循环别名）
+                    self.resolve_named_type(target)
                 } else {
                     // 如果找不到定义，保持 Named 类型
                     t.clone()
@@ -838,8 +1387,22 @@ impl Compiler {
     }
 
     /// 根据结构体类型和字段名获取字段索引
-    fn get_field_index(&self, struct_type: &StructType, field_name: &str) -> Option<usize> {
+    fn get_field_index(&self, struct_type: &StructType, field_name: &str) -> CompileResult<usize> {
         struct_type.fields.iter().position(|f| f.name == field_name)
+            .ok_or_else(|| CompileError::UndefinedField(struct_type.name.clone(), field_name.to_string()))
+    }
+
+    /// 字段所属的结构体类型：优先使用类型检查器标注的结构体名，没有标注时（未经类型检查）
+    /// 使用编译器自己推断的对象类型；都无法确定时报错而不是猜测字段下标
+    fn field_owner_type(&self, object: &Expr, field: &str, owner: &FieldOwner) -> CompileResult<StructType> {
+        let object_type = match owner.get() {
+            Some(struct_name) => self.resolve_named_type(&Type::Named(struct_name)),
+            None => self.infer_expression_type(object),
+        };
+        match object_type {
+            Type::Struct(struct_type) => Ok(struct_type),
+            _ => Err(CompileError::UnknownFieldOwner(field.to_string())),
+        }
     }
 }
 
@@ -848,4 +1411,352 @@ impl Default for Compiler {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::serializer::{BytecodeDeserializer, BytecodeSerializer};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::type_checker::TypeChecker;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn compile_source(source: &str) -> Chunk {
+        let mut lexer = Lexer::new(source.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+        let mut compiler = Compiler::new();
+        compiler.compile(program).unwrap()
+    }
+
+    /// 将源码的反汇编输出与 tests/golden/<name>.txt 比较。
+    /// 设置环境变量 ZERO_BLESS=1 时改为重写期望文件。
+    fn assert_golden(name: &str, source: &str) {
+        let actual = compile_source(source).disassemble_to_string("main");
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden")
+            .join(format!("{}.txt", name));
+
+        if std::env::var("ZERO_BLESS").is_ok() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &actual).unwrap();
+            return;
+        }
+
+        let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!("missing golden file {}, run with ZERO_BLESS=1 to create it", path.display())
+        });
+        assert_eq!(
+            expected, actual,
+            "bytecode for '{}' changed, run with ZERO_BLESS=1 to update",
+            name
+        );
+    }
+
+    #[test]
+    fn test_golden_if_else() {
+        assert_golden("if_else", r#"
+            let x = 1;
+            if x > 0 {
+                print(x);
+            } else {
+                print(0);
+            }
+        "#);
+    }
+
+    #[test]
+    fn test_golden_while() {
+        assert_golden("while", r#"
+            var i = 0;
+            while i < 3 {
+                i = i + 1;
+            }
+        "#);
+    }
+
+    #[test]
+    fn test_golden_function_call() {
+        assert_golden("function_call", r#"
+            fn add(a, b) {
+                return a + b;
+            }
+            print(add(1, 2));
+        "#);
+    }
+
+    #[test]
+    fn test_function_max_stack() {
+        let chunk = compile_source(r#"
+            fn scale(a, b) {
+                let c = a * (b + 1);
+                return c;
+            }
+        "#);
+        let function = chunk.constants.iter().find_map(|c| match c {
+            Value::Function(f) => Some(f),
+            _ => None,
+        }).unwrap();
+
+        // 两个参数 + 计算 a * (b + 1) 时的三个临时值
+        assert_eq!(function.max_stack, 5);
+    }
+
+    #[test]
+    fn test_constant_array_folds_to_single_load() {
+        let chunk = compile_source("let a = [1, 2, 3];");
+
+        let loads = chunk.code.iter().filter(|op| matches!(op, OpCode::LoadConst(_))).count();
+        assert_eq!(loads, 1);
+        assert!(!chunk.code.iter().any(|op| matches!(op, OpCode::NewArray(_))));
+        assert!(chunk.constants.contains(&Value::array(vec![
+            Value::Integer(1),
+            Value::Integer(2),
+            Value::Integer(3),
+        ])));
+
+        // 折叠后的常量能经过序列化往返
+        let mut bytes = Vec::new();
+        BytecodeSerializer::serialize(&chunk, &mut bytes).unwrap();
+        let restored = BytecodeDeserializer::deserialize(&mut bytes.as_slice()).unwrap();
+        assert_eq!(restored, chunk);
+
+        // 含非常量元素时逐个压栈
+        let chunk = compile_source("let x = 1; let a = [x, 2];");
+        assert!(chunk.code.iter().any(|op| matches!(op, OpCode::NewArray(2))));
+    }
+
+    #[test]
+    fn test_constants_inlined_at_use_sites() {
+        let function_code = |chunk: &Chunk, name: &str| chunk.constants.iter()
+            .find_map(|value| match value {
+                Value::Function(function) if function.name == name => Some(function.chunk.code.clone()),
+                _ => None,
+            })
+            .unwrap();
+        let chunk = compile_source(r#"
+            fn early() { return N; }
+            const N = 3;
+            const NAME = "zero";
+            fn scale(x) { return x * N; }
+            fn shadow(N) { return N; }
+            print(N + 1);
+            print(NAME);
+        "#);
+
+        // 声明之后的使用处直接加载值，全局变量只写入一次
+        let global_loads = chunk.code.iter().filter(|op| matches!(op, OpCode::LoadGlobal(_))).count();
+        assert_eq!(global_loads, 0);
+        assert!(chunk.constants.contains(&Value::String("zero".to_string())));
+        assert!(!function_code(&chunk, "scale").iter().any(|op| matches!(op, OpCode::LoadGlobal(_))));
+        // 声明之前编译的函数仍然读取全局变量，同名参数不受影响
+        assert!(function_code(&chunk, "early").iter().any(|op| matches!(op, OpCode::LoadGlobal(_))));
+        assert!(function_code(&chunk, "shadow").iter().any(|op| matches!(op, OpCode::LoadLocal(0))));
+
+        // 普通的不可变变量不会内联
+        let chunk = compile_source("let n = 3; print(n);");
+        assert!(chunk.code.iter().any(|op| matches!(op, OpCode::LoadGlobal(_))));
+    }
+
+    #[test]
+    fn test_break_continue_outside_loop_is_compile_error() {
+        for source in ["break;", "fn f() { continue; }", "while true { fn g() { break; } }"] {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let program = Parser::new(tokens).parse().unwrap();
+            let result = Compiler::new().compile(program);
+            assert!(matches!(result, Err(CompileError::InvalidBreakContinue)), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_nested_index_assign_as_value_needs_pure_outer_index() {
+        let compile = |source: &str| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            Compiler::new().compile(Parser::new(tokens).parse().unwrap())
+        };
+        let prelude = "var m = [[1, 2]]; fn f(x) { return x; } fn g() -> int { return 0; }";
+        assert!(compile(&format!("{} f(m[0][1] = 3);", prelude)).is_ok());
+        assert!(compile(&format!("{} m[g()][1] = 3;", prelude)).is_ok());
+        assert!(matches!(
+            compile(&format!("{} f(m[g()][1] = 3);", prelude)),
+            Err(CompileError::SideEffectingIndexInExpression)
+        ));
+    }
+
+    #[test]
+    fn test_assign_to_let_field_is_compile_error() {
+        let source = "struct P { let x: int, var y: int }; var p = P { x: 1, y: 2 }; p.x = 3;";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let result = Compiler::new().compile(program);
+        assert!(matches!(result, Err(CompileError::ReadonlyField(ref s, ref f)) if s == "P" && f == "x"));
+    }
+
+    #[test]
+    fn test_unknown_field_owner_is_compile_error() {
+        // 未经类型检查、编译器也推断不出对象类型时不猜测字段下标
+        let source = "struct P { x: int, y: int }; fn mk() { return P { x: 1, y: 2 }; } print(mk().y);";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let result = Compiler::new().compile(program.clone());
+        assert!(matches!(result, Err(CompileError::UnknownFieldOwner(ref f)) if f == "y"));
+
+        // 类型检查后使用推断出的结构体
+        TypeChecker::new().check(&program).unwrap();
+        let chunk = Compiler::new().compile(program).unwrap();
+        assert!(chunk.code.contains(&OpCode::FieldGet(1)));
+    }
+
+    #[test]
+    fn test_constant_pool_limit() {
+        let compile = |source: &str, max_constants: usize| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let program = Parser::new(tokens).parse().unwrap();
+            Compiler::new().with_max_constants(max_constants).compile(program)
+        };
+
+        // 每个全局声明使用两个常量（值和变量名）
+        let source = "let a = 1; let b = 2;";
+        assert_eq!(compile(source, 4).unwrap().constants.len(), 4);
+        assert!(matches!(compile(source, 3), Err(CompileError::TooManyConstants)));
+
+        // 函数体有自己的常量池，同样受限
+        let source = "fn f() { print(1); print(2); print(3); }";
+        assert!(compile(source, 4).is_ok());
+        assert!(matches!(compile(source, 2), Err(CompileError::TooManyConstants)));
+    }
+
+    #[test]
+    fn test_constants_are_deduplicated() {
+        let chunk = compile_source(r#"let a = 1.5; let b = 1.5; let s = "x"; let t = "x"; let n = 2; let f = 2.0;"#);
+        let count = |value: &Value| chunk.constants.iter().filter(|c| *c == value).count();
+        assert_eq!(count(&Value::Float(1.5)), 1);
+        assert_eq!(count(&Value::String("x".to_string())), 1);
+        // 整数和浮点数不会合并
+        assert_eq!(count(&Value::Integer(2)), 1);
+        assert_eq!(count(&Value::Float(2.0)), 1);
+
+        // 正零和负零按位区分，NaN 按位复用
+        let mut chunk = Chunk::new();
+        let zero = chunk.add_constant(Value::Float(0.0));
+        assert_eq!(chunk.find_constant(&Value::Float(0.0)), Some(zero));
+        assert_eq!(chunk.find_constant(&Value::Float(-0.0)), None);
+        let nan = chunk.add_constant(Value::Float(f64::NAN));
+        assert_eq!(chunk.find_constant(&Value::Float(f64::NAN)), Some(nan));
+        // 复合值不参与复用
+        chunk.add_constant(Value::array(vec![]));
+        assert_eq!(chunk.find_constant(&Value::array(vec![])), None);
+    }
+
+    #[test]
+    fn test_constant_pool_shrinks_on_loop_heavy_program() {
+        let chunk = compile_source(r#"
+            var total = 0;
+            for i in 0..10 {
+                for j in 0..10 {
+                    if (i + j) % 2 == 0 { total = total + 1; } else { total = total - 1; }
+                    if total > 100 { total = 0; }
+                }
+                total = total * 2 + 1;
+            }
+            var k = 0;
+            while k < 10 { k = k + 1; total = total + k * 2; }
+            print(total);
+        "#);
+        // 不复用时每条 LoadConst 和每次全局变量名引用都会占一个新槽位
+        let references = chunk.code.iter().filter(|op| matches!(op,
+            OpCode::LoadConst(_) | OpCode::LoadGlobal(_) | OpCode::StoreGlobal(_))).count();
+        assert!(chunk.constants.len() * 3 <= references, "{} constants for {} references", chunk.constants.len(), references);
+        for (index, constant) in chunk.constants.iter().enumerate() {
+            assert_eq!(chunk.find_constant(constant), Some(index));
+        }
+    }
+
+    #[test]
+    fn test_literal_arithmetic_is_folded() {
+        // 折叠前：LoadConst 2, LoadConst 3, LoadConst 4, Multiply, Add
+        let folded = compile_source("let x = 2 + 3 * 4;");
+        let literal = compile_source("let x = 14;");
+        assert_eq!(folded.len(), literal.len());
+        assert_eq!(folded.constants, literal.constants);
+        let unfolded = compile_source("var n = 4; let x = 2 + 3 * n;");
+        assert_eq!(unfolded.len() - folded.len(), 3 + 4);
+
+        let constants = |source: &str| compile_source(source).constants;
+        assert!(constants(r#"let s = "a" + "b" + "c";"#).contains(&Value::String("abc".to_string())));
+        assert!(constants("let f = 1 + 0.5 * 3;").contains(&Value::Float(2.5)));
+        assert!(constants("let m = (7 - 10) % 4;").contains(&Value::Integer(-3)));
+        // 常量和常量数组也使用折叠后的值
+        assert!(constants("const K = 60 * 60; fn f() -> int { return K; }").contains(&Value::Integer(3600)));
+        assert!(constants("let a = [1 + 1, 2 * 2];").contains(&Value::array(vec![Value::Integer(2), Value::Integer(4)])));
+
+        // 除数为零和溢出不折叠，运行时照常报错
+        let has_op = |source: &str, op: OpCode| compile_source(source).code.contains(&op);
+        assert!(has_op("let d = 1 / 0;", OpCode::Divide));
+        assert!(has_op("let d = 1.5 % 0.0;", OpCode::Modulo));
+        assert!(has_op("let big = 9223372036854775807 + 1;", OpCode::Add));
+        assert!(has_op("let wide = 65536 * 65536;", OpCode::Multiply));
+        let err = crate::vm::VM::new().execute(compile_source("let d = 1 / 0;")).unwrap_err();
+        assert!(err.to_string().starts_with("division by zero: 1 / 0"));
+    }
+
+    #[test]
+    fn test_debug_assert_stripped_in_release() {
+        let compile = |source: &str, debug_assertions: bool| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let program = Parser::new(tokens).parse().unwrap();
+            Compiler::new().with_debug_assertions(debug_assertions).compile(program).unwrap()
+        };
+        let mentions_debug_assert = |chunk: &Chunk| {
+            chunk.constants.iter().any(|c| match c {
+                Value::String(s) => s == "debug_assert",
+                Value::Function(f) => f.chunk.constants.contains(&Value::String("debug_assert".to_string())),
+                _ => false,
+            })
+        };
+
+        let source = "let x = 1; debug_assert(x > 0); fn f(n) { debug_assert(n > 0); return n; }";
+        let debug = compile(source, true);
+        assert!(mentions_debug_assert(&debug));
+
+        // release 模式下调用连同参数一起消失，与不写该语句时的字节码相同
+        let release = compile(source, false);
+        assert!(!mentions_debug_assert(&release));
+        assert_eq!(release, compile("let x = 1; fn f(n) { return n; }", true));
+    }
+
+    #[test]
+    fn test_opcodes_carry_statement_lines() {
+        let chunk = compile_source("let a = 1;\n\nfn f(x) {\n    let y = x + 1;\n    return y / 0;\n}\nprint(f(a));\n");
+        // 每条指令记录所在语句（程序或函数体的顶层语句）的起始行
+        assert_eq!(chunk.lines[0], 1);
+        let call = chunk.code.iter().position(|op| matches!(op, OpCode::Call(_))).unwrap();
+        assert_eq!(chunk.lines[call], 7);
+        let function = chunk.constants.iter().find_map(|c| match c {
+            Value::Function(f) => Some(f),
+            _ => None,
+        }).unwrap();
+        let divide = function.chunk.code.iter().position(|op| *op == OpCode::Divide).unwrap();
+        assert_eq!(function.chunk.lines[0], 4);
+        assert_eq!(function.chunk.lines[divide], 5);
+
+        let err = crate::vm::VM::new().execute(chunk).unwrap_err();
+        assert!(err.to_string().starts_with("division by zero: 2 / 0 at line 5"));
+
+        let chunk = compile_source("struct P { x: int };\nimpl P {\n    fn half(self) -> int {\n        print(self.x);\n        return self.x / 0;\n    }\n}\nprint(P { x: 4 }.half());\n");
+        let err = crate::vm::VM::new().execute(chunk).unwrap_err();
+        assert!(err.to_string().starts_with("division by zero: 4 / 0 at line 5"));
+
+        // 循环和分支中的语句记录自己的行，而不是外层 while 语句的行
+        let chunk = compile_source("var i = 0;\nwhile i < 3 {\n    i = i + 1;\n    if i == 2 {\n        print(10 / (i - 2));\n    }\n}\n");
+        let err = crate::vm::VM::new().execute(chunk).unwrap_err();
+        assert!(err.to_string().starts_with("division by zero: 10 / 0 at line 5"), "{}", err);
+
+        let chunk = compile_source("fn f(xs) {\n    for x in xs {\n        match x {\n            0 => {\n                return 1 % x;\n            }\n        }\n    }\n}\nf([0]);\n");
+        let err = crate::vm::VM::new().execute(chunk).unwrap_err();
+        assert!(err.to_string().starts_with("division by zero: 1 % 0 at line 5"), "{}", err);
+    }
+}
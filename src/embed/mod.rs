@@ -0,0 +1,133 @@
+//! 嵌入接口：一次调用完成编译和执行，适合测试和宿主程序
+
+use crate::ast::IntWidth;
+use crate::bytecode::Chunk;
+use crate::compiler::{CompileError, Compiler};
+use crate::error::CompilerError;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::type_checker::{TypeChecker, TypeError, TypeWarning};
+use crate::vm::{VMError, VM};
+use std::fmt;
+
+/// 编译和执行各阶段错误的统一类型
+#[derive(Debug)]
+pub enum ZeroError {
+    Lexer(CompilerError),
+    Parse(Vec<CompilerError>),  // 语法分析收集到的全部错误
+    Type(Box<TypeError>),  // 类型错误较大，装箱以免撑大整个 Result
+    Compile(CompileError),
+    /// 运行时错误；程序调用 `exit(code)` 时为 `VMError::Exit(code)`
+    Runtime(VMError),
+}
+
+impl fmt::Display for ZeroError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZeroError::Lexer(err) => write!(f, "Lexer error: {}", err),
+            ZeroError::Parse(errors) => {
+                let messages: Vec<String> = errors.iter().map(|err| format!("Parse error: {}", err)).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+            ZeroError::Type(err) => write!(f, "Type error: {}", err),
+            ZeroError::Compile(err) => write!(f, "Compile error: {}", err),
+            ZeroError::Runtime(err) => write!(f, "Runtime error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ZeroError {}
+
+impl From<CompilerError> for ZeroError {
+    fn from(err: CompilerError) -> Self {
+        ZeroError::Lexer(err)
+    }
+}
+
+impl From<TypeError> for ZeroError {
+    fn from(err: TypeError) -> Self {
+        ZeroError::Type(Box::new(err))
+    }
+}
+
+impl From<CompileError> for ZeroError {
+    fn from(err: CompileError) -> Self {
+        ZeroError::Compile(err)
+    }
+}
+
+impl From<VMError> for ZeroError {
+    fn from(err: VMError) -> Self {
+        ZeroError::Runtime(err)
+    }
+}
+
+/// 编译（含类型检查）并执行源码，返回 print 输出的全部内容（每次 print 一行）
+/// 类型检查警告被忽略；遇到的第一个错误直接返回，此前的输出随之丢弃
+pub fn eval(src: &str) -> Result<String, ZeroError> {
+    let (chunk, _) = compile(src, IntWidth::default(), TypeChecker::new())?;
+
+    let mut vm = VM::new().with_captured_output();
+    vm.execute(chunk)?;
+    Ok(vm.take_output())
+}
+
+/// 词法分析、语法分析、用给定的类型检查器检查并编译源码，返回字节码和类型检查警告
+/// （`eval` 和命令行的 `--eval` 共用）
+pub fn compile(src: &str, int_width: IntWidth, mut type_checker: TypeChecker) -> Result<(Chunk, Vec<TypeWarning>), ZeroError> {
+    let tokens = Lexer::new(src.to_string()).tokenize()?;
+    let mut parser = Parser::new(tokens).with_int_width(int_width);
+    let program = parser.parse().map_err(|err| match parser.errors() {
+        [] => ZeroError::Parse(vec![CompilerError::from(&err)]),
+        errors => ZeroError::Parse(errors.to_vec()),
+    })?;
+    type_checker.check(&program)?;
+    let chunk = Compiler::new().compile(program)?;
+    Ok((chunk, type_checker.warnings().to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_captures_output() {
+        assert_eq!(eval("print(1+2);").unwrap(), "3\n");
+        assert_eq!(eval("let xs = [1, 2]; for x in xs { print(x * 10); } print(\"done\");").unwrap(), "10\n20\ndone\n");
+        assert_eq!(eval("let x = 1;").unwrap(), "");
+    }
+
+    #[test]
+    fn test_eval_reports_first_error() {
+        assert!(matches!(eval("let x = 1 @ 2;"), Err(ZeroError::Lexer(_))));
+        assert!(matches!(eval("let = 1;"), Err(ZeroError::Parse(_))));
+        let error = eval("let x: int = \"a\";").unwrap_err();
+        assert!(matches!(error, ZeroError::Type(_)));
+        assert_eq!(error.to_string(), "Type error: expected int but found string in variable declaration 'x'");
+        assert!(matches!(eval("print(1); exit(3);"), Err(ZeroError::Runtime(VMError::Exit(3)))));
+
+        let error = eval("let a = [1]; print(a[5]);").unwrap_err();
+        assert!(matches!(error, ZeroError::Runtime(_)));
+        assert!(error.to_string().starts_with("Runtime error: "), "{}", error);
+
+        // 语法错误全部保留
+        match eval("let = 1;\nlet y = 2;\nlet = 3;") {
+            Err(ZeroError::Parse(errors)) => assert_eq!(errors.len(), 2),
+            other => panic!("expected parse errors, got {:?}", other),
+        }
+        let error = ZeroError::from(CompileError::UndefinedStruct("P".to_string()));
+        assert_eq!(error.to_string(), "Compile error: undefined struct 'P'");
+    }
+
+    #[test]
+    fn test_compile_uses_given_width_and_checker() {
+        assert!(compile("let x = 3000000000;", IntWidth::default(), TypeChecker::new()).is_ok());
+        assert!(matches!(
+            compile("let x = 3000000000;", IntWidth::I32, TypeChecker::new()),
+            Err(ZeroError::Parse(_))
+        ));
+
+        let (_, warnings) = compile("var x = 1; x = x;", IntWidth::default(), TypeChecker::new().with_self_assignment_lint(true)).unwrap();
+        assert_eq!(warnings, vec![TypeWarning::SelfAssignment { target: "x".to_string() }]);
+    }
+}
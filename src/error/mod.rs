@@ -19,8 +19,8 @@ pub enum ErrorMode {
     Detailed,
 }
 
-/// 源码位置信息
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// 源码位置信息（按行、列排序）
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct SourceLocation {
     pub line: usize,
     pub column: usize,
@@ -68,7 +68,8 @@ impl CompilerError {
 }
 
 /// 错误类型枚举 - 仅用于分类，不包含具体消息
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// 按编译阶段的先后声明，排序时同一位置的错误按阶段排列
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ErrorType {
     // 词法错误
     LexerUnterminatedString,
@@ -76,6 +77,7 @@ pub enum ErrorType {
     LexerInvalidCharacter,
     LexerInvalidNumber,
     LexerInvalidUnicodeEscape,
+    LexerUnterminatedComment,
     
     // 语法错误
     ParserUnexpectedToken,
@@ -102,6 +104,7 @@ impl ErrorType {
             Self::LexerInvalidCharacter => "L003",
             Self::LexerInvalidNumber => "L004",
             Self::LexerInvalidUnicodeEscape => "L005",
+            Self::LexerUnterminatedComment => "L006",
             Self::ParserUnexpectedToken => "P001",
             Self::ParserUnexpectedEOF => "P002",
             Self::ParserInvalidExpression => "P003",
@@ -120,6 +123,7 @@ impl ErrorType {
             Self::LexerInvalidCharacter => "lexer.L003",
             Self::LexerInvalidNumber => "lexer.L004",
             Self::LexerInvalidUnicodeEscape => "lexer.L005",
+            Self::LexerUnterminatedComment => "lexer.L006",
             Self::ParserUnexpectedToken => "parser.P001",
             Self::ParserUnexpectedEOF => "parser.P002",
             Self::ParserInvalidExpression => "parser.P003",
@@ -275,6 +279,7 @@ impl Default for ErrorCollector {
 pub struct ErrorDisplayer {
     registry: ErrorRegistry,
     mode: ErrorMode,
+    tab_width: usize,
 }
 
 impl ErrorDisplayer {
@@ -282,8 +287,32 @@ impl ErrorDisplayer {
         Self {
             registry: ErrorRegistry::default(),
             mode,
+            tab_width: crate::lexer::DEFAULT_TAB_WIDTH,
         }
     }
+
+    /// 设置源码片段中制表符展开的宽度，应与 `Lexer::with_tab_width` 一致
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+
+    /// 按制表位将制表符展开为空格，使插入符与词法分析器计算的列对齐
+    fn expand_tabs(&self, line: &str) -> String {
+        let mut output = String::with_capacity(line.len());
+        let mut column = 1;
+        for ch in line.chars() {
+            if ch == '\t' {
+                let next = crate::lexer::next_tab_stop(column, self.tab_width);
+                output.push_str(&" ".repeat(next - column));
+                column = next;
+            } else {
+                output.push(ch);
+                column += crate::lexer::Lexer::char_display_width(ch);
+            }
+        }
+        output
+    }
     
     pub fn with_registry(mut self, registry: ErrorRegistry) -> Self {
         self.registry = registry;
@@ -298,10 +327,12 @@ impl ErrorDisplayer {
         }
     }
     
-    /// 格式化所有错误
+    /// 格式化所有错误，按源码位置排列（与收集顺序无关），同一位置的错误按编译阶段排列
     pub fn format_errors(&self, errors: &[CompilerError], source: Option<&str>) -> String {
-        errors
-            .iter()
+        let mut sorted: Vec<&CompilerError> = errors.iter().collect();
+        sorted.sort_by(|a, b| (&a.location, &a.error_type).cmp(&(&b.location, &b.error_type)));
+        sorted
+            .into_iter()
             .map(|e| self.format_error(e, source))
             .collect::<Vec<_>>()
             .join("\n\n")
@@ -398,7 +429,7 @@ impl ErrorDisplayer {
             output.push_str(&format!(
                 "{:>width$} | {}\n",
                 location.line - 1,
-                lines[location.line - 2],
+                self.expand_tabs(lines[location.line - 2]),
                 width = line_num_width
             ));
         }
@@ -407,7 +438,7 @@ impl ErrorDisplayer {
         output.push_str(&format!(
             "{:>width$} | {}\n",
             location.line,
-            lines[location.line - 1],
+            self.expand_tabs(lines[location.line - 1]),
             width = line_num_width
         ));
         
@@ -430,7 +461,7 @@ impl ErrorDisplayer {
             output.push_str(&format!(
                 "{:>width$} | {}\n",
                 location.line + 1,
-                lines[location.line],
+                self.expand_tabs(lines[location.line]),
                 width = line_num_width
             ));
         }
@@ -499,6 +530,15 @@ impl CompilerError {
         .with_param("sequence", sequence)
     }
     
+    /// 位置覆盖最外层未闭合的 `/*`
+    pub fn unterminated_comment(line: usize, column: usize, offset: usize) -> Self {
+        Self::new(
+            "L006",
+            SourceLocation::new(line, column, offset, 2),
+            ErrorType::LexerUnterminatedComment,
+        )
+    }
+    
     pub fn unexpected_token(expected: String, found: String, line: usize, column: usize, offset: usize, length: usize) -> Self {
         Self::new(
             "P001",
@@ -533,4 +573,41 @@ impl fmt::Display for CompilerError {
     }
 }
 
-impl std::error::Error for CompilerError {}
\ No newline at end of file
+impl std::error::Error for CompilerError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_formatted_in_source_order() {
+        let mut collector = ErrorCollector::new();
+        collector.add(CompilerError::unexpected_eof(5, 1, 40));
+        collector.add(CompilerError::invalid_character('@', 2, 7, 12));
+        collector.add(CompilerError::invalid_expression(8, 3, 70));
+        // 同一行中列靠前的先输出；同一位置先输出较早阶段的错误，同一阶段按错误类型的声明顺序
+        collector.add(CompilerError::unexpected_token("';'".to_string(), "'}'".to_string(), 5, 1, 40, 1));
+        collector.add(CompilerError::unterminated_string(5, 1, 40));
+        collector.add(CompilerError::invalid_number("1x".to_string(), 2, 3, 8));
+
+        let output = ErrorDisplayer::new(ErrorMode::Simple).format_errors(collector.errors(), None);
+        // 简易模式每条为 "错误 [代码] 在 行:列: 标题"
+        let order: Vec<(&str, &str)> = output
+            .split("\n\n")
+            .map(|error| {
+                let (code, rest) = error.split_once(" 在 ").unwrap();
+                (code.trim_start_matches("错误 [").trim_end_matches(']'), rest.split(": ").next().unwrap())
+            })
+            .collect();
+        assert_eq!(order, [
+            ("L004", "2:3"),
+            ("L003", "2:7"),
+            ("L001", "5:1"),
+            ("P001", "5:1"),
+            ("P002", "5:1"),
+            ("P003", "8:3"),
+        ]);
+        // 收集器本身保留收集顺序
+        assert_eq!(collector.errors()[0].location.line, 5);
+    }
+}
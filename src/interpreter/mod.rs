@@ -1,4 +1,5 @@
 use crate::ast::{BinaryOp, Expr, Program, Stmt, UnaryOp, Parameter};
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -136,6 +137,7 @@ impl Interpreter {
                 mutable: _,
                 type_annotation: _,
                 initializer,
+                is_const: _,
             } => {
                 let value = if let Some(init) = initializer {
                     self.evaluate_expression(init)?
@@ -151,6 +153,7 @@ impl Interpreter {
                 parameters,
                 return_type: _,
                 body,
+                ..
             } => {
                 let func = Value::Function {
                     parameters: parameters.clone(),
@@ -173,6 +176,7 @@ impl Interpreter {
                 condition,
                 then_branch,
                 else_branch,
+                ..
             } => {
                 let condition_value = self.evaluate_expression(condition)?;
 
@@ -188,7 +192,24 @@ impl Interpreter {
                 Ok(Value::Null)
             }
 
-            Stmt::While { condition, body } => {
+            Stmt::Match { scrutinee, arms, default, .. } => {
+                let value = self.evaluate_expression(scrutinee)?;
+                let mut chosen = default.as_ref();
+                for (pattern, body) in arms {
+                    if self.evaluate_expression(pattern)? == value {
+                        chosen = Some(body);
+                        break;
+                    }
+                }
+                if let Some(body) = chosen {
+                    for stmt in body {
+                        self.execute_statement(stmt)?;
+                    }
+                }
+                Ok(Value::Null)
+            }
+
+            Stmt::While { condition, body, .. } => {
                 while self.evaluate_expression(condition)?.is_truthy() {
                     let mut should_break = false;
                     for stmt in body {
@@ -211,11 +232,20 @@ impl Interpreter {
                 Ok(Value::Null)
             }
 
+            Stmt::ForIn { .. } => {
+                // 遍历数组/范围值依赖数组支持，旧解释器不实现
+                Err(RuntimeError::InvalidOperation(
+                    "for-in over arrays and range values is unsupported in --old; run without --old".to_string(),
+                ))
+            }
+
             Stmt::For {
                 variable,
                 start,
                 end,
+                inclusive,
                 body,
+                ..
             } => {
                 let start_val = self.evaluate_expression(start)?;
                 let end_val = self.evaluate_expression(end)?;
@@ -223,7 +253,12 @@ impl Interpreter {
                 if let (Value::Integer(start_i), Value::Integer(end_i)) = (start_val, end_val) {
                     self.environment.push_scope();
 
-                    'outer: for i in start_i..end_i {
+                    let values: Box<dyn Iterator<Item = i64>> = if *inclusive {
+                        Box::new(start_i..=end_i)
+                    } else {
+                        Box::new(start_i..end_i)
+                    };
+                    'outer: for i in values {
                         self.environment
                             .define(variable.clone(), Value::Integer(i));
 
@@ -259,7 +294,7 @@ impl Interpreter {
                 Ok(Value::Null)
             }
 
-            Stmt::Block { statements } => {
+            Stmt::Block { statements, .. } => {
                 self.environment.push_scope();
 
                 for stmt in statements {
@@ -294,16 +329,20 @@ impl Interpreter {
                 Ok(Value::Null)
             }
 
-            Expr::FieldAccess { object: _, field: _ } => {
+            Expr::FieldAccess { .. } => {
                 // TODO: 实现字段访问的解释执行
                 Ok(Value::Null)
             }
 
-            Expr::FieldAssign { object: _, field: _, value } => {
+            Expr::FieldAssign { value, .. } => {
                 // TODO: 实现字段赋值的解释执行
                 self.evaluate_expression(value)
             }
 
+            Expr::Tuple(_) => {
+                Err(RuntimeError::InvalidOperation("Tuples not supported in legacy interpreter".to_string()))
+            }
+
             Expr::MethodCall { .. } => {
                 // Method calls are not supported in the legacy interpreter
                 // They are only used for the bytecode compiler
@@ -327,6 +366,13 @@ impl Interpreter {
 
             Expr::Call { callee, arguments } => self.evaluate_call(callee, arguments),
 
+            Expr::PostfixUpdate { name, operator } => {
+                let old = self.environment.get(name)?;
+                let new = self.evaluate_binary(&Expr::Identifier(name.clone()), operator, &Expr::Integer(1))?;
+                self.environment.set(name, new)?;
+                Ok(old)
+            }
+
             Expr::Assign { name, value } => {
                 let val = self.evaluate_expression(value)?;
                 self.environment.set(name, val.clone())?;
@@ -347,6 +393,21 @@ impl Interpreter {
                 ))
             }
             
+            Expr::If { condition, then_expr, else_expr } => {
+                if self.evaluate_expression(condition)?.is_truthy() {
+                    self.evaluate_expression(then_expr)
+                } else {
+                    self.evaluate_expression(else_expr)
+                }
+            }
+
+            Expr::Range { .. } => {
+                // 范围值依赖数组支持，旧解释器不实现（`for` 中直接写的范围按计数循环执行）
+                Err(RuntimeError::InvalidOperation(
+                    "range values are unsupported in --old; run without --old".to_string(),
+                ))
+            }
+
             Expr::IndexAssign { object, index, value } => {
                 // 数组索引赋值 - 暂时返回占位值
                 // TODO: 实现完整的数组索引赋值支持
@@ -441,29 +502,13 @@ impl Interpreter {
             BinaryOp::Equal => Ok(Value::Boolean(self.values_equal(&left_val, &right_val))),
             BinaryOp::NotEqual => Ok(Value::Boolean(!self.values_equal(&left_val, &right_val))),
 
-            BinaryOp::Less => match (left_val, right_val) {
-                (Value::Integer(l), Value::Integer(r)) => Ok(Value::Boolean(l < r)),
-                (Value::Float(l), Value::Float(r)) => Ok(Value::Boolean(l < r)),
-                _ => Err(RuntimeError::TypeMismatch("Invalid comparison".to_string())),
-            },
+            BinaryOp::Less => self.compare_numbers(&left_val, &right_val, |o| o == Some(Ordering::Less)),
 
-            BinaryOp::LessEqual => match (left_val, right_val) {
-                (Value::Integer(l), Value::Integer(r)) => Ok(Value::Boolean(l <= r)),
-                (Value::Float(l), Value::Float(r)) => Ok(Value::Boolean(l <= r)),
-                _ => Err(RuntimeError::TypeMismatch("Invalid comparison".to_string())),
-            },
+            BinaryOp::LessEqual => self.compare_numbers(&left_val, &right_val, |o| matches!(o, Some(Ordering::Less | Ordering::Equal))),
 
-            BinaryOp::Greater => match (left_val, right_val) {
-                (Value::Integer(l), Value::Integer(r)) => Ok(Value::Boolean(l > r)),
-                (Value::Float(l), Value::Float(r)) => Ok(Value::Boolean(l > r)),
-                _ => Err(RuntimeError::TypeMismatch("Invalid comparison".to_string())),
-            },
+            BinaryOp::Greater => self.compare_numbers(&left_val, &right_val, |o| o == Some(Ordering::Greater)),
 
-            BinaryOp::GreaterEqual => match (left_val, right_val) {
-                (Value::Integer(l), Value::Integer(r)) => Ok(Value::Boolean(l >= r)),
-                (Value::Float(l), Value::Float(r)) => Ok(Value::Boolean(l >= r)),
-                _ => Err(RuntimeError::TypeMismatch("Invalid comparison".to_string())),
-            },
+            BinaryOp::GreaterEqual => self.compare_numbers(&left_val, &right_val, |o| matches!(o, Some(Ordering::Greater | Ordering::Equal))),
 
             BinaryOp::And => Ok(Value::Boolean(left_val.is_truthy() && right_val.is_truthy())),
             BinaryOp::Or => Ok(Value::Boolean(left_val.is_truthy() || right_val.is_truthy())),
@@ -480,6 +525,10 @@ impl Interpreter {
                 Value::Float(f) => Ok(Value::Float(-f)),
                 _ => Err(RuntimeError::TypeMismatch("Invalid negation".to_string())),
             },
+            UnaryOp::Plus => match value {
+                Value::Integer(_) | Value::Float(_) => Ok(value),
+                _ => Err(RuntimeError::TypeMismatch("Invalid unary plus".to_string())),
+            },
         }
     }
 
@@ -524,6 +573,23 @@ impl Interpreter {
         Ok(Value::Null)
     }
 
+    /// 数值比较，整数和浮点数可混合比较（与VM的comparison_op一致）
+    /// 与 VM 一致：两个整数直接比较，不经过 f64；NaN 参与比较时 `op` 收到 None
+    fn compare_numbers<F>(&self, left: &Value, right: &Value, op: F) -> RuntimeResult<Value>
+    where
+        F: FnOnce(Option<Ordering>) -> bool,
+    {
+        let result = match (left, right) {
+            (Value::Integer(l), Value::Integer(r)) => op(Some(l.cmp(r))),
+            (Value::Float(l), Value::Float(r)) => op(l.partial_cmp(r)),
+            (Value::Integer(l), Value::Float(r)) => op((*l as f64).partial_cmp(r)),
+            (Value::Float(l), Value::Integer(r)) => op(l.partial_cmp(&(*r as f64))),
+            (Value::Char(l), Value::Char(r)) => op(Some(l.cmp(r))),
+            _ => return Err(RuntimeError::TypeMismatch("Invalid comparison".to_string())),
+        };
+        Ok(Value::Boolean(result))
+    }
+
     fn values_equal(&self, left: &Value, right: &Value) -> bool {
         match (left, right) {
             (Value::Integer(l), Value::Integer(r)) => l == r,
@@ -540,4 +606,61 @@ impl Default for Interpreter {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode;
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::vm::VM;
+
+    #[test]
+    fn test_mixed_numeric_comparison_matches_vm() {
+        let source = r#"
+            let lt = 3 < 3.5;
+            let le = 4 <= 3.5;
+            let gt = 3.5 > 3;
+            let ge = 2.5 >= 3;
+            let big_gt = 9007199254740993 > 9007199254740992;
+            let big_le = 9007199254740993 <= 9007199254740992;
+        "#;
+        let parse = || {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            Parser::new(tokens).parse().unwrap()
+        };
+
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(parse()).unwrap();
+
+        let mut vm = VM::new();
+        vm.execute(Compiler::new().compile(parse()).unwrap()).unwrap();
+
+        // 超过 2^53 的整数直接比较，不因转换为 f64 而相等
+        let cases = [("lt", true), ("le", false), ("gt", true), ("ge", false), ("big_gt", true), ("big_le", false)];
+        for (name, expected) in cases {
+            assert!(matches!(interpreter.environment.get(name), Ok(Value::Boolean(b)) if b == expected));
+            assert_eq!(vm.get_global(name), Some(&bytecode::Value::Boolean(expected)));
+        }
+    }
+
+    #[test]
+    fn test_range_values_are_rejected_clearly() {
+        for source in ["let r = 0..3;", "let xs = [1]; for x in xs { }"] {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let program = Parser::new(tokens).parse().unwrap();
+            match Interpreter::new().interpret(program) {
+                Err(RuntimeError::InvalidOperation(message)) => assert!(message.contains("unsupported in --old")),
+                other => panic!("expected an unsupported error, got {:?}", other),
+            }
+        }
+
+        // `for` 中直接写的范围仍按计数循环执行
+        let tokens = Lexer::new("var total = 0; for i in 1..=3 { total = total + i; }".to_string()).tokenize().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(Parser::new(tokens).parse().unwrap()).unwrap();
+        assert!(matches!(interpreter.environment.get("total"), Ok(Value::Integer(6))));
+    }
+}
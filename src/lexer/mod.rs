@@ -8,6 +8,14 @@ pub use crate::error::{CompilerError as LexerError};
 
 pub type LexerResult<T> = Result<T, LexerError>;
 
+/// 默认制表符宽度（列号按制表位计算，错误展示时按同样宽度展开）
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// 制表符之后的列号（列号从1开始）
+pub(crate) fn next_tab_stop(column: usize, tab_width: usize) -> usize {
+    column + tab_width - (column - 1) % tab_width
+}
+
 /// 词法分析器主结构
 pub struct Lexer {
     input: Vec<char>,
@@ -15,6 +23,7 @@ pub struct Lexer {
     line: usize,
     column: usize,
     current_char: Option<char>,
+    tab_width: usize,
 }
 
 impl Lexer {
@@ -27,15 +36,25 @@ impl Lexer {
             line: 1,
             column: 1,
             current_char,
+            tab_width: DEFAULT_TAB_WIDTH,
         }
     }
 
+    /// 设置制表符宽度（至少为1）；需要与 `ErrorDisplayer::with_tab_width` 保持一致
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+
     /// 前进到下一个字符，处理UTF-8和行列追踪
     fn advance(&mut self) {
         if let Some(ch) = self.current_char {
             if ch == '\n' {
                 self.line += 1;
                 self.column = 1;
+            } else if ch == '\t' {
+                // 跳到下一个制表位
+                self.column = next_tab_stop(self.column, self.tab_width);
             } else {
                 // UTF-8字符宽度处理
                 self.column += Self::char_display_width(ch);
@@ -47,7 +66,7 @@ impl Lexer {
     }
 
     /// 计算字符的显示宽度（用于正确的列位置计算）
-    fn char_display_width(ch: char) -> usize {
+    pub(crate) fn char_display_width(ch: char) -> usize {
         // 简化版本：大多数字符宽度为1，某些CJK字符为2
         if ch.is_ascii() {
             1
@@ -88,6 +107,33 @@ impl Lexer {
         }
     }
 
+    /// 跳过块注释 `/* ... */`，支持嵌套；到达文件末尾仍未闭合时报错
+    fn skip_block_comment(&mut self) -> LexerResult<()> {
+        let start = self.current_position();
+        let mut depth = 0;
+        loop {
+            match (self.current_char, self.peek(1)) {
+                (Some('/'), Some('*')) => {
+                    depth += 1;
+                    self.advance();
+                    self.advance();
+                }
+                (Some('*'), Some('/')) => {
+                    depth -= 1;
+                    self.advance();
+                    self.advance();
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                (Some(_), _) => self.advance(),
+                (None, _) => {
+                    return Err(LexerError::unterminated_comment(start.line, start.column, start.offset));
+                }
+            }
+        }
+    }
+
     /// 跳过单行注释
     fn skip_comment(&mut self) {
         if self.current_char == Some('/') && self.peek(1) == Some('/') {
@@ -374,8 +420,19 @@ impl Lexer {
             }
         }
 
-        if self.current_char != Some('\'') {
-            return Err(LexerError::unterminated_string(start_pos.line, start_pos.column, start_pos.offset));
+        match self.current_char {
+            // 空字符字面量 `''`：错误指向结束单引号
+            Some('\'') if value.is_empty() => {
+                return Err(LexerError::invalid_character('\'', self.line, self.column, self.position));
+            }
+            Some('\'') => {}
+            // 同一行后面还有结束单引号，说明是多字符字面量 `'ab'`：错误指向多出的第一个字符
+            Some(ch) if self.closing_quote_on_line() => {
+                return Err(LexerError::invalid_character(ch, self.line, self.column, self.position));
+            }
+            _ => {
+                return Err(LexerError::unterminated_string(start_pos.line, start_pos.column, start_pos.offset));
+            }
         }
 
         self.advance(); // 跳过结束单引号
@@ -384,10 +441,19 @@ impl Lexer {
         Ok(Token::new(TokenType::Char, value, start_pos, end_pos))
     }
 
-    /// 读取转义序列
+    /// 当前行剩余部分是否还有单引号
+    fn closing_quote_on_line(&self) -> bool {
+        self.input[self.position..]
+            .iter()
+            .take_while(|&&ch| ch != '\n')
+            .any(|&ch| ch == '\'')
+    }
+
+    /// 读取转义序列（调用前已跳过反斜杠）
     fn read_escape_sequence(&mut self) -> LexerResult<String> {
         let line = self.line;
-        let column = self.column;
+        // 错误位置指向反斜杠，使插入符覆盖整个转义序列
+        let column = self.column - 1;
         
         match self.current_char {
             Some('n') => {
@@ -527,6 +593,10 @@ impl Lexer {
                 self.skip_comment();
                 continue;
             }
+            if self.current_char == Some('/') && self.peek(1) == Some('*') {
+                self.skip_block_comment()?;
+                continue;
+            }
 
             break;
         }
@@ -567,6 +637,9 @@ impl Lexer {
                         if self.current_char == Some('=') {
                             self.advance();
                             Token::new(TokenType::PlusEqual, "+=".to_string(), start_pos, self.current_position())
+                        } else if self.current_char == Some('+') {
+                            self.advance();
+                            Token::new(TokenType::PlusPlus, "++".to_string(), start_pos, self.current_position())
                         } else {
                             Token::new(TokenType::Plus, "+".to_string(), start_pos, self.current_position())
                         }
@@ -579,6 +652,9 @@ impl Lexer {
                         } else if self.current_char == Some('>') {
                             self.advance();
                             Token::new(TokenType::Arrow, "->".to_string(), start_pos, self.current_position())
+                        } else if self.current_char == Some('-') {
+                            self.advance();
+                            Token::new(TokenType::MinusMinus, "--".to_string(), start_pos, self.current_position())
                         } else {
                             Token::new(TokenType::Minus, "-".to_string(), start_pos, self.current_position())
                         }
@@ -615,6 +691,9 @@ impl Lexer {
                         if self.current_char == Some('=') {
                             self.advance();
                             Token::new(TokenType::EqualEqual, "==".to_string(), start_pos, self.current_position())
+                        } else if self.current_char == Some('>') {
+                            self.advance();
+                            Token::new(TokenType::FatArrow, "=>".to_string(), start_pos, self.current_position())
                         } else {
                             Token::new(TokenType::Equal, "=".to_string(), start_pos, self.current_position())
                         }
@@ -652,7 +731,8 @@ impl Lexer {
                             self.advance();
                             Token::new(TokenType::And, "&&".to_string(), start_pos, self.current_position())
                         } else {
-                            Token::new(TokenType::Unknown, "&".to_string(), start_pos, self.current_position())
+                            // 单个 & 不是合法运算符
+                            return Err(LexerError::invalid_character('&', start_pos.line, start_pos.column, start_pos.offset));
                         }
                     }
                     '|' => {
@@ -661,7 +741,8 @@ impl Lexer {
                             self.advance();
                             Token::new(TokenType::Or, "||".to_string(), start_pos, self.current_position())
                         } else {
-                            Token::new(TokenType::Unknown, "|".to_string(), start_pos, self.current_position())
+                            // 单个 | 不是合法运算符
+                            return Err(LexerError::invalid_character('|', start_pos.line, start_pos.column, start_pos.offset));
                         }
                     }
                     '(' => {
@@ -700,19 +781,25 @@ impl Lexer {
                         self.advance();
                         Token::new(TokenType::Colon, ":".to_string(), start_pos, self.current_position())
                     }
+                    '?' => {
+                        self.advance();
+                        Token::new(TokenType::Question, "?".to_string(), start_pos, self.current_position())
+                    }
                     '.' => {
                         self.advance();
                         if self.current_char == Some('.') {
                             self.advance();
-                            Token::new(TokenType::DotDot, "..".to_string(), start_pos, self.current_position())
+                            if self.current_char == Some('=') {
+                                self.advance();
+                                Token::new(TokenType::DotDotEqual, "..=".to_string(), start_pos, self.current_position())
+                            } else {
+                                Token::new(TokenType::DotDot, "..".to_string(), start_pos, self.current_position())
+                            }
                         } else {
                             Token::new(TokenType::Dot, ".".to_string(), start_pos, self.current_position())
                         }
                     }
-                    _ => {
-                        self.advance();
-                        Token::new(TokenType::Unknown, ch.to_string(), start_pos, self.current_position())
-                    }
+                    _ => return Err(LexerError::invalid_character(ch, start_pos.line, start_pos.column, start_pos.offset)),
                 };
 
                 Ok(token)
@@ -720,8 +807,13 @@ impl Lexer {
         }
     }
 
-    /// 标记化整个输入
+    /// 标记化整个输入，科学计数法已按值转换为 Integer 或 Float
     pub fn tokenize(&mut self) -> LexerResult<Vec<Token>> {
+        Ok(TokenPreprocessor::preprocess(self.tokenize_raw()?))
+    }
+
+    /// 标记化整个输入但不做预处理，科学计数法保留为 `ScientificExponent`
+    pub fn tokenize_raw(&mut self) -> LexerResult<Vec<Token>> {
         let mut tokens = Vec::new();
         
         loop {
@@ -753,6 +845,52 @@ mod tests {
         assert_eq!(tokens[1].start_pos.column, 5);
     }
 
+    #[test]
+    fn test_token_spans_across_lines() {
+        let source = "a == b\n\n  // comment\n  c != \"x\ny\" >= d";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let span = |i: usize| {
+            let t = &tokens[i];
+            (t.value.as_str(), t.start_pos.line, t.start_pos.column, t.end_pos.line, t.end_pos.column, t.start_pos.offset)
+        };
+
+        // 多字符运算符覆盖两个字符
+        assert_eq!(span(1), ("==", 1, 3, 1, 5, 2));
+        assert_eq!(span(2), ("b", 1, 6, 1, 7, 5));
+        // 跳过的空行和注释会推进行号
+        assert_eq!(span(3), ("c", 4, 3, 4, 4, 23));
+        assert_eq!(span(4), ("!=", 4, 5, 4, 7, 25));
+        // 多行字符串结束于下一行
+        assert_eq!(span(5), ("x\ny", 4, 8, 5, 3, 28));
+        assert_eq!(span(6), (">=", 5, 4, 5, 6, 34));
+    }
+
+    #[test]
+    fn test_tab_advances_to_next_tab_stop() {
+        let tokens = Lexer::new("\tlet x\t= 1;".to_string()).tokenize().unwrap();
+        assert_eq!(tokens[0].start_pos.column, 5);
+        assert_eq!(tokens[1].start_pos.column, 9);
+        assert_eq!(tokens[2].start_pos.column, 13);
+
+        let tokens = Lexer::new("ab\tc".to_string()).with_tab_width(8).tokenize().unwrap();
+        assert_eq!(tokens[1].start_pos.column, 9);
+    }
+
+    #[test]
+    fn test_caret_aligned_under_tab_indented_error() {
+        use crate::error::{ErrorDisplayer, ErrorMode};
+
+        let source = "let x = 1;\n\t\tlet y = \"abc;";
+        let error = Lexer::new(source.to_string()).tokenize().unwrap_err();
+        let output = ErrorDisplayer::new(ErrorMode::Detailed).format_error(&error, Some(source));
+
+        let lines: Vec<&str> = output.lines().collect();
+        let code_line = lines.iter().find(|l| l.contains("let y")).unwrap();
+        let caret_line = lines.iter().find(|l| l.contains('^')).unwrap();
+        assert!(!code_line.contains('\t'));
+        assert_eq!(caret_line.find('^').unwrap() - "\x1b[1;31m".len(), code_line.find('"').unwrap());
+    }
+
     #[test]
     fn test_utf8_identifiers() {
         let mut lexer = Lexer::new("let 变量 = 10;".to_string());
@@ -771,6 +909,131 @@ mod tests {
         assert_eq!(tokens[0].value, "hello\nworld\t");
     }
 
+    #[test]
+    fn test_string_escapes_are_decoded() {
+        let tokens = Lexer::new(r#""tab\there" "\r\"\\\0""#.to_string()).tokenize().unwrap();
+        assert_eq!(tokens[0].value, "tab\there");
+        assert_eq!(tokens[1].value, "\r\"\\\0");
+
+        // 未知转义报错，位置指向反斜杠
+        let error = Lexer::new(r#"let s = "a\qb";"#.to_string()).tokenize().unwrap_err();
+        assert_eq!(error.error_type, crate::error::ErrorType::LexerInvalidEscapeSequence);
+        assert_eq!(error.location.column, 11);
+    }
+
+    #[test]
+    fn test_char_hex_and_unicode_escapes() {
+        let tokens = Lexer::new(r"'\x41' 'A' '\u{1F600}' '\u00e9'".to_string()).tokenize().unwrap();
+        assert!(tokens[..4].iter().all(|t| t.token_type == TokenType::Char));
+        assert_eq!(tokens[0].value, "A");
+        assert_eq!(tokens[0].value, tokens[1].value);
+        assert_eq!(tokens[2].value, "\u{1F600}");
+        assert_eq!(tokens[3].value, "é");
+
+        // 代理码点和超出范围的值被拒绝，错误位置指向反斜杠
+        for source in [r"let c = '\u{D800}';", r"let c = '\uDFFF';", r"let c = '\u{110000}';"] {
+            let error = Lexer::new(source.to_string()).tokenize().unwrap_err();
+            assert_eq!(error.error_type, crate::error::ErrorType::LexerInvalidUnicodeEscape, "{}", source);
+            assert_eq!(error.location.column, 10, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_string_unicode_escapes() {
+        let tokens = Lexer::new(r#""\u{4F60}\u597d" "\u{1F600}!""#.to_string()).tokenize().unwrap();
+        assert_eq!(tokens[0].value, "你好");
+        assert_eq!(tokens[1].value, "😀!");
+
+        // 错误参数携带出错的转义序列
+        for (source, sequence) in [(r#""\u{110000}""#, r"\u{110000}"), (r#""\u{D83D}""#, r"\u{D83D}"), (r#""\u{}""#, r"\u{}")] {
+            let error = Lexer::new(source.to_string()).tokenize().unwrap_err();
+            assert_eq!(error.error_type, crate::error::ErrorType::LexerInvalidUnicodeEscape, "{}", source);
+            assert_eq!(error.params.get("sequence").map(String::as_str), Some(sequence));
+        }
+    }
+
+    #[test]
+    fn test_char_literals() {
+        let tokens = Lexer::new(r"let c: char = 'x'; 'a' '\n' '\t' '\''".to_string()).tokenize().unwrap();
+        let chars: Vec<&str> = tokens.iter()
+            .filter(|t| t.token_type == TokenType::Char)
+            .map(|t| t.value.as_str())
+            .collect();
+        assert_eq!(chars, ["x", "a", "\n", "\t", "'"]);
+
+        // 空字面量指向结束单引号，多字符字面量指向多出的字符
+        let error = Lexer::new("let c = '';".to_string()).tokenize().unwrap_err();
+        assert_eq!(error.error_type, crate::error::ErrorType::LexerInvalidCharacter);
+        assert_eq!(error.location.column, 10);
+        let error = Lexer::new("let c = 'ab';".to_string()).tokenize().unwrap_err();
+        assert_eq!(error.error_type, crate::error::ErrorType::LexerInvalidCharacter);
+        assert_eq!(error.location.column, 11);
+        assert_eq!(error.params.get("character").map(String::as_str), Some("b"));
+
+        // 没有结束单引号仍然是未终止错误
+        let error = Lexer::new("let c = 'a;\nlet d = 1;".to_string()).tokenize().unwrap_err();
+        assert_eq!(error.error_type, crate::error::ErrorType::LexerUnterminatedString);
+    }
+
+    #[test]
+    fn test_arrow_is_single_token() {
+        let types = |source: &str| -> Vec<TokenType> {
+            Lexer::new(source.to_string()).tokenize().unwrap()
+                .into_iter()
+                .map(|t| t.token_type)
+                .collect()
+        };
+
+        let tokens = Lexer::new("fn add(a: int, b: int) -> int".to_string()).tokenize().unwrap();
+        let arrow = tokens.iter().find(|t| t.token_type == TokenType::Arrow).unwrap();
+        assert_eq!(arrow.value, "->");
+        assert_eq!(arrow.end_pos.column - arrow.start_pos.column, 2);
+
+        // 中间有空格时仍是减号和大于号
+        assert_eq!(types("a - > b")[1..3], [TokenType::Minus, TokenType::Greater]);
+        assert_eq!(types("a->b")[1], TokenType::Arrow);
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let source = "let a = 1; /* let b = 2; // 行注释\n /* 嵌套 */ let c = 3; */ let d = a /**/ * 2;";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+        assert_eq!(values, ["let", "a", "=", "1", ";", "let", "d", "=", "a", "*", "2", ";", ""]);
+        // 注释中的换行计入行号
+        assert_eq!(tokens[5].start_pos.line, 2);
+
+        // 未闭合的块注释指向最外层的 `/*`
+        let error = Lexer::new("let x = 1;\n  /* /* */\nlet y = 2;".to_string()).tokenize().unwrap_err();
+        assert_eq!(error.error_type, crate::error::ErrorType::LexerUnterminatedComment);
+        assert_eq!((error.location.line, error.location.column), (2, 3));
+    }
+
+    #[test]
+    fn test_invalid_characters_are_errors() {
+        for (source, ch, column) in [("let x = 1 @ 2;", '@', 11), ("let y = a & b;", '&', 11), ("a | b", '|', 3)] {
+            let error = Lexer::new(source.to_string()).tokenize().unwrap_err();
+            assert_eq!(error.error_type, crate::error::ErrorType::LexerInvalidCharacter, "{}", source);
+            assert_eq!(error.location.column, column, "{}", source);
+            assert_eq!(error.params.get("character").map(String::as_str), Some(ch.to_string().as_str()));
+        }
+    }
+
+    #[test]
+    fn test_invalid_character_display() {
+        use crate::error::{ErrorDisplayer, ErrorMode};
+
+        let source = "let tag = #1;";
+        let error = Lexer::new(source.to_string()).tokenize().unwrap_err();
+        assert_eq!(error.params.get("unicode").map(String::as_str), Some("0023"));
+        assert_eq!(ErrorDisplayer::new(ErrorMode::Simple).format_error(&error, Some(source)), "错误 [L003] 在 1:11: 无效字符 '#' (U+0023)");
+
+        let error = Lexer::new("let price = 5€;".to_string()).tokenize().unwrap_err();
+        assert_eq!(error.params.get("unicode").map(String::as_str), Some("20AC"));
+        let detailed = ErrorDisplayer::new(ErrorMode::Detailed).format_error(&error, Some("let price = 5€;"));
+        assert!(detailed.contains("U+20AC"), "{}", detailed);
+    }
+
     #[test]
     fn test_hex_numbers() {
         let mut lexer = Lexer::new("0xFF 0x10".to_string());
@@ -792,11 +1055,17 @@ mod tests {
     #[test]
     fn test_scientific_notation() {
         let mut lexer = Lexer::new("1e10 3.14e-5".to_string());
-        let tokens = lexer.tokenize().unwrap();
+        let tokens = lexer.tokenize_raw().unwrap();
         
         assert_eq!(tokens[0].token_type, TokenType::ScientificExponent);
         assert_eq!(tokens[0].value, "1e10");
         assert_eq!(tokens[1].token_type, TokenType::ScientificExponent);
+
+        // tokenize 按值推断类型：1e10 在 i64 范围内为整数，负指数为浮点数
+        let tokens = Lexer::new("1e10 1e-3 2.5e3".to_string()).tokenize().unwrap();
+        assert_eq!((tokens[0].token_type.clone(), tokens[0].value.as_str()), (TokenType::Integer, "10000000000"));
+        assert_eq!((tokens[1].token_type.clone(), tokens[1].value.as_str()), (TokenType::Float, "1e-3"));
+        assert_eq!(tokens[2].token_type, TokenType::Float);
     }
 
     #[test]
@@ -809,6 +1078,20 @@ mod tests {
         assert_eq!(tokens[2].token_type, TokenType::StarEqual);
         assert_eq!(tokens[3].token_type, TokenType::SlashEqual);
         assert_eq!(tokens[4].token_type, TokenType::PercentEqual);
+
+        // 只有紧挨着的 `=` 才组合，中间有空格时是两个 token
+        let tokens = Lexer::new("x += 1; x + = 1; x+=1".to_string()).tokenize().unwrap();
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.token_type.clone()).collect();
+        assert_eq!(types[1], TokenType::PlusEqual);
+        assert_eq!(types[5..7], [TokenType::Plus, TokenType::Equal]);
+        assert_eq!(types[10], TokenType::PlusEqual);
+        assert_eq!(tokens[1].end_pos.column - tokens[1].start_pos.column, 2);
+
+        let tokens = Lexer::new("i++ j-- a + +b c - -d".to_string()).tokenize().unwrap();
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.token_type.clone()).collect();
+        assert_eq!(types[..4], [TokenType::Identifier, TokenType::PlusPlus, TokenType::Identifier, TokenType::MinusMinus]);
+        assert_eq!(types[5..7], [TokenType::Plus, TokenType::Plus]);
+        assert_eq!(types[9..11], [TokenType::Minus, TokenType::Minus]);
     }
 
     #[test]
@@ -33,6 +33,7 @@ pub enum TokenType {
     // 关键字
     Let,
     Var,
+    Const,       // const关键字
     Fn,
     Return,
     If,
@@ -48,6 +49,7 @@ pub enum TokenType {
     Struct,      // struct关键字
     Type,        // type关键字
     Impl,        // impl关键字
+    Match,       // match关键字
     Pub,         // pub关键字
     Use,         // use关键字
     Mod,         // mod关键字
@@ -76,6 +78,10 @@ pub enum TokenType {
     StarEqual,      // *=
     SlashEqual,     // /=
     PercentEqual,   // %=
+
+    // 后置自增/自减
+    PlusPlus,       // ++
+    MinusMinus,     // --
     
     // 比较运算符
     Equal,          // =
@@ -101,16 +107,18 @@ pub enum TokenType {
     Comma,          // ,
     Semicolon,      // ;
     Colon,          // :
+    Question,       // ?
     Dot,            // .
     DotDot,         // ..
+    DotDotEqual,    // ..=
     Arrow,          // ->
+    FatArrow,       // =>
     
     // 科学计数法（将被预处理器转换）
     ScientificExponent,
     
     // 特殊
     EOF,
-    Unknown,
 }
 
 impl TokenType {
@@ -118,6 +126,7 @@ impl TokenType {
         match word {
             "let" => Some(TokenType::Let),
             "var" => Some(TokenType::Var),
+            "const" => Some(TokenType::Const),
             "fn" => Some(TokenType::Fn),
             "return" => Some(TokenType::Return),
             "if" => Some(TokenType::If),
@@ -133,6 +142,7 @@ impl TokenType {
             "struct" => Some(TokenType::Struct),
             "type" => Some(TokenType::Type),
             "impl" => Some(TokenType::Impl),
+            "match" => Some(TokenType::Match),
             "pub" => Some(TokenType::Pub),
             "use" => Some(TokenType::Use),
             "mod" => Some(TokenType::Mod),
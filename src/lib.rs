@@ -2,9 +2,12 @@
 pub mod ast;
 pub mod bytecode;
 pub mod compiler;
+pub mod embed;
 pub mod error;
 pub mod interpreter;
 pub mod lexer;
+pub mod linker;
 pub mod parser;
 pub mod type_checker;
-pub mod vm;
\ No newline at end of file
+pub mod vm;
+pub use embed::{eval, ZeroError};
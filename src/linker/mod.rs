@@ -0,0 +1,201 @@
+//! 多文件链接：按给出的顺序拼接各文件的顶层语句，所有文件共享同一个全局作用域
+//! 顶层的常量、结构体、类型别名、`impl` 和函数声明跨文件提升到其他语句之前，
+//! 因此前面文件的语句也能使用后面文件中的这些定义
+
+use crate::ast::{Program, Stmt};
+use std::collections::HashMap;
+use std::fmt;
+
+/// 链接错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    /// 同一个顶层名字在两个文件中都有定义
+    DuplicateDefinition {
+        name: String,
+        first_file: String,
+        second_file: String,
+    },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::DuplicateDefinition { name, first_file, second_file } => write!(
+                f,
+                "'{}' is defined in both '{}' and '{}'",
+                name, first_file, second_file
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// 顶层语句定义的名字；方法记为 `类型.方法`
+fn defined_names(stmt: &Stmt) -> Vec<String> {
+    match stmt {
+        Stmt::VarDeclaration { name, .. }
+        | Stmt::FnDeclaration { name, .. }
+        | Stmt::StructDeclaration { name, .. }
+        | Stmt::TypeAlias { name, .. } => vec![name.clone()],
+        Stmt::ImplBlock { type_name, methods } => methods
+            .iter()
+            .map(|method| format!("{}.{}", type_name, method.name))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 提升的顺序：常量和类型声明在前（函数签名可能用到它们），函数和 `impl` 在后；
+/// 不提升的语句返回 None
+fn hoist_rank(stmt: &Stmt) -> Option<usize> {
+    match stmt {
+        Stmt::VarDeclaration { is_const: true, .. }
+        | Stmt::StructDeclaration { .. }
+        | Stmt::TypeAlias { .. } => Some(0),
+        Stmt::FnDeclaration { .. } | Stmt::ImplBlock { .. } => Some(1),
+        _ => None,
+    }
+}
+
+/// 把 `(文件名, 程序)` 按顺序合并为一个程序
+/// 同一文件内的重复声明照常按遮蔽处理，不同文件定义同一个顶层名字时报错；
+/// 提升的声明按类别排在其他语句之前，每一类（以及其余语句）内部保持命令行顺序
+pub fn link(files: Vec<(String, Program)>) -> Result<Program, LinkError> {
+    let mut owners: HashMap<String, usize> = HashMap::new();
+    let mut linked = Program::new();
+
+    for (index, (_, program)) in files.iter().enumerate() {
+        for name in program.statements.iter().flat_map(defined_names) {
+            match owners.get(&name) {
+                Some(&owner) if owner != index => {
+                    return Err(LinkError::DuplicateDefinition {
+                        name,
+                        first_file: files[owner].0.clone(),
+                        second_file: files[index].0.clone(),
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    owners.insert(name, index);
+                }
+            }
+        }
+    }
+
+    // 稳定排序：同一类别内保持文件顺序和文件内的顺序
+    let mut statements: Vec<(Stmt, usize)> = files
+        .into_iter()
+        .flat_map(|(_, program)| {
+            let lines = program.lines;
+            program
+                .statements
+                .into_iter()
+                .enumerate()
+                .map(move |(index, stmt)| (stmt, lines.get(index).copied().unwrap_or(0)))
+        })
+        .collect();
+    statements.sort_by_key(|(stmt, _)| hoist_rank(stmt).unwrap_or(usize::MAX));
+
+    for (stmt, line) in statements {
+        linked.add_statement_at(stmt, line);
+    }
+    Ok(linked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::type_checker::TypeChecker;
+    use crate::bytecode::Value;
+    use crate::vm::VM;
+
+    fn file(name: &str, source: &str) -> (String, Program) {
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        (name.to_string(), Parser::new(tokens).parse().unwrap())
+    }
+
+    #[test]
+    fn test_second_file_calls_function_from_first() {
+        let program = link(vec![
+            file("math.zero", "fn square(n: int) -> int { return n * n; } var calls = 0;"),
+            file("main.zero", "calls = calls + 1; let result = square(7) + calls;"),
+        ])
+        .unwrap();
+
+        TypeChecker::new().check(&program).unwrap();
+        let chunk = Compiler::new().compile(program).unwrap();
+        let mut vm = VM::new();
+        vm.execute(chunk).unwrap();
+        assert_eq!(vm.get_global("result"), Some(&Value::Integer(50)));
+    }
+
+    #[test]
+    fn test_first_file_uses_hoisted_definitions_from_second() {
+        let program = link(vec![
+            file("main.zero", "let p = Point { x: 3, y: 4 }; let result: Count = square(p.norm2()) + LIMIT;"),
+            file(
+                "geometry.zero",
+                r#"
+                const LIMIT = 1;
+                var created = 0;
+                struct Point { x: int, y: int };
+                impl Point {
+                    fn norm2(self) -> int { return self.x * self.x + self.y * self.y; }
+                }
+                fn square(n: int) -> int { return n * n; }
+                type Count = int;
+                "#,
+            ),
+        ])
+        .unwrap();
+
+        // 常量和类型在前，函数和 impl 在后，其余语句保持原来的顺序
+        let kinds: Vec<&str> = program.statements.iter().map(|stmt| match stmt {
+            Stmt::VarDeclaration { is_const: true, .. } => "const",
+            Stmt::StructDeclaration { .. } => "struct",
+            Stmt::TypeAlias { .. } => "type",
+            Stmt::ImplBlock { .. } => "impl",
+            Stmt::FnDeclaration { .. } => "fn",
+            Stmt::VarDeclaration { name, .. } => name.as_str(),
+            _ => "other",
+        }).collect();
+        assert_eq!(kinds, ["const", "struct", "type", "impl", "fn", "p", "result", "created"]);
+
+        TypeChecker::new().check(&program).unwrap();
+        let chunk = Compiler::new().compile(program).unwrap();
+        let mut vm = VM::new();
+        vm.execute(chunk).unwrap();
+        assert_eq!(vm.get_global("result"), Some(&Value::Integer(626)));
+    }
+
+    #[test]
+    fn test_duplicate_definitions_across_files() {
+        let error = link(vec![
+            file("a.zero", "fn helper() { }"),
+            file("b.zero", "let x = 1;"),
+            file("c.zero", "var helper = 2;"),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            error,
+            LinkError::DuplicateDefinition {
+                name: "helper".to_string(),
+                first_file: "a.zero".to_string(),
+                second_file: "c.zero".to_string(),
+            }
+        );
+        assert_eq!(error.to_string(), "'helper' is defined in both 'a.zero' and 'c.zero'");
+
+        // 同一个类型的不同方法可以分布在多个文件中，同名方法不行
+        let methods = |name: &str| format!("impl P {{ fn {}(self) -> int {{ return 1; }} }}", name);
+        assert!(link(vec![file("a.zero", &methods("one")), file("b.zero", &methods("two"))]).is_ok());
+        assert!(link(vec![file("a.zero", &methods("one")), file("b.zero", &methods("one"))]).is_err());
+
+        // 同一文件内的重复声明是遮蔽，不是链接错误
+        assert!(link(vec![file("a.zero", "let x = 1; let x = 2;")]).is_ok());
+    }
+}
@@ -1,22 +1,17 @@
-mod lexer;
-mod parser;
-mod ast;
-mod bytecode;
-mod compiler;
-mod vm;
-mod type_checker;
-mod error;
-
-// 保留旧的解释器用于对比
-mod interpreter;
+// 命令行只负责参数解析和驱动，编译器各组件来自库 crate
+mod cli;
 
+use Zero_compiler::{ast, bytecode, compiler, embed, error, interpreter, lexer, linker, parser, type_checker, vm};
 use lexer::Lexer;
 use parser::Parser;
 use compiler::Compiler;
-use vm::VM;
+use vm::{VMError, VM};
 use type_checker::TypeChecker;
+use embed::ZeroError;
 use bytecode::serializer::{BytecodeSerializer, BytecodeDeserializer};
-use error::{ErrorMode, ErrorDisplayer};
+use error::ErrorDisplayer;
+use ast::IntWidth;
+use cli::{CliError, Command, Options};
 use std::env;
 use std::fs;
 use std::fs::File;
@@ -25,54 +20,57 @@ use std::process;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    let program = args.first().map(String::as_str).unwrap_or("Zero-compiler");
 
-    if args.len() < 2 {
-        eprintln!("Usage: {} <source_file.zero> [--dtl]", args[0]);
-        eprintln!("       {} --old <source_file.zero> [--dtl]  (use old interpreter)", args[0]);
-        eprintln!("       {} --compile <source_file.zero> <output.zbc> [--dtl]  (compile to bytecode)", args[0]);
-        eprintln!("       {} --run <bytecode_file.zbc>  (run bytecode file)", args[0]);
-        eprintln!("");
-        eprintln!("Options:");
-        eprintln!("  --dtl    显示详细的错误信息（包含源码片段和修复建议）");
-        process::exit(1);
-    }
-
-    // 检查是否有 --dtl 标志
-    let error_mode = if args.contains(&"--dtl".to_string()) {
-        ErrorMode::Detailed
-    } else {
-        ErrorMode::Simple
+    let invocation = match cli::parse_args(args.get(1..).unwrap_or(&[])) {
+        Ok(invocation) => invocation,
+        Err(CliError::NoInput) => {
+            eprint!("{}", cli::usage(program));
+            process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            eprint!("{}", cli::usage(program));
+            process::exit(1);
+        }
     };
+    let options = invocation.options;
 
-    match args[1].as_str() {
-        "--old" => {
-            if args.len() < 3 {
-                eprintln!("Usage: {} --old <source_file.zero> [--dtl]", args[0]);
-                process::exit(1);
-            }
-            let source = read_source_file(&args[2]);
+    match invocation.command {
+        Command::Old { source } => {
+            let source = read_source_file(&source);
             println!("Using old tree-walking interpreter...");
-            run_old(&source, error_mode);
+            run_old(&source, &options);
         }
-        "--compile" => {
-            if args.len() < 4 {
-                eprintln!("Usage: {} --compile <source_file.zero> <output.zbc> [--dtl]", args[0]);
-                process::exit(1);
-            }
-            let source = read_source_file(&args[2]);
-            compile_to_bytecode(&source, &args[3], error_mode);
+        Command::Compile { sources, output } => {
+            let files: Vec<(String, String)> = sources
+                .into_iter()
+                .map(|name| {
+                    let source = read_source_file(&name);
+                    (name, source)
+                })
+                .collect();
+            compile_to_bytecode(&files, &output, &options);
         }
-        "--run" => {
-            if args.len() < 3 {
-                eprintln!("Usage: {} --run <bytecode_file.zbc>", args[0]);
-                process::exit(1);
-            }
-            run_bytecode_file(&args[2]);
+        Command::RunBytecode { bytecode } => run_bytecode_file(&bytecode, options.int_width),
+        Command::Strings { bytecode } => list_bytecode_strings(&bytecode),
+        Command::Eval { expression } => eval(&expression, &options),
+        Command::Symbols { source } => {
+            let source = read_source_file(&source);
+            dump_symbols(&source, &options);
+        }
+        Command::Coverage { source } => {
+            let source = read_source_file(&source);
+            report_type_coverage(&source, &options);
         }
-        _ => {
-            let source = read_source_file(&args[1]);
+        Command::Explain { source } => {
+            let source = read_source_file(&source);
+            explain(&source, &options);
+        }
+        Command::Run { source } => {
+            let source = read_source_file(&source);
             println!("Using bytecode compiler + VM...");
-            run(&source, error_mode);
+            run(&source, &options);
         }
     }
 }
@@ -87,47 +85,83 @@ fn read_source_file(filename: &str) -> String {
     }
 }
 
-/// 编译源代码到字节码文件
-fn compile_to_bytecode(source: &str, output_file: &str, error_mode: ErrorMode) {
-    println!("Compiling {} to {}...", "source", output_file);
+/// 按命令行选项创建类型检查器：`--lint` 开启全部可选检查
+fn type_checker_for(options: &Options) -> TypeChecker {
+    let lint = options.lint;
+    TypeChecker::new()
+        .with_self_assignment_lint(lint)
+        .with_unused_parameter_lint(lint)
+        .with_call_consistency_lint(lint)
+        .with_shadowed_builtin_lint(lint)
+        .with_warnings(lint)
+}
 
-    // 词法分析
-    let mut lexer = Lexer::new(source.to_string());
-    let tokens = match lexer.tokenize() {
-        Ok(t) => t,
-        Err(err) => {
-            let displayer = ErrorDisplayer::new(error_mode);
-            eprintln!("{}", displayer.format_error(&err, Some(source)));
-            process::exit(1);
+/// 编译源代码到字节码文件；`files` 为 `(文件名, 源码)`，多个文件按顺序链接为一个程序
+fn compile_to_bytecode(
+    files: &[(String, String)],
+    output_file: &str,
+    options: &Options,
+) {
+    let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+    println!("Compiling {} to {}...", names.join(", "), output_file);
+
+    let mut programs = Vec::new();
+    for (name, source) in files {
+        // 多个文件时标明出错的文件
+        let location = if files.len() > 1 { format!("{}: ", name) } else { String::new() };
+
+        // 词法分析
+        let mut lexer = Lexer::new(source.to_string());
+        let tokens = match lexer.tokenize() {
+            Ok(t) => t,
+            Err(err) => {
+                let displayer = ErrorDisplayer::new(options.error_mode);
+                eprintln!("{}{}", location, displayer.format_error(&err, Some(source)));
+                process::exit(1);
+            }
+        };
+
+        // 语法分析
+        let mut parser = Parser::new(tokens)
+            .with_int_width(options.int_width)
+            .with_newline_terminators(options.newline_terminators);
+        match parser.parse() {
+            Ok(prog) => programs.push((name.clone(), prog)),
+            Err(_) => {
+                let displayer = ErrorDisplayer::new(options.error_mode);
+                eprintln!("{}{}", location, displayer.format_errors(parser.errors(), Some(source)));
+                process::exit(1);
+            }
         }
-    };
-
-    // 预处理tokens（处理科学计数法等）
-    let tokens = lexer::TokenPreprocessor::preprocess(tokens);
+    }
 
-    // 语法分析
-    let mut parser = Parser::new(tokens);
-    let program = match parser.parse() {
-        Ok(prog) => prog,
+    // 链接为一个程序
+    let program = match linker::link(programs) {
+        Ok(program) => program,
         Err(err) => {
-            eprintln!("Parse error: {:?}", err);
+            eprintln!("Link error: {}", err);
             process::exit(1);
         }
     };
 
     // 类型检查
-    let mut type_checker = TypeChecker::new();
-    if let Err(err) = type_checker.check(&program) {
-        eprintln!("Type error: {:?}", err);
+    let mut type_checker = type_checker_for(options);
+    if type_checker.check(&program).is_err() {
+        for err in type_checker.errors() {
+            eprintln!("Type error: {}", err);
+        }
         process::exit(1);
     }
+    for warning in type_checker.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
 
     // 编译为字节码
-    let mut compiler = Compiler::new();
+    let mut compiler = Compiler::new().with_debug_assertions(!options.release);
     let chunk = match compiler.compile(program) {
         Ok(chunk) => chunk,
         Err(err) => {
-            eprintln!("Compile error: {:?}", err);
+            eprintln!("Compile error: {}", err);
             process::exit(1);
         }
     };
@@ -142,7 +176,7 @@ fn compile_to_bytecode(source: &str, output_file: &str, error_mode: ErrorMode) {
     };
 
     let mut writer = BufWriter::new(file);
-    if let Err(err) = BytecodeSerializer::serialize(&chunk, &mut writer) {
+    if let Err(err) = BytecodeSerializer::serialize_with_int_width(&chunk, &mut writer, options.int_width) {
         eprintln!("Error serializing bytecode: {}", err);
         process::exit(1);
     }
@@ -150,8 +184,122 @@ fn compile_to_bytecode(source: &str, output_file: &str, error_mode: ErrorMode) {
     println!("Successfully compiled to {}", output_file);
 }
 
-/// 从字节码文件运行
-fn run_bytecode_file(filename: &str) {
+/// 类型检查后打印全局符号及其推导类型
+fn dump_symbols(source: &str, options: &Options) {
+    // 词法分析
+    let mut lexer = Lexer::new(source.to_string());
+    let tokens = match lexer.tokenize() {
+        Ok(t) => t,
+        Err(err) => {
+            let displayer = ErrorDisplayer::new(options.error_mode);
+            eprintln!("{}", displayer.format_error(&err, Some(source)));
+            process::exit(1);
+        }
+    };
+
+    // 语法分析
+    let mut parser = Parser::new(tokens)
+        .with_int_width(options.int_width)
+        .with_newline_terminators(options.newline_terminators);
+    let program = match parser.parse() {
+        Ok(prog) => prog,
+        Err(_) => {
+            let displayer = ErrorDisplayer::new(options.error_mode);
+            eprintln!("{}", displayer.format_errors(parser.errors(), Some(source)));
+            process::exit(1);
+        }
+    };
+
+    // 类型检查
+    let mut type_checker = type_checker_for(options);
+    if type_checker.check(&program).is_err() {
+        for err in type_checker.errors() {
+            eprintln!("Type error: {}", err);
+        }
+        process::exit(1);
+    }
+    for warning in type_checker.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    for symbol in type_checker.global_symbols() {
+        println!("{}", symbol);
+    }
+}
+
+/// `--coverage` 模式下覆盖率低于该百分比时给出警告
+const MIN_TYPE_COVERAGE: f64 = 50.0;
+
+/// 类型检查后报告类型覆盖率（Unknown 类型的表达式不计入）
+fn report_type_coverage(source: &str, options: &Options) {
+    // 词法分析
+    let mut lexer = Lexer::new(source.to_string());
+    let tokens = match lexer.tokenize() {
+        Ok(t) => t,
+        Err(err) => {
+            let displayer = ErrorDisplayer::new(options.error_mode);
+            eprintln!("{}", displayer.format_error(&err, Some(source)));
+            process::exit(1);
+        }
+    };
+
+    // 语法分析
+    let mut parser = Parser::new(tokens)
+        .with_int_width(options.int_width)
+        .with_newline_terminators(options.newline_terminators);
+    let program = match parser.parse() {
+        Ok(prog) => prog,
+        Err(_) => {
+            let displayer = ErrorDisplayer::new(options.error_mode);
+            eprintln!("{}", displayer.format_errors(parser.errors(), Some(source)));
+            process::exit(1);
+        }
+    };
+
+    // 类型检查
+    let mut type_checker = type_checker_for(options).with_min_type_coverage(MIN_TYPE_COVERAGE);
+    if type_checker.check(&program).is_err() {
+        for err in type_checker.errors() {
+            eprintln!("Type error: {}", err);
+        }
+        process::exit(1);
+    }
+    for warning in type_checker.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    println!("Type coverage: {}", type_checker.coverage());
+}
+
+/// 列出字节码文件常量池中的字符串（不解码顶层指令）
+fn list_bytecode_strings(filename: &str) {
+    let file = match File::open(filename) {
+        Ok(f) => f,
+        Err(err) => {
+            eprintln!("Error opening bytecode file: {}", err);
+            process::exit(1);
+        }
+    };
+
+    let mut reader = BufReader::new(file);
+    let result = match BytecodeDeserializer::read_string_constants(&mut reader) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Error reading bytecode: {}", err);
+            process::exit(1);
+        }
+    };
+
+    for string in &result.strings {
+        println!("{:?}", string);
+    }
+    if !result.complete {
+        eprintln!("Warning: stopped at data this version cannot decode; list may be incomplete");
+    }
+}
+
+/// 从字节码文件运行；按文件头记录的整数位宽执行，`--int32` 可强制使用 32 位
+fn run_bytecode_file(filename: &str, int_width: IntWidth) {
     println!("Loading bytecode from {}...", filename);
     
     let file = match File::open(filename) {
@@ -163,8 +311,8 @@ fn run_bytecode_file(filename: &str) {
     };
 
     let mut reader = BufReader::new(file);
-    let chunk = match BytecodeDeserializer::deserialize(&mut reader) {
-        Ok(c) => c,
+    let (chunk, file_int_width) = match BytecodeDeserializer::deserialize_with_int_width(&mut reader) {
+        Ok(loaded) => loaded,
         Err(err) => {
             eprintln!("Error deserializing bytecode: {}", err);
             process::exit(1);
@@ -179,54 +327,57 @@ fn run_bytecode_file(filename: &str) {
     }
 
     // VM执行
-    let mut vm = VM::new();
-    if let Err(err) = vm.execute(chunk) {
-        eprintln!("Runtime error: {:?}", err);
-        process::exit(1);
-    }
+    let int_width = if int_width == IntWidth::I32 { IntWidth::I32 } else { file_int_width };
+    let mut vm = VM::new().with_int_width(int_width);
+    exit_on_runtime_error(vm.execute(chunk));
 }
 
 
 
 /// 新的字节码编译器 + VM执行
-fn run(source: &str, error_mode: ErrorMode) {
+fn run(source: &str, options: &Options) {
     // 词法分析
     let mut lexer = Lexer::new(source.to_string());
     let tokens = match lexer.tokenize() {
         Ok(t) => t,
         Err(err) => {
-            let displayer = ErrorDisplayer::new(error_mode);
+            let displayer = ErrorDisplayer::new(options.error_mode);
             eprintln!("{}", displayer.format_error(&err, Some(source)));
             process::exit(1);
         }
     };
 
-    // 预处理tokens（处理科学计数法等）
-    let tokens = lexer::TokenPreprocessor::preprocess(tokens);
-
     // 语法分析
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::new(tokens)
+        .with_int_width(options.int_width)
+        .with_newline_terminators(options.newline_terminators);
     let program = match parser.parse() {
         Ok(prog) => prog,
-        Err(err) => {
-            eprintln!("Parse error: {:?}", err);
+        Err(_) => {
+            let displayer = ErrorDisplayer::new(options.error_mode);
+            eprintln!("{}", displayer.format_errors(parser.errors(), Some(source)));
             process::exit(1);
         }
     };
 
     // 类型检查
-    let mut type_checker = TypeChecker::new();
-    if let Err(err) = type_checker.check(&program) {
-        eprintln!("Type error: {:?}", err);
+    let mut type_checker = type_checker_for(options);
+    if type_checker.check(&program).is_err() {
+        for err in type_checker.errors() {
+            eprintln!("Type error: {}", err);
+        }
         process::exit(1);
     }
+    for warning in type_checker.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
 
     // 编译为字节码
-    let mut compiler = Compiler::new();
+    let mut compiler = Compiler::new().with_debug_assertions(!options.release);
     let chunk = match compiler.compile(program) {
         Ok(chunk) => chunk,
         Err(err) => {
-            eprintln!("Compile error: {:?}", err);
+            eprintln!("Compile error: {}", err);
             process::exit(1);
         }
     };
@@ -237,35 +388,209 @@ fn run(source: &str, error_mode: ErrorMode) {
     }
 
     // VM执行
-    let mut vm = VM::new();
-    if let Err(err) = vm.execute(chunk) {
-        eprintln!("Runtime error: {:?}", err);
-        process::exit(1);
+    let mut vm = VM::new().with_int_width(options.int_width);
+    exit_on_runtime_error(vm.execute(chunk));
+}
+
+/// `exit(code)` 以其退出码结束进程；其他运行时错误打印后以1退出
+fn exit_on_runtime_error(result: Result<(), VMError>) {
+    match result {
+        Ok(()) => {}
+        Err(VMError::Exit(code)) => process::exit(code),
+        Err(err) => {
+            eprintln!("Runtime error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// 保存 --eval 表达式结果的全局变量名
+const EVAL_RESULT: &str = "__eval__";
+
+/// 计算器模式：求值单个表达式并打印结果
+fn eval(input: &str, options: &Options) {
+    match evaluate(input, options) {
+        Ok(Some(result)) => println!("{}", result),
+        Ok(None) => {}
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
     }
 }
 
+/// 将输入包装为 `let __eval__ = (<expr>);` 执行并返回结果的字符串形式。
+/// 输入不是表达式（如 `let x = 1`）时按普通程序执行，结果为 None；
+/// 表达式的值为 null（如 `print(1)`）时同样不返回结果。
+fn evaluate(input: &str, options: &Options) -> Result<Option<String>, String> {
+    let input = input.trim().trim_end_matches(';');
+    let wrapped = format!("let {} = ({});", EVAL_RESULT, input);
+
+    let chunk = match compile_checked(&wrapped, options) {
+        Ok(chunk) => chunk,
+        Err(_) => {
+            let program = if input.ends_with('}') {
+                input.to_string()
+            } else {
+                format!("{};", input)
+            };
+            let chunk = compile_checked(&program, options)?;
+            execute_chunk(chunk, options.int_width)?;
+            return Ok(None);
+        }
+    };
+
+    let vm = execute_chunk(chunk, options.int_width)?;
+    Ok(vm.get_global(EVAL_RESULT)
+        .filter(|value| !matches!(value, bytecode::Value::Null))
+        .map(|value| value.to_string()))
+}
+
+/// 词法分析、语法分析、类型检查并编译，错误以可打印的消息返回
+fn compile_checked(source: &str, options: &Options) -> Result<bytecode::Chunk, String> {
+    let displayer = ErrorDisplayer::new(options.error_mode);
+    let (chunk, warnings) = embed::compile(source, options.int_width, type_checker_for(options)).map_err(|err| match err {
+        ZeroError::Lexer(err) => displayer.format_error(&err, Some(source)),
+        ZeroError::Parse(errors) => displayer.format_errors(&errors, Some(source)),
+        other => other.to_string(),
+    })?;
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(chunk)
+}
+
+/// 在新的VM中执行字节码，返回执行后的VM以便读取全局变量
+fn execute_chunk(chunk: bytecode::Chunk, int_width: IntWidth) -> Result<VM, String> {
+    let mut vm = VM::new().with_int_width(int_width);
+    match vm.execute(chunk) {
+        Ok(()) => Ok(vm),
+        Err(VMError::Exit(code)) => process::exit(code),
+        Err(err) => Err(format!("Runtime error: {}", err)),
+    }
+}
+
+/// 教学模式：逐条顶层语句打印源码、字节码和执行后的栈与全局变量
+fn explain(source: &str, options: &Options) {
+    match explain_program(source, options) {
+        Ok(walkthrough) => print!("{}", walkthrough),
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+    }
+}
+
+/// 生成 `--explain` 的完整输出。每条语句执行到下一条语句的第一条指令为止，
+/// 程序的 print 输出被捕获后显示在对应语句下；运行时错误之前的部分照常输出
+fn explain_program(source: &str, options: &Options) -> Result<String, String> {
+    let tokens = Lexer::new(source.to_string()).tokenize().map_err(|err| {
+        ErrorDisplayer::new(options.error_mode).format_error(&err, Some(source))
+    })?;
+
+    let mut parser = Parser::new(tokens)
+        .with_int_width(options.int_width)
+        .with_newline_terminators(options.newline_terminators);
+    let program = parser.parse().map_err(|_| {
+        ErrorDisplayer::new(options.error_mode).format_errors(parser.errors(), Some(source))
+    })?;
+
+    let mut type_checker = type_checker_for(options);
+    type_checker.check(&program).map_err(|err| format!("Type error: {}", err))?;
+
+    let mut compiler = Compiler::new();
+    let chunk = compiler.compile(program).map_err(|err| format!("Compile error: {}", err))?;
+    let offsets = compiler.statement_offsets().to_vec();
+    let halt = chunk.len() - 1;
+
+    let chars: Vec<char> = source.chars().collect();
+    let mut output = String::new();
+    let mut vm = VM::new().with_int_width(options.int_width).with_captured_output();
+    vm.load(chunk.clone());
+
+    for (index, (start_pos, end_pos)) in parser.statement_spans().iter().enumerate() {
+        let start = offsets[index];
+        let end = offsets.get(index + 1).copied().unwrap_or(halt);
+
+        output.push_str(&format!("=== Statement {} (line {}) ===\n", index + 1, start_pos.line));
+        let text: String = chars[start_pos.offset..end_pos.offset].iter().collect();
+        output.push_str(&format!("{}\n", text.trim()));
+
+        output.push_str("Bytecode:\n");
+        if start == end {
+            output.push_str("  (none)\n");
+        }
+        for offset in start..end {
+            let op = &chunk.code[offset];
+            output.push_str(&format!("  {}\n", chunk.format_instruction(offset, op)));
+            // 函数和方法体单独编译，加载时一并展示
+            if let bytecode::OpCode::LoadConst(idx) = op {
+                if let Some(bytecode::Value::Function(function)) = chunk.constants.get(*idx) {
+                    for line in function.chunk.disassemble_to_string(&function.name).lines() {
+                        output.push_str(&format!("    {}\n", line));
+                    }
+                }
+            }
+        }
+
+        let mut result = Ok(false);
+        while vm.top_level_ip() != Some(end) {
+            result = vm.run_steps(1);
+            if !matches!(result, Ok(false)) {
+                break;
+            }
+        }
+
+        let printed = vm.take_output();
+        if !printed.is_empty() {
+            output.push_str("Output:\n");
+            for line in printed.lines() {
+                output.push_str(&format!("  {}\n", line));
+            }
+        }
+
+        match result {
+            Err(VMError::Exit(code)) => {
+                output.push_str(&format!("Program exited with code {}\n", code));
+                return Ok(output);
+            }
+            Err(err) => return Err(format!("{}Runtime error: {}", output, err)),
+            Ok(_) => {}
+        }
+
+        let stack: Vec<String> = vm.stack().iter().map(|value| value.to_string()).collect();
+        output.push_str(&format!("Stack: [{}]\n", stack.join(", ")));
+        let mut globals: Vec<String> = vm.globals()
+            .filter(|(_, value)| !matches!(value, bytecode::Value::NativeFunction(_)))
+            .map(|(name, value)| format!("{} = {}", name, value.to_string()))
+            .collect();
+        globals.sort();
+        output.push_str(&format!("Globals: {}\n\n", if globals.is_empty() { "(none)".to_string() } else { globals.join(", ") }));
+    }
+
+    Ok(output)
+}
+
 /// 旧的树遍历解释器（用于对比）
-fn run_old(source: &str, error_mode: ErrorMode) {
+fn run_old(source: &str, options: &Options) {
     // 词法分析
     let mut lexer = Lexer::new(source.to_string());
     let tokens = match lexer.tokenize() {
         Ok(t) => t,
         Err(err) => {
-            let displayer = ErrorDisplayer::new(error_mode);
+            let displayer = ErrorDisplayer::new(options.error_mode);
             eprintln!("{}", displayer.format_error(&err, Some(source)));
             process::exit(1);
         }
     };
 
-    // 预处理tokens（处理科学计数法等）
-    let tokens = lexer::TokenPreprocessor::preprocess(tokens);
-
     // 语法分析
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::new(tokens).with_newline_terminators(options.newline_terminators);
     let program = match parser.parse() {
         Ok(prog) => prog,
-        Err(err) => {
-            eprintln!("Parse error: {:?}", err);
+        Err(_) => {
+            let displayer = ErrorDisplayer::new(options.error_mode);
+            eprintln!("{}", displayer.format_errors(parser.errors(), Some(source)));
             process::exit(1);
         }
     };
@@ -289,7 +614,7 @@ mod tests {
             let y = 20;
             print(x + y);
         "#;
-        run(source, ErrorMode::Simple);
+        run(source, &Options::default());
     }
 
     #[test]
@@ -302,7 +627,7 @@ mod tests {
             let result = add(5, 3);
             print(result);
         "#;
-        run(source, ErrorMode::Simple);
+        run(source, &Options::default());
     }
 
     #[test]
@@ -314,10 +639,10 @@ mod tests {
         "#;
         
         println!("\n=== Bytecode VM ===");
-        run(source, ErrorMode::Simple);
+        run(source, &Options::default());
         
         println!("\n=== Old Interpreter ===");
-        run_old(source, ErrorMode::Simple);
+        run_old(source, &Options::default());
     }
 
     #[test]
@@ -334,7 +659,7 @@ mod tests {
                 i = i + 1;
             }
         "#;
-        run(source, ErrorMode::Simple);
+        run(source, &Options::default());
     }
 
     #[test]
@@ -354,7 +679,7 @@ mod tests {
             print(multiply(6, 7));
             print(factorial(5));
         "#;
-        run(source, ErrorMode::Simple);
+        run(source, &Options::default());
     }
 
     #[test]
@@ -369,7 +694,7 @@ mod tests {
             print(s);
             print(b);
         "#;
-        run(source, ErrorMode::Simple);
+        run(source, &Options::default());
     }
 
     #[test]
@@ -382,7 +707,7 @@ mod tests {
             let result = add(10, 20);
             print(result);
         "#;
-        run(source, ErrorMode::Simple);
+        run(source, &Options::default());
     }
 
     #[test]
@@ -396,7 +721,42 @@ mod tests {
             let result = multiply(x, 10);
             print(result);
         "#;
-        run(source, ErrorMode::Simple);
+        run(source, &Options::default());
     }
 
+    #[test]
+    fn test_eval_expression() {
+        let eval = |input: &str| evaluate(input, &Options::default());
+
+        assert_eq!(eval("2+3*4"), Ok(Some("14".to_string())));
+        assert_eq!(eval("\"a\" + \"b\";"), Ok(Some("a".to_string() + "b")));
+
+        // 语句按普通程序执行，不产生结果
+        assert_eq!(eval("let x = 1"), Ok(None));
+        assert!(eval("1 + \"a\"").is_err());
+    }
+
+    #[test]
+    fn test_explain_walkthrough() {
+        let explain = |source: &str| explain_program(source, &Options::default());
+
+        let source = "let x = 10;\nvar total = 0;\nfor i in 0..3 {\n    total = total + i;\n}\n\
+            fn square(n: int) -> int { return n * n; }\nprint(square(x) + total);\n";
+        let text = explain(source).unwrap();
+        assert!(text.contains("=== Statement 3 (line 3) ===\nfor i in 0..3 {"));
+        assert!(text.contains("== square =="));
+        assert!(text.contains("Output:\n  103\n"));
+        assert!(text.ends_with("Globals: square = <function>, total = 3, x = 10\n\n"));
+
+        // 运行时错误之前的语句照常展示
+        let error = explain("let a = 1;\nlet b = [1, 2][a + 5];\n").unwrap_err();
+        assert!(error.contains("Globals: a = 1") && error.contains("Runtime error"));
+
+        // 示例程序可以逐条执行
+        for name in ["hello", "variables", "functions", "arrays", "break_continue", "methods"] {
+            let path = format!("{}/examples/{}.zero", env!("CARGO_MANIFEST_DIR"), name);
+            let source = fs::read_to_string(&path).unwrap();
+            assert!(explain(&source).is_ok(), "--explain failed on {}", path);
+        }
+    }
 }
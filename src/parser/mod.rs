@@ -1,26 +1,144 @@
-use crate::ast::{BinaryOp, Expr, Program, Stmt, UnaryOp, Type, Parameter, MethodDeclaration};
+use crate::ast::{BinaryOp, Expr, Program, Stmt, UnaryOp, Type, FunctionType, Parameter, MethodDeclaration, IntWidth};
+use crate::error::{CompilerError, ErrorCollector};
 use crate::lexer::token::{Token, TokenType, Position};
+use std::collections::HashMap;
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    int_width: IntWidth,
+    allow_struct_literal: bool,  // for 循环头部中 `name {` 是循环体而非结构体字面量
+    newline_terminators: bool,   // 换行可以代替语句末尾的 `;`
+    constants: HashMap<String, i64>, // 已声明的整数常量（用于类型中的数组长度）
+    shadowed_constants: Vec<String>,  // 当前作用域链中遮蔽了整数常量的局部名称（变量、参数、循环变量）
+    statement_spans: Vec<(Position, Position)>, // 顶层语句的起止位置
+    errors: ErrorCollector,      // 出错后恢复继续解析时收集的全部语法错误
 }
 
+/// 语法错误，都带有出错位置的 token（常量表达式的错误为表达式的第一个 token）
 #[derive(Debug)]
 pub enum ParseError {
     UnexpectedToken {
         expected: String,
-        found: TokenType,
+        found: Token,
     },
-    UnexpectedEOF,
-    InvalidExpression,
+    UnexpectedEOF(Token),
+    InvalidExpression(Token),
+    IntegerOutOfRange(String, Token),
+    /// 需要编译期常量的位置（常量初始值、数组长度）出现了非常量表达式
+    NotConstant(String, Token),
+}
+
+impl ParseError {
+    /// 出错位置的 token
+    pub fn token(&self) -> &Token {
+        match self {
+            ParseError::UnexpectedToken { found, .. } => found,
+            ParseError::UnexpectedEOF(token)
+            | ParseError::InvalidExpression(token)
+            | ParseError::IntegerOutOfRange(_, token)
+            | ParseError::NotConstant(_, token) => token,
+        }
+    }
+}
+
+impl From<&ParseError> for CompilerError {
+    fn from(err: &ParseError) -> Self {
+        let token = err.token();
+        let pos = &token.start_pos;
+        let length = token.end_pos.offset.saturating_sub(pos.offset).max(1);
+        match err {
+            ParseError::UnexpectedToken { expected, found } => CompilerError::unexpected_token(
+                expected.clone(), format!("{:?}", found.token_type), pos.line, pos.column, pos.offset, length,
+            ),
+            ParseError::UnexpectedEOF(_) => CompilerError::unexpected_eof(pos.line, pos.column, pos.offset),
+            ParseError::InvalidExpression(_) => CompilerError::invalid_expression(pos.line, pos.column, pos.offset),
+            ParseError::IntegerOutOfRange(value, _) => CompilerError::unexpected_token(
+                "integer within range".to_string(), value.clone(), pos.line, pos.column, pos.offset, length,
+            ),
+            ParseError::NotConstant(what, token) => CompilerError::unexpected_token(
+                format!("constant expression for {}", what), format!("{:?}", token.token_type),
+                pos.line, pos.column, pos.offset, length,
+            ),
+        }
+    }
 }
 
 type ParseResult<T> = Result<T, ParseError>;
 
+/// 中缀运算符的结合性（右结合的运算符可增加 `Right`，右操作数按同一优先级解析）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Associativity {
+    Left,
+    NonAssoc,
+}
+
+/// 中缀运算符构造的表达式
+#[derive(Debug)]
+enum InfixKind {
+    Binary(BinaryOp),
+    Range { inclusive: bool },
+}
+
+/// 中缀运算符表项：优先级越大结合越紧
+#[derive(Debug)]
+struct InfixOperator {
+    token: TokenType,
+    kind: InfixKind,
+    precedence: u8,
+    associativity: Associativity,
+}
+
+/// 中缀运算符表，新增运算符只需登记一项。
+/// 一元运算符（`!`、`-`）结合得比所有中缀运算符都紧，调用、索引和字段访问更紧
+const INFIX_OPERATORS: &[InfixOperator] = &[
+    infix(TokenType::DotDot, InfixKind::Range { inclusive: false }, 1, Associativity::NonAssoc),
+    infix(TokenType::DotDotEqual, InfixKind::Range { inclusive: true }, 1, Associativity::NonAssoc),
+    infix(TokenType::Or, InfixKind::Binary(BinaryOp::Or), 2, Associativity::Left),
+    infix(TokenType::And, InfixKind::Binary(BinaryOp::And), 3, Associativity::Left),
+    infix(TokenType::EqualEqual, InfixKind::Binary(BinaryOp::Equal), 4, Associativity::Left),
+    infix(TokenType::BangEqual, InfixKind::Binary(BinaryOp::NotEqual), 4, Associativity::Left),
+    infix(TokenType::Greater, InfixKind::Binary(BinaryOp::Greater), 5, Associativity::Left),
+    infix(TokenType::GreaterEqual, InfixKind::Binary(BinaryOp::GreaterEqual), 5, Associativity::Left),
+    infix(TokenType::Less, InfixKind::Binary(BinaryOp::Less), 5, Associativity::Left),
+    infix(TokenType::LessEqual, InfixKind::Binary(BinaryOp::LessEqual), 5, Associativity::Left),
+    infix(TokenType::Plus, InfixKind::Binary(BinaryOp::Add), 6, Associativity::Left),
+    infix(TokenType::Minus, InfixKind::Binary(BinaryOp::Subtract), 6, Associativity::Left),
+    infix(TokenType::Star, InfixKind::Binary(BinaryOp::Multiply), 7, Associativity::Left),
+    infix(TokenType::Slash, InfixKind::Binary(BinaryOp::Divide), 7, Associativity::Left),
+    infix(TokenType::Percent, InfixKind::Binary(BinaryOp::Modulo), 7, Associativity::Left),
+];
+
+const fn infix(token: TokenType, kind: InfixKind, precedence: u8, associativity: Associativity) -> InfixOperator {
+    InfixOperator { token, kind, precedence, associativity }
+}
+
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            int_width: IntWidth::default(),
+            allow_struct_literal: true,
+            newline_terminators: false,
+            constants: HashMap::new(),
+            shadowed_constants: Vec::new(),
+            statement_spans: Vec::new(),
+            errors: ErrorCollector::new(),
+        }
+    }
+
+    /// 设置整数字面量的位宽（用于范围检查）
+    pub fn with_int_width(mut self, int_width: IntWidth) -> Self {
+        self.int_width = int_width;
+        self
+    }
+
+    /// 允许用换行结束语句（自动插入分号），显式的 `;` 仍然有效
+    /// 只有在原本需要 `;` 的位置才会检查换行，表达式可以跨行继续
+    pub fn with_newline_terminators(mut self, enabled: bool) -> Self {
+        self.newline_terminators = enabled;
+        self
     }
 
     fn current_token(&self) -> Token {
@@ -62,31 +180,143 @@ impl Parser {
         false
     }
 
+    /// 当前位置是否可以结束语句：遇到 `;`，或在换行模式下位于新的一行、`}` 或文件末尾
+    fn at_statement_end(&self) -> bool {
+        if self.check(TokenType::Semicolon) {
+            return true;
+        }
+        if !self.newline_terminators {
+            return false;
+        }
+        matches!(self.current_token().token_type, TokenType::RightBrace | TokenType::EOF)
+            || self.on_new_line()
+    }
+
+    /// 换行模式下，当前 token 是否与上一个 token 不在同一行
+    fn on_new_line(&self) -> bool {
+        self.newline_terminators
+            && self.current > 0
+            && self.tokens[self.current - 1].end_pos.line < self.current_token().start_pos.line
+    }
+
+    /// 结束一条语句：消费 `;`，换行模式下也接受换行、`}` 或文件末尾
+    fn end_statement(&mut self, message: &str) -> ParseResult<()> {
+        if !self.at_statement_end() {
+            return Err(ParseError::UnexpectedToken {
+                expected: message.to_string(),
+                found: self.current_token(),
+            });
+        }
+        self.match_token(&[TokenType::Semicolon]);
+        Ok(())
+    }
+
     fn consume(&mut self, token_type: TokenType, message: &str) -> ParseResult<Token> {
         if self.check(token_type) {
             Ok(self.advance().clone())
         } else {
             Err(ParseError::UnexpectedToken {
                 expected: message.to_string(),
-                found: self.current_token().token_type.clone(),
+                found: self.current_token(),
             })
         }
     }
 
+    /// 解析整个程序。某条顶层语句出错时记录错误并跳过该语句继续解析，
+    /// 全部错误可通过 `errors` 取得，返回值中是第一个错误
     pub fn parse(&mut self) -> ParseResult<Program> {
         let mut program = Program::new();
+        let mut first_error = None;
 
         while !self.check(TokenType::EOF) {
-            let stmt = self.declaration()?;
-            program.add_statement(stmt);
+            let start_index = self.current;
+            let start = self.current_token().start_pos;
+            let shadowed = self.shadowed_constants.len();
+            // 常量只能在顶层声明，对之后的所有代码可见
+            let result = if self.match_token(&[TokenType::Const]) {
+                self.const_declaration()
+            } else {
+                self.declaration()
+            };
+            match result {
+                Ok(stmt) => {
+                    let end = self.tokens[self.current - 1].end_pos.clone();
+                    program.add_statement_at(stmt, start.line);
+                    self.statement_spans.push((start, end));
+                }
+                Err(err) => {
+                    self.errors.add(CompilerError::from(&err));
+                    self.synchronize(start_index);
+                    self.shadowed_constants.truncate(shadowed);
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+
+        match first_error {
+            Some(err) => Err(err),
+            None => Ok(program),
         }
+    }
 
-        Ok(program)
+    /// 上次 `parse` 收集到的全部语法错误
+    pub fn errors(&self) -> &[CompilerError] {
+        self.errors.errors()
+    }
+
+    /// 出错后跳过从 `start_index` 开始的顶层语句的剩余部分：
+    /// 跳到深度为 0 的 `;`、闭合出错处所在代码块的 `}`，
+    /// 或者下一个声明关键字（换行模式下还有新的一行）为止
+    fn synchronize(&mut self, start_index: usize) {
+        let error_index = self.current;
+        let mut depth = self.tokens[start_index..error_index.min(self.tokens.len())]
+            .iter()
+            .fold(0usize, |depth, token| match token.token_type {
+                TokenType::LeftBrace => depth + 1,
+                TokenType::RightBrace => depth.saturating_sub(1),
+                _ => depth,
+            });
+
+        while !self.check(TokenType::EOF) {
+            if depth == 0 && self.current > start_index {
+                let at_declaration = matches!(
+                    self.current_token().token_type,
+                    TokenType::Let | TokenType::Var | TokenType::Const | TokenType::Fn
+                        | TokenType::Struct | TokenType::Type | TokenType::Impl
+                );
+                if at_declaration || (self.current > error_index && self.on_new_line()) {
+                    return;
+                }
+            }
+            match self.advance().token_type {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace if depth <= 1 => {
+                    depth = 0;
+                    // `} else {` 和 `S { .. };` 还属于同一条语句
+                    if !matches!(self.current_token().token_type, TokenType::Else | TokenType::Semicolon) {
+                        return;
+                    }
+                }
+                TokenType::RightBrace => depth -= 1,
+                TokenType::Semicolon if depth == 0 => return,
+                _ => {}
+            }
+        }
+    }
+
+    /// 上次 `parse` 得到的各顶层语句在源码中的起止位置，与程序的语句一一对应
+    pub fn statement_spans(&self) -> &[(Position, Position)] {
+        &self.statement_spans
     }
 
     fn declaration(&mut self) -> ParseResult<Stmt> {
         if self.match_token(&[TokenType::Let, TokenType::Var]) {
             self.var_declaration()
+        } else if self.check(TokenType::Const) {
+            Err(ParseError::UnexpectedToken {
+                expected: "const declarations only at the top level".to_string(),
+                found: self.current_token(),
+            })
         } else if self.match_token(&[TokenType::Fn]) {
             self.fn_declaration()
         } else if self.match_token(&[TokenType::Struct]) {
@@ -121,16 +351,97 @@ impl Parser {
             None
         };
 
-        self.consume(TokenType::Semicolon, "Expected ';' after variable declaration")?;
+        self.end_statement("Expected ';' after variable declaration")?;
+        // 声明之后（而不是类型标注和初始值中）同名常量才被遮蔽
+        self.declare_local(&name);
 
         Ok(Stmt::VarDeclaration {
             name,
             mutable: is_mutable,
             type_annotation,
             initializer,
+            is_const: false,
+        })
+    }
+
+    /// 解析 `const NAME (: type)? = expr;`，初始值必须是编译期常量，声明为不可变变量
+    /// 整数常量在解析时求值（初始值折叠为字面量），可用于类型中的数组长度，如 `[int; N * 2]`
+    fn const_declaration(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expected constant name")?.value;
+
+        let type_annotation = if self.match_token(&[TokenType::Colon]) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        self.consume(TokenType::Equal, "Expected '=' after constant name")?;
+        let start = self.current_token();
+        let initializer = self.expression()?;
+        let initializer = match self.constant_integer(&initializer, &start)? {
+            Some(value) => {
+                self.constants.insert(name.clone(), value);
+                Expr::Integer(value)
+            }
+            None if Self::is_literal(&initializer) => initializer,
+            None => return Err(ParseError::NotConstant(format!("initializer of const '{}'", name), start)),
+        };
+
+        self.end_statement("Expected ';' after constant declaration")?;
+
+        Ok(Stmt::VarDeclaration {
+            name,
+            mutable: false,
+            type_annotation,
+            initializer: Some(initializer),
+            is_const: true,
         })
     }
 
+    /// 在解析时对整数常量表达式求值：字面量、已声明的整数常量及其算术运算
+    /// 不是常量时返回 None，常量运算溢出或除零时报告错误（位置为表达式的第一个 token `start`）
+    fn constant_integer(&self, expr: &Expr, start: &Token) -> ParseResult<Option<i64>> {
+        let value = match expr {
+            Expr::Integer(n) => Some(*n),
+            Expr::Identifier(name) if self.shadowed_constants.contains(name) => None,
+            Expr::Identifier(name) => self.constants.get(name).copied(),
+            Expr::Unary { operator, operand } => match (operator, self.constant_integer(operand, start)?) {
+                (UnaryOp::Negate, Some(n)) => Some(n.checked_neg().ok_or_else(|| ParseError::IntegerOutOfRange(format!("-{}", n), start.clone()))?),
+                (UnaryOp::Plus, n) => n,
+                _ => None,
+            },
+            Expr::Binary { left, operator, right } => {
+                let (Some(l), Some(r)) = (self.constant_integer(left, start)?, self.constant_integer(right, start)?) else {
+                    return Ok(None);
+                };
+                let result = match operator {
+                    BinaryOp::Add => l.checked_add(r),
+                    BinaryOp::Subtract => l.checked_sub(r),
+                    BinaryOp::Multiply => l.checked_mul(r),
+                    BinaryOp::Divide => l.checked_div(r),
+                    BinaryOp::Modulo => l.checked_rem(r),
+                    _ => return Ok(None),
+                };
+                let symbol = operator.symbol();
+                Some(result.ok_or_else(|| ParseError::IntegerOutOfRange(format!("{} {} {}", l, symbol, r), start.clone()))?)
+            }
+            _ => None,
+        };
+        match value {
+            Some(n) if !self.int_width.contains(n) => Err(ParseError::IntegerOutOfRange(n.to_string(), start.clone())),
+            _ => Ok(value),
+        }
+    }
+
+    /// 非整数的字面量（可直接作为常量初始值）
+    fn is_literal(expr: &Expr) -> bool {
+        match expr {
+            Expr::Float(_) | Expr::String(_) | Expr::Boolean(_) | Expr::Char(_) => true,
+            Expr::Unary { operator: UnaryOp::Negate | UnaryOp::Plus, operand } => matches!(operand.as_ref(), Expr::Float(_)),
+            _ => false,
+        }
+    }
+
     fn fn_declaration(&mut self) -> ParseResult<Stmt> {
         let name_token = self.consume(TokenType::Identifier, "Expected function name")?;
         let name = name_token.value.clone();
@@ -171,10 +482,12 @@ impl Parser {
         
         self.consume(TokenType::LeftBrace, "Expected '{' before function body")?;
 
-        let mut body = Vec::new();
-        while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
-            body.push(self.declaration()?);
+        let scope = self.shadowed_constants.len();
+        for parameter in &parameters {
+            self.declare_local(&parameter.name);
         }
+        let (body, body_lines) = self.function_body()?;
+        self.shadowed_constants.truncate(scope);
 
         self.consume(TokenType::RightBrace, "Expected '}' after function body")?;
 
@@ -183,6 +496,7 @@ impl Parser {
             parameters,
             return_type,
             body,
+            body_lines,
         })
     }
     
@@ -195,17 +509,7 @@ impl Parser {
         let mut fields = Vec::new();
         
         while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
-            let field_name_token = self.consume(TokenType::Identifier, "Expected field name")?;
-            let field_name = field_name_token.value.clone();
-            
-            self.consume(TokenType::Colon, "Expected ':' after field name")?;
-            
-            let field_type = self.parse_type()?;
-            
-            fields.push(crate::ast::StructField {
-                name: field_name,
-                field_type,
-            });
+            fields.push(self.struct_field()?);
             
             // 允许可选的逗号
             if self.match_token(&[TokenType::Comma]) {
@@ -216,11 +520,33 @@ impl Parser {
         }
         
         self.consume(TokenType::RightBrace, "Expected '}' after struct fields")?;
-        self.consume(TokenType::Semicolon, "Expected ';' after struct declaration")?;
+        self.end_statement("Expected ';' after struct declaration")?;
         
         Ok(Stmt::StructDeclaration { name, fields })
     }
     
+    /// 解析单个结构体字段：`[let|var] name: Type`
+    /// `let` 字段在构造后只读，`var` 或无标记的字段可以赋值
+    fn struct_field(&mut self) -> ParseResult<crate::ast::StructField> {
+        let is_mutable = !self.match_token(&[TokenType::Let]);
+        if is_mutable {
+            self.match_token(&[TokenType::Var]);
+        }
+
+        let field_name_token = self.consume(TokenType::Identifier, "Expected field name")?;
+        let name = field_name_token.value.clone();
+
+        self.consume(TokenType::Colon, "Expected ':' after field name")?;
+
+        let field_type = self.parse_type()?;
+
+        Ok(crate::ast::StructField {
+            name,
+            field_type,
+            is_mutable,
+        })
+    }
+
     fn type_alias_declaration(&mut self) -> ParseResult<Stmt> {
         let name_token = self.consume(TokenType::Identifier, "Expected type alias name")?;
         let name = name_token.value.clone();
@@ -234,17 +560,7 @@ impl Parser {
             let mut fields = Vec::new();
             
             while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
-                let field_name_token = self.consume(TokenType::Identifier, "Expected field name")?;
-                let field_name = field_name_token.value.clone();
-                
-                self.consume(TokenType::Colon, "Expected ':' after field name")?;
-                
-                let field_type = self.parse_type()?;
-                
-                fields.push(crate::ast::StructField {
-                    name: field_name,
-                    field_type,
-                });
+                fields.push(self.struct_field()?);
                 
                 // 允许可选的逗号
                 if self.match_token(&[TokenType::Comma]) {
@@ -265,7 +581,7 @@ impl Parser {
             self.parse_type()?
         };
         
-        self.consume(TokenType::Semicolon, "Expected ';' after type alias")?;
+        self.end_statement("Expected ';' after type alias")?;
 
         Ok(Stmt::TypeAlias { name, target_type })
     }
@@ -290,38 +606,40 @@ impl Parser {
 
             let mut parameters = Vec::new();
 
-            // 第一个参数应该是 self
-            if !self.check(TokenType::RightParen) {
-                let first_param = self.consume(TokenType::Identifier, "Expected parameter name")?;
+            // 第一个参数是 self 时为实例方法，否则为静态方法（关联函数）
+            // self 参数不需要类型注解，会自动推断为当前类型
+            let is_static = !(self.check(TokenType::Identifier) && self.current_token().value == "self");
+            if !is_static {
+                self.advance();
+                if !self.check(TokenType::RightParen) {
+                    self.consume(TokenType::Comma, "Expected ',' or ')' after self")?;
+                }
+            }
 
-                if first_param.value == "self" {
-                    // self 参数不需要类型注解，会自动推断为当前类型
-                    // 继续解析后面的参数
-                    if self.match_token(&[TokenType::Comma]) {
-                        loop {
-                            let param_name = self.consume(TokenType::Identifier, "Expected parameter name")?;
-
-                            let type_annotation = if self.match_token(&[TokenType::Colon]) {
-                                Some(self.parse_type()?)
-                            } else {
-                                None
-                            };
-
-                            parameters.push(Parameter {
-                                name: param_name.value.clone(),
-                                type_annotation,
-                            });
-
-                            if !self.match_token(&[TokenType::Comma]) {
-                                break;
-                            }
-                        }
+            if !self.check(TokenType::RightParen) {
+                loop {
+                    let param_name = self.consume(TokenType::Identifier, "Expected parameter name")?;
+                    if param_name.value == "self" {
+                        return Err(ParseError::UnexpectedToken {
+                            expected: "'self' only as the first method parameter".to_string(),
+                            found: param_name,
+                        });
                     }
-                } else {
-                    return Err(ParseError::UnexpectedToken {
-                        expected: "self".to_string(),
-                        found: TokenType::Identifier,
+
+                    let type_annotation = if self.match_token(&[TokenType::Colon]) {
+                        Some(self.parse_type()?)
+                    } else {
+                        None
+                    };
+
+                    parameters.push(Parameter {
+                        name: param_name.value.clone(),
+                        type_annotation,
                     });
+
+                    if !self.match_token(&[TokenType::Comma]) {
+                        break;
+                    }
                 }
             }
 
@@ -336,10 +654,12 @@ impl Parser {
 
             self.consume(TokenType::LeftBrace, "Expected '{' before method body")?;
 
-            let mut body = Vec::new();
-            while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
-                body.push(self.declaration()?);
+            let scope = self.shadowed_constants.len();
+            for parameter in &parameters {
+                self.declare_local(&parameter.name);
             }
+            let (body, body_lines) = self.function_body()?;
+            self.shadowed_constants.truncate(scope);
 
             self.consume(TokenType::RightBrace, "Expected '}' after method body")?;
 
@@ -348,6 +668,8 @@ impl Parser {
                 parameters,
                 return_type,
                 body,
+                body_lines,
+                is_static,
             });
         }
 
@@ -357,14 +679,77 @@ impl Parser {
     }
 
     fn parse_type(&mut self) -> ParseResult<Type> {
-        // 检查数组类型 [element_type]
+        // 检查数组类型 [element_type] 或定长数组类型 [element_type; N]
         if self.check(TokenType::LeftBracket) {
             self.advance(); // 消费 '['
             let element_type = self.parse_type()?;
+
+            // 长度可以是常量表达式（字面量、const 常量及其算术运算）
+            let length = if self.match_token(&[TokenType::Semicolon]) {
+                let start = self.current_token();
+                let expr = self.binary(0)?;
+                let length = self.constant_integer(&expr, &start)?
+                    .ok_or_else(|| ParseError::NotConstant("array length".to_string(), start.clone()))?;
+                Some(usize::try_from(length).map_err(|_| ParseError::IntegerOutOfRange(length.to_string(), start))?)
+            } else {
+                None
+            };
+
             self.consume(TokenType::RightBracket, "Expected ']' after array element type")?;
-            return Ok(Type::Array(Box::new(element_type)));
+            return Ok(match length {
+                Some(length) => Type::FixedArray(Box::new(element_type), length),
+                None => Type::Array(Box::new(element_type)),
+            });
         }
         
+        // 元组类型 (T1, T2)，单元素元组写作 (T,)，不带逗号的 (T) 就是 T
+        if self.match_token(&[TokenType::LeftParen]) {
+            let first = self.parse_type()?;
+            if !self.match_token(&[TokenType::Comma]) {
+                self.consume(TokenType::RightParen, "Expected ')' after type")?;
+                return Ok(first);
+            }
+
+            let mut elements = vec![first];
+            while !self.check(TokenType::RightParen) {
+                elements.push(self.parse_type()?);
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expected ')' after tuple element types")?;
+            return Ok(Type::Tuple(elements));
+        }
+
+        // 检查函数类型 fn(param_types) -> return_type
+        if self.match_token(&[TokenType::Fn]) {
+            self.consume(TokenType::LeftParen, "Expected '(' after 'fn' in function type")?;
+
+            let mut params = Vec::new();
+            if !self.check(TokenType::RightParen) {
+                loop {
+                    params.push(self.parse_type()?);
+                    if !self.match_token(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+
+            self.consume(TokenType::RightParen, "Expected ')' after function type parameters")?;
+
+            // 省略返回类型时视为void
+            let return_type = if self.match_token(&[TokenType::Arrow]) {
+                self.parse_type()?
+            } else {
+                Type::Void
+            };
+
+            return Ok(Type::Function(FunctionType {
+                params,
+                return_type: Box::new(return_type),
+            }));
+        }
+
         // 检查匿名结构体类型
         if self.match_token(&[TokenType::Struct]) {
             self.consume(TokenType::LeftBrace, "Expected '{' after 'struct'")?;
@@ -372,17 +757,7 @@ impl Parser {
             let mut fields = Vec::new();
             
             while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
-                let field_name_token = self.consume(TokenType::Identifier, "Expected field name")?;
-                let field_name = field_name_token.value.clone();
-                
-                self.consume(TokenType::Colon, "Expected ':' after field name")?;
-                
-                let field_type = self.parse_type()?;
-                
-                fields.push(crate::ast::StructField {
-                    name: field_name,
-                    field_type,
-                });
+                fields.push(self.struct_field()?);
                 
                 if self.match_token(&[TokenType::Comma]) {
                     // 继续
@@ -429,6 +804,11 @@ impl Parser {
                 self.advance();
                 Ok(Type::Char)
             }
+            // `char` 不是关键字（TokenType::Char 是字符字面量），按名称识别
+            TokenType::Identifier if token.value == "char" => {
+                self.advance();
+                Ok(Type::Char)
+            }
             TokenType::Identifier => {
                 // 用户定义的类型（结构体名或类型别名）
                 let type_name = token.value.clone();
@@ -437,7 +817,7 @@ impl Parser {
             }
             _ => Err(ParseError::UnexpectedToken {
                 expected: "type name".to_string(),
-                found: token.token_type.clone(),
+                found: token,
             }),
         }
     }
@@ -455,6 +835,8 @@ impl Parser {
             self.while_statement()
         } else if self.match_token(&[TokenType::For]) {
             self.for_statement()
+        } else if self.match_token(&[TokenType::Match]) {
+            self.match_statement()
         } else if self.match_token(&[TokenType::Print]) {
             self.print_statement()
         } else if self.match_token(&[TokenType::LeftBrace]) {
@@ -465,23 +847,23 @@ impl Parser {
     }
 
     fn break_statement(&mut self) -> ParseResult<Stmt> {
-        self.consume(TokenType::Semicolon, "Expected ';' after break")?;
+        self.end_statement("Expected ';' after break")?;
         Ok(Stmt::Break)
     }
 
     fn continue_statement(&mut self) -> ParseResult<Stmt> {
-        self.consume(TokenType::Semicolon, "Expected ';' after continue")?;
+        self.end_statement("Expected ';' after continue")?;
         Ok(Stmt::Continue)
     }
 
     fn return_statement(&mut self) -> ParseResult<Stmt> {
-        let value = if !self.check(TokenType::Semicolon) {
+        let value = if !self.at_statement_end() {
             Some(self.expression()?)
         } else {
             None
         };
 
-        self.consume(TokenType::Semicolon, "Expected ';' after return value")?;
+        self.end_statement("Expected ';' after return value")?;
 
         Ok(Stmt::Return { value })
     }
@@ -490,32 +872,30 @@ impl Parser {
         let condition = self.expression()?;
 
         self.consume(TokenType::LeftBrace, "Expected '{' after if condition")?;
-
-        let mut then_branch = Vec::new();
-        while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
-            then_branch.push(self.declaration()?);
-        }
-
+        let (then_branch, then_lines) = self.block_body()?;
         self.consume(TokenType::RightBrace, "Expected '}' after then branch")?;
 
-        let else_branch = if self.match_token(&[TokenType::Else]) {
-            self.consume(TokenType::LeftBrace, "Expected '{' after else")?;
-
-            let mut else_stmts = Vec::new();
-            while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
-                else_stmts.push(self.declaration()?);
+        let (else_branch, else_lines) = if self.match_token(&[TokenType::Else]) {
+            let line = self.current_token().start_pos.line;
+            if self.match_token(&[TokenType::If]) {
+                // `else if` 解析为只含一个嵌套 if 语句的 else 分支
+                (Some(vec![self.if_statement()?]), vec![line])
+            } else {
+                self.consume(TokenType::LeftBrace, "Expected '{' after else")?;
+                let (else_stmts, else_lines) = self.block_body()?;
+                self.consume(TokenType::RightBrace, "Expected '}' after else branch")?;
+                (Some(else_stmts), else_lines)
             }
-
-            self.consume(TokenType::RightBrace, "Expected '}' after else branch")?;
-            Some(else_stmts)
         } else {
-            None
+            (None, Vec::new())
         };
 
         Ok(Stmt::If {
             condition,
             then_branch,
             else_branch,
+            then_lines,
+            else_lines,
         })
     }
 
@@ -523,15 +903,10 @@ impl Parser {
         let condition = self.expression()?;
 
         self.consume(TokenType::LeftBrace, "Expected '{' after while condition")?;
-
-        let mut body = Vec::new();
-        while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
-            body.push(self.declaration()?);
-        }
-
+        let (body, body_lines) = self.block_body()?;
         self.consume(TokenType::RightBrace, "Expected '}' after while body")?;
 
-        Ok(Stmt::While { condition, body })
+        Ok(Stmt::While { condition, body, body_lines })
     }
 
     fn for_statement(&mut self) -> ParseResult<Stmt> {
@@ -540,62 +915,242 @@ impl Parser {
 
         self.consume(TokenType::In, "Expected 'in' after loop variable")?;
 
-        let start = self.expression()?;
+        let iterable = self.with_struct_literals(false, |p| p.expression())?;
 
-        self.consume(TokenType::DotDot, "Expected '..' in range")?;
+        self.consume(TokenType::LeftBrace, "Expected '{' after for range")?;
+        let scope = self.shadowed_constants.len();
+        self.declare_local(&variable);
+        let (body, body_lines) = self.block_body()?;
+        self.shadowed_constants.truncate(scope);
+        self.consume(TokenType::RightBrace, "Expected '}' after for body")?;
 
-        let end = self.expression()?;
+        // 不带步长的字面量范围直接按计数循环编译，其余按值遍历
+        match iterable {
+            Expr::Range { start, end, step: None, inclusive } => Ok(Stmt::For {
+                variable,
+                start: *start,
+                end: *end,
+                inclusive,
+                body,
+                body_lines,
+            }),
+            iterable => Ok(Stmt::ForIn {
+                variable,
+                iterable,
+                body,
+                body_lines,
+            }),
+        }
+    }
 
-        self.consume(TokenType::LeftBrace, "Expected '{' after for range")?;
+    /// `match expr { pattern => { ... }, _ => { ... } }`，分支间的逗号可省略
+    /// `_` 为默认分支，必须放在最后
+    fn match_statement(&mut self) -> ParseResult<Stmt> {
+        let scrutinee = self.with_struct_literals(false, |p| p.expression())?;
 
-        let mut body = Vec::new();
+        self.consume(TokenType::LeftBrace, "Expected '{' after match value")?;
+
+        let mut arms = Vec::new();
+        let mut arm_lines = Vec::new();
+        let mut default = None;
+        let mut default_lines = Vec::new();
         while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
-            body.push(self.declaration()?);
+            if default.is_some() {
+                return Err(ParseError::UnexpectedToken {
+                    expected: "'}' after default match arm '_'".to_string(),
+                    found: self.current_token(),
+                });
+            }
+
+            let is_default = self.check(TokenType::Identifier) && self.current_token().value == "_";
+            let pattern = if is_default {
+                self.advance();
+                None
+            } else {
+                Some(self.match_pattern()?)
+            };
+
+            self.consume(TokenType::FatArrow, "Expected '=>' after match pattern")?;
+            self.consume(TokenType::LeftBrace, "Expected '{' before match arm body")?;
+            let (body, lines) = self.block_body()?;
+            self.consume(TokenType::RightBrace, "Expected '}' after match arm body")?;
+            self.match_token(&[TokenType::Comma]);
+
+            match pattern {
+                Some(pattern) => {
+                    arms.push((pattern, body));
+                    arm_lines.push(lines);
+                }
+                None => {
+                    default = Some(body);
+                    default_lines = lines;
+                }
+            }
         }
 
-        self.consume(TokenType::RightBrace, "Expected '}' after for body")?;
+        self.consume(TokenType::RightBrace, "Expected '}' after match arms")?;
 
-        Ok(Stmt::For {
-            variable,
-            start,
-            end,
-            body,
-        })
+        Ok(Stmt::Match { scrutinee, arms, default, arm_lines, default_lines })
     }
 
+    /// match 模式：整数常量表达式（折叠为字面量）、字符串或布尔字面量
+    fn match_pattern(&mut self) -> ParseResult<Expr> {
+        let start = self.current_token();
+        let pattern = self.binary(0)?;
+        if matches!(pattern, Expr::String(_) | Expr::Boolean(_)) {
+            return Ok(pattern);
+        }
+        match self.constant_integer(&pattern, &start)? {
+            Some(n) => Ok(Expr::Integer(n)),
+            None => Err(ParseError::NotConstant("match pattern".to_string(), start)),
+        }
+    }
+
+    /// `print(x);` 解析为打印语句；实参个数不是 1 时解析为对内置函数 `print` 的普通调用，
+    /// 由类型检查像其他函数一样报告参数数量错误
     fn print_statement(&mut self) -> ParseResult<Stmt> {
         self.consume(TokenType::LeftParen, "Expected '(' after 'print'")?;
-        let value = self.expression()?;
-        self.consume(TokenType::RightParen, "Expected ')' after print value")?;
-        self.consume(TokenType::Semicolon, "Expected ';' after print statement")?;
+        let mut arguments = self.arguments()?;
+        self.end_statement("Expected ';' after print statement")?;
+
+        match arguments.pop() {
+            Some(value) if arguments.is_empty() => Ok(Stmt::Print { value }),
+            last => {
+                arguments.extend(last);
+                Ok(Stmt::Expression(Expr::call(Expr::identifier("print".to_string()), arguments)))
+            }
+        }
+    }
 
-        Ok(Stmt::Print { value })
+    /// 解析函数体语句，不以分号结尾的末尾表达式作为返回值（如 `{ a + b }`）
+    /// 同时返回各语句起始 token 所在的行号
+    fn function_body(&mut self) -> ParseResult<(Vec<Stmt>, Vec<usize>)> {
+        let mut body = Vec::new();
+        let mut lines = Vec::new();
+
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
+            lines.push(self.current_token().start_pos.line);
+            if self.at_tail_expression() {
+                let expr = self.expression()?;
+                body.push(Stmt::Return { value: Some(expr) });
+                break;
+            }
+
+            body.push(self.declaration()?);
+        }
+
+        Ok((body, lines))
     }
 
-    fn block_statement(&mut self) -> ParseResult<Stmt> {
+    /// 向前查看到函数体的 `}` 为止，判断剩余部分是否是末尾表达式：
+    /// 其中没有 `;`（换行模式下同层也没有换行）和只能出现在语句中的关键字，
+    /// 以 `if` 开头时必须带 `else` 且分支不为空（if 表达式的形式）
+    fn at_tail_expression(&self) -> bool {
+        let starts_with_if = self.check(TokenType::If);
+        let mut has_else = false;
+        let mut depth = 0usize;
+        for (index, token) in self.tokens.iter().enumerate().skip(self.current) {
+            if depth == 0
+                && index > self.current
+                && self.newline_terminators
+                && self.tokens[index - 1].end_pos.line < token.start_pos.line
+                && token.token_type != TokenType::RightBrace
+            {
+                return false;
+            }
+            match token.token_type {
+                // 空代码块 `{ }` 不能作为 if 表达式的分支（`S {}` 是空结构体字面量）
+                TokenType::LeftBrace
+                    if self.tokens.get(index + 1).is_some_and(|next| next.token_type == TokenType::RightBrace)
+                        && index.checked_sub(1).is_none_or(|prev| self.tokens[prev].token_type != TokenType::Identifier) =>
+                {
+                    return false;
+                }
+                TokenType::LeftBrace | TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+                TokenType::RightBrace if depth == 0 => return !starts_with_if || has_else,
+                TokenType::RightBrace | TokenType::RightParen | TokenType::RightBracket => {
+                    depth = depth.saturating_sub(1);
+                }
+                TokenType::Else if depth == 0 => has_else = true,
+                TokenType::Semicolon
+                | TokenType::EOF
+                | TokenType::Let
+                | TokenType::Var
+                | TokenType::Const
+                | TokenType::Fn
+                | TokenType::Struct
+                | TokenType::Type
+                | TokenType::Impl
+                | TokenType::Return
+                | TokenType::Break
+                | TokenType::Continue
+                | TokenType::While
+                | TokenType::For
+                | TokenType::Match
+                | TokenType::Print => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// 解析 `}` 之前的语句（不消耗 `}`），同时返回各语句起始 token 所在的行号
+    fn block_body(&mut self) -> ParseResult<(Vec<Stmt>, Vec<usize>)> {
         let mut statements = Vec::new();
+        let mut lines = Vec::new();
 
+        let scope = self.shadowed_constants.len();
         while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
+            lines.push(self.current_token().start_pos.line);
             statements.push(self.declaration()?);
         }
+        self.shadowed_constants.truncate(scope);
 
+        Ok((statements, lines))
+    }
+
+    /// 记录遮蔽了整数常量的局部名称：所在作用域结束前，类型中的该名称不再按常量求值
+    fn declare_local(&mut self, name: &str) {
+        if self.constants.contains_key(name) {
+            self.shadowed_constants.push(name.to_string());
+        }
+    }
+
+    fn block_statement(&mut self) -> ParseResult<Stmt> {
+        let (statements, lines) = self.block_body()?;
         self.consume(TokenType::RightBrace, "Expected '}' after block")?;
 
-        Ok(Stmt::Block { statements })
+        Ok(Stmt::Block { statements, lines })
     }
 
     fn expression_statement(&mut self) -> ParseResult<Stmt> {
         let expr = self.expression()?;
-        self.consume(TokenType::Semicolon, "Expected ';' after expression")?;
+        // `name { ... }` 中 `{` 之后不是字段时，标识符单独成为语句，`{` 开始下一条语句（代码块）
+        if matches!(expr, Expr::Identifier(_)) && self.check(TokenType::LeftBrace) {
+            return Ok(Stmt::Expression(expr));
+        }
+        self.end_statement("Expected ';' after expression")?;
         Ok(Stmt::Expression(expr))
     }
 
+    /// 在指定的结构体字面量许可下解析，结束后恢复原状态
+    fn with_struct_literals<T>(
+        &mut self,
+        allowed: bool,
+        parse: impl FnOnce(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<T> {
+        let saved = std::mem::replace(&mut self.allow_struct_literal, allowed);
+        let result = parse(self);
+        self.allow_struct_literal = saved;
+        result
+    }
+
     fn expression(&mut self) -> ParseResult<Expr> {
         self.assignment()
     }
 
     fn assignment(&mut self) -> ParseResult<Expr> {
-        let expr = self.or()?;
+        let expr = self.conditional()?;
 
         if self.match_token(&[TokenType::Equal]) {
             match expr {
@@ -607,18 +1162,18 @@ impl Parser {
                     let value = self.assignment()?;
                     return Ok(Expr::index_assign(*object, *index, value));
                 }
-                Expr::FieldAccess { object, field } => {
+                Expr::FieldAccess { object, field, .. } => {
                     let value = self.assignment()?;
                     return Ok(Expr::field_assign(*object, field, value));
                 }
-                _ => {}
+                _ => return Err(Self::invalid_assignment_target(self.tokens[self.current - 1].clone())),
             }
         } else if self.match_token(&[TokenType::PlusEqual, TokenType::MinusEqual,
                                       TokenType::StarEqual, TokenType::SlashEqual,
                                       TokenType::PercentEqual]) {
             // 获取运算符类型
-            let prev_token = self.tokens[self.current - 1].token_type.clone();
-            let op = match prev_token {
+            let prev_token = self.tokens[self.current - 1].clone();
+            let op = match prev_token.token_type {
                 TokenType::PlusEqual => BinaryOp::Add,
                 TokenType::MinusEqual => BinaryOp::Subtract,
                 TokenType::StarEqual => BinaryOp::Multiply,
@@ -640,130 +1195,94 @@ impl Parser {
                     let new_value = Expr::binary(expr, op, value);
                     return Ok(Expr::index_assign(*object, *index, new_value));
                 }
-                Expr::FieldAccess { object, field } => {
+                Expr::FieldAccess { object, field, .. } => {
                     let value = self.assignment()?;
                     // obj.field += y 转换为 obj.field = obj.field + y
                     let new_value = Expr::binary(expr, op, value);
                     return Ok(Expr::field_assign(*object, field, new_value));
                 }
-                _ => {}
+                _ => return Err(Self::invalid_assignment_target(prev_token)),
             }
         }
 
         Ok(expr)
     }
 
-    fn or(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.and()?;
-
-        while self.match_token(&[TokenType::Or]) {
-            let right = self.and()?;
-            expr = Expr::binary(expr, BinaryOp::Or, right);
+    /// 条件表达式 `cond ? a : b`，右结合（`a ? b : c ? d : e` 即 `a ? b : (c ? d : e)`）
+    /// 与 if 表达式含义相同，解析为 `Expr::If`
+    fn conditional(&mut self) -> ParseResult<Expr> {
+        let condition = self.binary(0)?;
+        if !self.match_token(&[TokenType::Question]) {
+            return Ok(condition);
         }
-
-        Ok(expr)
+        let then_expr = self.expression()?;
+        self.consume(TokenType::Colon, "Expected ':' in conditional expression")?;
+        let else_expr = self.conditional()?;
+        Ok(Expr::if_expr(condition, then_expr, else_expr))
     }
 
-    fn and(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.equality()?;
-
-        while self.match_token(&[TokenType::And]) {
-            let right = self.equality()?;
-            expr = Expr::binary(expr, BinaryOp::And, right);
+    /// 赋值运算符左边不是变量、下标或字段
+    fn invalid_assignment_target(operator: Token) -> ParseError {
+        ParseError::UnexpectedToken {
+            expected: "variable, index or field on the left of an assignment".to_string(),
+            found: operator,
         }
-
-        Ok(expr)
     }
 
-    fn equality(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.comparison()?;
-
-        while self.match_token(&[TokenType::EqualEqual, TokenType::BangEqual]) {
-            let op = match self.tokens.get(self.current.saturating_sub(1))
-                .map(|t| &t.token_type)
-                .unwrap() {
-                TokenType::EqualEqual => BinaryOp::Equal,
-                TokenType::BangEqual => BinaryOp::NotEqual,
-                _ => unreachable!(),
-            };
-            let right = self.comparison()?;
-            expr = Expr::binary(expr, op, right);
-        }
-
-        Ok(expr)
-    }
-
-    fn comparison(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.term()?;
-
-        while self.match_token(&[
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
-        ]) {
-            let op = match self.tokens.get(self.current.saturating_sub(1))
-                .map(|t| &t.token_type)
-                .unwrap() {
-                TokenType::Greater => BinaryOp::Greater,
-                TokenType::GreaterEqual => BinaryOp::GreaterEqual,
-                TokenType::Less => BinaryOp::Less,
-                TokenType::LessEqual => BinaryOp::LessEqual,
-                _ => unreachable!(),
-            };
-            let right = self.term()?;
-            expr = Expr::binary(expr, op, right);
-        }
-
-        Ok(expr)
-    }
-
-    fn term(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.factor()?;
-
-        while self.match_token(&[TokenType::Plus, TokenType::Minus]) {
-            let op = match self.tokens.get(self.current.saturating_sub(1))
-                .map(|t| &t.token_type)
-                .unwrap() {
-                TokenType::Plus => BinaryOp::Add,
-                TokenType::Minus => BinaryOp::Subtract,
-                _ => unreachable!(),
-            };
-            let right = self.factor()?;
-            expr = Expr::binary(expr, op, right);
-        }
-
-        Ok(expr)
+    /// 当前 token 对应的中缀运算符
+    fn infix_operator(&self) -> Option<&'static InfixOperator> {
+        let token_type = self.current_token().token_type;
+        INFIX_OPERATORS.iter().find(|op| op.token == token_type)
     }
 
-    fn factor(&mut self) -> ParseResult<Expr> {
+    /// 优先级爬升：解析优先级不低于 `min_precedence` 的中缀运算符链
+    fn binary(&mut self, min_precedence: u8) -> ParseResult<Expr> {
         let mut expr = self.unary()?;
 
-        while self.match_token(&[TokenType::Star, TokenType::Slash, TokenType::Percent]) {
-            let op = match self.tokens.get(self.current.saturating_sub(1))
-                .map(|t| &t.token_type)
-                .unwrap() {
-                TokenType::Star => BinaryOp::Multiply,
-                TokenType::Slash => BinaryOp::Divide,
-                TokenType::Percent => BinaryOp::Modulo,
-                _ => unreachable!(),
+        while let Some(op) = self.infix_operator().filter(|op| op.precedence >= min_precedence) {
+            self.advance();
+            // 右操作数只接受更高优先级的运算符，因此同级运算符向左结合
+            let right = self.binary(op.precedence + 1)?;
+            expr = match &op.kind {
+                InfixKind::Binary(binary_op) => Expr::binary(expr, binary_op.clone(), right),
+                InfixKind::Range { inclusive } => {
+                    // `step` 不是关键字，只在范围终点之后作为步长的引导词
+                    let step = if self.check(TokenType::Identifier) && self.current_token().value == "step" {
+                        self.advance();
+                        Some(self.binary(op.precedence + 1)?)
+                    } else {
+                        None
+                    };
+                    Expr::range(expr, right, step, *inclusive)
+                }
             };
-            let right = self.unary()?;
-            expr = Expr::binary(expr, op, right);
+
+            // 不可结合的运算符不能与同级运算符连写（如 `a..b..c`），留给调用方报错
+            if op.associativity == Associativity::NonAssoc
+                && self.infix_operator().is_some_and(|next| next.precedence == op.precedence)
+            {
+                break;
+            }
         }
 
         Ok(expr)
     }
 
     fn unary(&mut self) -> ParseResult<Expr> {
-        if self.match_token(&[TokenType::Bang, TokenType::Minus]) {
+        if self.match_token(&[TokenType::Bang, TokenType::Minus, TokenType::Plus]) {
             let op = match self.tokens.get(self.current.saturating_sub(1))
                 .map(|t| &t.token_type)
                 .unwrap() {
                 TokenType::Bang => UnaryOp::Not,
                 TokenType::Minus => UnaryOp::Negate,
+                TokenType::Plus => UnaryOp::Plus,
                 _ => unreachable!(),
             };
+            if op == UnaryOp::Negate {
+                if let Some(value) = self.negative_integer_literal()? {
+                    return Ok(Expr::integer(value));
+                }
+            }
             let operand = self.unary()?;
             return Ok(Expr::unary(op, operand));
         }
@@ -771,17 +1290,55 @@ impl Parser {
         self.call()
     }
 
+    /// 负号后紧跟的整数字面量本身超出范围、取负后才在范围内（如 `--int32` 下的 `-2147483648`）时，
+    /// 整体解析为负数字面量；其余情况返回 None，按普通取负处理
+    fn negative_integer_literal(&mut self) -> ParseResult<Option<i64>> {
+        let followed_by_postfix = self.tokens.get(self.current + 1).is_some_and(|token| {
+            matches!(token.token_type, TokenType::LeftParen | TokenType::LeftBracket | TokenType::Dot)
+        });
+        if !self.check(TokenType::Integer) || followed_by_postfix {
+            return Ok(None);
+        }
+
+        let width = self.int_width;
+        let fits = |value: i128| i64::try_from(value).ok().filter(|value| width.contains(*value));
+        let token = self.current_token().clone();
+        let magnitude = Self::parse_integer_literal(&token.value);
+        if magnitude.and_then(fits).is_some() {
+            return Ok(None);
+        }
+
+        self.advance();
+        magnitude
+            .and_then(|magnitude| fits(-magnitude))
+            .map(Some)
+            .ok_or_else(|| ParseError::IntegerOutOfRange(format!("-{}", token.value), token))
+    }
+
     fn call(&mut self) -> ParseResult<Expr> {
         let mut expr = self.primary()?;
 
         loop {
             if self.match_token(&[TokenType::LeftParen]) {
-                expr = self.finish_call(expr)?;
+                expr = self.with_struct_literals(true, |p| p.finish_call(expr))?;
             } else if self.match_token(&[TokenType::LeftBracket]) {
-                let index = self.expression()?;
+                let index = self.with_struct_literals(true, |p| p.expression())?;
                 self.consume(TokenType::RightBracket, "Expected ']' after index")?;
                 expr = Expr::index(expr, index);
             } else if self.match_token(&[TokenType::Dot]) {
+                // 元组元素 `t.0`；`t.0.1` 中的 `0.1` 被词法分析为一个浮点数
+                if self.check(TokenType::Integer) || self.check(TokenType::Float) {
+                    let token = self.advance().clone();
+                    for index in token.value.split('.') {
+                        let index = index.parse::<usize>().map_err(|_| ParseError::UnexpectedToken {
+                            expected: "tuple index after '.'".to_string(),
+                            found: token.clone(),
+                        })?;
+                        expr = Expr::field_access(expr, index.to_string());
+                    }
+                    continue;
+                }
+
                 // 字段访问或方法调用
                 let field_token = self.consume(TokenType::Identifier, "Expected field name after '.'")?;
                 let field = field_token.value.clone();
@@ -789,7 +1346,7 @@ impl Parser {
                 // 检查是否是方法调用 (后面跟着左括号)
                 if self.check(TokenType::LeftParen) {
                     self.advance(); // 消费 '('
-                    expr = self.finish_method_call(expr, field)?;
+                    expr = self.with_struct_literals(true, |p| p.finish_method_call(expr, field))?;
                 } else {
                     expr = Expr::field_access(expr, field);
                 }
@@ -798,28 +1355,42 @@ impl Parser {
             }
         }
 
+        // 后置 `++`/`--` 只能作用于变量，结合得比一元运算符紧（`-i++` 是 `-(i++)`）
+        // 换行模式下另起一行的 `++` 不属于上一行
+        if !self.on_new_line() && self.match_token(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let token = self.tokens[self.current - 1].clone();
+            let operator = if token.token_type == TokenType::PlusPlus { BinaryOp::Add } else { BinaryOp::Subtract };
+            return match expr {
+                Expr::Identifier(name) => Ok(Expr::postfix_update(name, operator)),
+                _ => Err(Self::invalid_assignment_target(token)),
+            };
+        }
+
         Ok(expr)
     }
 
-    fn finish_call(&mut self, callee: Expr) -> ParseResult<Expr> {
-        let mut arguments = Vec::new();
-
-        if !self.check(TokenType::RightParen) {
-            loop {
-                arguments.push(self.expression()?);
-
-                if !self.match_token(&[TokenType::Comma]) {
-                    break;
-                }
-            }
+    /// 当前的 `{` 之后是否是结构体字面量的内容：空的 `{}` 或者以 `字段名:` 开头，
+    /// 其他情况下标识符是单独的表达式，`{` 开始的是代码块
+    fn struct_literal_follows(&self) -> bool {
+        match self.peek(1).token_type {
+            TokenType::RightBrace => true,
+            TokenType::Identifier => self.peek(2).token_type == TokenType::Colon,
+            _ => false,
         }
+    }
 
-        self.consume(TokenType::RightParen, "Expected ')' after arguments")?;
-
+    fn finish_call(&mut self, callee: Expr) -> ParseResult<Expr> {
+        let arguments = self.arguments()?;
         Ok(Expr::call(callee, arguments))
     }
 
     fn finish_method_call(&mut self, object: Expr, method: String) -> ParseResult<Expr> {
+        let arguments = self.arguments()?;
+        Ok(Expr::method_call(object, method, arguments))
+    }
+
+    /// 解析 `(` 之后的实参列表，包括结尾的 `)`
+    fn arguments(&mut self) -> ParseResult<Vec<Expr>> {
         let mut arguments = Vec::new();
 
         if !self.check(TokenType::RightParen) {
@@ -833,8 +1404,42 @@ impl Parser {
         }
 
         self.consume(TokenType::RightParen, "Expected ')' after arguments")?;
+        Ok(arguments)
+    }
 
-        Ok(Expr::method_call(object, method, arguments))
+    /// 解析 if 表达式 `if c { a } else { b }`，表达式形式必须带 else 分支，
+    /// `else if` 链接另一个 if 表达式
+    fn if_expression(&mut self) -> ParseResult<Expr> {
+        let condition = self.with_struct_literals(false, |p| p.expression())?;
+        let then_expr = self.block_expression()?;
+
+        self.consume(TokenType::Else, "Expected 'else' in if expression")?;
+        let else_expr = if self.match_token(&[TokenType::If]) {
+            self.if_expression()?
+        } else {
+            self.block_expression()?
+        };
+
+        Ok(Expr::if_expr(condition, then_expr, else_expr))
+    }
+
+    /// 解析只包含一个值表达式的代码块 `{ expr }`
+    fn block_expression(&mut self) -> ParseResult<Expr> {
+        self.consume(TokenType::LeftBrace, "Expected '{' in if expression")?;
+        let expr = self.with_struct_literals(true, |p| p.expression())?;
+        self.consume(TokenType::RightBrace, "Expected '}' after if expression branch")?;
+        Ok(expr)
+    }
+
+    /// 解析整数字面量（支持 0x/0b/0o 前缀），超出 u64 时返回 None；调用方按位宽检查范围
+    fn parse_integer_literal(literal: &str) -> Option<i128> {
+        let (digits, radix) = match literal.get(..2) {
+            Some("0x") => (&literal[2..], 16),
+            Some("0b") => (&literal[2..], 2),
+            Some("0o") => (&literal[2..], 8),
+            _ => (literal, 10),
+        };
+        u64::from_str_radix(digits, radix).ok().map(i128::from)
     }
 
     fn primary(&mut self) -> ParseResult<Expr> {
@@ -847,8 +1452,11 @@ impl Parser {
         }
 
         if self.match_token(&[TokenType::Integer]) {
-            let value = self.tokens.get(self.current.saturating_sub(1))
-                .unwrap().value.parse::<i64>().unwrap();
+            let token = self.tokens[self.current - 1].clone();
+            let value = Self::parse_integer_literal(&token.value)
+                .and_then(|v| i64::try_from(v).ok())
+                .filter(|v| self.int_width.contains(*v))
+                .ok_or_else(|| ParseError::IntegerOutOfRange(token.value.clone(), token.clone()))?;
             return Ok(Expr::integer(value));
         }
 
@@ -867,21 +1475,25 @@ impl Parser {
         if self.match_token(&[TokenType::Char]) {
             let value = self.tokens.get(self.current.saturating_sub(1))
                 .unwrap().value.clone();
-            // 解析字符字面量 (移除单引号)
-            let char_value = if value.len() >= 2 {
-                value.chars().nth(0).unwrap_or('\0')
-            } else {
-                '\0'
-            };
+            // 词法分析器已去掉单引号并解码转义，值恰好是一个字符
+            let char_value = value.chars().next().unwrap_or('\0');
             return Ok(Expr::Char(char_value));
         }
 
+        // 表达式位置的 `print` 是内置函数的普通全局变量（如 `let p = print;`）
+        if self.match_token(&[TokenType::Print]) {
+            return Ok(Expr::identifier("print".to_string()));
+        }
+
         if self.match_token(&[TokenType::Identifier]) {
             let name = self.tokens.get(self.current.saturating_sub(1))
                 .unwrap().value.clone();
             
             // 检查是否是结构体字面量 StructName { field: value, ... }
-            if self.check(TokenType::LeftBrace) {
+            // 换行模式下另起一行的 `{` 是代码块而非结构体字面量
+            if self.allow_struct_literal && self.check(TokenType::LeftBrace) && !self.on_new_line()
+                && self.struct_literal_follows()
+            {
                 self.advance(); // 消费 '{'
                 
                 let mut fields = Vec::new();
@@ -911,10 +1523,27 @@ impl Parser {
             return Ok(Expr::identifier(name));
         }
 
+        if self.match_token(&[TokenType::If]) {
+            return self.if_expression();
+        }
+
+        // 括号表达式，或者元组字面量（至少有一个逗号，单元素元组写作 `(a,)`）
         if self.match_token(&[TokenType::LeftParen]) {
-            let expr = self.expression()?;
-            self.consume(TokenType::RightParen, "Expected ')' after expression")?;
-            return Ok(expr);
+            let expr = self.with_struct_literals(true, |p| p.expression())?;
+            if !self.match_token(&[TokenType::Comma]) {
+                self.consume(TokenType::RightParen, "Expected ')' after expression")?;
+                return Ok(expr);
+            }
+
+            let mut elements = vec![expr];
+            while !self.check(TokenType::RightParen) {
+                elements.push(self.with_struct_literals(true, |p| p.expression())?);
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expected ')' after tuple elements")?;
+            return Ok(Expr::tuple(elements));
         }
 
         // 数组字面量 [elem1, elem2, ...]
@@ -923,7 +1552,7 @@ impl Parser {
             
             if !self.check(TokenType::RightBracket) {
                 loop {
-                    elements.push(self.expression()?);
+                    elements.push(self.with_struct_literals(true, |p| p.expression())?);
                     
                     if !self.match_token(&[TokenType::Comma]) {
                         break;
@@ -935,7 +1564,7 @@ impl Parser {
             return Ok(Expr::array(elements));
         }
 
-        Err(ParseError::InvalidExpression)
+        Err(ParseError::InvalidExpression(self.current_token()))
     }
 }
 
@@ -944,6 +1573,10 @@ mod tests {
     use super::*;
     use crate::lexer::Lexer;
 
+    fn parse(source: &str) -> ParseResult<Program> {
+        Parser::new(Lexer::new(source.to_string()).tokenize().unwrap()).parse()
+    }
+
     #[test]
     fn test_parse_variable_declaration() {
         let mut lexer = Lexer::new("let x = 42;".to_string());
@@ -973,4 +1606,625 @@ mod tests {
 
         assert_eq!(program.statements.len(), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_integer_literal_range_depends_on_width() {
+        let source = "let x = 3000000000;";
+
+        assert!(parse(source).is_ok());
+
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let result = Parser::new(tokens).with_int_width(IntWidth::I32).parse();
+        assert!(matches!(result, Err(ParseError::IntegerOutOfRange(ref v, _)) if v == "3000000000"));
+
+        // 负号与字面量一起检查范围，各位宽的最小值都能写出
+        let parse_as = |source: &str, width: IntWidth| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            Parser::new(tokens).with_int_width(width).parse()
+        };
+        let initializer = |program: Program| match program.statements.into_iter().next() {
+            Some(Stmt::VarDeclaration { initializer: Some(expr), .. }) => expr,
+            other => panic!("expected a declaration, got {:?}", other),
+        };
+        let min32 = parse_as("let x = -2147483648;", IntWidth::I32).unwrap();
+        assert_eq!(initializer(min32), Expr::Integer(i32::MIN as i64));
+        let min64 = parse_as("let x = -9223372036854775808;", IntWidth::I64).unwrap();
+        assert_eq!(initializer(min64), Expr::Integer(i64::MIN));
+        let max32 = parse_as("let x = -2147483647;", IntWidth::I32).unwrap();
+        assert_eq!(initializer(max32), Expr::unary(UnaryOp::Negate, Expr::Integer(2147483647)));
+        assert!(matches!(
+            parse_as("let x = -2147483649;", IntWidth::I32),
+            Err(ParseError::IntegerOutOfRange(ref v, _)) if v == "-2147483649"
+        ));
+        assert!(matches!(
+            parse_as("let x = 2147483648 - 1;", IntWidth::I32),
+            Err(ParseError::IntegerOutOfRange(ref v, _)) if v == "2147483648"
+        ));
+    }
+
+    #[test]
+    fn test_method_call_and_field_access() {
+        let expression = |input: &str| {
+            match parse(input).unwrap().statements.remove(0) {
+                Stmt::Expression(expr) => expr,
+                other => panic!("expected expression statement, got {:?}", other),
+            }
+        };
+
+        match expression("p.dist(q, 2);") {
+            Expr::MethodCall { object, method, arguments } => {
+                assert!(matches!(*object, Expr::Identifier(ref name) if name == "p"));
+                assert_eq!(method, "dist");
+                assert_eq!(arguments.len(), 2);
+            }
+            other => panic!("expected method call, got {:?}", other),
+        }
+        assert!(matches!(expression("p.x;"), Expr::FieldAccess { .. }));
+
+        // 链式调用：a.b.c(1).d 是对方法调用结果的字段访问
+        match expression("a.b.c(1).d;") {
+            Expr::FieldAccess { object, field, .. } => {
+                assert_eq!(field, "d");
+                assert!(matches!(*object, Expr::MethodCall { ref method, ref object, .. }
+                    if method == "c" && matches!(**object, Expr::FieldAccess { .. })));
+            }
+            other => panic!("expected field access, got {:?}", other),
+        }
+
+        // 加括号后调用的是字段中保存的函数值
+        assert!(matches!(
+            expression("(p.handler)(1);"),
+            Expr::Call { ref callee, .. } if matches!(**callee, Expr::FieldAccess { .. })
+        ));
+    }
+
+    #[test]
+    fn test_impl_block_methods() {
+        let program = parse(
+            "impl Point { fn len(self) -> int { return 0; } fn scale(self, k: int) { } fn origin() -> Point { } }"
+        ).unwrap();
+        match &program.statements[0] {
+            Stmt::ImplBlock { type_name, methods } => {
+                assert_eq!(type_name, "Point");
+                let shapes: Vec<(&str, bool, usize)> = methods.iter()
+                    .map(|m| (m.name.as_str(), m.is_static, m.parameters.len()))
+                    .collect();
+                // self 不计入参数列表
+                assert_eq!(shapes, [("len", false, 0), ("scale", false, 1), ("origin", true, 0)]);
+                assert_eq!(methods[0].return_type, Some(Type::Int));
+                assert_eq!(methods[1].parameters[0].type_annotation, Some(Type::Int));
+            }
+            other => panic!("expected impl block, got {:?}", other),
+        }
+
+        // self 只能是第一个参数
+        assert!(parse("impl P { fn f(x, self) { } }").is_err());
+        assert!(parse("impl P { fn f(self self) { } }").is_err());
+        assert!(parse("impl P { let x = 1; }").is_err());
+    }
+
+    #[test]
+    fn test_compound_assignment_desugars() {
+        let expression = |input: &str| match parse(input).unwrap().statements.remove(0) {
+            Stmt::Expression(expr) => expr,
+            other => panic!("expected expression statement, got {:?}", other),
+        };
+
+        match expression("arr[i] += 1;") {
+            Expr::IndexAssign { value, .. } => {
+                assert_eq!(render(&value), "(arr[i] + 1)");
+            }
+            other => panic!("expected index assignment, got {:?}", other),
+        }
+        match expression("p.x *= 2;") {
+            Expr::FieldAssign { field, value, .. } => {
+                assert_eq!(field, "x");
+                assert!(matches!(*value, Expr::Binary { operator: BinaryOp::Multiply, .. }));
+            }
+            other => panic!("expected field assignment, got {:?}", other),
+        }
+
+        // 左边不能赋值时在运算符处报错
+        assert!(matches!(
+            parse("x + 1 += 2;"),
+            Err(ParseError::UnexpectedToken { found: Token { token_type: TokenType::PlusEqual, .. }, .. })
+        ));
+        assert!(matches!(parse("1 = 2;"), Err(ParseError::UnexpectedToken { found: Token { token_type: TokenType::Equal, .. }, .. })));
+    }
+
+    #[test]
+    fn test_postfix_increment_and_decrement() {
+        let cases = [
+            ("i++", "(i++)"),
+            ("i--", "(i--)"),
+            ("-i++", "(-(i++))"),
+            ("total = i++ + j--", "(total = ((i++) + (j--)))"),
+            ("a[i++]", "a[(i++)]"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_expression(source).unwrap(), expected, "{}", source);
+        }
+
+        // 只能作用于变量
+        for source in ["a[0]++", "p.x--", "f()++", "i++++", "1++"] {
+            assert!(matches!(
+                parse_expression(source),
+                Err(ParseError::UnexpectedToken { found: Token { token_type: TokenType::PlusPlus | TokenType::MinusMinus, .. }, .. })
+            ), "{}", source);
+        }
+
+        // 换行模式下另起一行的 `++` 不会接到上一行的变量上
+        assert!(parse_with_newlines("var i = 0\ni++\nprint(i)").is_ok());
+        assert!(parse_with_newlines("var i = 0\ni\n++").is_err());
+    }
+
+    #[test]
+    fn test_for_range_inclusive_flag() {
+        let statement = |input: &str| parse(input).unwrap().statements.remove(0);
+        assert!(matches!(statement("for i in 1..=10 { }"), Stmt::For { inclusive: true, .. }));
+        assert!(matches!(statement("for i in 1..10 { }"), Stmt::For { inclusive: false, .. }));
+        assert!(matches!(statement("let r = 1..=3; for i in r { }"), Stmt::VarDeclaration { .. }));
+        // 带步长的范围按值遍历
+        let Stmt::ForIn { iterable, .. } = statement("for i in 10..=0 step k + 1 { }") else {
+            panic!("expected for-in over a stepped range");
+        };
+        assert_eq!(
+            iterable,
+            Expr::range(Expr::Integer(10), Expr::Integer(0), Some(Expr::binary(Expr::Identifier("k".to_string()), BinaryOp::Add, Expr::Integer(1))), true)
+        );
+        // `step` 只在范围之后有特殊含义，仍可作普通变量名
+        assert!(parse("let step = 2; let r = 0..10 step step;").is_ok());
+    }
+
+    #[test]
+    fn test_match_statement() {
+        let program = parse("const N = 2; match x { 1 => { print(1); }, -N * 2 => { } \"a\" => { } true => { }, _ => { print(0); } }").unwrap();
+        let Stmt::Match { scrutinee, arms, default, .. } = &program.statements[1] else {
+            panic!("expected match statement, got {:?}", program.statements[1]);
+        };
+        assert_eq!(scrutinee, &Expr::Identifier("x".to_string()));
+        let patterns: Vec<&Expr> = arms.iter().map(|(pattern, _)| pattern).collect();
+        assert_eq!(patterns, [
+            &Expr::Integer(1),
+            &Expr::Integer(-4),
+            &Expr::String("a".to_string()),
+            &Expr::Boolean(true),
+        ]);
+        assert_eq!(arms[0].1.len(), 1);
+        assert_eq!(default.as_ref().map(Vec::len), Some(1));
+
+        // 被匹配的值后的 `{` 不会被当作结构体字面量
+        assert!(matches!(&parse("match p { _ => { } }").unwrap().statements[0], Stmt::Match { arms, default: Some(_), .. } if arms.is_empty()));
+        assert!(matches!(
+            parse("match x { _ => { } 1 => { } }"),
+            Err(ParseError::UnexpectedToken { found: Token { token_type: TokenType::Integer, .. }, .. })
+        ));
+        assert!(matches!(parse("match x { y => { } }"), Err(ParseError::NotConstant(..))));
+        assert!(matches!(
+            parse("match x { 1 { } }"),
+            Err(ParseError::UnexpectedToken { found: Token { token_type: TokenType::LeftBrace, .. }, .. })
+        ));
+    }
+
+    #[test]
+    fn test_scientific_notation_literals() {
+        let program = parse("let x = 2.5e3; let y = 1e10;").unwrap();
+        let initializers: Vec<&Expr> = program.statements.iter().filter_map(|stmt| match stmt {
+            Stmt::VarDeclaration { initializer: Some(expr), .. } => Some(expr),
+            _ => None,
+        }).collect();
+        assert_eq!(initializers, [&Expr::Float(2500.0), &Expr::Integer(10_000_000_000)]);
+    }
+
+    #[test]
+    fn test_parse_tail_expression_as_return() {
+        let mut lexer = Lexer::new("fn add(a, b) { let c = a; c + b }".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        match &program.statements[0] {
+            Stmt::FnDeclaration { body, .. } => {
+                assert_eq!(body.len(), 2);
+                assert!(matches!(body[1], Stmt::Return { value: Some(_) }));
+            }
+            other => panic!("expected function declaration, got {:?}", other),
+        }
+
+        // 是否为末尾表达式由向前查看决定，不回溯
+        let last = |program: Program| match program.statements.into_iter().next() {
+            Some(Stmt::FnDeclaration { mut body, .. }) => body.pop().unwrap(),
+            other => panic!("expected function declaration, got {:?}", other),
+        };
+        let tail = last(parse("fn f(c: bool) -> int { if c { 1 } else { 2 } }").unwrap());
+        assert!(matches!(tail, Stmt::Return { value: Some(Expr::If { .. }) }));
+        let tail = last(parse("fn f(c: bool) { if c { print(1); } else { print(2); } }").unwrap());
+        assert!(matches!(tail, Stmt::If { .. }));
+        let tail = last(parse("fn f(n: int) { if n > 0 { } else { } }").unwrap());
+        assert!(matches!(tail, Stmt::If { .. }));
+        let tail = last(parse("fn f() -> P { P { x: 1 } }").unwrap());
+        assert!(matches!(tail, Stmt::Return { value: Some(Expr::StructLiteral { .. }) }));
+        let tail = last(parse_with_newlines("fn f(a: int) -> int {\n    print(a)\n    a + 1\n}").unwrap());
+        assert!(matches!(tail, Stmt::Return { value: Some(Expr::Binary { .. }) }));
+        let tail = last(parse_with_newlines("fn f(a: int) {\n    a + 1\n    print(a)\n}").unwrap());
+        assert!(!matches!(tail, Stmt::Return { .. }));
+
+        // 函数体中的错误只记录一次
+        let tokens = Lexer::new("fn f() { let x = ; x + }\nlet y = 1;".to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        assert!(parser.parse().is_err());
+        assert_eq!(parser.errors().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_function_type_annotation() {
+        let mut lexer = Lexer::new("let op: fn(int, int) -> int = add;".to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        let expected = Type::Function(FunctionType {
+            params: vec![Type::Int, Type::Int],
+            return_type: Box::new(Type::Int),
+        });
+        assert!(matches!(
+            &program.statements[0],
+            Stmt::VarDeclaration { type_annotation: Some(t), .. } if *t == expected
+        ));
+    }
+
+    #[test]
+    fn test_parse_fixed_array_type() {
+        let program = parse("let buf: [int; 4] = [0, 0, 0, 0];").unwrap();
+        match &program.statements[0] {
+            Stmt::VarDeclaration { type_annotation: Some(annotation), .. } => {
+                assert_eq!(annotation, &Type::FixedArray(Box::new(Type::Int), 4));
+                assert_eq!(annotation.to_string(), "[int; 4]");
+            }
+            other => panic!("expected variable declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_else_if_chain() {
+        let source = "if x > 1 { print(1); } else if x > 0 { print(2); } else { print(3); }";
+        let program = parse(source).unwrap();
+        assert_eq!(program.statements.len(), 1);
+
+        let Stmt::If { then_branch, else_branch: Some(else_branch), .. } = &program.statements[0] else {
+            panic!("expected if statement, got {:?}", program.statements[0]);
+        };
+        assert_eq!(then_branch.len(), 1);
+        // else if 是 else 分支中唯一的嵌套 if 语句
+        match else_branch.as_slice() {
+            [Stmt::If { condition, else_branch: Some(last), .. }] => {
+                assert_eq!(render(condition), "(x > 0)");
+                assert!(matches!(last.as_slice(), [Stmt::Print { .. }]), "{:?}", last);
+            }
+            other => panic!("expected nested if, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_constant_array_lengths() {
+        let program = parse("const N = 4; const M: int = (N + 2) * 2 % 5; let a: [int; N * M] = []; const PI = -3.14;").unwrap();
+        match &program.statements[2] {
+            Stmt::VarDeclaration { type_annotation: Some(annotation), .. } => {
+                assert_eq!(annotation, &Type::FixedArray(Box::new(Type::Int), 8));
+            }
+            other => panic!("expected variable declaration, got {:?}", other),
+        }
+        assert!(matches!(&program.statements[0], Stmt::VarDeclaration { mutable: false, is_const: true, .. }));
+        // 整数常量的初始值折叠为字面量
+        assert!(matches!(&program.statements[1], Stmt::VarDeclaration { initializer: Some(Expr::Integer(2)), .. }));
+        assert!(matches!(parse("let n = 4;").unwrap().statements[0], Stmt::VarDeclaration { is_const: false, .. }));
+
+        // 普通变量不是常量；长度不能为负
+        assert!(matches!(parse("let n = 4; let a: [int; n] = [];"), Err(ParseError::NotConstant(..))));
+        assert!(matches!(parse("const N = 2; let a: [int; N - 3] = [];"), Err(ParseError::IntegerOutOfRange(ref v, _)) if v == "-1"));
+        assert!(matches!(parse("const N = 1 / 0;"), Err(ParseError::IntegerOutOfRange(..))));
+        assert!(matches!(parse("fn f() -> int { return 1; } const N = f();"), Err(ParseError::NotConstant(..))));
+        // 常量只能在顶层声明
+        assert!(matches!(parse("fn f() { const N = 1; }"), Err(ParseError::UnexpectedToken { found: Token { token_type: TokenType::Const, .. }, .. })));
+
+        // 被局部变量、参数或循环变量遮蔽的常量名不再是常量，作用域结束后恢复
+        for shadowed in [
+            "const N = 2; fn f() { let N = 3; let a: [int; N] = [1, 2]; }",
+            "const N = 2; fn f(N: int) { let a: [int; N] = [1, 2]; }",
+            "const N = 2; for N in 0..3 { let a: [int; N] = [1, 2]; }",
+            "const N = 2; if true { let N = 3; if true { let a: [int; N] = [1, 2]; } }",
+        ] {
+            assert!(matches!(parse(shadowed), Err(ParseError::NotConstant(..))), "{}", shadowed);
+        }
+        for visible in [
+            "const N = 2; fn f() { { let N = 3; } let a: [int; N] = [1, 2]; }",
+            "const N = 2; fn f(N: int) { } let a: [int; N] = [1, 2];",
+            "const N = 2; fn f() { let N: [int; N] = [1, 2]; }",
+        ] {
+            assert!(parse(visible).is_ok(), "{}", visible);
+        }
+    }
+
+    fn parse_with_newlines(source: &str) -> ParseResult<Program> {
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        Parser::new(tokens).with_newline_terminators(true).parse()
+    }
+
+    #[test]
+    fn test_newline_terminates_statements() {
+        let source = "let x = 1\nlet y = 2;\nprint(x + y)\nfn f() {\n    if x > 0 {\n        return\n    }\n    x = x + 1\n}\n";
+        let program = parse_with_newlines(source).unwrap();
+        assert_eq!(program.statements.len(), 4);
+
+        // 单独一行的 return 不会吞掉下一行
+        match &program.statements[3] {
+            Stmt::FnDeclaration { body, .. } => {
+                assert_eq!(body.len(), 2);
+                assert!(matches!(&body[0], Stmt::If { then_branch, .. }
+                    if matches!(then_branch.as_slice(), [Stmt::Return { value: None }])));
+            }
+            other => panic!("expected function declaration, got {:?}", other),
+        }
+
+        // 默认模式仍然要求分号
+        assert!(parse("let x = 1\nlet y = 2;").is_err());
+    }
+
+    #[test]
+    fn test_expression_continues_across_lines() {
+        let program = parse_with_newlines("let total = 1 +\n    2 *\n    3\nprint(total)").unwrap();
+        assert_eq!(program.statements.len(), 2);
+        assert!(matches!(
+            &program.statements[0],
+            Stmt::VarDeclaration { initializer: Some(Expr::Binary { operator: BinaryOp::Add, .. }), .. }
+        ));
+
+        // 同一行内缺少分号仍然是错误
+        assert!(parse_with_newlines("let a = 1 let b = 2").is_err());
+    }
+
+    /// 把表达式渲染为完全加括号的形式，便于断言结合方式
+    fn render(expr: &Expr) -> String {
+        match expr {
+            Expr::Integer(n) => n.to_string(),
+            Expr::Boolean(b) => b.to_string(),
+            Expr::Identifier(name) => name.clone(),
+            Expr::Binary { left, operator, right } => {
+                format!("({} {} {})", render(left), operator.symbol(), render(right))
+            }
+            Expr::Unary { operator: UnaryOp::Not, operand } => format!("(!{})", render(operand)),
+            Expr::Unary { operator: UnaryOp::Negate, operand } => format!("(-{})", render(operand)),
+            Expr::Unary { operator: UnaryOp::Plus, operand } => format!("(+{})", render(operand)),
+            Expr::Range { start, end, step, inclusive } => {
+                let step = step.as_ref().map(|step| format!(" step {}", render(step))).unwrap_or_default();
+                format!("({}{}{}{})", render(start), if *inclusive { "..=" } else { ".." }, render(end), step)
+            }
+            Expr::Assign { name, value } => format!("({} = {})", name, render(value)),
+            Expr::PostfixUpdate { name, operator: BinaryOp::Add } => format!("({}++)", name),
+            Expr::PostfixUpdate { name, .. } => format!("({}--)", name),
+            Expr::Index { object, index } => format!("{}[{}]", render(object), render(index)),
+            Expr::If { condition, then_expr, else_expr } => {
+                format!("({} ? {} : {})", render(condition), render(then_expr), render(else_expr))
+            }
+            Expr::Tuple(elements) => {
+                let elements: Vec<String> = elements.iter().map(render).collect();
+                format!("({},)", elements.join(", "))
+            }
+            Expr::FieldAccess { object, field, .. } => format!("{}.{}", render(object), field),
+            Expr::Call { callee, arguments } => {
+                let arguments: Vec<String> = arguments.iter().map(render).collect();
+                format!("{}({})", render(callee), arguments.join(", "))
+            }
+            other => panic!("unexpected expression {:?}", other),
+        }
+    }
+
+    fn parse_expression(source: &str) -> ParseResult<String> {
+        let program = parse(&format!("{};", source))?;
+        match &program.statements[..] {
+            [Stmt::Expression(expr)] => Ok(render(expr)),
+            other => panic!("expected one expression statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_operator_precedence_and_associativity() {
+        let cases = [
+            ("2 + 3 * 4 == 14", "((2 + (3 * 4)) == 14)"),
+            ("a - b - c", "((a - b) - c)"),
+            ("a / b % c * d", "(((a / b) % c) * d)"),
+            ("a < b == c >= d", "((a < b) == (c >= d))"),
+            ("a || b && c == d", "(a || (b && (c == d)))"),
+            ("a && b || c && d", "((a && b) || (c && d))"),
+            ("-a * !b", "((-a) * (!b))"),
+            ("-a - -b", "((-a) - (-b))"),
+            ("0..n + 1", "(0..(n + 1))"),
+            ("a + 1..=b * 2", "((a + 1)..=(b * 2))"),
+            ("x = y = 1 + 2", "(x = (y = (1 + 2)))"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_expression(source).unwrap(), expected, "{}", source);
+        }
+
+        // 范围运算符不可结合
+        assert!(parse_expression("1..2..3").is_err());
+    }
+
+    #[test]
+    fn test_negative_and_chained_indexing() {
+        let index = |object: Expr, index: Expr| Expr::index(object, index);
+        let ident = |name: &str| Expr::Identifier(name.to_string());
+        let expression = |source: &str| match parse(&format!("{};", source)).unwrap().statements.remove(0) {
+            Stmt::Expression(expr) => expr,
+            other => panic!("expected expression statement, got {:?}", other),
+        };
+
+        assert_eq!(
+            expression("arr[-1]"),
+            index(ident("arr"), Expr::unary(UnaryOp::Negate, Expr::Integer(1)))
+        );
+        assert_eq!(expression("matrix[i][j]"), index(index(ident("matrix"), ident("i")), ident("j")));
+        assert_eq!(
+            expression("m[i][j] = 5"),
+            Expr::index_assign(index(ident("m"), ident("i")), ident("j"), Expr::Integer(5))
+        );
+        // 取负作用于整个索引表达式
+        assert_eq!(parse_expression("-arr[0]").unwrap(), "(-arr[0])");
+        assert_eq!(parse_expression("a[i + 1][-j]").unwrap(), "a[(i + 1)][(-j)]");
+    }
+
+    #[test]
+    fn test_conditional_expression() {
+        let cases = [
+            ("a > b ? a : b", "((a > b) ? a : b)"),
+            ("a ? b : c ? d : e", "(a ? b : (c ? d : e))"),
+            ("a ? b ? c : d : e", "(a ? (b ? c : d) : e)"),
+            ("a || b ? 1 + 2 : -3", "((a || b) ? (1 + 2) : (-3))"),
+            ("m = a ? b : c", "(m = (a ? b : c))"),
+            ("a ? x = 1 : 2", "(a ? (x = 1) : 2)"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_expression(source).unwrap(), expected, "{}", source);
+        }
+
+        assert!(matches!(
+            parse_expression("a ? b"),
+            Err(ParseError::UnexpectedToken { found: Token { token_type: TokenType::Semicolon, .. }, .. })
+        ));
+        // 条件表达式不是赋值目标
+        assert!(parse_expression("a ? b : c = 1").is_err());
+    }
+
+    #[test]
+    fn test_recovers_and_reports_every_syntax_error() {
+        let source = "let a = ;\nfn f(x) {\n    if x > 0 {\n        return x +;\n    } else {\n        return 0;\n    }\n}\nlet b = 2;\nlet c = (1 + 2;\nprint(b);\n";
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+
+        // 返回值仍是第一个错误
+        assert!(matches!(parser.parse(), Err(ParseError::InvalidExpression(_))));
+
+        // `else` 分支和其后的正确语句没有产生连带错误
+        let errors: Vec<(&str, usize, usize)> = parser.errors().iter()
+            .map(|e| (e.code.as_str(), e.location.line, e.location.column))
+            .collect();
+        assert_eq!(errors, vec![("P003", 1, 9), ("P003", 4, 19), ("P001", 10, 15)]);
+        assert_eq!(parser.errors()[2].params.get("found").map(String::as_str), Some("Semicolon"));
+
+        // 换行模式下出错的语句在行尾结束
+        let tokens = Lexer::new("let a = 1\nlet b = * 2\nprint(a)\nlet c = 3 +\n".to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens).with_newline_terminators(true);
+        assert!(parser.parse().is_err());
+        let lines: Vec<usize> = parser.errors().iter().map(|e| e.location.line).collect();
+        assert_eq!(lines, vec![2, 5]);
+
+        // 没有错误时不收集任何东西
+        let tokens = Lexer::new("let a = 1;".to_string()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        assert!(parser.parse().is_ok());
+        assert!(parser.errors().is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors_carry_positions() {
+        let position = |err: &ParseError| (err.token().start_pos.line, err.token().start_pos.column);
+
+        let err = parse("let x = 1;\nlet y = (2 + ;").unwrap_err();
+        assert!(matches!(err, ParseError::InvalidExpression(Token { token_type: TokenType::Semicolon, .. })));
+        assert_eq!(position(&err), (2, 14));
+
+        // 常量表达式的错误指向表达式开头
+        let err = parse("const N = 1;\nlet a: [int; N - 3] = [];").unwrap_err();
+        assert_eq!(position(&err), (2, 14));
+        let error = CompilerError::from(&err);
+        assert_eq!((error.code.as_str(), error.location.line, error.location.column), ("P001", 2, 14));
+        assert_eq!(error.params.get("found").map(String::as_str), Some("-2"));
+
+        // 错误的长度覆盖整个 token
+        let err = parse("let value = 1;\nvalue + 1 += 2;").unwrap_err();
+        assert!(matches!(err, ParseError::UnexpectedToken { found: Token { token_type: TokenType::PlusEqual, .. }, .. }));
+        let error = CompilerError::from(&err);
+        assert_eq!((error.location.line, error.location.column, error.location.length), (2, 11, 2));
+    }
+
+    #[test]
+    fn test_tuple_literals_and_element_access() {
+        let cases = [
+            ("(1, 2)", "(1, 2,)"),
+            ("(a + 1, -b, c,)", "((a + 1), (-b), c,)"),
+            ("(a,)", "(a,)"),
+            ("(a)", "a"),
+            ("((1, 2), 3)", "((1, 2,), 3,)"),
+            ("t.0 + t.1", "(t.0 + t.1)"),
+            // `0.1` 被词法分析为浮点数，仍然是两次元素访问
+            ("t.0.1", "t.0.1"),
+            ("pair.1(x)", "pair.1(x)"),
+            ("f(a, b).0", "f(a, b).0"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(parse_expression(source).unwrap(), expected, "{}", source);
+        }
+
+        assert!(parse_expression("()").is_err());
+        assert!(matches!(
+            parse_expression("t.0x1"),
+            Err(ParseError::UnexpectedToken { found: Token { token_type: TokenType::Integer, .. }, .. })
+        ));
+
+        match &parse("let p: ((int, string), (bool,), (int)) = x;").unwrap().statements[0] {
+            Stmt::VarDeclaration { type_annotation: Some(annotation), .. } => {
+                assert_eq!(annotation, &Type::Tuple(vec![
+                    Type::Tuple(vec![Type::Int, Type::String]),
+                    Type::Tuple(vec![Type::Bool]),
+                    Type::Int,
+                ]));
+                assert_eq!(annotation.to_string(), "((int, string), (bool,), int)");
+            }
+            other => panic!("expected variable declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_print_statement_arguments() {
+        assert!(matches!(parse("print(1);").unwrap().statements.as_slice(), [Stmt::Print { value: Expr::Integer(1) }]));
+        // 其他实参个数解析为普通调用，由类型检查报告参数数量
+        for (source, count) in [("print();", 0), ("print(1, 2);", 2)] {
+            let program = parse(source).unwrap();
+            assert!(matches!(
+                program.statements.as_slice(),
+                [Stmt::Expression(Expr::Call { callee, arguments })]
+                    if **callee == Expr::Identifier("print".to_string()) && arguments.len() == count
+            ), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_struct_literal_needs_field_pairs() {
+        let initializer = |source: &str| match parse(source).unwrap().statements.remove(0) {
+            Stmt::VarDeclaration { initializer: Some(expr), .. } => expr,
+            other => panic!("expected variable declaration, got {:?}", other),
+        };
+
+        assert!(matches!(initializer("let p = Point { x: 1, y: 2 };"), Expr::StructLiteral { ref fields, .. } if fields.len() == 2));
+        assert!(matches!(initializer("let e = Empty {};"), Expr::StructLiteral { ref fields, .. } if fields.is_empty()));
+
+        // `{` 之后不是 `字段名:` 时标识符单独成为表达式语句，`{` 开始的是其后的代码块
+        let program = parse("fn f(point) { point { print(point); } }").unwrap();
+        let Stmt::FnDeclaration { body, .. } = &program.statements[0] else {
+            panic!("expected function declaration, got {:?}", program.statements[0]);
+        };
+        assert!(matches!(
+            body.as_slice(),
+            [Stmt::Expression(Expr::Identifier(name)), Stmt::Block { statements, .. }]
+                if name == "point" && matches!(statements.as_slice(), [Stmt::Print { .. }])
+        ), "{:?}", body);
+        let program = parse_with_newlines("let point = 1\npoint { print(point) }").unwrap();
+        assert!(matches!(program.statements.as_slice(), [_, Stmt::Expression(Expr::Identifier(_)), Stmt::Block { .. }]));
+
+        // 其他表达式之后仍然需要分号
+        assert!(matches!(
+            parse("fn f(point) { point + 1 { print(point); } }"),
+            Err(ParseError::UnexpectedToken { found: Token { token_type: TokenType::LeftBrace, .. }, .. })
+        ));
+    }
+}
@@ -1,8 +1,9 @@
 use crate::ast::{Expr, Program, Stmt, BinaryOp, UnaryOp, Type, Parameter, FunctionType, MethodDeclaration};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 /// 类型检查错误
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum TypeError {
     TypeMismatch {
         expected: Type,
@@ -16,10 +17,12 @@ pub enum TypeError {
         found: usize,
         function: String,
     },
+    // 类型装箱，保持 TypeError（以及每个 TypeResult）足够小
     ArgumentTypeMismatch {
-        expected: Type,
-        found: Type,
+        expected: Box<Type>,
+        found: Box<Type>,
         argument: usize,
+        parameter: Option<String>,  // 参数名（内置函数和函数值没有记录参数名）
         function: String,
     },
     ReturnTypeMismatch {
@@ -36,17 +39,234 @@ pub enum TypeError {
     ImmutableAssignment {
         variable: String,
     },
+    ReadonlyFieldAssignment {
+        struct_name: String,
+        field: String,
+    },
     BreakOutsideLoop,
     ContinueOutsideLoop,
+    DivisionByZero {
+        operator: String,
+    },
+    ArrayLengthMismatch {
+        expected: usize,
+        found: usize,
+        location: String,
+    },
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+    },
+    /// 条件直接是赋值 `x = ...`，多半是把 `==` 写成了 `=`
+    AssignmentInCondition {
+        variable: String,
+        location: String,
+    },
+    /// `const` 声明没有初始值
+    ConstWithoutInitializer(String),
+    /// 标注了返回类型的函数存在不经过 `return` 就结束的路径
+    MissingReturn {
+        function: String,
+    },
+    /// 返回 void 的调用出现在需要值的位置（如 `let x = f();`、运算数、实参）
+    VoidValueUsed {
+        location: String,
+    },
+    /// 类型别名直接或经由其他别名引用自身（如 `type A = B; type B = A;`）
+    CyclicTypeAlias(String),
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeError::TypeMismatch { expected, found, location } => {
+                write!(f, "expected {} but found {} in {}", expected, found, location)
+            }
+            TypeError::UndefinedVariable(name) => write!(f, "undefined variable '{}'", name),
+            TypeError::UndefinedFunction(name) => write!(f, "undefined function '{}'", name),
+            TypeError::ArgumentCountMismatch { expected, found, function } => write!(
+                f,
+                "'{}' expects {} arguments but got {}",
+                function, expected, found
+            ),
+            TypeError::ArgumentTypeMismatch { expected, found, argument, parameter, function } => {
+                match parameter {
+                    Some(parameter) => write!(
+                        f,
+                        "argument {} ('{}') of '{}' expects {} but found {}",
+                        argument, parameter, function, expected, found
+                    ),
+                    None => write!(
+                        f,
+                        "argument {} of '{}' expects {} but found {}",
+                        argument, function, expected, found
+                    ),
+                }
+            }
+            TypeError::ReturnTypeMismatch { expected, found, function } => write!(
+                f,
+                "'{}' should return {} but returns {}",
+                function, expected, found
+            ),
+            TypeError::CannotInferType(what) => write!(f, "cannot infer the type of {}", what),
+            TypeError::InvalidOperation { operator, left_type, right_type } => write!(
+                f,
+                "'{}' cannot be applied to {} and {}",
+                operator, left_type, right_type
+            ),
+            TypeError::ImmutableAssignment { variable } => {
+                write!(f, "cannot assign to immutable variable '{}'", variable)
+            }
+            TypeError::ReadonlyFieldAssignment { struct_name, field } => {
+                write!(f, "cannot assign to readonly field '{}' of '{}'", field, struct_name)
+            }
+            TypeError::BreakOutsideLoop => write!(f, "'break' outside of a loop"),
+            TypeError::ContinueOutsideLoop => write!(f, "'continue' outside of a loop"),
+            TypeError::DivisionByZero { operator } => write!(f, "'{}' by constant zero", operator),
+            TypeError::ArrayLengthMismatch { expected, found, location } => write!(
+                f,
+                "expected an array of length {} but found length {} in {}",
+                expected, found, location
+            ),
+            TypeError::IndexOutOfBounds { index, length } => {
+                write!(f, "index {} is out of bounds for an array of length {}", index, length)
+            }
+            TypeError::AssignmentInCondition { variable, location } => write!(
+                f,
+                "assignment to '{}' used as the {}; did you mean '=='?",
+                variable, location
+            ),
+            TypeError::ConstWithoutInitializer(name) => {
+                write!(f, "const '{}' must have an initializer", name)
+            }
+            TypeError::MissingReturn { function } => {
+                write!(f, "'{}' can reach the end of its body without returning a value", function)
+            }
+            TypeError::VoidValueUsed { location } => {
+                write!(f, "a void call is used as a value in {}", location)
+            }
+            TypeError::CyclicTypeAlias(name) => write!(f, "type alias '{}' refers to itself", name),
+        }
+    }
 }
 
 type TypeResult<T> = Result<T, TypeError>;
 
+/// 类型检查警告（不阻止编译）
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeWarning {
+    /// int 与 float 之间的 `==`/`!=`：按当前语义两者永远不相等
+    MixedNumericEquality {
+        operator: String,
+        left_type: Type,
+        right_type: Type,
+    },
+    /// `x = x;` 之类把位置赋值给自身的语句（需启用 lint）
+    SelfAssignment {
+        target: String,
+    },
+    /// 函数体中从未引用的参数（需启用 lint，`_` 开头的参数除外）
+    UnusedParameter {
+        function: String,
+        parameter: String,
+    },
+    /// 同一未标注参数在不同调用处收到不兼容的实参类型（需启用 lint）
+    InconsistentArgumentType {
+        function: String,
+        argument: usize,
+        first: Type,
+        found: Type,
+    },
+    /// 变量、函数或参数与内置函数同名（需启用 lint）；遮蔽是允许的，但常常是误用
+    ShadowedBuiltin {
+        name: String,
+        signature: Type,
+    },
+    /// 声明后在作用域结束前从未读取的变量（需启用 lint，`_` 开头的变量除外）
+    UnusedVariable {
+        name: String,
+    },
+    /// 变量声明遮蔽了外层作用域中类型不同的同名绑定（需启用 lint）
+    ShadowedVariable {
+        name: String,
+        outer: Type,
+        inner: Type,
+    },
+    /// 类型覆盖率低于设定的阈值（需通过 `with_min_type_coverage` 启用）
+    LowTypeCoverage {
+        coverage: TypeCoverage,
+        threshold: f64,
+    },
+}
+
+impl fmt::Display for TypeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeWarning::MixedNumericEquality { operator, left_type, right_type } => write!(
+                f,
+                "'{}' between {} and {} compares different numeric types and is never equal",
+                operator, left_type, right_type
+            ),
+            TypeWarning::SelfAssignment { target } => {
+                write!(f, "'{} = {}' assigns a value to itself and has no effect", target, target)
+            }
+            TypeWarning::UnusedParameter { function, parameter } => {
+                write!(f, "parameter '{}' of '{}' is never used", parameter, function)
+            }
+            TypeWarning::InconsistentArgumentType { function, argument, first, found } => write!(
+                f,
+                "argument {} of '{}' is {} here but was {} at its first call",
+                argument, function, found, first
+            ),
+            TypeWarning::ShadowedBuiltin { name, signature } => {
+                write!(f, "'{}' shadows the builtin function {} ({})", name, name, signature)
+            }
+            TypeWarning::UnusedVariable { name } => write!(f, "variable '{}' is never used", name),
+            TypeWarning::ShadowedVariable { name, outer, inner } => write!(
+                f,
+                "'{}' of type {} shadows an outer '{}' of type {}",
+                name, inner, name, outer
+            ),
+            TypeWarning::LowTypeCoverage { coverage, threshold } => {
+                write!(f, "type coverage is {}, below the {:.1}% threshold", coverage, threshold)
+            }
+        }
+    }
+}
+
+/// 类型覆盖率：推导出具体类型（非 Unknown）的表达式占所有表达式的比例
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TypeCoverage {
+    pub typed: usize,
+    pub total: usize,
+}
+
+impl TypeCoverage {
+    /// 百分比；没有表达式时为 100
+    pub fn percentage(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.typed as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+impl fmt::Display for TypeCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}% ({}/{} expressions typed)", self.percentage(), self.typed, self.total)
+    }
+}
+
 /// 符号表条目
 #[derive(Debug, Clone)]
 struct Symbol {
     symbol_type: Type,
     is_mutable: bool,
+    used: bool,  // 是否被读取过
+    parameter_names: Vec<String>,  // 函数声明的参数名，其他符号为空
+    loop_counter: bool,  // `for i in a..b` 的循环变量：编译后直接作为计数器递增，只能赋 int
+    builtin: bool,  // 内置函数（未被同名声明遮蔽）
 }
 
 /// 符号表（支持作用域）
@@ -71,7 +291,60 @@ impl SymbolTable {
 
     pub fn define(&mut self, name: String, symbol_type: Type, is_mutable: bool) {
         if let Some(scope) = self.scopes.last_mut() {
-            scope.insert(name, Symbol { symbol_type, is_mutable });
+            scope.insert(name, Symbol {
+                symbol_type,
+                is_mutable,
+                used: false,
+                parameter_names: Vec::new(),
+                loop_counter: false,
+                builtin: false,
+            });
+        }
+    }
+
+    /// 定义内置函数
+    pub fn define_builtin(&mut self, name: String, symbol_type: Type) {
+        self.define(name.clone(), symbol_type, true);
+        if let Some(symbol) = self.scopes.last_mut().and_then(|scope| scope.get_mut(&name)) {
+            symbol.builtin = true;
+        }
+    }
+
+    /// 定义范围 for 循环的循环变量（可变的 int）
+    pub fn define_loop_counter(&mut self, name: String) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, Symbol {
+                symbol_type: Type::Int,
+                is_mutable: true,
+                used: false,
+                parameter_names: Vec::new(),
+                loop_counter: true,
+                builtin: false,
+            });
+        }
+    }
+
+    /// 定义函数符号，同时记录参数名（用于错误信息）
+    pub fn define_function(&mut self, name: String, symbol_type: Type, parameter_names: Vec<String>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, Symbol {
+                symbol_type,
+                is_mutable: false,
+                used: false,
+                parameter_names,
+                loop_counter: false,
+                builtin: false,
+            });
+        }
+    }
+
+    /// 将最内层可见的同名符号标记为已使用
+    pub fn mark_used(&mut self, name: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(symbol) = scope.get_mut(name) {
+                symbol.used = true;
+                return;
+            }
         }
     }
 
@@ -83,56 +356,524 @@ impl SymbolTable {
         }
         None
     }
+
+    /// 全局作用域中的所有符号，按名称排序
+    pub fn global_symbols(&self) -> Vec<SymbolInfo> {
+        let mut symbols: Vec<SymbolInfo> = self.scopes[0]
+            .iter()
+            .map(|(name, symbol)| SymbolInfo {
+                name: name.clone(),
+                symbol_type: symbol.symbol_type.clone(),
+                is_mutable: symbol.is_mutable,
+            })
+            .collect();
+        symbols.sort_by(|a, b| a.name.cmp(&b.name));
+        symbols
+    }
+}
+
+/// 对外公开的符号信息（用于调试输出推导出的类型）
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolInfo {
+    pub name: String,
+    pub symbol_type: Type,
+    pub is_mutable: bool,
+}
+
+impl fmt::Display for SymbolInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keyword = if self.is_mutable { "var" } else { "let" };
+        write!(f, "{} {}: {}", keyword, self.name, self.symbol_type)
+    }
 }
 
 /// 方法签名信息
 #[derive(Debug, Clone)]
 struct MethodSignature {
     params: Vec<Type>,
+    param_names: Vec<String>,
     return_type: Type,
+    is_static: bool,
+}
+
+/// 内置函数的名称和类型，与 `bytecode::builtins::all` 中的函数对应
+fn builtin_signatures() -> Vec<(&'static str, Type)> {
+    [
+        ("print", vec![Type::Unknown], Type::Void),
+        ("len", vec![Type::Unknown], Type::Int),
+        ("to_array", vec![Type::Range], Type::Array(Box::new(Type::Int))),
+        ("contains", vec![Type::Unknown, Type::Unknown], Type::Bool),
+        ("exit", vec![Type::Int], Type::Void),
+        ("assert", vec![Type::Bool], Type::Void),
+        ("debug_assert", vec![Type::Bool], Type::Void),
+        ("typeof", vec![Type::Unknown], Type::String),
+        // 整数数组求和的结果为 int，见 `Expr::Call` 的推断
+        ("sum", vec![Type::Array(Box::new(Type::Float))], Type::Float),
+    ]
+    .into_iter()
+    .map(|(name, params, return_type)| {
+        (name, Type::Function(FunctionType { params, return_type: Box::new(return_type) }))
+    })
+    .collect()
 }
 
 /// 类型检查器
 pub struct TypeChecker {
     symbol_table: SymbolTable,
     current_function_return_type: Option<Type>,
+    inferred_return_types: Vec<Type>,  // 未标注返回类型的函数中各 return 的类型
     loop_depth: usize,  // 追踪循环嵌套深度
     methods: HashMap<String, HashMap<String, MethodSignature>>,  // type_name -> (method_name -> signature)
+    warnings: Vec<TypeWarning>,
+    lint_self_assignment: bool,  // 是否报告自赋值语句
+    lint_unused_parameters: bool,  // 是否报告未使用的函数参数
+    lint_call_consistency: bool,  // 是否检查未标注参数在各调用处的实参类型是否一致
+    lint_shadowed_builtins: bool,  // 是否报告与内置函数同名的声明
+    lint_unused_variables: bool,  // 是否报告声明后从未读取的变量
+    lint_shadowed_variables: bool,  // 是否报告遮蔽外层作用域同名变量的声明
+    declared_variables: Vec<(String, usize)>,  // 已声明的变量及其作用域深度，作用域结束时检查是否被读取
+    call_argument_types: HashMap<String, Vec<Option<Type>>>,  // 函数名 -> 各未标注参数首次调用时的实参类型
+    min_type_coverage: Option<f64>,  // 覆盖率低于该百分比时报告警告
+    coverage_seen: HashSet<usize>,  // 本次检查中已统计的表达式地址（同一表达式可能被多次推导；检查期间语法树不会移动）
+    coverage: TypeCoverage,
+    expected_type: Option<Type>,  // 上下文给出的类型（声明的注解、被赋值变量的类型），只作用于紧接着推导的表达式
+    errors: Vec<TypeError>,  // 已发现的错误（出错的语句被跳过，检查继续）
+    void_allowed: bool,  // 紧接着推导的表达式的值会被丢弃（表达式语句、return），可以是 void 调用
 }
 
 impl TypeChecker {
     pub fn new() -> Self {
-        TypeChecker {
+        let mut checker = TypeChecker {
             symbol_table: SymbolTable::new(),
             current_function_return_type: None,
+            inferred_return_types: Vec::new(),
             loop_depth: 0,
             methods: HashMap::new(),
+            warnings: Vec::new(),
+            lint_self_assignment: false,
+            lint_unused_parameters: false,
+            lint_call_consistency: false,
+            lint_shadowed_builtins: false,
+            lint_unused_variables: false,
+            lint_shadowed_variables: false,
+            declared_variables: Vec::new(),
+            call_argument_types: HashMap::new(),
+            min_type_coverage: None,
+            coverage_seen: HashSet::new(),
+            coverage: TypeCoverage::default(),
+            expected_type: None,
+            errors: Vec::new(),
+            void_allowed: false,
+        };
+        checker.define_builtins();
+        checker
+    }
+
+    /// 启用自赋值检查（`x = x;`、`arr[i] = arr[i];` 报告为警告）
+    pub fn with_self_assignment_lint(mut self, enabled: bool) -> Self {
+        self.lint_self_assignment = enabled;
+        self
+    }
+
+    /// 启用未使用参数检查（`_` 开头的参数不报告）
+    pub fn with_unused_parameter_lint(mut self, enabled: bool) -> Self {
+        self.lint_unused_parameters = enabled;
+        self
+    }
+
+    /// 启用调用一致性检查：未标注类型的参数以首次调用的实参类型为准，
+    /// 之后的调用传入不兼容的类型时报告警告
+    pub fn with_call_consistency_lint(mut self, enabled: bool) -> Self {
+        self.lint_call_consistency = enabled;
+        self
+    }
+
+    /// 启用内置函数遮蔽检查：变量、函数、参数或循环变量与内置函数同名时报告警告
+    pub fn with_shadowed_builtin_lint(mut self, enabled: bool) -> Self {
+        self.lint_shadowed_builtins = enabled;
+        self
+    }
+
+    /// 启用未使用变量检查：`let`/`var` 声明的变量在作用域结束前从未被读取时报告警告（`_` 开头的除外）
+    pub fn with_unused_variable_lint(mut self, enabled: bool) -> Self {
+        self.lint_unused_variables = enabled;
+        self
+    }
+
+    /// 启用变量遮蔽检查：声明的变量遮蔽外层作用域中类型不同的同名绑定时报告警告
+    pub fn with_shadowed_variable_lint(mut self, enabled: bool) -> Self {
+        self.lint_shadowed_variables = enabled;
+        self
+    }
+
+    /// 启用变量警告：同时开启未使用变量检查和变量遮蔽检查，警告不会使检查失败
+    pub fn with_warnings(self, enabled: bool) -> Self {
+        self.with_unused_variable_lint(enabled)
+            .with_shadowed_variable_lint(enabled)
+    }
+
+    /// 检查结束时若类型覆盖率低于 `min_percent`（0–100）则报告警告
+    pub fn with_min_type_coverage(mut self, min_percent: f64) -> Self {
+        self.min_type_coverage = Some(min_percent);
+        self
+    }
+
+    /// 将内置函数注册为全局符号（与VM中的全局变量对应，可被遮蔽）
+    fn define_builtins(&mut self) {
+        for (name, func_type) in builtin_signatures() {
+            self.symbol_table.define_builtin(name.to_string(), func_type);
+        }
+    }
+
+    /// 声明的名字与内置函数同名时报告警告（需启用 lint）
+    fn check_shadowed_builtin(&mut self, name: &str) {
+        if !self.lint_shadowed_builtins {
+            return;
+        }
+        if let Some((_, signature)) = builtin_signatures().into_iter().find(|(builtin, _)| *builtin == name) {
+            self.warnings.push(TypeWarning::ShadowedBuiltin { name: name.to_string(), signature });
+        }
+    }
+
+    /// 两个类型的共同类型（用于 if 表达式分支和推断返回类型）：
+    /// Unknown 让位于另一方，int 与 float 混合时为 float，不兼容时返回 None
+    fn common_type(&self, a: &Type, b: &Type) -> Option<Type> {
+        match (a, b) {
+            (a, b) if a == b => Some(a.clone()),
+            (Type::Unknown, other) | (other, Type::Unknown) => Some(other.clone()),
+            (a, b) if a.is_numeric() && b.is_numeric() => Some(Type::Float),
+            (a, b) if self.resolve_type(a).is_compatible_with(&self.resolve_type(b)) => Some(a.clone()),
+            _ => None,
+        }
+    }
+
+    /// 统一未标注返回类型的函数中各 return 的类型，冲突时返回（已统一的类型, 冲突的类型）
+    fn unify_return_types(&self, returns: &[Type]) -> Result<Type, (Type, Type)> {
+        let mut inferred = returns[0].clone();
+        for found in &returns[1..] {
+            inferred = self.common_type(&inferred, found).ok_or_else(|| (inferred.clone(), found.clone()))?;
+        }
+        Ok(inferred)
+    }
+
+    /// 记录未标注参数的实参类型：首次调用时记下，之后与其不兼容则报告警告
+    fn record_call_argument(&mut self, function: &str, index: usize, arg_type: &Type) {
+        if *arg_type == Type::Unknown {
+            return;
+        }
+        let slot = match self.call_argument_types.get_mut(function).and_then(|slots| slots.get_mut(index)) {
+            Some(slot) => slot,
+            None => return,
+        };
+        let first = match slot {
+            Some(first) => first.clone(),
+            None => {
+                *slot = Some(arg_type.clone());
+                return;
+            }
+        };
+        if self.common_type(&first, arg_type).is_none() {
+            self.warnings.push(TypeWarning::InconsistentArgumentType {
+                function: function.to_string(),
+                argument: index + 1,
+                first,
+                found: arg_type.clone(),
+            });
+        }
+    }
+
+    /// 定长数组类型的目标只接受元素数量恰好为 N 的数组字面量
+    fn fixed_array_literal_error(expected: &Type, value: &Expr, location: &str) -> Option<TypeError> {
+        match (expected, value) {
+            (Type::FixedArray(_, length), Expr::Array { elements }) if elements.len() != *length => {
+                Some(TypeError::ArrayLengthMismatch {
+                    expected: *length,
+                    found: elements.len(),
+                    location: location.to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// 定长数组的常量索引越界时在编译期报告（负索引从末尾计数，有效范围为 -N..N）
+    fn constant_index_error(&self, obj_type: &Type, index: &Expr) -> Option<TypeError> {
+        let constant = match index {
+            Expr::Integer(i) => Some(*i),
+            Expr::Unary { operator: UnaryOp::Negate, operand } => match operand.as_ref() {
+                Expr::Integer(i) => Some(-*i),
+                _ => None,
+            },
+            Expr::Unary { operator: UnaryOp::Plus, operand } => match operand.as_ref() {
+                Expr::Integer(i) => Some(*i),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let (Type::FixedArray(_, length), Some(index)) = (self.resolve_type(obj_type), constant) {
+            let length_i64 = length as i64;
+            if index < -length_i64 || index >= length_i64 {
+                return Some(TypeError::IndexOutOfBounds { index, length });
+            }
+        }
+        None
+    }
+
+    /// 条件本身是赋值表达式时报告（赋值的值可能恰好是 bool，不能只靠类型检查发现）
+    fn assignment_in_condition(condition: &Expr, location: &str) -> Option<TypeError> {
+        match condition {
+            Expr::Assign { name, .. } => Some(TypeError::AssignmentInCondition {
+                variable: name.clone(),
+                location: location.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// 返回 void 的调用的描述，用于 VoidValueUsed
+    fn void_call_location(expr: &Expr) -> String {
+        match expr {
+            Expr::Call { callee, .. } => match callee.as_ref() {
+                Expr::Identifier(name) => format!("call to '{}'", name),
+                _ => "call".to_string(),
+            },
+            Expr::MethodCall { method, .. } => format!("call to method '{}'", method),
+            _ => "expression".to_string(),
+        }
+    }
+
+    /// 表达式是结构体类型名本身（而非该类型的值）时返回类型名
+    fn struct_type_name<'a>(&self, expr: &'a Expr) -> Option<&'a str> {
+        let Expr::Identifier(name) = expr else {
+            return None;
+        };
+        match self.symbol_table.get(name).map(|symbol| &symbol.symbol_type) {
+            Some(Type::Struct(struct_type)) if struct_type.name == *name => Some(name),
+            _ => None,
+        }
+    }
+
+    /// 表达式是否为常量零（`0`、`0.0` 及其取负或加正号）
+    fn is_constant_zero(expr: &Expr) -> bool {
+        match expr {
+            Expr::Integer(0) => true,
+            Expr::Float(f) => *f == 0.0,
+            Expr::Unary { operator: UnaryOp::Negate | UnaryOp::Plus, operand } => Self::is_constant_zero(operand),
+            _ => false,
+        }
+    }
+
+    /// 语句序列是否在每条路径上都以 `return` 结束（`while true` 且不含 break 的循环视为不会结束）
+    fn always_returns(stmts: &[Stmt]) -> bool {
+        stmts.iter().any(|stmt| match stmt {
+            Stmt::Return { .. } => true,
+            Stmt::Block { statements, .. } => Self::always_returns(statements),
+            Stmt::If { then_branch, else_branch: Some(else_branch), .. } => {
+                Self::always_returns(then_branch) && Self::always_returns(else_branch)
+            }
+            Stmt::Match { arms, default: Some(default), .. } => {
+                arms.iter().all(|(_, body)| Self::always_returns(body)) && Self::always_returns(default)
+            }
+            Stmt::While { condition: Expr::Boolean(true), body, .. } => !Self::contains_break(body),
+            _ => false,
+        })
+    }
+
+    /// 语句序列中是否有跳出当前循环的 break（嵌套循环中的 break 不算）
+    fn contains_break(stmts: &[Stmt]) -> bool {
+        stmts.iter().any(|stmt| match stmt {
+            Stmt::Break => true,
+            Stmt::Block { statements, .. } => Self::contains_break(statements),
+            Stmt::If { then_branch, else_branch, .. } => {
+                Self::contains_break(then_branch) || else_branch.as_deref().is_some_and(Self::contains_break)
+            }
+            Stmt::Match { arms, default, .. } => {
+                arms.iter().any(|(_, body)| Self::contains_break(body))
+                    || default.as_deref().is_some_and(Self::contains_break)
+            }
+            _ => false,
+        })
+    }
+
+    /// 返回类型不是 void/未知的函数存在不返回的路径时报告
+    fn missing_return(&self, function: &str, return_type: &Type, body: &[Stmt]) -> Option<TypeError> {
+        match self.resolve_type(return_type) {
+            Type::Void | Type::Unknown => None,
+            _ if Self::always_returns(body) => None,
+            _ => Some(TypeError::MissingReturn { function: function.to_string() }),
+        }
+    }
+
+    /// 弹出作用域，先报告其中未使用的变量
+    fn pop_scope(&mut self) {
+        self.report_unused_variables();
+        self.symbol_table.pop_scope();
+    }
+
+    /// 报告当前作用域中声明后从未读取的变量，并清除这些声明记录
+    /// （出错恢复时丢弃的更深作用域的记录一并清除）
+    fn report_unused_variables(&mut self) {
+        let depth = self.symbol_table.scopes.len() - 1;
+        while let Some((name, declared_depth)) = self.declared_variables.pop() {
+            if declared_depth < depth {
+                self.declared_variables.push((name, declared_depth));
+                break;
+            }
+            if declared_depth == depth {
+                self.warn_if_unused(&name);
+            }
+        }
+    }
+
+    /// 当前作用域中的同名变量从未被读取时报告（需启用 lint）
+    fn warn_if_unused(&mut self, name: &str) {
+        if !self.lint_unused_variables || name.starts_with('_') {
+            return;
+        }
+        let unused = self.symbol_table.scopes.last()
+            .and_then(|scope| scope.get(name))
+            .is_some_and(|symbol| !symbol.used);
+        if unused {
+            self.warnings.push(TypeWarning::UnusedVariable { name: name.to_string() });
+        }
+    }
+
+    /// 声明变量前检查：遮蔽外层类型不同的绑定、同一作用域中被重新声明的旧变量未使用（需启用 lint）
+    fn check_variable_declaration(&mut self, name: &str, var_type: &Type) {
+        let depth = self.symbol_table.scopes.len() - 1;
+        if self.declared_variables.iter().any(|(declared, d)| declared == name && *d == depth) {
+            self.warn_if_unused(name);
+        }
+        self.declared_variables.push((name.to_string(), depth));
+
+        // 内置函数的遮蔽由 with_shadowed_builtin_lint 报告
+        if !self.lint_shadowed_variables || builtin_signatures().iter().any(|(builtin, _)| *builtin == name) {
+            return;
+        }
+        let outer = self.symbol_table.scopes[..depth].iter().rev()
+            .find_map(|scope| scope.get(name))
+            .map(|symbol| self.resolve_type(&symbol.symbol_type));
+        let inner = self.resolve_type(var_type);
+        if let Some(outer) = outer {
+            if outer != Type::Unknown && inner != Type::Unknown && outer != inner {
+                self.warnings.push(TypeWarning::ShadowedVariable { name: name.to_string(), outer, inner });
+            }
+        }
+    }
+
+    /// 在函数作用域弹出前报告当前作用域中从未被读取的参数
+    fn report_unused_parameters(&mut self, function: &str, parameters: &[Parameter]) {
+        if !self.lint_unused_parameters {
+            return;
+        }
+        for param in parameters {
+            if param.name.starts_with('_') {
+                continue;
+            }
+            let used = self.symbol_table.scopes.last()
+                .and_then(|scope| scope.get(&param.name))
+                .is_none_or(|symbol| symbol.used);
+            if !used {
+                self.warnings.push(TypeWarning::UnusedParameter {
+                    function: function.to_string(),
+                    parameter: param.name.clone(),
+                });
+            }
+        }
+    }
+
+    /// 若表达式把某个位置原样赋值给自身，返回该位置的源码写法
+    fn self_assignment_target(expr: &Expr) -> Option<String> {
+        let (target, value) = match expr {
+            Expr::Assign { name, value } => {
+                return match value.as_ref() {
+                    Expr::Identifier(source) if source == name => Some(name.clone()),
+                    _ => None,
+                };
+            }
+            Expr::IndexAssign { object, index, value } => {
+                (Expr::Index { object: object.clone(), index: index.clone() }, value)
+            }
+            Expr::FieldAssign { object, field, value, owner } => {
+                (Expr::FieldAccess { object: object.clone(), field: field.clone(), owner: owner.clone() }, value)
+            }
+            _ => return None,
+        };
+        if target == **value {
+            Self::place_source(&target)
+        } else {
+            None
+        }
+    }
+
+    /// 无副作用的位置表达式的源码写法，含调用等其他表达式时返回 None
+    fn place_source(expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Identifier(name) => Some(name.clone()),
+            Expr::Integer(value) => Some(value.to_string()),
+            Expr::Index { object, index } => {
+                Some(format!("{}[{}]", Self::place_source(object)?, Self::place_source(index)?))
+            }
+            Expr::FieldAccess { object, field, .. } => {
+                Some(format!("{}.{}", Self::place_source(object)?, field))
+            }
+            _ => None,
         }
     }
 
+    /// 类型检查过程中产生的警告
+    pub fn warnings(&self) -> &[TypeWarning] {
+        &self.warnings
+    }
+
+    /// 已检查表达式的类型覆盖率（Unknown 类型的表达式计为未覆盖）
+    pub fn coverage(&self) -> TypeCoverage {
+        self.coverage
+    }
+
+    /// 类型检查后全局作用域的符号及其推导类型
+    pub fn global_symbols(&self) -> Vec<SymbolInfo> {
+        self.symbol_table.global_symbols()
+    }
+
     /// 解析类型（将Named类型解析为实际类型）
     fn resolve_type(&self, t: &Type) -> Type {
+        self.resolve_type_guarded(t, &mut Vec::new())
+    }
+
+    /// `visiting` 记录正在展开的名字，再次遇到时保持 Named 不再展开（递归结构体、循环别名）
+    fn resolve_type_guarded(&self, t: &Type, visiting: &mut Vec<String>) -> Type {
         match t {
             Type::Named(name) => {
                 // 查找符号表中的类型别名或结构体定义
-                if let Some(symbol) = self.symbol_table.get(name) {
-                    // 递归解析，防止链式别名
-                    self.resolve_type(&symbol.symbol_type)
-                } else {
-                    // 如果找不到定义，保持原样（后续会报错）
-                    t.clone()
+                match self.symbol_table.get(name) {
+                    Some(symbol) if !visiting.contains(name) => {
+                        // 递归解析，防止链式别名
+                        visiting.push(name.clone());
+                        let resolved = self.resolve_type_guarded(&symbol.symbol_type, visiting);
+                        visiting.pop();
+                        resolved
+                    }
+                    // 找不到定义时保持原样（后续会报错）
+                    _ => t.clone(),
                 }
             }
             Type::Array(element_type) => {
                 // 递归解析数组元素类型
-                Type::Array(Box::new(self.resolve_type(element_type)))
+                Type::Array(Box::new(self.resolve_type_guarded(element_type, visiting)))
+            }
+            Type::FixedArray(element_type, length) => {
+                Type::FixedArray(Box::new(self.resolve_type_guarded(element_type, visiting)), *length)
+            }
+            Type::Tuple(elements) => {
+                Type::Tuple(elements.iter().map(|e| self.resolve_type_guarded(e, visiting)).collect())
             }
             Type::Function(func_type) => {
                 // 递归解析函数参数和返回类型
                 let params = func_type.params.iter()
-                    .map(|p| self.resolve_type(p))
+                    .map(|p| self.resolve_type_guarded(p, visiting))
                     .collect();
-                let return_type = Box::new(self.resolve_type(&func_type.return_type));
+                let return_type = Box::new(self.resolve_type_guarded(&func_type.return_type, visiting));
                 Type::Function(FunctionType { params, return_type })
             }
             Type::Struct(struct_type) => {
@@ -140,7 +881,8 @@ impl TypeChecker {
                 let fields = struct_type.fields.iter()
                     .map(|f| crate::ast::StructField {
                         name: f.name.clone(),
-                        field_type: self.resolve_type(&f.field_type),
+                        field_type: self.resolve_type_guarded(&f.field_type, visiting),
+                        is_mutable: f.is_mutable,
                     })
                     .collect();
                 Type::Struct(crate::ast::StructType {
@@ -153,12 +895,88 @@ impl TypeChecker {
         }
     }
 
+    /// 别名的目标类型是否（经由其他别名）引用别名自身；经过结构体的递归是允许的
+    fn alias_refers_to_itself(&self, alias: &str, t: &Type, visited: &mut HashSet<String>) -> bool {
+        match t {
+            Type::Named(name) if name == alias => true,
+            Type::Named(name) => {
+                visited.insert(name.clone())
+                    && self.symbol_table.get(name)
+                        .is_some_and(|symbol| self.alias_refers_to_itself(alias, &symbol.symbol_type, visited))
+            }
+            Type::Array(element_type) | Type::FixedArray(element_type, _) => {
+                self.alias_refers_to_itself(alias, element_type, visited)
+            }
+            Type::Tuple(elements) => elements.iter().any(|e| self.alias_refers_to_itself(alias, e, visited)),
+            Type::Function(func_type) => {
+                func_type.params.iter().any(|p| self.alias_refers_to_itself(alias, p, visited))
+                    || self.alias_refers_to_itself(alias, &func_type.return_type, visited)
+            }
+            _ => false,
+        }
+    }
+
     /// 检查程序
+    ///
+    /// 出错的语句不会中断检查，所有错误按出现顺序记录在 `errors()` 中，这里返回第一个
     pub fn check(&mut self, program: &Program) -> TypeResult<()> {
-        for stmt in &program.statements {
-            self.check_statement(stmt)?;
+        // 错误、警告和覆盖率只属于本次检查的程序，上一个程序的表达式地址可能被新的语法树复用
+        self.errors.clear();
+        self.warnings.clear();
+        self.coverage_seen.clear();
+        self.coverage = TypeCoverage::default();
+        self.check_statements(&program.statements);
+        self.report_unused_variables();
+        if let Some(threshold) = self.min_type_coverage {
+            if self.coverage.percentage() < threshold {
+                self.warnings.push(TypeWarning::LowTypeCoverage { coverage: self.coverage, threshold });
+            }
+        }
+        match self.errors.first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// 检查中发现的所有类型错误
+    pub fn errors(&self) -> &[TypeError] {
+        &self.errors
+    }
+
+    /// 逐条检查语句：出错时记录错误、恢复作用域等状态，继续检查后面的语句
+    /// （同一语句内的错误仍然立即中断）
+    fn check_statements(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            let scope_depth = self.symbol_table.scopes.len();
+            let loop_depth = self.loop_depth;
+            let return_type = self.current_function_return_type.clone();
+            let returns = self.inferred_return_types.clone();
+
+            if let Err(error) = self.check_statement(stmt) {
+                self.errors.push(error);
+                self.symbol_table.scopes.truncate(scope_depth);
+                self.declared_variables.retain(|(_, depth)| *depth < scope_depth);
+                self.loop_depth = loop_depth;
+                self.current_function_return_type = return_type;
+                self.inferred_return_types = returns;
+                self.expected_type = None;
+
+                // 声明出错的变量仍然定义（类型取注解、结构体字面量的类型或未知），避免之后的使用处连带报错
+                if let Stmt::VarDeclaration { name, mutable, type_annotation, initializer, .. } = stmt {
+                    let defined = self.symbol_table.scopes.last().is_some_and(|scope| scope.contains_key(name));
+                    if !defined {
+                        let literal_type = match initializer {
+                            Some(Expr::StructLiteral { struct_name, .. }) => {
+                                Some(self.resolve_type(&Type::Named(struct_name.clone())))
+                            }
+                            _ => None,
+                        };
+                        let var_type = type_annotation.clone().or(literal_type).unwrap_or(Type::Unknown);
+                        self.symbol_table.define(name.clone(), var_type, *mutable);
+                    }
+                }
+            }
         }
-        Ok(())
     }
 
     /// 检查语句
@@ -175,6 +993,9 @@ impl TypeChecker {
             }
 
             Stmt::TypeAlias { name, target_type } => {
+                if self.alias_refers_to_itself(name, target_type, &mut HashSet::new()) {
+                    return Err(TypeError::CyclicTypeAlias(name.clone()));
+                }
                 // 注册类型别名
                 self.symbol_table.define(name.clone(), target_type.clone(), false);
                 Ok(())
@@ -202,42 +1023,72 @@ impl TypeChecker {
                         method.name.clone(),
                         MethodSignature {
                             params: param_types.clone(),
+                            param_names: method.parameters.iter().map(|p| p.name.clone()).collect(),
                             return_type: ret_type.clone(),
+                            is_static: method.is_static,
                         },
                     );
 
-                    // 检查方法体
+                    // 检查方法体（未标注返回类型时像函数一样由 return 推断）
                     self.symbol_table.push_scope();
-                    self.current_function_return_type = Some(ret_type);
+                    self.current_function_return_type = Some(match method.return_type {
+                        Some(_) => ret_type.clone(),
+                        None => Type::Unknown,
+                    });
+                    let outer_returns = std::mem::take(&mut self.inferred_return_types);
 
-                    // 添加 self 参数到作用域
-                    if let Some(symbol) = self.symbol_table.get(type_name) {
+                    // 添加 self 参数到作用域（静态方法没有 self）
+                    if let Some(symbol) = self.symbol_table.get(type_name).filter(|_| !method.is_static) {
                         self.symbol_table.define("self".to_string(), symbol.symbol_type.clone(), false);
                     }
 
                     // 添加其他参数到作用域
                     for param in &method.parameters {
                         let param_type = param.type_annotation.clone().unwrap_or(Type::Unknown);
+                        self.check_shadowed_builtin(&param.name);
                         self.symbol_table.define(param.name.clone(), param_type, false);
                     }
 
                     // 检查方法体
-                    for stmt in &method.body {
-                        self.check_statement(stmt)?;
+                    self.check_statements(&method.body);
+                    let method_name = format!("{}.{}", type_name, method.name);
+                    if let Some(error) = self.missing_return(&method_name, &ret_type, &method.body) {
+                        return Err(error);
                     }
 
-                    self.symbol_table.pop_scope();
+                    self.report_unused_parameters(&method_name, &method.parameters);
+                    self.pop_scope();
                     self.current_function_return_type = None;
+
+                    // 没有带值的 return 时保持 void；任一 return 的类型未知时为 Unknown
+                    let returns = std::mem::replace(&mut self.inferred_return_types, outer_returns);
+                    if method.return_type.is_none() && !returns.is_empty() {
+                        let mut inferred = self.unify_return_types(&returns).map_err(|(expected, found)| {
+                            TypeError::ReturnTypeMismatch { expected, found, function: method_name.clone() }
+                        })?;
+                        if returns.contains(&Type::Unknown) {
+                            inferred = Type::Unknown;
+                        }
+                        if let Some(signature) = method_map.get_mut(&method.name) {
+                            signature.return_type = inferred;
+                        }
+                    }
                 }
 
-                // 注册方法到方法表
-                self.methods.insert(type_name.clone(), method_map);
+                // 注册方法到方法表（同一类型可以有多个 impl 块）
+                self.methods.entry(type_name.clone()).or_default().extend(method_map);
 
                 Ok(())
             }
 
             Stmt::Expression(expr) => {
+                self.void_allowed = true;
                 self.infer_type(expr)?;
+                if self.lint_self_assignment {
+                    if let Some(target) = Self::self_assignment_target(expr) {
+                        self.warnings.push(TypeWarning::SelfAssignment { target });
+                    }
+                }
                 Ok(())
             }
 
@@ -246,8 +1097,15 @@ impl TypeChecker {
                 mutable,
                 type_annotation,
                 initializer,
+                is_const,
             } => {
+                if *is_const && initializer.is_none() {
+                    return Err(TypeError::ConstWithoutInitializer(name.clone()));
+                }
+
                 let actual_type = if let Some(init) = initializer {
+                    // 注解的元素类型传给空数组字面量（`let xs: [int] = [];`）
+                    self.expected_type = type_annotation.clone();
                     self.infer_type(init)?
                 } else {
                     Type::Null
@@ -259,7 +1117,11 @@ impl TypeChecker {
                     let resolved_actual = self.resolve_type(&actual_type);
 
                     // 检查类型注解和初始化值是否匹配
-                    if let Some(_init) = initializer {
+                    if let Some(init) = initializer {
+                        let location = format!("variable declaration '{}'", name);
+                        if let Some(err) = Self::fixed_array_literal_error(&resolved_annotated, init, &location) {
+                            return Err(err);
+                        }
                         if !resolved_annotated.is_compatible_with(&resolved_actual) && resolved_actual != Type::Unknown {
                             return Err(TypeError::TypeMismatch {
                                 expected: resolved_annotated.clone(),
@@ -274,6 +1136,8 @@ impl TypeChecker {
                     actual_type
                 };
 
+                self.check_shadowed_builtin(name);
+                self.check_variable_declaration(name, &var_type);
                 self.symbol_table.define(name.clone(), var_type, *mutable);
                 Ok(())
             }
@@ -283,6 +1147,7 @@ impl TypeChecker {
                 parameters,
                 return_type,
                 body,
+                ..
             } => {
                 // 构建函数类型
                 let param_types: Vec<Type> = parameters
@@ -291,6 +1156,7 @@ impl TypeChecker {
                     .collect();
 
                 let ret_type = return_type.clone().unwrap_or(Type::Unknown);
+                let param_names: Vec<String> = parameters.iter().map(|p| p.name.clone()).collect();
 
                 let function_type = Type::Function(FunctionType {
                     params: param_types.clone(),
@@ -298,39 +1164,76 @@ impl TypeChecker {
                 });
 
                 // 注册函数
-                self.symbol_table.define(name.clone(), function_type, false);
+                self.check_shadowed_builtin(name);
+                self.symbol_table.define_function(name.clone(), function_type, param_names.clone());
+                if self.lint_call_consistency && param_types.contains(&Type::Unknown) {
+                    self.call_argument_types.insert(name.clone(), vec![None; param_types.len()]);
+                } else {
+                    self.call_argument_types.remove(name);
+                }
 
-                // 检查函数体
+                // 检查函数体（嵌套函数有自己的 return 列表）
                 self.symbol_table.push_scope();
-                self.current_function_return_type = Some(ret_type);
+                self.current_function_return_type = Some(ret_type.clone());
+                let outer_returns = std::mem::take(&mut self.inferred_return_types);
 
                 // 添加参数到作用域
                 for param in parameters {
                     let param_type = param.type_annotation.clone().unwrap_or(Type::Unknown);
+                    self.check_shadowed_builtin(&param.name);
                     self.symbol_table.define(param.name.clone(), param_type, false);
                 }
 
                 // 检查函数体语句
-                for stmt in body {
-                    self.check_statement(stmt)?;
+                self.check_statements(body);
+                if let Some(error) = self.missing_return(name, &ret_type, body) {
+                    return Err(error);
                 }
 
+                self.report_unused_parameters(name, parameters);
                 self.current_function_return_type = None;
-                self.symbol_table.pop_scope();
+                self.pop_scope();
+
+                // 未标注返回类型时统一所有 return 的类型，冲突则报错；
+                // 任一 return 的类型未知时保守地保持 Unknown
+                let returns = std::mem::replace(&mut self.inferred_return_types, outer_returns);
+                if return_type.is_none() && !returns.is_empty() {
+                    let inferred = self.unify_return_types(&returns).map_err(|(expected, found)| {
+                        TypeError::ReturnTypeMismatch { expected, found, function: name.clone() }
+                    })?;
+                    if !returns.contains(&Type::Unknown) {
+                        let function_type = Type::Function(FunctionType {
+                            params: param_types,
+                            return_type: Box::new(inferred),
+                        });
+                        self.symbol_table.define_function(name.clone(), function_type, param_names);
+                    }
+                }
                 Ok(())
             }
 
             Stmt::Return { value } => {
                 let return_type = if let Some(expr) = value {
+                    // void 函数中 `return f();` 返回的仍是 void，返回类型由下面的检查负责
+                    self.void_allowed = true;
                     self.infer_type(expr)?
                 } else {
                     Type::Void
                 };
 
-                if let Some(expected_type) = &self.current_function_return_type {
+                if self.current_function_return_type == Some(Type::Unknown) {
+                    // 未标注返回类型的函数：记录下来，函数体检查完后统一
+                    self.inferred_return_types.push(return_type);
+                } else if let Some(expected_type) = &self.current_function_return_type {
                     let resolved_expected = self.resolve_type(expected_type);
                     let resolved_return = self.resolve_type(&return_type);
 
+                    if let Some(err) = value.as_ref().and_then(|expr| {
+                        Self::fixed_array_literal_error(&resolved_expected, expr, "return value")
+                    }) {
+                        return Err(err);
+                    }
+
                     if resolved_expected != Type::Unknown
                         && resolved_return != Type::Unknown
                         && !resolved_expected.is_compatible_with(&resolved_return) {
@@ -349,7 +1252,11 @@ impl TypeChecker {
                 condition,
                 then_branch,
                 else_branch,
+                ..
             } => {
+                if let Some(error) = Self::assignment_in_condition(condition, "if condition") {
+                    return Err(error);
+                }
                 let cond_type = self.infer_type(condition)?;
                 if cond_type != Type::Bool && cond_type != Type::Unknown {
                     return Err(TypeError::TypeMismatch {
@@ -360,26 +1267,50 @@ impl TypeChecker {
                 }
 
                 self.symbol_table.push_scope();
-                for stmt in then_branch {
-                    self.check_statement(stmt)?;
-                }
-                self.symbol_table.pop_scope();
+                self.check_statements(then_branch);
+                self.pop_scope();
 
                 if let Some(else_stmts) = else_branch {
                     self.symbol_table.push_scope();
-                    for stmt in else_stmts {
-                        self.check_statement(stmt)?;
-                    }
-                    self.symbol_table.pop_scope();
+                    self.check_statements(else_stmts);
+                    self.pop_scope();
                 }
 
                 Ok(())
             }
 
-            Stmt::While { condition, body } => {
-                let cond_type = self.infer_type(condition)?;
-                if cond_type != Type::Bool && cond_type != Type::Unknown {
-                    return Err(TypeError::TypeMismatch {
+            Stmt::Match { scrutinee, arms, default, .. } => {
+                let scrutinee_type = self.infer_type(scrutinee)?;
+                for (pattern, _) in arms {
+                    let pattern_type = self.infer_type(pattern)?;
+                    if pattern_type != scrutinee_type
+                        && pattern_type != Type::Unknown
+                        && scrutinee_type != Type::Unknown
+                    {
+                        return Err(TypeError::TypeMismatch {
+                            expected: scrutinee_type,
+                            found: pattern_type,
+                            location: "match pattern".to_string(),
+                        });
+                    }
+                }
+
+                for body in arms.iter().map(|(_, body)| body).chain(default) {
+                    self.symbol_table.push_scope();
+                    self.check_statements(body);
+                    self.pop_scope();
+                }
+
+                Ok(())
+            }
+
+            Stmt::While { condition, body, .. } => {
+                if let Some(error) = Self::assignment_in_condition(condition, "while condition") {
+                    return Err(error);
+                }
+                let cond_type = self.infer_type(condition)?;
+                if cond_type != Type::Bool && cond_type != Type::Unknown {
+                    return Err(TypeError::TypeMismatch {
                         expected: Type::Bool,
                         found: cond_type,
                         location: "while condition".to_string(),
@@ -388,10 +1319,8 @@ impl TypeChecker {
 
                 self.loop_depth += 1;
                 self.symbol_table.push_scope();
-                for stmt in body {
-                    self.check_statement(stmt)?;
-                }
-                self.symbol_table.pop_scope();
+                self.check_statements(body);
+                self.pop_scope();
                 self.loop_depth -= 1;
 
                 Ok(())
@@ -402,6 +1331,7 @@ impl TypeChecker {
                 start,
                 end,
                 body,
+                ..
             } => {
                 let start_type = self.infer_type(start)?;
                 let end_type = self.infer_type(end)?;
@@ -424,13 +1354,44 @@ impl TypeChecker {
 
                 self.loop_depth += 1;
                 self.symbol_table.push_scope();
-                self.symbol_table.define(variable.clone(), Type::Int, true);
+                self.check_shadowed_builtin(variable);
+                self.symbol_table.define_loop_counter(variable.clone());
 
-                for stmt in body {
-                    self.check_statement(stmt)?;
-                }
+                self.check_statements(body);
 
-                self.symbol_table.pop_scope();
+                self.pop_scope();
+                self.loop_depth -= 1;
+                Ok(())
+            }
+
+            Stmt::ForIn {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                let iterable_type = self.infer_type(iterable)?;
+                let element_type = match self.resolve_type(&iterable_type) {
+                    Type::Array(element_type) | Type::FixedArray(element_type, _) => *element_type,
+                    Type::Range => Type::Int,
+                    Type::Unknown => Type::Unknown,
+                    other => {
+                        return Err(TypeError::TypeMismatch {
+                            expected: Type::Range,
+                            found: other,
+                            location: "for loop iterable".to_string(),
+                        });
+                    }
+                };
+
+                self.loop_depth += 1;
+                self.symbol_table.push_scope();
+                self.check_shadowed_builtin(variable);
+                self.symbol_table.define(variable.clone(), element_type, false);
+
+                self.check_statements(body);
+
+                self.pop_scope();
                 self.loop_depth -= 1;
                 Ok(())
             }
@@ -454,20 +1415,20 @@ impl TypeChecker {
                 Ok(())
             }
 
-            Stmt::Block { statements } => {
+            Stmt::Block { statements, .. } => {
                 self.symbol_table.push_scope();
-                for stmt in statements {
-                    self.check_statement(stmt)?;
-                }
-                self.symbol_table.pop_scope();
+                self.check_statements(statements);
+                self.pop_scope();
                 Ok(())
             }
         }
     }
 
-    /// 推断表达式类型
+    /// 推断表达式类型，并记入类型覆盖率
     fn infer_type(&mut self, expr: &Expr) -> TypeResult<Type> {
-        match expr {
+        let expected = self.expected_type.take();
+        let allow_void = std::mem::take(&mut self.void_allowed);
+        let inferred = match expr {
             Expr::StructLiteral { struct_name, fields } => {
                 // 查找结构体类型
                 if let Some(symbol) = self.symbol_table.get(struct_name) {
@@ -485,6 +1446,15 @@ impl TypeChecker {
                             });
                         }
 
+                        // 数量相同但有重复字段时，必然有声明的字段没有给出
+                        if let Some(missing) = struct_def.fields.iter().find(|f| fields.iter().all(|(name, _)| *name != f.name)) {
+                            return Err(TypeError::TypeMismatch {
+                                expected: struct_type.clone(),
+                                found: Type::Unknown,
+                                location: format!("struct {} is missing field {}", struct_name, missing.name),
+                            });
+                        }
+
                         // 检查每个字段的类型
                         for (field_name, field_expr) in fields {
                             let field_type = self.infer_type(field_expr)?;
@@ -493,6 +1463,10 @@ impl TypeChecker {
                             let field_def = struct_def.fields.iter().find(|f| &f.name == field_name);
                             if let Some(def) = field_def {
                                 let expected_type = self.resolve_type(&def.field_type);
+                                let location = format!("field {} in struct {}", field_name, struct_name);
+                                if let Some(err) = Self::fixed_array_literal_error(&expected_type, field_expr, &location) {
+                                    return Err(err);
+                                }
                                 if !field_type.is_compatible_with(&expected_type) {
                                     return Err(TypeError::TypeMismatch {
                                         expected: expected_type,
@@ -514,49 +1488,69 @@ impl TypeChecker {
                 }
             }
 
-            Expr::FieldAccess { object, field } => {
+            Expr::FieldAccess { object, field, owner } => {
+                // 返回类型等处的结构体写作 Named，需要先解析
                 let obj_type = self.infer_type(object)?;
+                let obj_type = self.resolve_type(&obj_type);
                 match obj_type {
                     Type::Struct(struct_type) => {
-                        for f in &struct_type.fields {
-                            if &f.name == field {
-                                return Ok(f.field_type.clone());
-                            }
-                        }
-                        Err(TypeError::UndefinedVariable(format!("Field {} not found", field)))
+                        // 标注结构体名，编译器据此确定字段下标
+                        owner.set(struct_type.name.clone());
+                        struct_type.fields.iter()
+                            .find(|f| &f.name == field)
+                            .map(|f| f.field_type.clone())
+                            .ok_or_else(|| TypeError::UndefinedVariable(format!("Field {} not found", field)))
                     }
+                    // 元组元素按位置取类型，`t.0` 的字段名是下标
+                    Type::Tuple(ref elements) => field.parse::<usize>().ok()
+                        .and_then(|i| elements.get(i))
+                        .cloned()
+                        .ok_or_else(|| TypeError::UndefinedVariable(format!("Element {} not found in tuple {}", field, obj_type))),
                     _ => Err(TypeError::InvalidOperation {
-                        operator: "field access".to_string(),
+                        operator: ".".to_string(),
                         left_type: obj_type,
                         right_type: Type::Unknown,
                     }),
                 }
             }
 
-            Expr::FieldAssign { object, field, value } => {
+            Expr::FieldAssign { object, field, value, owner } => {
                 let obj_type = self.infer_type(object)?;
+                let obj_type = self.resolve_type(&obj_type);
                 let val_type = self.infer_type(value)?;
                 match obj_type {
                     Type::Struct(struct_type) => {
-                        for f in &struct_type.fields {
-                            if &f.name == field {
-                                let resolved_field = self.resolve_type(&f.field_type);
-                                let resolved_val = self.resolve_type(&val_type);
+                        owner.set(struct_type.name.clone());
+                        let f = struct_type.fields.iter()
+                            .find(|f| &f.name == field)
+                            .ok_or_else(|| TypeError::UndefinedVariable(format!("Field {} not found", field)))?;
+
+                        // let 字段只能在结构体字面量中初始化
+                        if !f.is_mutable {
+                            return Err(TypeError::ReadonlyFieldAssignment {
+                                struct_name: struct_type.name.clone(),
+                                field: field.clone(),
+                            });
+                        }
 
-                                if !resolved_field.is_compatible_with(&resolved_val) && resolved_val != Type::Unknown {
-                                    return Err(TypeError::TypeMismatch {
-                                        expected: resolved_field,
-                                        found: resolved_val,
-                                        location: format!("field assignment to {}", field),
-                                    });
-                                }
-                                return Ok(val_type);
-                            }
+                        let resolved_field = self.resolve_type(&f.field_type);
+                        let resolved_val = self.resolve_type(&val_type);
+
+                        let location = format!("field assignment to {}", field);
+                        if let Some(err) = Self::fixed_array_literal_error(&resolved_field, value, &location) {
+                            return Err(err);
                         }
-                        Err(TypeError::UndefinedVariable(format!("Field {} not found", field)))
+                        if !resolved_field.is_compatible_with(&resolved_val) && resolved_val != Type::Unknown {
+                            return Err(TypeError::TypeMismatch {
+                                expected: resolved_field,
+                                found: resolved_val,
+                                location: format!("field assignment to {}", field),
+                            });
+                        }
+                        Ok(val_type)
                     }
                     _ => Err(TypeError::InvalidOperation {
-                        operator: "field assignment".to_string(),
+                        operator: ".".to_string(),
                         left_type: obj_type,
                         right_type: val_type,
                     }),
@@ -570,6 +1564,7 @@ impl TypeChecker {
             Expr::Char(_) => Ok(Type::Char),
 
             Expr::Identifier(name) => {
+                self.symbol_table.mark_used(name);
                 if let Some(symbol) = self.symbol_table.get(name) {
                     Ok(symbol.symbol_type.clone())
                 } else {
@@ -585,6 +1580,13 @@ impl TypeChecker {
                 let left_type = self.infer_type(left)?;
                 let right_type = self.infer_type(right)?;
 
+                // 除数是常量零时在编译期报告，而不是留到运行时
+                if matches!(operator, BinaryOp::Divide | BinaryOp::Modulo) && Self::is_constant_zero(right) {
+                    return Err(TypeError::DivisionByZero {
+                        operator: operator.symbol().to_string(),
+                    });
+                }
+
                 match operator {
                     BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide => {
                         // 允许Unknown类型参与运算
@@ -604,7 +1606,7 @@ impl TypeChecker {
                             Ok(Type::String)
                         } else {
                             Err(TypeError::InvalidOperation {
-                                operator: format!("{:?}", operator),
+                                operator: operator.symbol().to_string(),
                                 left_type,
                                 right_type,
                             })
@@ -618,19 +1620,62 @@ impl TypeChecker {
                             Ok(Type::Int)
                         } else {
                             Err(TypeError::InvalidOperation {
-                                operator: "modulo".to_string(),
+                                operator: operator.symbol().to_string(),
                                 left_type,
                                 right_type,
                             })
                         }
                     }
 
-                    BinaryOp::Equal
-                    | BinaryOp::NotEqual
-                    | BinaryOp::Less
+                    BinaryOp::Equal | BinaryOp::NotEqual => {
+                        let left_type = self.resolve_type(&left_type);
+                        let right_type = self.resolve_type(&right_type);
+
+                        if left_type == Type::Unknown
+                            || right_type == Type::Unknown
+                            || left_type == Type::Null
+                            || right_type == Type::Null
+                        {
+                            Ok(Type::Bool)
+                        } else if left_type.is_numeric() && right_type.is_numeric() {
+                            if left_type != right_type {
+                                self.warnings.push(TypeWarning::MixedNumericEquality {
+                                    operator: operator.symbol().to_string(),
+                                    left_type,
+                                    right_type,
+                                });
+                            }
+                            Ok(Type::Bool)
+                        } else if left_type.is_compatible_with(&right_type) {
+                            Ok(Type::Bool)
+                        } else {
+                            Err(TypeError::InvalidOperation {
+                                operator: operator.symbol().to_string(),
+                                left_type,
+                                right_type,
+                            })
+                        }
+                    }
+
+                    // 数字之间或字符之间（按码位）才能比较大小
+                    BinaryOp::Less
                     | BinaryOp::LessEqual
                     | BinaryOp::Greater
-                    | BinaryOp::GreaterEqual => Ok(Type::Bool),
+                    | BinaryOp::GreaterEqual => {
+                        let comparable = left_type == Type::Unknown
+                            || right_type == Type::Unknown
+                            || (left_type.is_numeric() && right_type.is_numeric())
+                            || (left_type == Type::Char && right_type == Type::Char);
+                        if comparable {
+                            Ok(Type::Bool)
+                        } else {
+                            Err(TypeError::InvalidOperation {
+                                operator: operator.symbol().to_string(),
+                                left_type,
+                                right_type,
+                            })
+                        }
+                    }
 
                     BinaryOp::And | BinaryOp::Or => {
                         if left_type == Type::Unknown || right_type == Type::Unknown {
@@ -639,7 +1684,7 @@ impl TypeChecker {
                             Ok(Type::Bool)
                         } else {
                             Err(TypeError::InvalidOperation {
-                                operator: format!("{:?}", operator),
+                                operator: operator.symbol().to_string(),
                                 left_type,
                                 right_type,
                             })
@@ -663,14 +1708,15 @@ impl TypeChecker {
                             })
                         }
                     }
-                    UnaryOp::Negate => {
+                    UnaryOp::Negate | UnaryOp::Plus => {
                         if operand_type.is_numeric() {
                             Ok(operand_type)
                         } else {
+                            let name = if *operator == UnaryOp::Plus { "plus" } else { "negate" };
                             Err(TypeError::TypeMismatch {
                                 expected: Type::Int,
                                 found: operand_type,
-                                location: "unary negate operator".to_string(),
+                                location: format!("unary {} operator", name),
                             })
                         }
                     }
@@ -678,6 +1724,7 @@ impl TypeChecker {
             }
 
             Expr::Assign { name, value } => {
+                self.expected_type = self.symbol_table.get(name).map(|symbol| symbol.symbol_type.clone());
                 let value_type = self.infer_type(value)?;
 
                 if let Some(symbol) = self.symbol_table.get(name) {
@@ -691,6 +1738,20 @@ impl TypeChecker {
                     let resolved_symbol = self.resolve_type(&symbol.symbol_type);
                     let resolved_value = self.resolve_type(&value_type);
 
+                    let location = format!("assignment to variable '{}'", name);
+                    if let Some(err) = Self::fixed_array_literal_error(&resolved_symbol, value, &location) {
+                        return Err(err);
+                    }
+
+                    // 循环变量之后还要递增并与结束值比较，float 和类型未知的值都不接受
+                    if symbol.loop_counter && resolved_value != Type::Int {
+                        return Err(TypeError::TypeMismatch {
+                            expected: Type::Int,
+                            found: resolved_value,
+                            location: format!("assignment to loop variable '{}'", name),
+                        });
+                    }
+
                     // 只有当类型都不是Unknown时才检查类型兼容性
                     if resolved_symbol != Type::Unknown
                         && resolved_value != Type::Unknown
@@ -708,64 +1769,35 @@ impl TypeChecker {
                 }
             }
 
-            Expr::Call { callee, arguments } => {
-                // 获取被调用函数的类型
-                if let Expr::Identifier(func_name) = callee.as_ref() {
-                    if let Some(symbol) = self.symbol_table.get(func_name) {
-                        if let Type::Function(func_type) = &symbol.symbol_type {
-                            // 检查参数数量
-                            if func_type.params.len() != arguments.len() {
-                                return Err(TypeError::ArgumentCountMismatch {
-                                    expected: func_type.params.len(),
-                                    found: arguments.len(),
-                                    function: func_name.clone(),
-                                });
-                            }
-
-                            // 克隆函数类型以避免借用冲突
-                            let params = func_type.params.clone();
-                            let return_type = *func_type.return_type.clone();
-
-                            // 检查每个参数的类型
-                            for (i, (param_type, arg)) in
-                                params.iter().zip(arguments.iter()).enumerate()
-                            {
-                                let arg_type = self.infer_type(arg)?;
-                                let resolved_param = self.resolve_type(param_type);
-                                let resolved_arg = self.resolve_type(&arg_type);
-
-                                if !resolved_param.is_compatible_with(&resolved_arg) {
-                                    return Err(TypeError::ArgumentTypeMismatch {
-                                        expected: resolved_param,
-                                        found: resolved_arg,
-                                        argument: i + 1,
-                                        function: func_name.clone(),
-                                    });
-                                }
-                            }
+            Expr::PostfixUpdate { name, operator } => {
+                self.symbol_table.mark_used(name);
+                let Some(symbol) = self.symbol_table.get(name) else {
+                    return Err(TypeError::UndefinedVariable(name.clone()));
+                };
+                if !symbol.is_mutable {
+                    return Err(TypeError::ImmutableAssignment {
+                        variable: name.clone(),
+                    });
+                }
 
-                            // 返回函数的返回类型
-                            Ok(return_type)
-                        } else {
-                            Err(TypeError::TypeMismatch {
-                                expected: Type::Function(FunctionType {
-                                    params: vec![],
-                                    return_type: Box::new(Type::Unknown),
-                                }),
-                                found: symbol.symbol_type.clone(),
-                                location: format!("function call '{}'", func_name),
-                            })
-                        }
-                    } else {
-                        Err(TypeError::UndefinedFunction(func_name.clone()))
-                    }
-                } else {
-                    // 对于非标识符调用（如高阶函数），返回Unknown
-                    Ok(Type::Unknown)
+                // 只有数值变量可以自增/自减，值是变量原来的类型
+                let var_type = self.resolve_type(&symbol.symbol_type);
+                match var_type {
+                    Type::Int | Type::Float | Type::Unknown => Ok(var_type),
+                    _ => Err(TypeError::InvalidOperation {
+                        operator: if *operator == BinaryOp::Add { "++" } else { "--" }.to_string(),
+                        left_type: var_type,
+                        right_type: Type::Unknown,
+                    }),
                 }
             }
 
+            Expr::Call { callee, arguments } => self.infer_call(callee, arguments),
+
             Expr::MethodCall { object, method, arguments } => {
+                // 接收者是结构体类型名本身时调用静态方法
+                let static_call = self.struct_type_name(object).is_some();
+
                 // 获取对象的类型
                 let obj_type = self.infer_type(object)?;
                 let obj_type = self.resolve_type(&obj_type);
@@ -789,6 +1821,14 @@ impl TypeChecker {
                     .and_then(|type_methods| type_methods.get(method))
                     .cloned()
                     .ok_or_else(|| TypeError::UndefinedFunction(format!("Method {} not found on type {}", method, type_name)))?;
+                if method_sig.is_static != static_call {
+                    let message = if method_sig.is_static {
+                        format!("Method {} on type {} is static; call it as {}.{}()", method, type_name, type_name, method)
+                    } else {
+                        format!("Method {} on type {} needs an instance, not the type name", method, type_name)
+                    };
+                    return Err(TypeError::UndefinedFunction(message));
+                }
 
                 // 检查参数数量
                 if method_sig.params.len() != arguments.len() {
@@ -805,11 +1845,16 @@ impl TypeChecker {
                     let resolved_param = self.resolve_type(param_type);
                     let resolved_arg = self.resolve_type(&arg_type);
 
+                    let location = format!("argument {} of '{}.{}'", i + 1, type_name, method);
+                    if let Some(err) = Self::fixed_array_literal_error(&resolved_param, arg, &location) {
+                        return Err(err);
+                    }
                     if !resolved_param.is_compatible_with(&resolved_arg) && resolved_arg != Type::Unknown {
                         return Err(TypeError::ArgumentTypeMismatch {
-                            expected: resolved_param,
-                            found: resolved_arg,
+                            expected: Box::new(resolved_param),
+                            found: Box::new(resolved_arg),
                             argument: i + 1,
+                            parameter: method_sig.param_names.get(i).cloned(),
                             function: format!("{}.{}", type_name, method),
                         });
                     }
@@ -820,14 +1865,20 @@ impl TypeChecker {
             }
 
             Expr::Array { elements } => {
+                // 上下文期望的元素类型，嵌套的数组字面量（`[[], [1]]`）逐层传递
+                let expected_element = expected
+                    .and_then(|t| self.resolve_type(&t).get_element_type().cloned());
+
                 if elements.is_empty() {
-                    // 空数组需要类型注解，这里返回Unknown
-                    Ok(Type::Unknown)
+                    // 空数组的元素类型只能来自类型注解，没有时返回Unknown
+                    Ok(expected_element.map_or(Type::Unknown, |element| Type::Array(Box::new(element))))
                 } else {
                     // 推断数组元素类型（所有元素必须同类型）
+                    self.expected_type = expected_element.clone();
                     let first_type = self.infer_type(&elements[0])?;
                     
                     for elem in elements.iter().skip(1) {
+                        self.expected_type = expected_element.clone();
                         let elem_type = self.infer_type(elem)?;
                         // 数组要求严格的类型匹配，不允许类型自动转换
                         if first_type != elem_type && elem_type != Type::Unknown && first_type != Type::Unknown {
@@ -843,8 +1894,18 @@ impl TypeChecker {
                 }
             }
 
+            Expr::Tuple(elements) => {
+                let mut element_types = Vec::with_capacity(elements.len());
+                for element in elements {
+                    element_types.push(self.infer_type(element)?);
+                }
+                Ok(Type::Tuple(element_types))
+            }
+
             Expr::Index { object, index } => {
+                // 先解析别名，多维索引（`matrix[i][j]`）中每一层的元素类型都可能是别名
                 let obj_type = self.infer_type(object)?;
+                let obj_type = self.resolve_type(&obj_type);
                 let idx_type = self.infer_type(index)?;
                 
                 // 索引必须是整数
@@ -856,16 +1917,67 @@ impl TypeChecker {
                     });
                 }
                 
-                // 返回数组元素类型
+                if let Some(err) = self.constant_index_error(&obj_type, index) {
+                    return Err(err);
+                }
+
+                // 返回数组元素类型（范围的元素为int，字符串的元素为char）
                 if let Some(element_type) = obj_type.get_element_type() {
                     Ok(element_type.clone())
+                } else if obj_type == Type::Range {
+                    Ok(Type::Int)
+                } else if obj_type == Type::String {
+                    Ok(Type::Char)
                 } else {
                     Ok(Type::Unknown)
                 }
             }
+
+            Expr::If { condition, then_expr, else_expr } => {
+                if let Some(error) = Self::assignment_in_condition(condition, "if condition") {
+                    return Err(error);
+                }
+                let cond_type = self.infer_type(condition)?;
+                if cond_type != Type::Bool && cond_type != Type::Unknown {
+                    return Err(TypeError::TypeMismatch {
+                        expected: Type::Bool,
+                        found: cond_type,
+                        location: "if condition".to_string(),
+                    });
+                }
+
+                // 两个分支需要有共同类型：int 与 float 混合时结果为 float；
+                // 整个 if 表达式的值被丢弃时分支也可以是 void 调用
+                self.void_allowed = allow_void;
+                let then_type = self.infer_type(then_expr)?;
+                self.void_allowed = allow_void;
+                let else_type = self.infer_type(else_expr)?;
+                self.common_type(&then_type, &else_type).ok_or(TypeError::TypeMismatch {
+                    expected: then_type,
+                    found: else_type,
+                    location: "else branch of if expression".to_string(),
+                })
+            }
+
+            Expr::Range { start, end, step, .. } => {
+                let bounds = [(start, "range start"), (end, "range end")].into_iter()
+                    .chain(step.iter().map(|step| (step, "range step")));
+                for (bound, location) in bounds {
+                    let bound_type = self.infer_type(bound)?;
+                    if bound_type != Type::Int && bound_type != Type::Unknown {
+                        return Err(TypeError::TypeMismatch {
+                            expected: Type::Int,
+                            found: bound_type,
+                            location: location.to_string(),
+                        });
+                    }
+                }
+                Ok(Type::Range)
+            }
             
             Expr::IndexAssign { object, index, value } => {
                 let obj_type = self.infer_type(object)?;
+                let obj_type = self.resolve_type(&obj_type);
                 let idx_type = self.infer_type(index)?;
                 let val_type = self.infer_type(value)?;
                 
@@ -878,6 +1990,10 @@ impl TypeChecker {
                     });
                 }
                 
+                if let Some(err) = self.constant_index_error(&obj_type, index) {
+                    return Err(err);
+                }
+
                 // 值类型必须与数组元素类型兼容
                 if let Some(element_type) = obj_type.get_element_type() {
                     let resolved_element = self.resolve_type(element_type);
@@ -890,10 +2006,130 @@ impl TypeChecker {
                             location: "array element assignment".to_string(),
                         });
                     }
+                } else if !matches!(obj_type, Type::Unknown | Type::Named(_)) {
+                    // 只有数组的元素可以赋值（字符串不可变，元组元素也不能赋值）
+                    return Err(TypeError::InvalidOperation {
+                        operator: "[]=".to_string(),
+                        left_type: obj_type,
+                        right_type: val_type,
+                    });
                 }
                 
                 Ok(val_type)
             }
+        }?;
+
+        if !allow_void
+            && matches!(expr, Expr::Call { .. } | Expr::MethodCall { .. })
+            && self.resolve_type(&inferred) == Type::Void
+        {
+            return Err(TypeError::VoidValueUsed { location: Self::void_call_location(expr) });
+        }
+
+        // 每个表达式只按第一次推导的结果统计一次
+        if self.coverage_seen.insert(expr as *const Expr as usize) {
+            self.coverage.total += 1;
+            if self.resolve_type(&inferred) != Type::Unknown {
+                self.coverage.typed += 1;
+            }
+        }
+        Ok(inferred)
+    }
+
+    /// 推导函数调用的类型并检查参数；被调用者类型未知时只推导参数
+    fn infer_call(&mut self, callee: &Expr, arguments: &[Expr]) -> TypeResult<Type> {
+        // 确定被调用的函数类型：具名函数（含函数类型的参数和变量），或类型为函数的任意表达式（如 `fs[0](x)`、`make()(x)`）
+        let (func_type, function, param_names, builtin) = if let Expr::Identifier(func_name) = callee {
+            self.symbol_table.mark_used(func_name);
+            let Some(symbol) = self.symbol_table.get(func_name) else {
+                return Err(TypeError::UndefinedFunction(func_name.clone()));
+            };
+            match &symbol.symbol_type {
+                Type::Function(func_type) => {
+                    (func_type.clone(), func_name.clone(), symbol.parameter_names.clone(), symbol.builtin)
+                }
+                // 未标注类型的参数或变量，调用时无法检查
+                Type::Unknown => {
+                    for arg in arguments {
+                        self.infer_type(arg)?;
+                    }
+                    return Ok(Type::Unknown);
+                }
+                other => {
+                    return Err(TypeError::TypeMismatch {
+                        expected: Type::Function(FunctionType {
+                            params: vec![],
+                            return_type: Box::new(Type::Unknown),
+                        }),
+                        found: other.clone(),
+                        location: format!("function call '{}'", func_name),
+                    })
+                }
+            }
+        } else {
+            let callee_type = self.infer_type(callee)?;
+            let function = Self::place_source(callee).unwrap_or_else(|| "<function value>".to_string());
+            match self.resolve_type(&callee_type) {
+                Type::Function(func_type) => (func_type, function, Vec::new(), false),
+                Type::Unknown => {
+                    for arg in arguments {
+                        self.infer_type(arg)?;
+                    }
+                    return Ok(Type::Unknown);
+                }
+                other => {
+                    return Err(TypeError::TypeMismatch {
+                        expected: Type::Function(FunctionType {
+                            params: vec![],
+                            return_type: Box::new(Type::Unknown),
+                        }),
+                        found: other,
+                        location: format!("function call '{}'", function),
+                    })
+                }
+            }
+        };
+
+        // 检查参数数量
+        if func_type.params.len() != arguments.len() {
+            return Err(TypeError::ArgumentCountMismatch {
+                expected: func_type.params.len(),
+                found: arguments.len(),
+                function,
+            });
+        }
+
+        // 检查每个参数的类型
+        let mut argument_types = Vec::with_capacity(arguments.len());
+        for (i, (param_type, arg)) in func_type.params.iter().zip(arguments.iter()).enumerate() {
+            let arg_type = self.infer_type(arg)?;
+            let resolved_param = self.resolve_type(param_type);
+            let resolved_arg = self.resolve_type(&arg_type);
+            argument_types.push(resolved_arg.clone());
+
+            if resolved_param == Type::Unknown {
+                self.record_call_argument(&function, i, &resolved_arg);
+            }
+            let location = format!("argument {} of '{}'", i + 1, function);
+            if let Some(err) = Self::fixed_array_literal_error(&resolved_param, arg, &location) {
+                return Err(err);
+            }
+            if !resolved_param.is_compatible_with(&resolved_arg) {
+                return Err(TypeError::ArgumentTypeMismatch {
+                    expected: Box::new(resolved_param),
+                    found: Box::new(resolved_arg),
+                    argument: i + 1,
+                    parameter: param_names.get(i).cloned(),
+                    function,
+                });
+            }
+        }
+
+        // 返回函数的返回类型；内置 sum 对整数数组求和得到 int（与运行时一致）
+        if builtin && function == "sum" && argument_types[0].get_element_type() == Some(&Type::Int) {
+            Ok(Type::Int)
+        } else {
+            Ok(*func_type.return_type)
         }
     }
 }
@@ -910,6 +2146,15 @@ mod tests {
     use crate::lexer::Lexer;
     use crate::parser::Parser;
 
+    fn parse(src: &str) -> Program {
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    fn check(src: &str) -> TypeResult<()> {
+        TypeChecker::new().check(&parse(src))
+    }
+
     #[test]
     fn test_type_check_variable() {
         let input = "let x: int = 42;";
@@ -945,4 +2190,988 @@ mod tests {
         let mut checker = TypeChecker::new();
         assert!(checker.check(&program).is_ok());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_invalid_operation_uses_operator_symbol() {
+        let input = "let x = 1 + \"a\";";
+        let mut lexer = Lexer::new(input.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        let mut checker = TypeChecker::new();
+        let message = format!("{:?}", checker.check(&program).unwrap_err());
+        assert!(message.contains("\"+\""));
+        assert!(!message.contains("Add"));
+    }
+
+    #[test]
+    fn test_assignment_in_condition() {
+        match check("var x = 1; if x = 5 { }") {
+            Err(err @ TypeError::AssignmentInCondition { .. }) => {
+                assert_eq!(err.to_string(), "assignment to 'x' used as the if condition; did you mean '=='?");
+            }
+            other => panic!("expected assignment-in-condition error, got {:?}", other),
+        }
+        // 赋值的值是 bool 时同样报告
+        assert!(matches!(
+            check("var done = false; while done = true { }"),
+            Err(TypeError::AssignmentInCondition { location, .. }) if location == "while condition"
+        ));
+        assert!(check("var x = 1; if x == 5 { } let y = if (x = 2) == 2 { 1 } else { 0 };").is_ok());
+    }
+
+    #[test]
+    fn test_else_if_conditions_must_be_bool() {
+        assert!(check("let x = 1; if x > 1 { } else if x > 0 { } else { }").is_ok());
+        match check("let x = 1; if x > 1 { } else if x + 1 { } else { }") {
+            Err(TypeError::TypeMismatch { expected: Type::Bool, found: Type::Int, location }) => {
+                assert_eq!(location, "if condition");
+            }
+            other => panic!("expected condition type mismatch, got {:?}", other),
+        }
+        assert!(check("let x = 1; if x > 1 { } else if x > 0 { } else if \"s\" { }").is_err());
+    }
+
+    #[test]
+    fn test_for_range_bounds_must_be_int() {
+        assert!(check("for i in 1..=10 { let x: int = i; }").is_ok());
+        match check("for i in 1..=2.5 { }") {
+            Err(TypeError::TypeMismatch { expected: Type::Int, found: Type::Float, location }) => {
+                assert_eq!(location, "for loop end");
+            }
+            other => panic!("expected for loop bound type mismatch, got {:?}", other),
+        }
+        assert!(matches!(
+            check("for i in \"a\"..10 { }"),
+            Err(TypeError::TypeMismatch { location, .. }) if location == "for loop start"
+        ));
+        // 步长同样必须是整数
+        assert!(check("for i in 0..10 step 2 { let x: int = i; }").is_ok());
+        assert!(matches!(
+            check("let r = 0..10 step 0.5;"),
+            Err(TypeError::TypeMismatch { location, .. }) if location == "range step"
+        ));
+    }
+
+    #[test]
+    fn test_match_pattern_types() {
+        assert!(check("let s = \"a\"; match s { \"a\" => { let n: int = 1; } _ => { } }").is_ok());
+        assert!(check("fn f(x) { match x { 1 => { } \"a\" => { } } }").is_ok());
+        match check("match 1 { 1 => { } \"a\" => { } }") {
+            Err(TypeError::TypeMismatch { expected: Type::Int, found: Type::String, location }) => {
+                assert_eq!(location, "match pattern");
+            }
+            other => panic!("expected match pattern type mismatch, got {:?}", other),
+        }
+        // 分支体各自是独立作用域
+        assert!(check("match 1 { 1 => { let y = 1; } _ => { } } let z = y;").is_err());
+        assert!(check("match true { true => { let y: int = \"s\"; } }").is_err());
+    }
+
+    #[test]
+    fn test_static_method_calls() {
+        let decls = "struct Point { x: int, y: int }; impl Point { \
+            fn origin() -> Point { return Point { x: 0, y: 0 }; } \
+            fn sum(self) -> int { return self.x + self.y; } }";
+
+        assert!(check(&format!("{} let p = Point.origin(); let s: int = p.sum();", decls)).is_ok());
+        // 静态方法只能通过类型名调用，实例方法只能通过实例调用
+        assert!(matches!(
+            check(&format!("{} let p = Point.origin(); let q = p.origin();", decls)),
+            Err(TypeError::UndefinedFunction(_))
+        ));
+        assert!(matches!(check(&format!("{} let s = Point.sum();", decls)), Err(TypeError::UndefinedFunction(_))));
+        // 静态方法中没有 self
+        assert!(check("struct P { x: int }; impl P { fn f() -> int { return self.x; } }").is_err());
+    }
+
+    #[test]
+    fn test_unary_plus_requires_numeric_operand() {
+        assert!(check("let a: bool = +5 == 5; let b: float = +1.5;").is_ok());
+        match check("let s = +\"x\";") {
+            Err(TypeError::TypeMismatch { found: Type::String, location, .. }) => {
+                assert_eq!(location, "unary plus operator");
+            }
+            other => panic!("expected unary plus type mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_type_check_function_typed_variable() {
+        let input = r#"
+            type BinOp = fn(int, int) -> int;
+            fn add(a: int, b: int) -> int { return a + b; }
+            let op: fn(int, int) -> int = add;
+            let aliased: BinOp = op;
+            let sum: int = aliased(1, 2);
+        "#;
+        let mut lexer = Lexer::new(input.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        let mut checker = TypeChecker::new();
+        assert!(checker.check(&program).is_ok());
+
+        // 参数数量不同的函数不能赋给该类型
+        let input = r#"
+            fn negate(a: int) -> int { return -a; }
+            let op: fn(int, int) -> int = negate;
+        "#;
+        assert!(check(input).is_err());
+    }
+
+    #[test]
+    fn test_higher_order_function_calls() {
+        let prelude = r#"
+            fn inc(x: int) -> int { return x + 1; }
+            fn add(a: int, b: int) -> int { return a + b; }
+            fn apply(f: fn(int) -> int, x: int) -> int { return f(x); }
+            fn pick() -> fn(int) -> int { return inc; }
+        "#;
+        let check = |input: &str| check(&format!("{}{}", prelude, input));
+
+        assert!(check("let y: int = apply(inc, 2);").is_ok());
+        assert!(check("let fs = [inc, inc]; let y: int = fs[0](1) + pick()(2);").is_ok());
+        // 未标注类型的参数可以调用，但无法检查
+        assert!(check("fn call_any(f, x) { return f(x, x); }").is_ok());
+
+        // 传入的函数类型与参数类型不符
+        assert!(matches!(
+            check("let y = apply(add, 2);"),
+            Err(TypeError::ArgumentTypeMismatch { argument: 1, function, .. }) if function == "apply"
+        ));
+        // 通过函数类型的参数调用时检查参数数量和类型
+        assert!(matches!(
+            check("fn bad(f: fn(int) -> int) -> int { return f(1, 2); }"),
+            Err(TypeError::ArgumentCountMismatch { expected: 1, found: 2, function }) if function == "f"
+        ));
+        assert!(matches!(
+            check("fn bad(f: fn(int) -> int) -> int { return f(\"a\"); }"),
+            Err(TypeError::ArgumentTypeMismatch { expected, found, .. }) if *expected == Type::Int && *found == Type::String
+        ));
+        // 非标识符的被调用者同样检查
+        assert!(matches!(
+            check("let fs = [inc]; let y = fs[0](\"a\");"),
+            Err(TypeError::ArgumentTypeMismatch { function, .. }) if function == "fs[0]"
+        ));
+        assert!(matches!(
+            check("let y = pick()(1, 2);"),
+            Err(TypeError::ArgumentCountMismatch { function, .. }) if function == "<function value>"
+        ));
+        assert!(matches!(check("let s: string = pick()(1);"), Err(TypeError::TypeMismatch { .. })));
+        assert!(matches!(
+            check("let n = 3; let y = [n][0](1);"),
+            Err(TypeError::TypeMismatch { found: Type::Int, .. })
+        ));
+    }
+
+    #[test]
+    fn test_trailing_expression_checked_against_return_type() {
+        let input = "fn add(a: int, b: int) -> string { a + b }";
+        assert!(check(input).is_err());
+    }
+
+    #[test]
+    fn test_global_symbols_include_inferred_types() {
+        let input = "let x = 1; var name = \"zero\";";
+        let program = parse(input);
+
+        let mut checker = TypeChecker::new();
+        checker.check(&program).unwrap();
+
+        let dumped: Vec<String> = checker.global_symbols().iter().map(|s| s.to_string()).collect();
+        assert!(dumped.contains(&"let x: int".to_string()));
+        assert!(dumped.contains(&"var name: string".to_string()));
+    }
+
+    #[test]
+    fn test_equality_between_incompatible_types_is_error() {
+        let program = parse("let b = \"a\" == 1;");
+
+        let result = TypeChecker::new().check(&program);
+        assert!(matches!(
+            result,
+            Err(TypeError::InvalidOperation { ref operator, left_type: Type::String, right_type: Type::Int })
+                if operator == "=="
+        ));
+    }
+
+    #[test]
+    fn test_mixed_numeric_equality_warns() {
+        let program = parse("let b = 1 == 1.0;");
+
+        let mut checker = TypeChecker::new();
+        assert!(checker.check(&program).is_ok());
+        assert_eq!(
+            checker.warnings(),
+            &[TypeWarning::MixedNumericEquality {
+                operator: "==".to_string(),
+                left_type: Type::Int,
+                right_type: Type::Float,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_let_struct_field_is_readonly_after_construction() {
+        let input = r#"
+            struct Point { let x: int, var y: int };
+            var p = Point { x: 1, y: 2 };
+            p.y = 3;
+        "#;
+        assert!(check(input).is_ok());
+
+        let input = r#"
+            struct Point { let x: int, var y: int };
+            var p = Point { x: 1, y: 2 };
+            p.x = 3;
+        "#;
+        let program = parse(input);
+        assert!(matches!(
+            TypeChecker::new().check(&program),
+            Err(TypeError::ReadonlyFieldAssignment { ref struct_name, ref field })
+                if struct_name == "Point" && field == "x"
+        ));
+    }
+
+    #[test]
+    fn test_if_expression_branches_need_common_type() {
+        assert!(check("let x: float = if true { 1 } else { 2.5 };").is_ok());
+
+        assert!(check("let x = if true { 1 } else { \"a\" };").is_err());
+
+        // 表达式形式必须带 else 分支
+        let tokens = Lexer::new("let x = if true { 1 };".to_string()).tokenize().unwrap();
+        assert!(Parser::new(tokens).parse().is_err());
+    }
+
+    #[test]
+    fn test_conditional_expression_types() {
+        assert!(check("let a = 1; let b = 2; let m: int = a > b ? a : b;").is_ok());
+        assert!(check("let x: float = true ? 1 : 2.5;").is_ok());
+        assert!(check("fn pick(c, a, b) { return c ? a : b; }").is_ok());
+        match check("let x = 1 ? 2 : 3;") {
+            Err(TypeError::TypeMismatch { expected: Type::Bool, found: Type::Int, .. }) => {}
+            other => panic!("expected condition type mismatch, got {:?}", other),
+        }
+        assert!(matches!(
+            check("let x = true ? 1 : \"a\";"),
+            Err(TypeError::TypeMismatch { expected: Type::Int, found: Type::String, .. })
+        ));
+        assert!(check("let s: string = false ? \"a\" : 1 < 2 ? \"b\" : \"c\";").is_ok());
+    }
+
+    #[test]
+    fn test_self_assignment_lint() {
+        let check = |input: &str| {
+            let mut checker = TypeChecker::new().with_self_assignment_lint(true);
+            checker.check(&parse(input)).unwrap();
+            checker.warnings().to_vec()
+        };
+
+        assert_eq!(
+            check("var x = 1; x = x;"),
+            vec![TypeWarning::SelfAssignment { target: "x".to_string() }]
+        );
+        assert_eq!(
+            check("var arr = [1, 2]; let i = 0; arr[i] = arr[i];"),
+            vec![TypeWarning::SelfAssignment { target: "arr[i]".to_string() }]
+        );
+        assert!(check("var x = 1; x = x + 1;").is_empty());
+
+        // 默认不启用
+        let program = parse("var x = 1; x = x;");
+        let mut checker = TypeChecker::new();
+        checker.check(&program).unwrap();
+        assert!(checker.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_unused_parameter_lint() {
+        let input = r#"
+            fn scale(value: int, factor: int, _unused: int) -> int {
+                value * 2
+            }
+        "#;
+        let program = parse(input);
+
+        let mut checker = TypeChecker::new().with_unused_parameter_lint(true);
+        checker.check(&program).unwrap();
+        assert_eq!(
+            checker.warnings(),
+            &[TypeWarning::UnusedParameter {
+                function: "scale".to_string(),
+                parameter: "factor".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unused_and_shadowed_variable_lints() {
+        let input = r#"
+            let xs = [1, 2, 3];
+            let total = 0;
+            let _ignored = 1;
+            fn sum(n: int) -> int {
+                var acc = 0;
+                let xs = "shadow";
+                for i in 0..n {
+                    let step = i;
+                    acc += 1;
+                }
+                let n = n * 2;
+                return acc + n;
+            }
+            var x = 1;
+            x = 2;
+            let x = 3;
+            print(len(xs) + x);
+        "#;
+        let program = parse(input);
+
+        let mut checker = TypeChecker::new().with_warnings(true);
+        checker.check(&program).unwrap();
+        let unused = |name: &str| TypeWarning::UnusedVariable { name: name.to_string() };
+        // 只赋值不读取也算未使用；同名的 `n` 与参数类型相同，不报告遮蔽
+        assert_eq!(
+            checker.warnings(),
+            &[
+                TypeWarning::ShadowedVariable {
+                    name: "xs".to_string(),
+                    outer: Type::Array(Box::new(Type::Int)),
+                    inner: Type::String,
+                },
+                unused("step"),
+                unused("xs"),
+                unused("x"),
+                unused("total"),
+            ]
+        );
+
+        // 两个检查也可单独开启
+        let mut checker = TypeChecker::new().with_shadowed_variable_lint(true);
+        checker.check(&program).unwrap();
+        assert_eq!(checker.warnings().len(), 1);
+
+        // 默认不启用
+        let mut checker = TypeChecker::new();
+        checker.check(&program).unwrap();
+        assert!(checker.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_argument_type_mismatch_names_parameter() {
+        let error = check(r#"
+            fn repeat(text: string, count: int, separator: string) -> string { text }
+            repeat("a", "3", ",");
+        "#).unwrap_err();
+        assert!(matches!(
+            &error,
+            TypeError::ArgumentTypeMismatch { argument: 2, parameter: Some(name), .. } if name == "count"
+        ));
+        assert!(format!("{:?}", error).contains("count"));
+
+        let error = check(r#"
+            struct Counter { value: int };
+            impl Counter {
+                fn add(self, amount: int) -> int { self.value + amount }
+            }
+            let c = Counter { value: 1 };
+            c.add(true);
+        "#).unwrap_err();
+        assert!(matches!(
+            &error,
+            TypeError::ArgumentTypeMismatch { parameter: Some(name), .. } if name == "amount"
+        ));
+
+        // 内置函数没有记录参数名
+        assert!(matches!(
+            check("exit(\"now\");"),
+            Err(TypeError::ArgumentTypeMismatch { parameter: None, .. })
+        ));
+    }
+
+    #[test]
+    fn test_sum_signature() {
+        let found = |src: &str| match check(src) {
+            Err(TypeError::TypeMismatch { found, .. }) => found,
+            other => panic!("{}: {:?}", src, other),
+        };
+        // 结果类型随元素类型
+        assert_eq!(found("let s: string = sum([1, 2]);"), Type::Int);
+        assert_eq!(found("let s: string = sum([1.5, 2.0]);"), Type::Float);
+        // 只接受数组
+        assert!(matches!(check("sum(\"abc\");"), Err(TypeError::ArgumentTypeMismatch { .. })));
+        // 同名的用户函数按其声明的返回类型
+        assert_eq!(
+            found("fn sum(xs: [int]) -> float { return 0.0; } let s: string = sum([1]);"),
+            Type::Float
+        );
+    }
+
+    #[test]
+    fn test_shadowed_builtin_lint() {
+        let input = r#"
+            let len = 5;
+            fn typeof(value) -> string {
+                return "custom";
+            }
+            fn count(items, contains) -> int {
+                return len;
+            }
+            for assert in 0..2 { }
+        "#;
+        let program = parse(input);
+
+        let mut checker = TypeChecker::new().with_shadowed_builtin_lint(true);
+        checker.check(&program).unwrap();
+        let shadowed: Vec<&str> = checker.warnings().iter().map(|warning| match warning {
+            TypeWarning::ShadowedBuiltin { name, .. } => name.as_str(),
+            other => panic!("unexpected warning {:?}", other),
+        }).collect();
+        assert_eq!(shadowed, ["len", "typeof", "contains", "assert"]);
+        // 警告中带有被遮蔽的内置函数签名
+        assert_eq!(
+            checker.warnings()[0].to_string(),
+            "'len' shadows the builtin function len (fn(unknown) -> int)"
+        );
+
+        // 遮蔽是允许的，默认不报告
+        let mut checker = TypeChecker::new();
+        checker.check(&program).unwrap();
+        assert!(checker.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_call_consistency_lint() {
+        let input = r#"
+            fn show(value, times: int) {
+                print(value);
+            }
+            show(1, 1);
+            show(2.5, 2);
+            show("three", 3);
+        "#;
+        let program = parse(input);
+
+        // int 之后的 float 可以统一为 float，string 则不兼容
+        let mut checker = TypeChecker::new().with_call_consistency_lint(true);
+        checker.check(&program).unwrap();
+        assert_eq!(
+            checker.warnings(),
+            &[TypeWarning::InconsistentArgumentType {
+                function: "show".to_string(),
+                argument: 1,
+                first: Type::Int,
+                found: Type::String,
+            }]
+        );
+
+        let mut checker = TypeChecker::new();
+        checker.check(&program).unwrap();
+        assert!(checker.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_division_by_constant_zero_is_compile_time_error() {
+        assert!(matches!(
+            check("let a = 1 / 0;"),
+            Err(TypeError::DivisionByZero { ref operator }) if operator == "/"
+        ));
+        assert!(matches!(
+            check("let b = 5 % 0;"),
+            Err(TypeError::DivisionByZero { ref operator }) if operator == "%"
+        ));
+        assert!(check("let c = 1.5 / -0.0;").is_err());
+        assert!(check("var x = 2; let d = 1 / x;").is_ok());
+    }
+
+    #[test]
+    fn test_negative_and_nested_index_types() {
+        assert!(check("let a = [1, 2, 3]; let x: int = a[-1];").is_ok());
+        assert!(check("let m: [[int]] = [[1, 2], [3, 4]]; let x: int = m[1][-1];").is_ok());
+        assert!(matches!(
+            check("let m: [[int]] = [[1, 2]]; let s: string = m[0][0];"),
+            Err(TypeError::TypeMismatch { expected: Type::String, found: Type::Int, .. })
+        ));
+        assert!(matches!(
+            check("var m: [[int]] = [[1, 2]]; m[0][-1] = \"x\";"),
+            Err(TypeError::TypeMismatch { location, .. }) if location == "array element assignment"
+        ));
+        assert!(matches!(
+            check("let m: [[int]] = [[1]]; let x = m[0][\"a\"];"),
+            Err(TypeError::TypeMismatch { expected: Type::Int, found: Type::String, .. })
+        ));
+
+        // 每一层的元素类型都可以是别名
+        let alias = "type Row = [int]; type Grid = [Row]; let g: Grid = [[1, 2]];";
+        assert!(check(&format!("{} let x: int = g[0][1];", alias)).is_ok());
+        assert!(check(&format!("{} let s: string = g[0][1];", alias)).is_err());
+
+        // 定长数组的每一层都检查常量索引
+        assert!(matches!(
+            check("let f: [[int; 2]; 3] = [[1, 2], [3, 4], [5, 6]]; let x = f[-3][-3];"),
+            Err(TypeError::IndexOutOfBounds { index: -3, length: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_string_index_type() {
+        assert!(check("let s = \"abc\"; let c: char = s[0]; let d: char = s[-1];").is_ok());
+        assert!(check("type Name = string; let n: Name = \"zero\"; let c: char = n[1];").is_ok());
+        assert!(matches!(
+            check("let s = \"abc\"; let t: string = s[0];"),
+            Err(TypeError::TypeMismatch { expected: Type::String, found: Type::Char, .. })
+        ));
+        assert!(matches!(
+            check("let s = \"abc\"; let c = s[\"a\"];"),
+            Err(TypeError::TypeMismatch { expected: Type::Int, found: Type::String, .. })
+        ));
+    }
+
+    #[test]
+    fn test_nested_and_empty_typed_arrays() {
+        let check = |input: &str| {
+            let mut checker = TypeChecker::new();
+            checker.check(&parse(input)).map(|_| checker)
+        };
+
+        let program = parse("let m: [[int]] = [[1, 2], [3]];");
+        let nested = Type::Array(Box::new(Type::Array(Box::new(Type::Int))));
+        assert!(matches!(
+            &program.statements[0],
+            Stmt::VarDeclaration { type_annotation: Some(t), .. } if *t == nested && t.to_string() == "[[int]]"
+        ));
+
+        // 空数组字面量和嵌套的空数组都使用注解的元素类型
+        assert!(check("let m: [[int]] = [[1, 2], [3]]; let x: int = m[0][1];").is_ok());
+        assert!(check("let m: [[int]] = [[], [1]]; let e: [int] = [];").is_ok());
+        assert!(check("type Row = [int]; let m: [Row] = [[], [1]]; let x: int = m[1][0];").is_ok());
+        assert!(check("var xs: [string] = [\"a\"]; xs = [];").is_ok());
+        assert!(matches!(
+            check("let m: [[int]] = [[], [\"a\"]];"),
+            Err(TypeError::TypeMismatch { .. })
+        ));
+
+        let checker = check("let xs: [int] = [];").unwrap();
+        assert_eq!(checker.coverage().percentage(), 100.0);
+    }
+
+    #[test]
+    fn test_struct_literal_fields_checked_by_name() {
+        let point = "struct Point { x: int, y: string };";
+
+        assert!(check(&format!("{} let p = Point {{ y: \"a\", x: 1 }};", point)).is_ok());
+        assert!(matches!(
+            check(&format!("{} let p = Point {{ y: 1, x: \"a\" }};", point)),
+            Err(TypeError::TypeMismatch { expected: Type::String, found: Type::Int, .. })
+        ));
+        assert!(matches!(
+            check(&format!("{} let p = Point {{ x: 1, x: 2 }};", point)),
+            Err(TypeError::TypeMismatch { ref location, .. }) if location.contains("missing field y")
+        ));
+    }
+
+    #[test]
+    fn test_print_arity_and_value() {
+        assert!(check("print(1); let p = print; p(\"x\"); let f: fn(int) = print;").is_ok());
+        for (input, found) in [("print();", 0), ("print(1, 2);", 2)] {
+            assert!(matches!(
+                check(input),
+                Err(TypeError::ArgumentCountMismatch { expected: 1, found: n, ref function }) if n == found && function == "print"
+            ), "{}", input);
+        }
+        assert!(matches!(
+            check("let p = print; p(1, 2);"),
+            Err(TypeError::ArgumentCountMismatch { expected: 1, found: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_index_assign_checks_element_type() {
+        for input in [
+            "var xs: [int] = [1, 2]; xs[0] = \"s\";",
+            "type Row = [int]; var r: Row = [1]; r[0] = \"s\";",
+            "var m: [[int]] = [[1]]; m[0][0] = \"s\";",
+            "fn f(xs: [int; 2]) { xs[1] = true; }",
+        ] {
+            assert!(
+                matches!(check(input), Err(TypeError::TypeMismatch { expected: Type::Int, .. })),
+                "{}",
+                input
+            );
+        }
+
+        // 元素类型未知的数组保持宽松
+        assert!(check("var xs = []; xs[0] = \"s\"; fn g(ys) { ys[0] = 1; ys[1] = \"a\"; }").is_ok());
+        assert!(check("var xs: [float] = [1.0]; xs[0] = 2;").is_ok());
+
+        // 字符串和元组的元素不能赋值
+        for input in ["var s = \"abc\"; s[0] = 'z';", "var t = (1, \"a\"); t[0] = 2;", "var n = 1; n[0] = 2;"] {
+            assert!(matches!(check(input), Err(TypeError::InvalidOperation { .. })), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_void_call_used_as_value() {
+        let defs = "fn log(n: int) -> void { print(n); } struct P { x: int }; impl P { fn show(self) { print(self.x); } } let p = P { x: 1 };";
+
+        // 作为表达式语句、return 或被丢弃的 if 表达式分支时合法
+        assert!(check(&format!("{} log(1); p.show(); fn f() -> void {{ return log(2); }} (if true {{ log(3) }} else {{ p.show() }});", defs)).is_ok());
+
+        for input in [
+            "let x = log(1);",
+            "let n = 1 + log(1);",
+            "log(log(1));",
+            "print(p.show());",
+            "let xs = [log(1)];",
+            "let v = if true { log(1) } else { log(2) };",
+            "let b = print(1);",
+        ] {
+            assert!(
+                matches!(check(&format!("{} {}", defs, input)), Err(TypeError::VoidValueUsed { .. })),
+                "{}",
+                input
+            );
+        }
+        assert!(matches!(
+            check(&format!("{} let s = p.show();", defs)),
+            Err(TypeError::VoidValueUsed { ref location }) if location == "call to method 'show'"
+        ));
+    }
+
+    #[test]
+    fn test_char_type() {
+        assert!(check("let c: char = 'z'; print(c); let b: bool = c > 'a' && c != 'q';").is_ok());
+        assert!(check("fn next(c: char) -> char { return c; } let d: char = next('a');").is_ok());
+        for input in ["let c: char = \"z\";", "let c: char = 1;", "let s: string = 'z';"] {
+            assert!(matches!(check(input), Err(TypeError::TypeMismatch { .. })), "{}", input);
+        }
+        for input in ["let c = 'a' + 'b';", "let b = 'a' < 1;", "let b = \"a\" < \"b\";"] {
+            assert!(matches!(check(input), Err(TypeError::InvalidOperation { .. })), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_postfix_update_types() {
+        assert!(check("var i = 0; let old: int = i++; i--;").is_ok());
+        assert!(check("var f: float = 1.5; let g: float = f--;").is_ok());
+        assert!(check("fn f(n) { var m = n; return m++; }").is_ok());
+
+        assert!(matches!(
+            check("let i = 0; i++;"),
+            Err(TypeError::ImmutableAssignment { variable }) if variable == "i"
+        ));
+        assert!(matches!(check("missing++;"), Err(TypeError::UndefinedVariable(_))));
+        assert!(matches!(
+            check("var s = \"a\"; s--;"),
+            Err(TypeError::InvalidOperation { operator, left_type: Type::String, .. }) if operator == "--"
+        ));
+        assert!(matches!(
+            check("var i = 0; let s: string = i++;"),
+            Err(TypeError::TypeMismatch { expected: Type::String, found: Type::Int, .. })
+        ));
+    }
+
+    #[test]
+    fn test_tuple_types() {
+        let divmod = "fn divmod(a: int, b: int) -> (int, int) { return (a / b, a % b); }";
+        assert!(check(&format!("{} let r = divmod(7, 2); let q: int = r.0; let m: int = r.1;", divmod)).is_ok());
+        assert!(check("let t = ((1, \"a\"), true); let s: string = t.0.1; let b: bool = t.1;").is_ok());
+        assert!(check("type Pair = (string, int); let p: Pair = (\"x\", 1); let n: int = p.1;").is_ok());
+
+        // 元素按位置推断，个数和类型都要匹配
+        assert!(matches!(
+            check("let t = (1, \"a\"); let x: int = t.1;"),
+            Err(TypeError::TypeMismatch { expected: Type::Int, found: Type::String, .. })
+        ));
+        assert!(check("let t: (int, string) = (1, 2);").is_err());
+        assert!(check("let t: (int, int) = (1, 2, 3);").is_err());
+        assert!(matches!(check("let t = (1, 2); let x = t.2;"), Err(TypeError::UndefinedVariable(_))));
+        assert!(check(&format!("{} let r: (int, string) = divmod(7, 2);", divmod)).is_err());
+        // 元组元素不能单独赋值
+        assert!(matches!(
+            check("var t = (1, 2); t.0 = 3;"),
+            Err(TypeError::InvalidOperation { operator, .. }) if operator == "."
+        ));
+    }
+
+    #[test]
+    fn test_fixed_array_length_checked() {
+        assert!(check("let a: [int; 3] = [1, 2, 3];").is_ok());
+        assert!(matches!(
+            check("let a: [int; 3] = [1, 2];"),
+            Err(TypeError::ArrayLengthMismatch { expected: 3, found: 2, .. })
+        ));
+        assert!(check("var a: [int; 3] = [1, 2, 3]; a = [4, 5, 6, 7];").is_err());
+
+        // 常量索引越界是编译期错误，负索引从末尾计数
+        assert!(check("let a: [int; 3] = [1, 2, 3]; let x = a[2]; let y = a[-3];").is_ok());
+        assert!(matches!(
+            check("let a: [int; 3] = [1, 2, 3]; let x = a[3];"),
+            Err(TypeError::IndexOutOfBounds { index: 3, length: 3 })
+        ));
+        assert!(check("var a: [int; 3] = [1, 2, 3]; a[-4] = 0;").is_err());
+
+        // 长度可以是常量表达式
+        assert!(check("const N = 4; let a: [int; N] = [1, 2, 3, 4]; let b: [int; N / 2 + 1] = [1, 2, 3];").is_ok());
+        assert!(matches!(
+            check("const N = 4; let a: [int; N] = [1, 2, 3];"),
+            Err(TypeError::ArrayLengthMismatch { expected: 4, found: 3, .. })
+        ));
+        assert!(matches!(check("const N = 4; N = 5;"), Err(TypeError::ImmutableAssignment { .. })));
+
+        // 参数、返回值和结构体字段同样检查长度
+        let length_error = |src: &str| match check(src) {
+            Err(TypeError::ArrayLengthMismatch { expected, found, location }) => (expected, found, location),
+            other => panic!("{}: {:?}", src, other),
+        };
+        assert!(check("fn f(b: [int; 3]) {} f([1, 2, 3]);").is_ok());
+        assert_eq!(
+            length_error("fn f(b: [int; 3]) {} f([1, 2]);"),
+            (3, 2, "argument 1 of 'f'".to_string())
+        );
+        assert_eq!(length_error("fn g() -> [int; 3] { return [1]; }"), (3, 1, "return value".to_string()));
+        assert_eq!(
+            length_error("struct S { a: [int; 2] }; let s = S { a: [1, 2, 3] };"),
+            (2, 3, "field a in struct S".to_string())
+        );
+        assert_eq!(
+            length_error("struct S { a: [int; 2] }; var s = S { a: [1, 2] }; s.a = [1];"),
+            (2, 1, "field assignment to a".to_string())
+        );
+        assert_eq!(
+            length_error("struct S { x: int }; impl S { fn put(self, b: [int; 2]) {} } let s = S { x: 1 }; s.put([1, 2, 3]);"),
+            (2, 3, "argument 1 of 'S.put'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_const_declarations() {
+        assert!(check("const NAME = \"zero\"; const N: float = 2; let s: string = NAME;").is_ok());
+        assert!(matches!(check("const N = 1; N++;"), Err(TypeError::ImmutableAssignment { .. })));
+        assert!(matches!(check("const N: string = 1;"), Err(TypeError::TypeMismatch { .. })));
+
+        // 解析器总会给出初始值，手工构造的语法树也需要检查
+        let mut program = Program::new();
+        program.add_statement(Stmt::VarDeclaration {
+            name: "N".to_string(),
+            mutable: false,
+            type_annotation: Some(Type::Int),
+            initializer: None,
+            is_const: true,
+        });
+        assert!(matches!(
+            TypeChecker::new().check(&program),
+            Err(TypeError::ConstWithoutInitializer(name)) if name == "N"
+        ));
+    }
+
+    #[test]
+    fn test_struct_equality_requires_same_struct_type() {
+        let structs = "struct A { x: int }; struct B { x: int }; let a = A { x: 1 }; let b = B { x: 1 };";
+
+        assert!(check(&format!("{} let same = a == A {{ x: 2 }};", structs)).is_ok());
+        assert!(matches!(
+            check(&format!("{} let mixed = a == b;", structs)),
+            Err(TypeError::InvalidOperation { .. })
+        ));
+        assert!(check(&format!("{} let mixed = a != b;", structs)).is_err());
+    }
+
+    #[test]
+    fn test_chained_assignment_checks_each_target() {
+        assert!(check("var a = 0; var b = 0; a = b = 5;").is_ok());
+        assert!(matches!(
+            check("var a = 0; let b = 0; a = b = 5;"),
+            Err(TypeError::ImmutableAssignment { ref variable }) if variable == "b"
+        ));
+        // 内层赋值的值类型继续传给外层目标
+        assert!(matches!(
+            check("var s = \"x\"; var n = 0; s = n = 5;"),
+            Err(TypeError::TypeMismatch { expected: Type::String, found: Type::Int, .. })
+        ));
+    }
+
+    #[test]
+    fn test_type_coverage() {
+        let check = |input: &str| {
+            let mut checker = TypeChecker::new().with_min_type_coverage(50.0);
+            checker.check(&parse(input)).unwrap();
+            checker
+        };
+
+        let annotated = check(r#"
+            fn scale(x: int, factor: int) -> int { return x * factor; }
+            let a: int = scale(2, 3);
+            let b: float = 1.5 + 2.0;
+        "#);
+        assert_eq!(annotated.coverage().percentage(), 100.0);
+        assert!(annotated.warnings().is_empty());
+
+        let unannotated = check(r#"
+            fn combine(x, y) { return x + y * x; }
+            fn pick(a, b) { let c = a; return c; }
+        "#);
+        let coverage = unannotated.coverage();
+        assert!(coverage.total > 0 && coverage.percentage() < 20.0, "{}", coverage);
+        assert!(matches!(
+            unannotated.warnings(),
+            [TypeWarning::LowTypeCoverage { threshold, .. }] if *threshold == 50.0
+        ));
+
+        // 通过未标注的函数值调用，结果类型未知
+        let through_value = check("fn f(g) { return g(1); }").coverage();
+        assert!(through_value.typed < through_value.total, "{}", through_value);
+
+        // 重复使用同一个检查器时只统计最近一次检查的程序
+        let mut checker = TypeChecker::new();
+        checker.check(&parse("let a = 1;")).unwrap();
+        checker.check(&parse("let b = 2;")).unwrap();
+        assert_eq!(checker.coverage().total, 1);
+    }
+
+    #[test]
+    fn test_collects_errors_from_every_statement() {
+        let input = r#"
+            let a: int = "one";
+            fn f(n: int) -> int {
+                let s: string = n;
+                while true { break; }
+                return s + 1;
+            }
+            let b = a + 1;
+            let c = missing;
+        "#;
+        let program = parse(input);
+
+        let mut checker = TypeChecker::new();
+        assert!(matches!(checker.check(&program), Err(TypeError::TypeMismatch { found: Type::String, .. })));
+        // 出错的声明仍然定义了 a 和 s，`a + 1` 和 `s + 1` 按注解类型检查；函数体出错后作用域已恢复
+        let errors = checker.errors();
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(matches!(errors[1], TypeError::TypeMismatch { expected: Type::String, found: Type::Int, .. }));
+        assert!(matches!(errors[2], TypeError::InvalidOperation { .. }));
+        assert!(matches!(&errors[3], TypeError::UndefinedVariable(name) if name == "missing"));
+        assert!(checker.global_symbols().iter().all(|symbol| symbol.name != "n" && symbol.name != "s"));
+
+        // 结构体字面量出错时变量仍是该结构体类型，字段访问不连带报错
+        let input = "struct P { x: int }; let p = P { x: \"a\" }; let x: int = p.x;";
+        let program = parse(input);
+        let mut checker = TypeChecker::new();
+        assert!(checker.check(&program).is_err());
+        assert_eq!(checker.errors().len(), 1, "{:?}", checker.errors());
+
+        // 再次检查时不保留上一个程序的错误和警告
+        let mut checker = TypeChecker::new().with_self_assignment_lint(true);
+        assert!(checker.check(&parse("var v = 1; v = v; let w: int = true;")).is_err());
+        assert_eq!((checker.errors().len(), checker.warnings().len()), (1, 1));
+        assert!(checker.check(&parse("let z = 1;")).is_ok());
+        assert!(checker.errors().is_empty());
+        assert!(checker.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_for_loop_variable_stays_int() {
+        // 循环变量可以赋 int（跳过若干次迭代），其他类型都会破坏递增和比较
+        assert!(check("for i in 0..10 { i = i + 2; i += 1; i++; }").is_ok());
+        for input in [
+            "for i in 0..3 { i = \"a\"; }",
+            "for i in 0..3 { i = 1.5; }",
+            "for i in 0..3 { i += 0.5; }",
+            "fn f(x) { return x; } for i in 0..3 { i = f(1); }",
+        ] {
+            assert!(matches!(check(input), Err(TypeError::TypeMismatch { expected: Type::Int, .. })), "{}", input);
+        }
+        // 循环体中同名的新变量不受限制
+        assert!(check("for i in 0..3 { var i = 1.5; i = 2.5; }").is_ok());
+    }
+
+    #[test]
+    fn test_cyclic_type_aliases() {
+        assert!(matches!(check("type A = A;"), Err(TypeError::CyclicTypeAlias(name)) if name == "A"));
+        assert!(matches!(
+            check("type A = B; type B = A; let x: A = 1;"),
+            Err(TypeError::CyclicTypeAlias(name)) if name == "B"
+        ));
+        assert!(matches!(
+            check("type C = A; type A = B; type B = [C];"),
+            Err(TypeError::CyclicTypeAlias(name)) if name == "B"
+        ));
+
+        // 经过结构体的递归不是循环别名，解析时也不会无限展开
+        assert!(check("type Id = int; type Ids = [Id]; let xs: Ids = [1];").is_ok());
+        assert!(check("struct Node { v: int, next: [Node] }; let n = Node { v: 1, next: [] }; let v: int = n.v;").is_ok());
+        assert!(check("struct Node { v: int, rest: Nodes }; type Nodes = [Node];").is_ok());
+    }
+
+    #[test]
+    fn test_missing_return_on_some_path() {
+        assert!(check("fn sign(n: int) -> int { if n < 0 { return -1; } else { return 1; } }").is_ok());
+        assert!(check("fn f(n: int) -> int { match n { 0 => { return 1; }, _ => { return 2; } } }").is_ok());
+        assert!(check("fn f() -> int { while true { return 1; } }").is_ok());
+        assert!(check("fn f(n: int) { if n > 0 { return; } }").is_ok());
+        assert!(check("fn f(n) { if n > 0 { return 1; } }").is_ok());
+        assert!(check("struct P { x: int }; impl P { fn get(self) -> int { return self.x; } }").is_ok());
+
+        for input in [
+            "fn f(n: int) -> int { if n > 0 { return 1; } }",
+            "fn f() -> int { }",
+            "fn f(n: int) -> int { while n > 0 { return 1; } }",
+            "fn f() -> int { while true { if true { break; } return 1; } }",
+            "fn f(n: int) -> int { match n { 0 => { return 1; } } }",
+        ] {
+            assert!(
+                matches!(check(input), Err(TypeError::MissingReturn { ref function }) if function == "f"),
+                "{}",
+                input
+            );
+        }
+        // 嵌套循环中的 break 不会跳出外层的 while true
+        assert!(check("fn f() -> int { while true { while true { break; } return 1; } }").is_ok());
+        assert!(matches!(
+            check("struct P { x: int }; impl P { fn get(self) -> int { if self.x > 0 { return 1; } } }"),
+            Err(TypeError::MissingReturn { ref function }) if function == "P.get"
+        ));
+    }
+
+    #[test]
+    fn test_unannotated_return_type_inferred() {
+        let check = |input: &str| {
+            let mut checker = TypeChecker::new();
+            checker.check(&parse(input)).map(|_| checker)
+        };
+
+        assert!(matches!(
+            check("fn f(n: int) { if n > 0 { return 1; } return \"one\"; }"),
+            Err(TypeError::ReturnTypeMismatch { expected: Type::Int, found: Type::String, ref function })
+                if function == "f"
+        ));
+
+        // 一致的 return 推断出返回类型，调用方得到真实类型
+        let checker = check("fn g(n: int) { if n > 0 { return 1; } return 2.5; } let x = g(1);").unwrap();
+        assert_eq!(checker.symbol_table.get("x").unwrap().symbol_type, Type::Float);
+        assert!(check("fn h() { return 1; } let s: string = h();").is_err());
+
+        // 末尾表达式同样参与推断；返回未知类型的函数保持 Unknown
+        let checker = check("fn k(a: int) { a * 2 } fn id(v) { return v; } let y = k(1); let z = id(1);").unwrap();
+        assert_eq!(checker.symbol_table.get("y").unwrap().symbol_type, Type::Int);
+        assert_eq!(checker.symbol_table.get("z").unwrap().symbol_type, Type::Unknown);
+
+        // 未标注返回类型的方法同样由 return 推断，没有 return 时为 void
+        let point = "struct P { x: int }; impl P { fn get(self) { return self.x; } fn show(self) { print(self.x); } }";
+        let checker = check(&format!("{} let p = P {{ x: 1 }}; let v = p.get(); p.show();", point)).unwrap();
+        assert_eq!(checker.symbol_table.get("v").unwrap().symbol_type, Type::Int);
+        assert_eq!(checker.methods["P"]["show"].return_type, Type::Void);
+        assert!(matches!(
+            check(&format!("{} let p = P {{ x: 1 }}; let s: string = p.get();", point)),
+            Err(TypeError::TypeMismatch { expected: Type::String, found: Type::Int, .. })
+        ));
+        assert!(matches!(
+            check("struct P { x: int }; impl P { fn f(self) { if self.x > 0 { return 1; } return \"x\"; } }"),
+            Err(TypeError::ReturnTypeMismatch { ref function, .. }) if function == "P.f"
+        ));
+    }
+}
@@ -1,5 +1,10 @@
-use crate::bytecode::{Chunk, OpCode, Value, Function};
+use crate::ast::IntWidth;
+use crate::bytecode::{builtins, Chunk, NativeError, NativeHost, OpCode, Value, Function};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
+
+mod snapshot;
 
 /// 虚拟机运行时错误
 #[derive(Debug)]
@@ -8,12 +13,66 @@ pub enum VMError {
     StackOverflow,
     TypeError(String),
     UndefinedVariable(String),
-    DivisionByZero,
+    DivisionByZero { operator: &'static str, numerator: String, line: usize },
+    IntegerOverflow,
     InvalidOperation(String),
+    StackImbalance { function: String, depth: usize },
+    /// `exit(code)` 终止了程序；这是控制流信号而不是错误，将来的异常处理不应捕获它
+    Exit(i32),
+    /// 执行某条指令时发生的错误，附带所在函数、指令偏移和操作码（与反汇编输出对应）
+    AtInstruction {
+        function: String,
+        ip: usize,
+        opcode: OpCode,
+        error: Box<VMError>,
+    },
+}
+
+impl VMError {
+    /// 去掉指令位置信息，得到实际发生的错误
+    pub fn into_inner(self) -> VMError {
+        match self {
+            VMError::AtInstruction { error, .. } => error.into_inner(),
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for VMError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VMError::StackUnderflow => write!(f, "stack underflow"),
+            VMError::StackOverflow => write!(f, "stack overflow"),
+            VMError::TypeError(msg) => write!(f, "type error: {}", msg),
+            VMError::UndefinedVariable(name) => write!(f, "undefined variable '{}'", name),
+            VMError::DivisionByZero { operator, numerator, line } => {
+                write!(f, "division by zero: {} {} 0", numerator, operator)?;
+                if *line > 0 {
+                    write!(f, " at line {}", line)?;
+                }
+                Ok(())
+            }
+            VMError::IntegerOverflow => write!(f, "integer overflow"),
+            VMError::InvalidOperation(msg) => write!(f, "{}", msg),
+            VMError::StackImbalance { function, depth } => write!(
+                f,
+                "stack imbalance: function '{}' returned with {} values in its frame",
+                function, depth
+            ),
+            VMError::Exit(code) => write!(f, "program exited with code {}", code),
+            VMError::AtInstruction { function, ip, opcode, error } => {
+                write!(f, "{} (in {} at {:04}: {:?})", error, function, ip, opcode)
+            }
+        }
+    }
 }
 
 type VMResult<T> = Result<T, VMError>;
 
+/// 嵌入方注册的扩展指令处理器：接收当前调用帧的操作数栈区域（参数、局部变量和临时值）和指令操作数，
+/// 可自行压栈/弹栈；调用者帧的值不在其中，不会被破坏
+pub type ExtensionHandler = fn(&mut Vec<Value>, u32) -> Result<(), VMError>;
+
 /// 调用帧（用于函数调用）
 #[derive(Debug, Clone)]
 struct CallFrame {
@@ -28,40 +87,183 @@ pub struct VM {
     globals: HashMap<String, Value>, // 全局变量
     frames: Vec<CallFrame>,          // 调用栈
     current_frame: usize,            // 当前帧索引
+    int_width: IntWidth,             // 整数位宽
+    extensions: HashMap<u8, ExtensionHandler>, // 扩展指令处理器
+    print_limits: (usize, usize),    // print 输出上限（每个数组的元素数，嵌套深度）
+    captured_output: Option<String>, // 捕获的 print 输出（None 时写到标准输出）
 }
 
 impl VM {
     pub fn new() -> Self {
+        // 内置函数是普通全局变量，可以被别名或遮蔽
+        let globals = builtins::all()
+            .into_iter()
+            .map(|native| (native.name.to_string(), Value::NativeFunction(native)))
+            .collect();
+
         VM {
             stack: Vec::with_capacity(256),
-            globals: HashMap::new(),
+            globals,
             frames: Vec::new(),
             current_frame: 0,
+            int_width: IntWidth::default(),
+            extensions: HashMap::new(),
+            print_limits: (usize::MAX, usize::MAX),
+            captured_output: None,
+        }
+    }
+
+    /// 设置整数运算的位宽（超出范围时报告溢出）
+    pub fn with_int_width(mut self, int_width: IntWidth) -> Self {
+        self.int_width = int_width;
+        self
+    }
+
+    /// 限制 print 的输出规模，避免误打印巨大数组时刷屏：每个数组最多显示 `max_elems` 个元素，
+    /// 嵌套超过 `max_depth` 层的数组显示为 `[...]`；默认不限制
+    pub fn with_print_limits(mut self, max_elems: usize, max_depth: usize) -> Self {
+        self.print_limits = (max_elems, max_depth);
+        self
+    }
+
+    /// 按 print 上限格式化值（`print` 语句和内置 `print` 函数共用）
+    pub fn format_for_print(&self, value: &Value) -> String {
+        let (max_elems, max_depth) = self.print_limits;
+        value.to_string_limited(max_elems, max_depth)
+    }
+
+    /// 把 print 的输出收集到缓冲区而不是写到标准输出，用 `take_output` 读取
+    pub fn with_captured_output(mut self) -> Self {
+        self.captured_output = Some(String::new());
+        self
+    }
+
+    /// 取出并清空已捕获的输出（每次 print 一行）；未开启捕获时返回空字符串
+    pub fn take_output(&mut self) -> String {
+        self.captured_output.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// 注册扩展指令处理器，`OpCode::Extension(id, _)` 执行时调用；重复注册会覆盖旧处理器
+    pub fn register_extension(&mut self, id: u8, handler: ExtensionHandler) {
+        self.extensions.insert(id, handler);
+    }
+
+    /// 读取全局变量（用于嵌入和引擎间的对比测试）
+    pub fn get_global(&self, name: &str) -> Option<&Value> {
+        self.globals.get(name)
+    }
+
+    /// 全局变量（含内置函数），顺序不固定
+    pub fn globals(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.globals.iter()
+    }
+
+    /// 当前值栈，栈底在前
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+    /// 只在主帧中执行时返回下一条指令的位置；正在执行函数调用或程序已结束时返回 None
+    pub fn top_level_ip(&self) -> Option<usize> {
+        match self.frames.as_slice() {
+            [main] => Some(main.ip),
+            _ => None,
         }
     }
 
-    /// 执行字节码
+    /// 替换已定义的全局变量并返回旧值（用于热重载函数）
+    /// 正在执行的调用帧持有旧函数的副本，继续运行旧代码；之后的调用使用新值
+    pub fn replace_global(&mut self, name: &str, value: Value) -> VMResult<Value> {
+        match self.globals.get_mut(name) {
+            Some(slot) => Ok(std::mem::replace(slot, value)),
+            None => Err(VMError::UndefinedVariable(name.to_string())),
+        }
+    }
+
+    /// 执行字节码（全局变量在多次执行之间保留）
     pub fn execute(&mut self, chunk: Chunk) -> VMResult<()> {
+        self.load(chunk);
+        self.resume()
+    }
+
+    /// 加载字节码但不执行，之后用 `run_steps` 分段执行或用 `resume` 运行到结束
+    pub fn load(&mut self, chunk: Chunk) {
+        // 清除上一次执行出错时留下的帧
+        self.frames.clear();
+        self.stack.clear();
+        self.current_frame = 0;
+
         // 创建主函数帧
+        let max_stack = chunk.max_stack_depth(0);
         let main_function = Function {
             name: "<script>".to_string(),
             arity: 0,
             chunk,
             locals_count: 0,
+            max_stack,
         };
+        self.stack.reserve(max_stack);
 
         self.frames.push(CallFrame {
             function: main_function,
             ip: 0,
             stack_offset: 0,
         });
+    }
+
+    /// 从当前位置运行到程序结束（如恢复快照之后）
+    pub fn resume(&mut self) -> VMResult<()> {
+        self.run(None).map(|_| ())
+    }
+
+    /// 最多执行 `max_steps` 条指令，程序已结束时返回 true
+    pub fn run_steps(&mut self, max_steps: usize) -> VMResult<bool> {
+        self.run(Some(max_steps))
+    }
+
+    /// 运行指令，出错时附上出错指令的位置
+    fn run(&mut self, max_steps: Option<usize>) -> VMResult<bool> {
+        self.run_instructions(max_steps).map_err(|error| self.locate_error(error))
+    }
+
+    /// 为错误附上当前帧正在执行的指令；`Return` 切换帧之后的错误已在指令内部定位
+    fn locate_error(&self, error: VMError) -> VMError {
+        match self.frames.get(self.current_frame) {
+            Some(frame) => Self::error_at(frame, error),
+            None => error,
+        }
+    }
 
-        self.run()
+    /// 为错误附上指定帧中刚执行的指令；指令指针在执行前已递增，出错的指令在 ip - 1
+    /// `exit` 不是错误，保持原样
+    fn error_at(frame: &CallFrame, error: VMError) -> VMError {
+        if matches!(error, VMError::Exit(_) | VMError::AtInstruction { .. }) {
+            return error;
+        }
+        let ip = frame.ip.saturating_sub(1);
+        match frame.function.chunk.code.get(ip) {
+            Some(opcode) => VMError::AtInstruction {
+                function: frame.function.name.clone(),
+                ip,
+                opcode: opcode.clone(),
+                error: Box::new(error),
+            },
+            None => error,
+        }
     }
 
-    /// 主执行循环
-    fn run(&mut self) -> VMResult<()> {
+    /// 主执行循环，`max_steps` 为 None 时运行到结束；返回程序是否已结束
+    fn run_instructions(&mut self, max_steps: Option<usize>) -> VMResult<bool> {
+        let mut steps = 0;
         loop {
+            if self.frames.is_empty() {
+                return Ok(true);
+            }
+            if max_steps == Some(steps) {
+                return Ok(false);
+            }
+            steps += 1;
+
             let frame = &self.frames[self.current_frame];
             
             // 调试输出（可选）
@@ -141,7 +343,7 @@ impl VM {
 
                 // 算术运算
                 OpCode::Add => self.binary_op(|a, b| match (a, b) {
-                    (Value::Integer(x), Value::Integer(y)) => Ok(Value::Integer(x + y)),
+                    (Value::Integer(x), Value::Integer(y)) => x.checked_add(y).map(Value::Integer).ok_or(VMError::IntegerOverflow),
                     (Value::Float(x), Value::Float(y)) => Ok(Value::Float(x + y)),
                     (Value::Integer(x), Value::Float(y)) => Ok(Value::Float(x as f64 + y)),
                     (Value::Float(x), Value::Integer(y)) => Ok(Value::Float(x + y as f64)),
@@ -150,7 +352,7 @@ impl VM {
                 })?,
 
                 OpCode::Subtract => self.binary_op(|a, b| match (a, b) {
-                    (Value::Integer(x), Value::Integer(y)) => Ok(Value::Integer(x - y)),
+                    (Value::Integer(x), Value::Integer(y)) => x.checked_sub(y).map(Value::Integer).ok_or(VMError::IntegerOverflow),
                     (Value::Float(x), Value::Float(y)) => Ok(Value::Float(x - y)),
                     (Value::Integer(x), Value::Float(y)) => Ok(Value::Float(x as f64 - y)),
                     (Value::Float(x), Value::Integer(y)) => Ok(Value::Float(x - y as f64)),
@@ -158,55 +360,39 @@ impl VM {
                 })?,
 
                 OpCode::Multiply => self.binary_op(|a, b| match (a, b) {
-                    (Value::Integer(x), Value::Integer(y)) => Ok(Value::Integer(x * y)),
+                    (Value::Integer(x), Value::Integer(y)) => x.checked_mul(y).map(Value::Integer).ok_or(VMError::IntegerOverflow),
                     (Value::Float(x), Value::Float(y)) => Ok(Value::Float(x * y)),
                     (Value::Integer(x), Value::Float(y)) => Ok(Value::Float(x as f64 * y)),
                     (Value::Float(x), Value::Integer(y)) => Ok(Value::Float(x * y as f64)),
                     _ => Err(VMError::TypeError("Invalid operands for multiplication".to_string())),
                 })?,
 
-                OpCode::Divide => self.binary_op(|a, b| match (a, b) {
-                    (Value::Integer(x), Value::Integer(y)) => {
-                        if y == 0 {
-                            return Err(VMError::DivisionByZero);
-                        }
-                        Ok(Value::Integer(x / y))
-                    }
-                    (Value::Float(x), Value::Float(y)) => {
-                        if y == 0.0 {
-                            return Err(VMError::DivisionByZero);
-                        }
-                        Ok(Value::Float(x / y))
-                    }
-                    (Value::Integer(x), Value::Float(y)) => {
-                        if y == 0.0 {
-                            return Err(VMError::DivisionByZero);
-                        }
-                        Ok(Value::Float(x as f64 / y))
-                    }
-                    (Value::Float(x), Value::Integer(y)) => {
-                        if y == 0 {
-                            return Err(VMError::DivisionByZero);
-                        }
-                        Ok(Value::Float(x / y as f64))
-                    }
-                    _ => Err(VMError::TypeError("Invalid operands for division".to_string())),
-                })?,
+                OpCode::Divide => {
+                    self.check_divisor("/")?;
+                    self.binary_op(|a, b| match (a, b) {
+                        (Value::Integer(x), Value::Integer(y)) => x.checked_div(y).map(Value::Integer).ok_or(VMError::IntegerOverflow),
+                        (Value::Float(x), Value::Float(y)) => Ok(Value::Float(x / y)),
+                        (Value::Integer(x), Value::Float(y)) => Ok(Value::Float(x as f64 / y)),
+                        (Value::Float(x), Value::Integer(y)) => Ok(Value::Float(x / y as f64)),
+                        _ => Err(VMError::TypeError("Invalid operands for division".to_string())),
+                    })?
+                }
 
-                OpCode::Modulo => self.binary_op(|a, b| match (a, b) {
-                    (Value::Integer(x), Value::Integer(y)) => {
-                        if y == 0 {
-                            return Err(VMError::DivisionByZero);
-                        }
-                        Ok(Value::Integer(x % y))
-                    }
-                    _ => Err(VMError::TypeError("Invalid operands for modulo".to_string())),
-                })?,
+                OpCode::Modulo => {
+                    self.check_divisor("%")?;
+                    self.binary_op(|a, b| match (a, b) {
+                        (Value::Integer(x), Value::Integer(y)) => x.checked_rem(y).map(Value::Integer).ok_or(VMError::IntegerOverflow),
+                        _ => Err(VMError::TypeError("Invalid operands for modulo".to_string())),
+                    })?
+                }
 
                 OpCode::Negate => {
                     let value = self.pop()?;
                     let result = match value {
-                        Value::Integer(i) => Value::Integer(-i),
+                        Value::Integer(i) => {
+                            let negated = i.checked_neg().ok_or(VMError::IntegerOverflow)?;
+                            Value::Integer(self.check_int_width(negated)?)
+                        }
                         Value::Float(f) => Value::Float(-f),
                         _ => return Err(VMError::TypeError("Cannot negate non-numeric value".to_string())),
                     };
@@ -226,10 +412,10 @@ impl VM {
                     self.push(Value::Boolean(a != b))?;
                 }
 
-                OpCode::Greater => self.comparison_op(|a, b| a > b)?,
-                OpCode::GreaterEqual => self.comparison_op(|a, b| a >= b)?,
-                OpCode::Less => self.comparison_op(|a, b| a < b)?,
-                OpCode::LessEqual => self.comparison_op(|a, b| a <= b)?,
+                OpCode::Greater => self.comparison_op(|o| o == Some(Ordering::Greater))?,
+                OpCode::GreaterEqual => self.comparison_op(|o| matches!(o, Some(Ordering::Greater | Ordering::Equal)))?,
+                OpCode::Less => self.comparison_op(|o| o == Some(Ordering::Less))?,
+                OpCode::LessEqual => self.comparison_op(|o| matches!(o, Some(Ordering::Less | Ordering::Equal)))?,
 
                 // 逻辑运算
                 OpCode::Not => {
@@ -289,6 +475,11 @@ impl VM {
                             
                             // 移除function对象，参数上移
                             self.stack.remove(stack_offset);
+
+                            // 预留被调用函数所需的栈空间（参数已在栈上）
+                            self.stack.reserve(func.max_stack.saturating_sub(arg_count));
+
+                            // 可能出错的检查都在压入新帧之前完成，出错时仍定位到调用者的 Call
                             
                             self.frames.push(CallFrame {
                                 function: func,
@@ -297,25 +488,61 @@ impl VM {
                             });
                             self.current_frame += 1;
                         }
+                        Value::NativeFunction(native) => {
+                            if native.arity != arg_count {
+                                return Err(VMError::InvalidOperation(
+                                    format!("Expected {} arguments but got {}", native.arity, arg_count)
+                                ));
+                            }
+
+                            // 栈布局: [..., native, arg1, arg2, ...]
+                            let callee_slot = self.stack.len() - arg_count - 1;
+                            let args = self.stack.split_off(callee_slot + 1);
+                            self.stack.truncate(callee_slot);
+
+                            let result = (native.function)(self, &args).map_err(|err| match err {
+                                NativeError::Message(message) => VMError::InvalidOperation(message),
+                                NativeError::Exit(code) => VMError::Exit(code),
+                            })?;
+                            self.push(result)?;
+                        }
                         _ => return Err(VMError::TypeError("Can only call functions".to_string())),
                     }
                 }
 
                 OpCode::Return => {
+                    let frame_offset = self.frames[self.current_frame].stack_offset;
+
+                    // 调试模式下检查栈平衡：帧内只能有局部变量槽位和返回值，
+                    // 从嵌套作用域返回时部分局部变量可能尚未分配，故只检查上界
+                    if cfg!(debug_assertions) {
+                        let frame = &self.frames[self.current_frame];
+                        let depth = self.stack.len().saturating_sub(frame_offset);
+                        if depth == 0 || depth > frame.function.locals_count + 1 {
+                            return Err(VMError::StackImbalance {
+                                function: frame.function.name.clone(),
+                                depth,
+                            });
+                        }
+                    }
+
                     let result = self.pop()?;
                     
                     // 清理当前帧的栈
-                    let frame_offset = self.frames[self.current_frame].stack_offset;
                     self.stack.truncate(frame_offset);
                     
-                    self.frames.pop();
+                    // 保留弹出的帧：切换到调用者之后出错仍定位到这条 Return
+                    let returned = self.frames.pop();
                     
                     if self.frames.is_empty() {
-                        return Ok(());
+                        return Ok(true);
                     }
                     
                     self.current_frame -= 1;
-                    self.push(result)?;
+                    self.push(result).map_err(|error| match &returned {
+                        Some(frame) => Self::error_at(frame, error),
+                        None => error,
+                    })?;
                 }
 
                 // 栈操作
@@ -337,7 +564,7 @@ impl VM {
                     }
                     // 反转以保持正确顺序
                     elements.reverse();
-                    self.push(Value::Array(elements))?;
+                    self.push(Value::array(elements))?;
                 }
 
                 OpCode::ArrayGet => {
@@ -351,6 +578,7 @@ impl VM {
                     
                     match array {
                         Value::Array(arr) => {
+                            let arr = arr.borrow();
                             let actual_idx = if idx < 0 {
                                 // 负索引：从末尾访问
                                 let len = arr.len() as i64;
@@ -367,10 +595,57 @@ impl VM {
                             
                             self.push(arr[actual_idx].clone())?;
                         }
+                        Value::Range { .. } => {
+                            let element = usize::try_from(idx).ok().and_then(|i| array.range_get(i));
+                            match element {
+                                Some(value) => self.push(Value::Integer(value))?,
+                                None => return Err(VMError::InvalidOperation(format!(
+                                    "Range index {} out of bounds (length: {})",
+                                    idx,
+                                    array.range_len().unwrap_or(0)
+                                ))),
+                            }
+                        }
+                        // 字符串按字符（而非字节）索引，负索引同样从末尾访问
+                        Value::String(s) => {
+                            let len = s.chars().count() as i64;
+                            let actual_idx = if idx < 0 { len + idx } else { idx };
+                            match usize::try_from(actual_idx).ok().and_then(|i| s.chars().nth(i)) {
+                                Some(c) => self.push(Value::Char(c))?,
+                                None => return Err(VMError::InvalidOperation(
+                                    format!("String index {} out of bounds (length: {})", idx, len)
+                                )),
+                            }
+                        }
                         _ => return Err(VMError::TypeError("Can only index arrays".to_string())),
                     }
                 }
 
+                OpCode::NewRange(inclusive) => {
+                    let end = self.pop()?;
+                    let start = self.pop()?;
+                    match (start, end) {
+                        (Value::Integer(start), Value::Integer(end)) => {
+                            self.push(Value::Range { start, end, step: 1, inclusive })?;
+                        }
+                        _ => return Err(VMError::TypeError("Range bounds must be integers".to_string())),
+                    }
+                }
+
+                OpCode::RangeStep => {
+                    let step = self.pop()?;
+                    let range = self.pop()?;
+                    match (range, step) {
+                        (_, Value::Integer(0)) => {
+                            return Err(VMError::InvalidOperation("Range step cannot be zero".to_string()));
+                        }
+                        (Value::Range { start, end, inclusive, .. }, Value::Integer(step)) => {
+                            self.push(Value::Range { start, end, step, inclusive })?;
+                        }
+                        _ => return Err(VMError::TypeError("Range step must be an integer".to_string())),
+                    }
+                }
+
                 OpCode::ArraySet => {
                     let value = self.pop()?;
                     let index = self.pop()?;
@@ -381,37 +656,39 @@ impl VM {
                         _ => return Err(VMError::TypeError("Array index must be an integer".to_string())),
                     };
 
-                    // 我们需要可变引用来修改数组
-                    // 但由于所有权问题，这里需要重新构建数组
-                    match array {
-                        Value::Array(mut arr) => {
-                            let actual_idx = if idx < 0 {
-                                let len = arr.len() as i64;
-                                (len + idx) as usize
-                            } else {
-                                idx as usize
-                            };
-
-                            if actual_idx >= arr.len() {
-                                return Err(VMError::InvalidOperation(
-                                    format!("Array index {} out of bounds (length: {})", idx, arr.len())
-                                ));
-                            }
+                    // 数组存储被变量和栈上的副本共享时，as_array_mut 先复制再写入，保持值语义
+                    let mut array = array;
+                    let Some(mut arr) = array.as_array_mut() else {
+                        return Err(VMError::TypeError("Can only index arrays".to_string()));
+                    };
+                    let actual_idx = if idx < 0 {
+                        let len = arr.len() as i64;
+                        (len + idx) as usize
+                    } else {
+                        idx as usize
+                    };
 
-                            arr[actual_idx] = value.clone();
-                            // 只推送修改后的数组，不推送值
-                            // 这样调用者可以决定如何处理结果
-                            self.push(Value::Array(arr))?;
-                        }
-                        _ => return Err(VMError::TypeError("Can only index arrays".to_string())),
+                    if actual_idx >= arr.len() {
+                        return Err(VMError::InvalidOperation(
+                            format!("Array index {} out of bounds (length: {})", idx, arr.len())
+                        ));
                     }
+
+                    arr[actual_idx] = value;
+                    drop(arr);
+                    // 只推送修改后的数组，不推送值
+                    // 这样调用者可以决定如何处理结果
+                    self.push(array)?;
                 }
 
                 OpCode::ArrayLen => {
                     let array = self.pop()?;
                     match array {
                         Value::Array(arr) => {
-                            self.push(Value::Integer(arr.len() as i64))?;
+                            self.push(Value::Integer(arr.borrow().len() as i64))?;
+                        }
+                        range @ Value::Range { .. } => {
+                            self.push(Value::Integer(range.range_len().unwrap_or(0) as i64))?;
                         }
                         _ => return Err(VMError::TypeError("Can only get length of arrays".to_string())),
                     }
@@ -480,11 +757,27 @@ impl VM {
                 // 其他
                 OpCode::Print => {
                     let value = self.pop()?;
-                    println!("{}", value.to_string());
+                    self.print_line(&value);
                 }
 
                 OpCode::Halt => {
-                    return Ok(());
+                    self.frames.clear();
+                    return Ok(true);
+                }
+
+                OpCode::Extension(id, operand) => {
+                    let handler = *self.extensions.get(&id).ok_or_else(|| {
+                        VMError::InvalidOperation(format!("Unregistered extension opcode #{}", id))
+                    })?;
+                    // 只把当前帧的栈区域交给处理器，执行后再接回
+                    let frame_base = self.frames[self.current_frame].stack_offset;
+                    let mut frame_stack = self.stack.split_off(frame_base);
+                    let result = handler(&mut frame_stack, operand);
+                    self.stack.append(&mut frame_stack);
+                    result?;
+                    if self.stack.len() > 1024 {
+                        return Err(VMError::StackOverflow);
+                    }
                 }
             }
         }
@@ -517,22 +810,65 @@ impl VM {
     {
         let b = self.pop()?;
         let a = self.pop()?;
-        let result = op(a, b)?;
+        let result = match op(a, b)? {
+            Value::Integer(i) => Value::Integer(self.check_int_width(i)?),
+            other => other,
+        };
         self.push(result)
     }
 
+    /// 除法和取模前检查除数，为零时报告运算符、被除数和行号；
+    /// 操作数类型不适用于该运算时不检查，由运算本身报告类型错误
+    fn check_divisor(&self, operator: &'static str) -> VMResult<()> {
+        let operands_valid = match (self.peek(1)?, self.peek(0)?) {
+            (Value::Integer(_), Value::Integer(_)) => true,
+            (Value::Integer(_) | Value::Float(_), Value::Integer(_) | Value::Float(_)) => operator == "/",
+            _ => false,
+        };
+        let is_zero = match self.peek(0)? {
+            Value::Integer(0) => true,
+            Value::Float(y) => *y == 0.0,
+            _ => false,
+        };
+        if !operands_valid || !is_zero {
+            return Ok(());
+        }
+
+        // 手写或加载的字节码中行号表可能比指令短
+        let frame = &self.frames[self.current_frame];
+        let line = frame.function.chunk.lines.get(frame.ip - 1).copied().unwrap_or(0);
+        Err(VMError::DivisionByZero {
+            operator,
+            numerator: self.peek(1)?.to_string(),
+            line,
+        })
+    }
+
+    /// 检查整数结果是否在当前位宽范围内
+    fn check_int_width(&self, value: i64) -> VMResult<i64> {
+        if self.int_width.contains(value) {
+            Ok(value)
+        } else {
+            Err(VMError::IntegerOverflow)
+        }
+    }
+
+    /// 比较两个数值，`op` 根据比较结果给出布尔值（NaN 参与比较时结果为 None）
+    /// 两个整数直接比较，不经过 f64，超过 2^53 的整数也能正确比较
     fn comparison_op<F>(&mut self, op: F) -> VMResult<()>
     where
-        F: FnOnce(f64, f64) -> bool,
+        F: FnOnce(Option<Ordering>) -> bool,
     {
         let b = self.pop()?;
         let a = self.pop()?;
 
         let result = match (a, b) {
-            (Value::Integer(x), Value::Integer(y)) => op(x as f64, y as f64),
-            (Value::Float(x), Value::Float(y)) => op(x, y),
-            (Value::Integer(x), Value::Float(y)) => op(x as f64, y),
-            (Value::Float(x), Value::Integer(y)) => op(x, y as f64),
+            (Value::Integer(x), Value::Integer(y)) => op(Some(x.cmp(&y))),
+            (Value::Float(x), Value::Float(y)) => op(x.partial_cmp(&y)),
+            (Value::Integer(x), Value::Float(y)) => op((x as f64).partial_cmp(&y)),
+            (Value::Float(x), Value::Integer(y)) => op(x.partial_cmp(&(y as f64))),
+            // 字符按码位比较
+            (Value::Char(x), Value::Char(y)) => op(Some(x.cmp(&y))),
             _ => return Err(VMError::TypeError("Cannot compare non-numeric values".to_string())),
         };
 
@@ -544,4 +880,1262 @@ impl Default for VM {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+// print 语句和内置 print 函数共用同一个输出目标和上限
+impl NativeHost for VM {
+    fn print_line(&mut self, value: &Value) {
+        let line = self.format_for_print(value);
+        match &mut self.captured_output {
+            Some(output) => {
+                output.push_str(&line);
+                output.push('\n');
+            }
+            None => println!("{}", line),
+        }
+    }
+
+    fn int_width(&self) -> IntWidth {
+        self.int_width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::type_checker::TypeChecker;
+
+    fn chunk_with_add(a: i64, b: i64) -> Chunk {
+        let mut chunk = Chunk::new();
+        let a = chunk.add_constant(Value::Integer(a));
+        let b = chunk.add_constant(Value::Integer(b));
+        chunk.write(OpCode::LoadConst(a), 1);
+        chunk.write(OpCode::LoadConst(b), 1);
+        chunk.write(OpCode::Add, 1);
+        chunk.write(OpCode::Pop, 1);
+        chunk.write(OpCode::Halt, 1);
+        chunk
+    }
+
+    #[test]
+    fn test_i32_mode_detects_overflow() {
+        let chunk = chunk_with_add(i32::MAX as i64, 1);
+        assert!(VM::new().execute(chunk.clone()).is_ok());
+
+        let result = VM::new().with_int_width(IntWidth::I32).execute(chunk).map_err(VMError::into_inner);
+        assert!(matches!(result, Err(VMError::IntegerOverflow)));
+    }
+
+    #[test]
+    fn test_i64_overflow_is_reported() {
+        let chunk = chunk_with_add(i64::MAX, 1);
+        assert!(matches!(VM::new().execute(chunk).map_err(VMError::into_inner), Err(VMError::IntegerOverflow)));
+    }
+
+    #[test]
+    fn test_return_detects_stack_imbalance() {
+        // 函数体多压入一个值却没有Pop
+        let mut body = Chunk::new();
+        let one = body.add_constant(Value::Integer(1));
+        body.write(OpCode::LoadConst(one), 1);
+        body.write(OpCode::LoadConst(one), 1);
+        body.write(OpCode::Return, 1);
+        let function = Function {
+            name: "leaky".to_string(),
+            arity: 0,
+            chunk: body,
+            locals_count: 0,
+            max_stack: 2,
+        };
+
+        let mut chunk = Chunk::new();
+        let callee = chunk.add_constant(Value::Function(function));
+        chunk.write(OpCode::LoadConst(callee), 1);
+        chunk.write(OpCode::Call(0), 1);
+        chunk.write(OpCode::Pop, 1);
+        chunk.write(OpCode::Halt, 1);
+
+        match VM::new().execute(chunk).map_err(VMError::into_inner) {
+            Err(VMError::StackImbalance { function, depth }) => {
+                assert_eq!(function, "leaky");
+                assert_eq!(depth, 2);
+            }
+            other => panic!("expected stack imbalance, got {:?}", other),
+        }
+
+        // 帧内的局部变量槽位不算失衡：从嵌套作用域和循环中返回
+        let vm = run_source(
+            "fn find(n: int) -> int {\n let base = n * 2;\n for i in 0..n {\n let twice = i * 2;\n if twice > base - 3 {\n let hit = i;\n return hit;\n }\n }\n return -1;\n}\nlet a = find(5);\nlet b = find(0);",
+        );
+        assert_eq!(vm.get_global("a"), Some(&Value::Integer(4)));
+        assert_eq!(vm.get_global("b"), Some(&Value::Integer(-1)));
+    }
+
+    #[test]
+    fn test_error_after_return_is_located_in_returning_function() {
+        // 返回值压回调用者的栈时溢出：错误属于被调用函数的 Return，而不是调用者的 Call
+        let mut body = Chunk::new();
+        body.write(OpCode::Return, 7);
+        let callee = Function { name: "inner".to_string(), arity: 0, chunk: body, locals_count: 0, max_stack: 1 };
+
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::Call(0), 1);
+        chunk.write(OpCode::Halt, 1);
+        let mut vm = VM::new();
+        vm.load(chunk);
+        vm.frames[0].ip = 1;
+        vm.stack = vec![Value::Null; 1101];
+        vm.frames.push(CallFrame { function: callee, ip: 0, stack_offset: 1100 });
+        vm.current_frame = 1;
+
+        match vm.resume() {
+            Err(VMError::AtInstruction { function, ip, opcode, error }) => {
+                assert_eq!((function.as_str(), ip, opcode), ("inner", 0, OpCode::Return));
+                assert!(matches!(*error, VMError::StackOverflow));
+            }
+            other => panic!("expected a located stack overflow, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_division_by_zero_reports_operands() {
+        for (op, symbol, name) in [(OpCode::Divide, "/", "Divide"), (OpCode::Modulo, "%", "Modulo")] {
+            let mut chunk = Chunk::new();
+            let seven = chunk.add_constant(Value::Integer(7));
+            let zero = chunk.add_constant(Value::Integer(0));
+            chunk.write(OpCode::LoadConst(seven), 4);
+            chunk.write(OpCode::LoadConst(zero), 4);
+            chunk.write(op, 4);
+            chunk.write(OpCode::Halt, 4);
+
+            let err = VM::new().execute(chunk).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("division by zero: 7 {} 0 at line 4 (in <script> at 0002: {})", symbol, name)
+            );
+        }
+
+
+        // 行号表比指令短时行号记为 0，不会越界
+        let mut chunk = Chunk::new();
+        let seven = chunk.add_constant(Value::Integer(7));
+        let zero = chunk.add_constant(Value::Integer(0));
+        chunk.code = vec![OpCode::LoadConst(seven), OpCode::LoadConst(zero), OpCode::Divide, OpCode::Halt];
+        assert!(matches!(
+            VM::new().execute(chunk).map_err(VMError::into_inner),
+            Err(VMError::DivisionByZero { line: 0, .. })
+        ));
+
+        // 操作数类型无效时先报告类型错误
+        for (left, right, op) in [
+            (Value::String("a".to_string()), Value::Integer(0), OpCode::Divide),
+            (Value::Float(5.0), Value::Integer(0), OpCode::Modulo),
+        ] {
+            let mut chunk = Chunk::new();
+            let left = chunk.add_constant(left);
+            let right = chunk.add_constant(right);
+            chunk.write(OpCode::LoadConst(left), 1);
+            chunk.write(OpCode::LoadConst(right), 1);
+            chunk.write(op, 1);
+            chunk.write(OpCode::Halt, 1);
+            assert!(matches!(
+                VM::new().execute(chunk).map_err(VMError::into_inner),
+                Err(VMError::TypeError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_runtime_error_reports_instruction() {
+        let source = r#"
+            fn get(items: [int; 3], i: int) -> int {
+                return items[i];
+            }
+            let items = [1, 2, 3];
+            let ok = get(items, 0);
+            let bad = get(items, 5);
+        "#;
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let chunk = Compiler::new().compile(program).unwrap();
+        let mut vm = VM::new();
+
+        // 错误发生在函数 get 中，ip 是该函数字节码中的 ArrayGet 指令
+        match vm.execute(chunk) {
+            Err(VMError::AtInstruction { function, ip, opcode, error }) => {
+                assert_eq!(function, "get");
+                assert_eq!(opcode, OpCode::ArrayGet);
+                let Some(Value::Function(get)) = vm.get_global("get") else {
+                    panic!("expected function global 'get'");
+                };
+                assert_eq!(get.chunk.code[ip], OpCode::ArrayGet);
+                assert!(matches!(*error, VMError::InvalidOperation(_)));
+            }
+            other => panic!("expected located runtime error, got {:?}", other),
+        }
+
+        // exit 不是错误，不附带位置
+        let tokens = Lexer::new("exit(2);".to_string()).tokenize().unwrap();
+        let chunk = Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap();
+        assert!(matches!(VM::new().execute(chunk), Err(VMError::Exit(2))));
+    }
+
+    #[test]
+    fn test_call_reserves_max_stack() {
+        let mut body = Chunk::new();
+        body.write(OpCode::LoadNull, 1);
+        body.write(OpCode::Return, 1);
+        let function = Function {
+            name: "deep".to_string(),
+            arity: 0,
+            chunk: body,
+            locals_count: 0,
+            max_stack: 1024,
+        };
+
+        let mut chunk = Chunk::new();
+        let callee = chunk.add_constant(Value::Function(function));
+        chunk.write(OpCode::LoadConst(callee), 1);
+        chunk.write(OpCode::Call(0), 1);
+        chunk.write(OpCode::Pop, 1);
+        chunk.write(OpCode::Halt, 1);
+
+        let mut vm = VM::new();
+        vm.execute(chunk).unwrap();
+        assert!(vm.stack.capacity() >= 1024);
+    }
+
+    fn run_source(source: &str) -> VM {
+        let mut lexer = Lexer::new(source.to_string());
+        let tokens = lexer.tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        TypeChecker::new().check(&program).unwrap();
+        let chunk = Compiler::new().compile(program).unwrap();
+
+        let mut vm = VM::new();
+        vm.execute(chunk).unwrap();
+        vm
+    }
+
+    #[test]
+    fn test_builtin_aliased_to_local() {
+        let vm = run_source(r#"
+            fn count(items) {
+                let size = len;
+                return size(items);
+            }
+            let n = count([1, 2, 3]);
+        "#);
+        assert_eq!(vm.globals.get("n"), Some(&Value::Integer(3)));
+    }
+
+    #[test]
+    fn test_trailing_expression_is_returned() {
+        let vm = run_source(r#"
+            fn add(a: int, b: int) -> int {
+                a + b
+            }
+            let sum = add(2, 3);
+        "#);
+        assert_eq!(vm.globals.get("sum"), Some(&Value::Integer(5)));
+    }
+
+    #[test]
+    fn test_range_value_iterated_twice() {
+        let vm = run_source(r#"
+            let r = 0..5;
+            var total = 0;
+            for i in r {
+                total = total + i;
+            }
+            for i in r {
+                total = total + i;
+            }
+            let n = len(r);
+            let items = to_array(1..=3);
+            let has = contains(r, 3);
+        "#);
+        assert_eq!(vm.globals.get("total"), Some(&Value::Integer(20)));
+        assert_eq!(vm.globals.get("n"), Some(&Value::Integer(5)));
+        assert_eq!(
+            vm.globals.get("items"),
+            Some(&Value::array(vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)]))
+        );
+        assert_eq!(vm.globals.get("has"), Some(&Value::Boolean(true)));
+
+        // 带步长的范围，步长可以为负
+        let vm = run_source(r#"
+            let evens = 0..10 step 2;
+            var total = 0;
+            for i in evens { total = total + i; }
+            let down = to_array(5..=1 step -2);
+            let n = len(evens);
+            let has = contains(evens, 3);
+        "#);
+        assert_eq!(vm.globals.get("total"), Some(&Value::Integer(20)));
+        assert_eq!(
+            vm.globals.get("down"),
+            Some(&Value::array(vec![Value::Integer(5), Value::Integer(3), Value::Integer(1)]))
+        );
+        assert_eq!(vm.globals.get("n"), Some(&Value::Integer(5)));
+        assert_eq!(vm.globals.get("has"), Some(&Value::Boolean(false)));
+
+        // 步长为 0 是运行时错误
+        let tokens = Lexer::new("var s = 0; let r = 0..3 step s;".to_string()).tokenize().unwrap();
+        let chunk = Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap();
+        let err = VM::new().execute(chunk).map_err(VMError::into_inner).unwrap_err();
+        assert!(matches!(err, VMError::InvalidOperation(ref message) if message == "Range step cannot be zero"));
+    }
+
+    #[test]
+    fn test_ranges_spanning_most_of_i64() {
+        let vm = run_source(r#"
+            let r = (0 - 9000000000000000000)..9000000000000000000;
+            var first = 0;
+            for i in r { first = i; break; }
+            let n = len(r);
+            let second = r[1];
+            let near_end = contains(r, 8999999999999999999);
+            let at_end = contains(r, 9000000000000000000);
+        "#);
+        assert_eq!(vm.globals.get("first"), Some(&Value::Integer(-9_000_000_000_000_000_000)));
+        // 真实长度超过 i64::MAX 时饱和
+        assert_eq!(vm.globals.get("n"), Some(&Value::Integer(i64::MAX)));
+        assert_eq!(vm.globals.get("second"), Some(&Value::Integer(-8_999_999_999_999_999_999)));
+        assert_eq!(vm.globals.get("near_end"), Some(&Value::Boolean(true)));
+        assert_eq!(vm.globals.get("at_end"), Some(&Value::Boolean(false)));
+
+        let down = Value::Range { start: i64::MAX, end: i64::MIN, step: -3, inclusive: true };
+        assert_eq!(down.range_len(), Some(6_148_914_691_236_517_206));
+        assert_eq!(down.range_get(6_148_914_691_236_517_205), Some(i64::MIN));
+        assert!(down.range_contains(i64::MAX - 6));
+        assert!(down.range_contains(i64::MIN));
+        assert!(!down.range_contains(i64::MIN + 1));
+    }
+
+    #[test]
+    fn test_if_expression_binds_value() {
+        let vm = run_source(r#"
+            fn sign(n: int) -> int {
+                let s = if n < 0 { -1 } else if n == 0 { 0 } else { 1 };
+                return s;
+            }
+            let a = sign(-5);
+            let b = sign(0);
+            let label = if b == 0 { "zero" } else { "nonzero" };
+        "#);
+        assert_eq!(vm.globals.get("a"), Some(&Value::Integer(-1)));
+        assert_eq!(vm.globals.get("b"), Some(&Value::Integer(0)));
+        assert_eq!(vm.globals.get("label"), Some(&Value::String("zero".to_string())));
+    }
+
+    #[test]
+    fn test_nested_index_assignment_evaluates_indices_once() {
+        let vm = run_source(r#"
+            var calls = 0;
+            fn next() -> int { calls += 1; return calls - 1; }
+            var m: [[int]] = [[1, 2], [3, 4]];
+            m[next()][0] = 9;
+            var cube = [[[0, 0], [0, 0]], [[0, 0], [0, 0]]];
+            cube[next()][next() - 1][0] = 7;
+            fn local_write() -> [[int]] {
+                var grid = [[0], [0], [0]];
+                grid[next() - 2][0] = 5;
+                return grid;
+            }
+            let grid = local_write();
+        "#);
+        assert_eq!(vm.get_global("calls"), Some(&Value::Integer(4)));
+        assert_eq!(
+            vm.get_global("m"),
+            Some(&Value::array(vec![
+                Value::array(vec![Value::Integer(9), Value::Integer(2)]),
+                Value::array(vec![Value::Integer(3), Value::Integer(4)]),
+            ]))
+        );
+        let Some(Value::Array(cube)) = vm.get_global("cube") else {
+            panic!("expected cube array");
+        };
+        assert_eq!(
+            cube.borrow()[1],
+            Value::array(vec![
+                Value::array(vec![Value::Integer(0), Value::Integer(0)]),
+                Value::array(vec![Value::Integer(7), Value::Integer(0)]),
+            ])
+        );
+        assert_eq!(
+            vm.get_global("grid"),
+            Some(&Value::array(vec![
+                Value::array(vec![Value::Integer(0)]),
+                Value::array(vec![Value::Integer(5)]),
+                Value::array(vec![Value::Integer(0)]),
+            ]))
+        );
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_nested_index_assignment() {
+        let vm = run_source(r#"
+            var m: [[int]] = [[1, 2], [3, 4]];
+            m[1][-1] = 9;
+            m[-2][0] += 6;
+            let corner = m[-1][-2];
+            fn fill(n: int) -> [[[int]]] {
+                var cube = [[[0, 0], [0, 0]], [[0, 0], [0, 0]]];
+                cube[1][0][1] = n;
+                return cube;
+            }
+            let cube = fill(5);
+        "#);
+        assert_eq!(
+            vm.get_global("m"),
+            Some(&Value::array(vec![
+                Value::array(vec![Value::Integer(7), Value::Integer(2)]),
+                Value::array(vec![Value::Integer(3), Value::Integer(9)]),
+            ]))
+        );
+        assert_eq!(vm.get_global("corner"), Some(&Value::Integer(3)));
+        let Some(Value::Array(cube)) = vm.get_global("cube") else {
+            panic!("expected cube array");
+        };
+        assert_eq!(
+            cube.borrow()[1],
+            Value::array(vec![
+                Value::array(vec![Value::Integer(0), Value::Integer(5)]),
+                Value::array(vec![Value::Integer(0), Value::Integer(0)]),
+            ])
+        );
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_struct_literal_fields_in_any_order() {
+        let vm = run_source(r#"
+            struct Point { x: int, y: int, label: string };
+            var calls = 0;
+            fn next() -> int { calls += 1; return calls; }
+            let p = Point { label: "p", y: next(), x: next() };
+            let x = p.x;
+            let y = p.y;
+            let label = p.label;
+            let q = Point { x: 5, y: 6, label: "q" };
+            let qy = q.y;
+        "#);
+        // 字段按名称存放，字段值按书写顺序求值
+        assert_eq!(vm.get_global("x"), Some(&Value::Integer(2)));
+        assert_eq!(vm.get_global("y"), Some(&Value::Integer(1)));
+        assert_eq!(vm.get_global("label"), Some(&Value::String("p".to_string())));
+        assert_eq!(vm.get_global("qy"), Some(&Value::Integer(6)));
+    }
+
+    #[test]
+    fn test_field_index_from_static_type() {
+        let vm = run_source(r#"
+            struct P { x: int, y: int };
+            struct Line { start: P, end: P };
+            type Pt = P;
+            fn make() -> P { return P { x: 1, y: 2 }; }
+            fn alias() -> Pt { return P { x: 3, y: 4 }; }
+            let line = Line { start: P { x: 5, y: 6 }, end: P { x: 7, y: 8 } };
+            let a = make().y;
+            let b = alias().y;
+            let c = line.end.y;
+            var q = make();
+            q.y = 9;
+            let d = q.y;
+            let dx = q.x;
+            let e = line.start.y;
+            let p = if true { P { x: 10, y: 11 } } else { make() };
+            let f = p.y;
+            // 返回类型由类型检查器推断，编译器使用类型检查器的标注
+            fn inferred() { return P { x: 12, y: 13 }; }
+            let g = inferred().y;
+            fn pick(ps: [P]) -> int { return ps[1].y; }
+            let h = pick([P { x: 0, y: 0 }, P { x: 14, y: 15 }]);
+        "#);
+        assert_eq!(vm.get_global("a"), Some(&Value::Integer(2)));
+        assert_eq!(vm.get_global("b"), Some(&Value::Integer(4)));
+        assert_eq!(vm.get_global("c"), Some(&Value::Integer(8)));
+        assert_eq!(vm.get_global("d"), Some(&Value::Integer(9)));
+        assert_eq!(vm.get_global("dx"), Some(&Value::Integer(1)));
+        assert_eq!(vm.get_global("e"), Some(&Value::Integer(6)));
+        assert_eq!(vm.get_global("f"), Some(&Value::Integer(11)));
+        assert_eq!(vm.get_global("g"), Some(&Value::Integer(13)));
+        assert_eq!(vm.get_global("h"), Some(&Value::Integer(15)));
+    }
+
+    #[test]
+    fn test_char_comparison() {
+        let vm = run_source(r#"
+            let c: char = 'z';
+            print(c);
+            let lt = 'a' < 'b';
+            let ge = c >= 'é';
+            let same = c <= 'z';
+        "#);
+        // 按码位比较
+        assert_eq!(vm.get_global("lt"), Some(&Value::Boolean(true)));
+        assert_eq!(vm.get_global("ge"), Some(&Value::Boolean(false)));
+        assert_eq!(vm.get_global("same"), Some(&Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_string_indexing() {
+        let vm = run_source(r#"
+            let s = "héllo";
+            let second = s[1];
+            let last = s[-1];
+            var count = 0;
+            var i = 0;
+            while i < len(s) {
+                if s[i] == 'l' {
+                    count += 1;
+                }
+                i += 1;
+            }
+        "#);
+        // 按字符而非字节索引
+        assert_eq!(vm.get_global("second"), Some(&Value::Char('é')));
+        assert_eq!(vm.get_global("last"), Some(&Value::Char('o')));
+        assert_eq!(vm.get_global("count"), Some(&Value::Integer(2)));
+
+        for source in [r#"let s = "abc"; let c = s[3];"#, r#"let s = "abc"; let c = s[-4];"#, r#"let s = ""; let c = s[0];"#] {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let chunk = Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap();
+            assert!(matches!(
+                VM::new().execute(chunk).map_err(VMError::into_inner),
+                Err(VMError::InvalidOperation(ref message)) if message.starts_with("String index")
+            ), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_postfix_increment_and_decrement() {
+        let vm = run_source(r#"
+            var i = 5;
+            let old = i++;
+            fn sum_below(n: int) -> int {
+                var k = 0;
+                var total = 0;
+                while k < n {
+                    total += k++;
+                }
+                return total;
+            }
+            let sum = sum_below(4);
+            var f = 1.5;
+            let before = f--;
+            var arr = [10, 20, 30];
+            var j = 0;
+            let first = arr[j++];
+            let second = arr[j++];
+            j--;
+        "#);
+        assert_eq!(vm.get_global("old"), Some(&Value::Integer(5)));
+        assert_eq!(vm.get_global("i"), Some(&Value::Integer(6)));
+        assert_eq!(vm.get_global("sum"), Some(&Value::Integer(6)));
+        assert_eq!(vm.get_global("before"), Some(&Value::Float(1.5)));
+        assert_eq!(vm.get_global("f"), Some(&Value::Float(0.5)));
+        assert_eq!(vm.get_global("first"), Some(&Value::Integer(10)));
+        assert_eq!(vm.get_global("second"), Some(&Value::Integer(20)));
+        assert_eq!(vm.get_global("j"), Some(&Value::Integer(1)));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_tuples() {
+        let vm = run_source(r#"
+            fn divmod(a: int, b: int) -> (int, int) {
+                return (a / b, a % b);
+            }
+            let r = divmod(17, 5);
+            let q = r.0;
+            let m = r.1;
+            let nested = ((1, "one"), true);
+            let name = nested.0.1;
+            let single = (42,);
+            let pair = (divmod, 9);
+            let rem = pair.0(pair.1, 2).1;
+        "#);
+        assert_eq!(vm.get_global("q"), Some(&Value::Integer(3)));
+        assert_eq!(vm.get_global("m"), Some(&Value::Integer(2)));
+        assert_eq!(vm.get_global("name"), Some(&Value::String("one".to_string())));
+        // 元组在运行时表示为数组
+        assert_eq!(vm.get_global("single"), Some(&Value::array(vec![Value::Integer(42)])));
+        assert_eq!(vm.get_global("rem"), Some(&Value::Integer(1)));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_higher_order_functions() {
+        let vm = run_source(r#"
+            fn inc(x: int) -> int { return x + 1; }
+            fn double(x: int) -> int { return x * 2; }
+            fn apply(f: fn(int) -> int, x: int) -> int { return f(x); }
+            fn twice(f: fn(int) -> int, x: int) -> int { return f(f(x)); }
+            fn pick(first: bool) -> fn(int) -> int { return first ? inc : double; }
+            let a = apply(inc, 1);
+            let b = twice(double, 3);
+            let fs = [inc, double];
+            let c = fs[1](5) + pick(false)(10);
+        "#);
+        assert_eq!(vm.get_global("a"), Some(&Value::Integer(2)));
+        assert_eq!(vm.get_global("b"), Some(&Value::Integer(12)));
+        assert_eq!(vm.get_global("c"), Some(&Value::Integer(30)));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_conditional_expression() {
+        let vm = run_source(r#"
+            let a = 3;
+            let b = 7;
+            let m = a > b ? a : b;
+            fn sign(n: int) -> int {
+                return n < 0 ? -1 : n == 0 ? 0 : 1;
+            }
+            let signs = [sign(-4), sign(0), sign(9)];
+            var calls = 0;
+            fn bump() -> int { calls = calls + 1; return calls; }
+            // 只求值被选中的分支
+            let picked = a < b ? 10 : bump();
+            let label = m == 7 ? "seven" : "other";
+        "#);
+        assert_eq!(vm.get_global("m"), Some(&Value::Integer(7)));
+        assert_eq!(
+            vm.get_global("signs"),
+            Some(&Value::array(vec![Value::Integer(-1), Value::Integer(0), Value::Integer(1)]))
+        );
+        assert_eq!(vm.get_global("picked"), Some(&Value::Integer(10)));
+        assert_eq!(vm.get_global("calls"), Some(&Value::Integer(0)));
+        assert_eq!(vm.get_global("label"), Some(&Value::String("seven".to_string())));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_struct_literal_fields_out_of_declaration_order() {
+        let vm = run_source(r#"
+            struct Person {
+                name: string,
+                age: int,
+                score: float
+            };
+            fn local_age() -> int {
+                let q = Person { score: 9.5, name: "Bob", age: 40 };
+                return q.age;
+            }
+            let p = Person { age: 30, score: 1.5, name: "Alice" };
+            let name = p.name;
+            let age = p.age;
+            let score = p.score;
+            let q_age = local_age();
+        "#);
+        assert_eq!(vm.globals.get("name"), Some(&Value::String("Alice".to_string())));
+        assert_eq!(vm.globals.get("age"), Some(&Value::Integer(30)));
+        assert_eq!(vm.globals.get("score"), Some(&Value::Float(1.5)));
+        assert_eq!(vm.globals.get("q_age"), Some(&Value::Integer(40)));
+    }
+
+    #[test]
+    fn test_exit_stops_with_code() {
+        let source = r#"
+            var reached = false;
+            fn finish(code: int) {
+                exit(code);
+                reached = true;
+            }
+            finish(2);
+            reached = true;
+        "#;
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        TypeChecker::new().check(&program).unwrap();
+        let chunk = Compiler::new().compile(program).unwrap();
+
+        let mut vm = VM::new();
+        assert!(matches!(vm.execute(chunk), Err(VMError::Exit(2))));
+        assert_eq!(vm.globals.get("reached"), Some(&Value::Boolean(false)));
+    }
+
+    #[test]
+    fn test_assertions() {
+        let run = |source: &str, debug_assertions: bool| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let program = Parser::new(tokens).parse().unwrap();
+            TypeChecker::new().check(&program).unwrap();
+            let chunk = Compiler::new().with_debug_assertions(debug_assertions).compile(program).unwrap();
+            let mut vm = VM::new();
+            vm.execute(chunk).map(|_| vm).map_err(VMError::into_inner)
+        };
+
+        let vm = run("assert(1 < 2); debug_assert(true); let done = true;", true).unwrap();
+        assert_eq!(vm.globals.get("done"), Some(&Value::Boolean(true)));
+
+        assert!(matches!(
+            run("assert(1 > 2);", false),
+            Err(VMError::InvalidOperation(ref message)) if message == "assert() failed"
+        ));
+
+        // debug 模式下检查 debug_assert；release 模式下不执行，参数也不求值
+        let source = "var calls = 0; fn check() -> bool { calls = calls + 1; return false; } debug_assert(check());";
+        assert!(matches!(
+            run(source, true),
+            Err(VMError::InvalidOperation(ref message)) if message == "debug_assert() failed"
+        ));
+        let vm = run(source, false).unwrap();
+        assert_eq!(vm.globals.get("calls"), Some(&Value::Integer(0)));
+
+        // 用户定义的同名函数（顶层或局部）不是内置的 debug_assert，release 模式下照常调用
+        let shadowed = "var calls = 0; fn debug_assert(x: bool) { calls = calls + 1; } debug_assert(false);";
+        let vm = run(shadowed, false).unwrap();
+        assert_eq!(vm.globals.get("calls"), Some(&Value::Integer(1)));
+        let parameter = "var calls = 0; fn count(x: bool) { calls = calls + 1; }
+            fn apply(debug_assert: fn(bool) -> void) { debug_assert(false); } apply(count);";
+        let vm = run(parameter, false).unwrap();
+        assert_eq!(vm.globals.get("calls"), Some(&Value::Integer(1)));
+
+        // 通过别名调用是普通调用，release 模式下同样检查断言
+        assert!(matches!(
+            run("let da = debug_assert; da(false);", false),
+            Err(VMError::InvalidOperation(ref message)) if message == "debug_assert() failed"
+        ));
+    }
+
+    #[test]
+    fn test_char_escape_equality() {
+        let vm = run_source(r#"
+            let hex = '\x41' == 'A';
+            let unicode = '\u{1F600}' == '😀';
+            let distinct = 'a' == 'b';
+        "#);
+        assert_eq!(vm.globals.get("hex"), Some(&Value::Boolean(true)));
+        assert_eq!(vm.globals.get("unicode"), Some(&Value::Boolean(true)));
+        assert_eq!(vm.globals.get("distinct"), Some(&Value::Boolean(false)));
+    }
+
+    #[test]
+    fn test_char_type_annotation() {
+        let vm = run_source(r#"
+            let c: char = 'x';
+            let quote: char = '\'';
+        "#);
+        assert_eq!(vm.globals.get("c"), Some(&Value::Char('x')));
+        assert_eq!(vm.globals.get("quote"), Some(&Value::Char('\'')));
+    }
+
+    #[test]
+    fn test_chained_assignment() {
+        let vm = run_source(r#"
+            var a = 0;
+            var b = 0;
+            a = b = 5;
+
+            fn locals() -> int {
+                var x = 0;
+                var y = 0;
+                x = y = 3;
+                return x * 10 + y;
+            }
+            let local_result = locals();
+
+            // 局部变量与全局变量混合
+            var g = 0;
+            fn mixed() -> int {
+                var l = 0;
+                l = g = 7;
+                g = l = l + 1;
+                return l;
+            }
+            let mixed_result = mixed();
+
+            // 作为表达式使用时产生被赋的值
+            var c = 0;
+            let d = (c = a = 9) + 1;
+
+            // 循环中重复执行，语句上下文不能在栈上残留值
+            var i = 0;
+            var j = 0;
+            while i < 100 {
+                i = j = i + 1;
+            }
+        "#);
+        assert_eq!(vm.globals.get("a"), Some(&Value::Integer(9)));
+        assert_eq!(vm.globals.get("b"), Some(&Value::Integer(5)));
+        assert_eq!(vm.globals.get("local_result"), Some(&Value::Integer(33)));
+        assert_eq!(vm.globals.get("mixed_result"), Some(&Value::Integer(8)));
+        assert_eq!(vm.globals.get("g"), Some(&Value::Integer(8)));
+        assert_eq!(vm.globals.get("c"), Some(&Value::Integer(9)));
+        assert_eq!(vm.globals.get("d"), Some(&Value::Integer(10)));
+        assert_eq!(vm.globals.get("j"), Some(&Value::Integer(100)));
+        assert!(vm.stack.is_empty(), "stack not empty: {:?}", vm.stack);
+    }
+
+    #[test]
+    fn test_struct_equality_and_map_keys() {
+        let vm = run_source(r#"
+            struct Point { x: int, y: int };
+            let a = Point { x: 1, y: 2 };
+            let b = Point { y: 2, x: 1 };
+            let c = Point { x: 2, y: 1 };
+            let same = a == b;
+            let different = a == c;
+            let not_equal = a != c;
+        "#);
+        assert_eq!(vm.globals.get("same"), Some(&Value::Boolean(true)));
+        assert_eq!(vm.globals.get("different"), Some(&Value::Boolean(false)));
+        assert_eq!(vm.globals.get("not_equal"), Some(&Value::Boolean(true)));
+
+        // 相等的结构体产生相同的键
+        let key = |name: &str| vm.globals[name].to_key().unwrap();
+        let mut map = HashMap::new();
+        map.insert(key("a"), "first");
+        map.insert(key("c"), "second");
+        assert_eq!(map.get(&key("b")), Some(&"first"));
+        map.insert(key("b"), "replaced");
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&key("a")], "replaced");
+
+        // 含浮点字段的结构体不能作为键
+        let with_float = Value::Struct(crate::bytecode::StructValue {
+            struct_name: "Vec2".to_string(),
+            fields: vec![Value::Float(0.5), Value::Integer(1)],
+        });
+        assert_eq!(with_float.to_key(), None);
+    }
+
+    #[test]
+    fn test_replace_global_function_hot_reload() {
+        let parse = |source: &str| {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            Parser::new(tokens).parse().unwrap()
+        };
+
+        let mut compiler = Compiler::new();
+        let chunk = compiler.compile(parse("fn speed() -> int { 1 } let before = speed();")).unwrap();
+        let mut vm = VM::new();
+        vm.execute(chunk).unwrap();
+        assert_eq!(vm.get_global("before"), Some(&Value::Integer(1)));
+
+        // 单独重新编译函数并替换
+        let stmt = parse("fn speed() -> int { 2 }").statements.remove(0);
+        let function = compiler.compile_function_declaration(stmt).unwrap();
+        let old = vm.replace_global("speed", Value::Function(function)).unwrap();
+        assert!(matches!(old, Value::Function(ref f) if f.name == "speed"));
+
+        let chunk = Compiler::new().compile(parse("let after = speed();")).unwrap();
+        vm.execute(chunk).unwrap();
+        assert_eq!(vm.get_global("after"), Some(&Value::Integer(2)));
+        assert_eq!(vm.get_global("before"), Some(&Value::Integer(1)));
+
+        assert!(matches!(
+            vm.replace_global("missing", Value::Null),
+            Err(VMError::UndefinedVariable(_))
+        ));
+    }
+
+    #[test]
+    fn test_registered_extension_opcode() {
+        fn square(stack: &mut Vec<Value>, _operand: u32) -> Result<(), VMError> {
+            match stack.pop() {
+                Some(Value::Integer(n)) => {
+                    stack.push(Value::Integer(n * n));
+                    Ok(())
+                }
+                other => Err(VMError::TypeError(format!("square expects an integer, got {:?}", other))),
+            }
+        }
+
+        let mut chunk = Chunk::new();
+        let value = chunk.add_constant(Value::Integer(7));
+        let name = chunk.add_constant(Value::String("result".to_string()));
+        chunk.write(OpCode::LoadConst(value), 1);
+        chunk.write(OpCode::Extension(1, 0), 1);
+        chunk.write(OpCode::StoreGlobal(name), 1);
+        chunk.write(OpCode::Pop, 1);
+        chunk.write(OpCode::Halt, 1);
+
+        let mut vm = VM::new();
+        vm.register_extension(1, square);
+        vm.execute(chunk.clone()).unwrap();
+        assert_eq!(vm.get_global("result"), Some(&Value::Integer(49)));
+
+        // 未注册的扩展报告运行时错误
+        assert!(matches!(VM::new().execute(chunk).map_err(VMError::into_inner), Err(VMError::InvalidOperation(_))));
+
+
+        // 处理器只能看到当前帧的栈区域：调用者压入的 100 不在其中，也不会被弹出
+        fn frame_depth(stack: &mut Vec<Value>, _operand: u32) -> Result<(), VMError> {
+            let depth = stack.len() as i64;
+            stack.push(Value::Integer(depth));
+            Ok(())
+        }
+        let mut body = Chunk::new();
+        body.write(OpCode::Extension(2, 0), 1);
+        body.write(OpCode::Return, 1);
+        let function = Function { name: "depth".to_string(), arity: 1, chunk: body, locals_count: 1, max_stack: 3 };
+
+        let mut chunk = Chunk::new();
+        let outer = chunk.add_constant(Value::Integer(100));
+        let function = chunk.add_constant(Value::Function(function));
+        let argument = chunk.add_constant(Value::Integer(5));
+        let depth = chunk.add_constant(Value::String("depth".to_string()));
+        let kept = chunk.add_constant(Value::String("kept".to_string()));
+        chunk.write(OpCode::LoadConst(outer), 1);
+        chunk.write(OpCode::LoadConst(function), 1);
+        chunk.write(OpCode::LoadConst(argument), 1);
+        chunk.write(OpCode::Call(1), 1);
+        chunk.write(OpCode::StoreGlobal(depth), 1);
+        chunk.write(OpCode::Pop, 1);
+        chunk.write(OpCode::StoreGlobal(kept), 1);
+        chunk.write(OpCode::Pop, 1);
+        chunk.write(OpCode::Halt, 1);
+
+        let mut vm = VM::new();
+        vm.register_extension(2, frame_depth);
+        vm.execute(chunk).unwrap();
+        // 帧内只有参数
+        assert_eq!(vm.get_global("depth"), Some(&Value::Integer(1)));
+        assert_eq!(vm.get_global("kept"), Some(&Value::Integer(100)));
+    }
+
+    #[test]
+    fn test_unary_plus_is_identity() {
+        let vm = run_source("let a = +5 == 5; let b = +-2; let c = -+2.5;");
+        assert_eq!(vm.get_global("a"), Some(&Value::Boolean(true)));
+        assert_eq!(vm.get_global("b"), Some(&Value::Integer(-2)));
+        assert_eq!(vm.get_global("c"), Some(&Value::Float(-2.5)));
+    }
+
+    #[test]
+    fn test_break_and_continue() {
+        let vm = run_source(r#"
+            var i = 0;
+            var w = 0;
+            while i < 10 {
+                i = i + 1;
+                let doubled = i * 2;
+                if i == 3 { continue; }
+                if i > 6 { break; }
+                w = w + doubled;
+            }
+
+            var c = 0;
+            for k in 0..10 {
+                let y = k * 10;
+                if k == 2 { continue; }
+                if k == 5 { break; }
+                c = c + y;
+            }
+
+            var e = 0;
+            for x in [1, 2, 3, 4, 5] {
+                if x % 2 == 0 { continue; }
+                if x == 5 { break; }
+                e = e + x;
+            }
+
+            fn nested() -> int {
+                var n = 0;
+                for a in 0..4 {
+                    for b in 0..4 {
+                        let t = a * b;
+                        if b - a > 0 { break; }
+                        if b == 1 { continue; }
+                        n = n + t + 1;
+                    }
+                }
+                return n;
+            }
+            let f = nested();
+        "#);
+        assert_eq!(vm.get_global("w"), Some(&Value::Integer(2 + 4 + 8 + 10 + 12)));
+        assert_eq!(vm.get_global("c"), Some(&Value::Integer(10 + 30 + 40)));
+        assert_eq!(vm.get_global("e"), Some(&Value::Integer(1 + 3)));
+        // (a, b) 取 b <= a 且 b != 1：(0,0) (1,0) (2,0) (2,2) (3,0) (3,2) (3,3)
+        assert_eq!(vm.get_global("f"), Some(&Value::Integer(7 + 4 + 6 + 9)));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_break_and_continue_in_nested_while() {
+        let vm = run_source(r#"
+            var total = 0;
+            var rows = 0;
+            var i = 0;
+            while i < 5 {
+                i = i + 1;
+                let row = i * 100;
+                if i == 2 { continue; }
+                var j = 0;
+                while true {
+                    j = j + 1;
+                    let cell = row + j;
+                    if j == 2 { continue; }
+                    if j > i { break; }
+                    total = total + cell;
+                }
+                if i == 4 { break; }
+                rows = rows + 1;
+            }
+        "#);
+        // i = 1: j = 1；i = 3: j = 1, 3；i = 4: j = 1, 3, 4；内层 break 只退出内层循环
+        assert_eq!(vm.get_global("total"), Some(&Value::Integer(101 + 301 + 303 + 401 + 403 + 404)));
+        assert_eq!(vm.get_global("rows"), Some(&Value::Integer(2)));
+        assert_eq!(vm.get_global("i"), Some(&Value::Integer(4)));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_compound_assignment_targets() {
+        let vm = run_source(r#"
+            struct P { x: int, y: int };
+            var arr = [1, 2, 3];
+            var i = 1;
+            arr[i] += 1;
+            arr[i + 1] -= 3;
+            var p = P { x: 5, y: 1 };
+            p.x *= 2;
+            p.y %= 1;
+            var n = 7;
+            n /= 2;
+            let x = p.x;
+            let y = p.y;
+        "#);
+        assert_eq!(
+            vm.get_global("arr"),
+            Some(&Value::array(vec![Value::Integer(1), Value::Integer(3), Value::Integer(0)]))
+        );
+        assert_eq!(vm.get_global("x"), Some(&Value::Integer(10)));
+        assert_eq!(vm.get_global("y"), Some(&Value::Integer(0)));
+        assert_eq!(vm.get_global("n"), Some(&Value::Integer(3)));
+    }
+
+    #[test]
+    fn test_inclusive_for_loop() {
+        let vm = run_source(r#"
+            var sum = 0;
+            for i in 1..=10 {
+                sum = sum + i;
+            }
+            var skipped = 0;
+            for i in 1..=5 {
+                if i == 2 { continue; }
+                if i == 5 { break; }
+                skipped = skipped + i;
+            }
+            var empty = 0;
+            for i in 5..=4 {
+                empty = empty + 1;
+            }
+            // 结束值为最大整数时不会在最后一次递增时溢出
+            var last = 0;
+            for i in 9223372036854775806..=9223372036854775807 {
+                last = i;
+            }
+        "#);
+        assert_eq!(vm.get_global("sum"), Some(&Value::Integer(55)));
+        assert_eq!(vm.get_global("skipped"), Some(&Value::Integer(1 + 3 + 4)));
+        assert_eq!(vm.get_global("empty"), Some(&Value::Integer(0)));
+        assert_eq!(vm.get_global("last"), Some(&Value::Integer(i64::MAX)));
+        // 大整数比较不经过浮点数
+        let vm = run_source("let a = 9223372036854775806 < 9223372036854775807; let b = 9007199254740993 > 9007199254740992;");
+        assert_eq!(vm.get_global("a"), Some(&Value::Boolean(true)));
+        assert_eq!(vm.get_global("b"), Some(&Value::Boolean(true)));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_match_statement() {
+        let vm = run_source(r#"
+            const LIMIT = 10;
+            fn describe(n: int) -> string {
+                var result = "";
+                match n {
+                    0 => { result = "zero"; }
+                    -1 => { result = "minus one"; }
+                    LIMIT => { result = "limit"; }
+                    _ => { result = "other"; }
+                }
+                return result;
+            }
+            let a = describe(0);
+            let b = describe(-1);
+            let c = describe(10);
+            let d = describe(7);
+
+            var greeting = "";
+            match "en" {
+                "fr" => { greeting = "bonjour"; },
+                "en" => { greeting = "hello"; },
+            }
+            var flag = 0;
+            match 1 < 2 {
+                false => { flag = 1; }
+                true => { let local = 2; flag = local; }
+            }
+            var untouched = 1;
+            match 5 {
+                1 => { untouched = 0; }
+            }
+
+            // 循环中的 break/continue 会弹出 match 的隐藏局部变量
+            var seen = 0;
+            for i in 0..10 {
+                match i {
+                    2 => { continue; }
+                    5 => { break; }
+                    _ => { seen = seen + i; }
+                }
+            }
+        "#);
+        assert_eq!(vm.get_global("a"), Some(&Value::String("zero".to_string())));
+        assert_eq!(vm.get_global("b"), Some(&Value::String("minus one".to_string())));
+        assert_eq!(vm.get_global("c"), Some(&Value::String("limit".to_string())));
+        assert_eq!(vm.get_global("d"), Some(&Value::String("other".to_string())));
+        assert_eq!(vm.get_global("greeting"), Some(&Value::String("hello".to_string())));
+        assert_eq!(vm.get_global("flag"), Some(&Value::Integer(2)));
+        assert_eq!(vm.get_global("untouched"), Some(&Value::Integer(1)));
+        assert_eq!(vm.get_global("seen"), Some(&Value::Integer(1 + 3 + 4)));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_typeof() {
+        let vm = run_source(r#"
+            struct Point { x: int, y: int };
+            fn id(n: int) -> int { return n; }
+            let a = typeof(1);
+            let b = typeof([1]);
+            let c = typeof(Point { x: 1, y: 2 });
+            let d = typeof(1.5) + "," + typeof("s") + "," + typeof(true) + "," + typeof('c');
+            let e = typeof(id) + "," + typeof(print) + "," + typeof(0..3);
+        "#);
+        let global = |name: &str| vm.get_global(name).map(Value::to_string);
+        assert_eq!(global("a").as_deref(), Some("int"));
+        assert_eq!(global("b").as_deref(), Some("array"));
+        assert_eq!(global("c").as_deref(), Some("Point"));
+        assert_eq!(global("d").as_deref(), Some("float,string,bool,char"));
+        assert_eq!(global("e").as_deref(), Some("function,function,range"));
+    }
+
+    #[test]
+    fn test_sum_builtin() {
+        let vm = run_source(r#"
+            let xs = to_array(1..=100);
+            let a = sum(xs);
+            let b = sum([1.0, 2.5, 3.0]);
+            let c = sum([]);
+            let total = sum;
+            let d = total([0.5, 0.25]);
+        "#);
+        assert_eq!(vm.get_global("a"), Some(&Value::Integer(5050)));
+        assert_eq!(vm.get_global("b"), Some(&Value::Float(6.5)));
+        assert_eq!(vm.get_global("c"), Some(&Value::Integer(0)));
+        assert_eq!(vm.get_global("d"), Some(&Value::Float(0.75)));
+
+        for (source, message) in [
+            ("let x = sum([9223372036854775807, 1]);", "sum() overflowed"),
+            ("let x = sum([1, \"a\"]);", "sum() expects numbers, got a"),
+            ("let x = sum(0..3);", "sum() expects an array, got 0..3"),
+        ] {
+            let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+            let chunk = Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap();
+            let error = VM::new().execute(chunk).unwrap_err();
+            assert!(error.to_string().contains(message), "{}: {}", source, error);
+        }
+
+        // 与 `+` 一样遵守整数位宽
+        let tokens = Lexer::new("let x = sum([2147483647, 1]);".to_string()).tokenize().unwrap();
+        let chunk = Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap();
+        let error = VM::new().with_int_width(IntWidth::I32).execute(chunk).unwrap_err();
+        assert!(error.to_string().contains("sum() overflowed"), "{}", error);
+    }
+
+    #[test]
+    fn test_arrays_share_storage_but_keep_value_semantics() {
+        let vm = run_source(r#"
+            let a = [1, 2, 3];
+            var b = a;
+            b[0] = 9;
+            var c = [[1], [2]];
+            let d = c;
+            c[1][0] = 7;
+        "#);
+        assert_eq!(vm.get_global("a"), Some(&Value::array(vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)])));
+        assert_eq!(vm.get_global("b"), Some(&Value::array(vec![Value::Integer(9), Value::Integer(2), Value::Integer(3)])));
+        assert_eq!(
+            vm.get_global("d"),
+            Some(&Value::array(vec![Value::array(vec![Value::Integer(1)]), Value::array(vec![Value::Integer(2)])]))
+        );
+
+        // 原生函数借用参数的存储，返回的数组与参数共享存储
+        let mut vm = VM::new();
+        let xs = Value::array((0..1000).map(Value::Integer).collect());
+        let result = (builtins::lookup("to_array").unwrap().function)(&mut vm, std::slice::from_ref(&xs)).unwrap();
+        assert!(std::rc::Rc::ptr_eq(xs.as_array().unwrap(), result.as_array().unwrap()));
+
+        // 取出元素：共享时复制，唯一持有时直接移出
+        let shared = xs.as_array().unwrap().clone();
+        assert_eq!(crate::bytecode::array_into_vec(shared).len(), 1000);
+        drop(result);
+        let Value::Array(unique) = xs else { unreachable!() };
+        let ptr = unique.borrow().as_ptr();
+        assert_eq!(crate::bytecode::array_into_vec(unique).as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_print_limits() {
+        let big = Value::array((1..=1000).map(Value::Integer).collect());
+        let vm = VM::new().with_print_limits(3, 2);
+        assert_eq!(vm.format_for_print(&big), "[1, 2, 3, ... (997 more)]");
+
+        // 超过深度的嵌套数组折叠为 [...]，空数组照常显示
+        let nested = Value::array(vec![
+            Value::array(vec![Value::array(vec![Value::Integer(1)]), Value::array(vec![])]),
+        ]);
+        assert_eq!(vm.format_for_print(&nested), "[[[...], []]]");
+
+        // 默认不限制
+        assert_eq!(VM::new().format_for_print(&big), big.to_string());
+        assert!(VM::new().format_for_print(&big).ends_with("999, 1000]"));
+    }
+
+    #[test]
+    fn test_print_builtin_uses_vm_output() {
+        // 通过别名调用的 print 函数和 print 语句写到同一个输出，并遵守同样的上限
+        let tokens = Lexer::new("let show = print; show([1, 2, 3, 4]); print([5, 6, 7, 8]);".to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let chunk = Compiler::new().compile(program).unwrap();
+        let mut vm = VM::new().with_print_limits(2, 1).with_captured_output();
+        vm.execute(chunk).unwrap();
+        assert_eq!(vm.take_output(), "[1, 2, ... (2 more)]\n[5, 6, ... (2 more)]\n");
+    }
+
+    #[test]
+    fn test_static_methods() {
+        let vm = run_source(r#"
+            struct Point { x: int, y: int };
+            impl Point {
+                fn origin() -> Point {
+                    return Point { x: 0, y: 0 };
+                }
+                fn new(x: int, y: int) -> Point {
+                    return Point { x: x, y: y };
+                }
+                fn sum(self) -> int {
+                    return self.x + self.y;
+                }
+            }
+            let o = Point.origin();
+            let p = Point.new(3, 4);
+            let total = p.sum() + o.sum();
+            let y = Point.new(5, 6).y;
+        "#);
+        assert_eq!(vm.get_global("total"), Some(&Value::Integer(7)));
+        assert_eq!(vm.get_global("y"), Some(&Value::Integer(6)));
+    }
+}
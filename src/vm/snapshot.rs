@@ -0,0 +1,209 @@
+//! 虚拟机状态快照：把值栈、全局变量和调用帧写成二进制格式，恢复后从暂停处继续执行
+//! 值和函数沿用字节码文件的编码（见 `BytecodeSerializer`）
+
+use super::{CallFrame, VM};
+use crate::ast::IntWidth;
+use crate::bytecode::serializer::{preallocation, BytecodeDeserializer, BytecodeSerializer};
+use crate::bytecode::Value;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Read, Result as IoResult, Write};
+
+/// 快照文件魔数 "ZVMS"
+const MAGIC: [u8; 4] = [0x5A, 0x56, 0x4D, 0x53];
+const VERSION: u16 = 1;
+
+impl VM {
+    /// 保存当前执行状态（值栈、全局变量、调用帧及各帧的指令指针）
+    /// 整数位宽、print 输出上限和扩展指令处理器属于配置而非状态，不写入快照
+    pub fn serialize_state<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+
+        // 全局变量按名称排序，相同状态总是得到相同的字节
+        let mut globals: Vec<(&String, &Value)> = self.globals.iter().collect();
+        globals.sort_by(|a, b| a.0.cmp(b.0));
+        write_len(writer, globals.len())?;
+        for (name, value) in globals {
+            write_string(writer, name)?;
+            BytecodeSerializer::write_value(value, writer, IntWidth::I64)?;
+        }
+
+        write_len(writer, self.stack.len())?;
+        for value in &self.stack {
+            BytecodeSerializer::write_value(value, writer, IntWidth::I64)?;
+        }
+
+        write_len(writer, self.frames.len())?;
+        for frame in &self.frames {
+            BytecodeSerializer::write_function(&frame.function, writer, IntWidth::I64)?;
+            write_len(writer, frame.ip)?;
+            write_len(writer, frame.stack_offset)?;
+        }
+        write_len(writer, self.current_frame)
+    }
+
+    /// 用快照替换当前执行状态，之后调用 `resume` 或 `run_steps` 继续执行
+    /// 快照不完整或与自身矛盾（如指令指针越界）时返回 InvalidData，VM 状态保持不变
+    pub fn restore_state<R: Read>(&mut self, reader: &mut R) -> IoResult<()> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid("Invalid snapshot magic number"));
+        }
+        let mut version = [0u8; 2];
+        reader.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != VERSION {
+            return Err(invalid(&format!("Unsupported snapshot version {}", version)));
+        }
+
+        let count = read_len(reader)?;
+        let mut globals = HashMap::with_capacity(preallocation(count));
+        for _ in 0..count {
+            let name = BytecodeDeserializer::read_string(reader)?;
+            globals.insert(name, BytecodeDeserializer::read_value(reader)?);
+        }
+
+        let count = read_len(reader)?;
+        let mut stack = Vec::with_capacity(preallocation(count));
+        for _ in 0..count {
+            stack.push(BytecodeDeserializer::read_value(reader)?);
+        }
+
+        let count = read_len(reader)?;
+        let mut frames = Vec::with_capacity(preallocation(count));
+        for _ in 0..count {
+            let function = BytecodeDeserializer::read_function(reader)?;
+            let ip = read_len(reader)?;
+            let stack_offset = read_len(reader)?;
+            if ip >= function.chunk.code.len() || stack_offset > stack.len() {
+                return Err(invalid(&format!("Corrupt call frame for '{}'", function.name)));
+            }
+            frames.push(CallFrame { function, ip, stack_offset });
+        }
+        let current_frame = read_len(reader)?;
+        if !frames.is_empty() && current_frame >= frames.len() {
+            return Err(invalid("Current frame index out of range"));
+        }
+
+        self.globals = globals;
+        self.stack = stack;
+        self.frames = frames;
+        self.current_frame = current_frame;
+        Ok(())
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+fn write_len<W: Write>(writer: &mut W, len: usize) -> IoResult<()> {
+    let len = u32::try_from(len).map_err(|_| invalid("Snapshot section too large"))?;
+    BytecodeSerializer::write_u32(writer, len)
+}
+
+fn read_len<R: Read>(reader: &mut R) -> IoResult<usize> {
+    Ok(BytecodeDeserializer::read_u32(reader)? as usize)
+}
+
+fn write_string<W: Write>(writer: &mut W, s: &str) -> IoResult<()> {
+    write_len(writer, s.len())?;
+    writer.write_all(s.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    const PROGRAM: &str = r#"
+        var log = "";
+        fn fib(n: int) -> int {
+            if n < 2 {
+                return n;
+            }
+            return fib(n - 1) + fib(n - 2);
+        }
+        fn digits(n: int) -> string {
+            let names = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+            if n < 10 {
+                return names[n];
+            }
+            return digits(n / 10) + names[n % 10];
+        }
+        for i in 0..10 {
+            log = log + " " + digits(fib(i));
+            print(digits(fib(i)));
+        }
+        let done = true;
+    "#;
+
+    fn compile(source: &str) -> crate::bytecode::Chunk {
+        let tokens = Lexer::new(source.to_string()).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        Compiler::new().compile(program).unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_resumes_where_it_left_off() {
+        let mut expected = VM::new().with_captured_output();
+        expected.execute(compile(PROGRAM)).unwrap();
+        assert_eq!(expected.get_global("log"), Some(&Value::String(" 0 1 1 2 3 5 8 13 21 34".to_string())));
+        let expected_output = expected.take_output();
+        assert_eq!(expected_output, "0\n1\n1\n2\n3\n5\n8\n13\n21\n34\n");
+
+        // 在不同位置暂停（包括递归调用深处），恢复到新的 VM 后结果相同
+        for steps in [1, 17, 150, 400, 1000] {
+            let mut vm = VM::new().with_captured_output();
+            vm.load(compile(PROGRAM));
+            assert!(!vm.run_steps(steps).unwrap(), "program finished before step {}", steps);
+
+            let mut bytes = Vec::new();
+            vm.serialize_state(&mut bytes).unwrap();
+
+            // 输出缓冲属于配置而非状态：暂停前的输出留在原 VM，恢复后的输出接在后面
+            let mut restored = VM::new().with_captured_output();
+            restored.restore_state(&mut bytes.as_slice()).unwrap();
+            restored.resume().unwrap();
+            assert_eq!(restored.globals, expected.globals, "snapshot after {} steps", steps);
+            assert_eq!(vm.take_output() + &restored.take_output(), expected_output, "snapshot after {} steps", steps);
+            assert!(restored.stack.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_corrupt_snapshot_rejected() {
+        let mut vm = VM::new();
+        vm.load(compile("let x = 1;"));
+        let mut bytes = Vec::new();
+        vm.serialize_state(&mut bytes).unwrap();
+
+        // 截断的快照不会改动已有状态
+        let mut target = VM::new();
+        target.execute(compile("let y = 2;")).unwrap();
+        assert!(target.restore_state(&mut &bytes[..bytes.len() - 1]).is_err());
+        assert_eq!(target.get_global("y"), Some(&Value::Integer(2)));
+
+        // 伪造的巨大数量和字符串长度不会预先分配内存，只会因数据不足而失败
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        for crafted in [
+            vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F],              // 全局变量数量 u32::MAX
+            vec![0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F],        // 1 个全局变量，名字长度 u32::MAX
+            vec![0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F],        // 值栈长度 u32::MAX
+            vec![0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F],  // 调用帧数量 u32::MAX
+        ] {
+            let snapshot = [header.clone(), crafted].concat();
+            assert!(target.restore_state(&mut snapshot.as_slice()).is_err());
+        }
+        assert_eq!(target.get_global("y"), Some(&Value::Integer(2)));
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = 0;
+        let error = target.restore_state(&mut bad_magic.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}